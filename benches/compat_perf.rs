@@ -0,0 +1,109 @@
+//! Workloads shared with Python's `str.format` and `parse`, for comparing the two.
+//!
+//! The workloads are read from `benches/fixtures/compat_perf.tsv`, whose
+//! header describes the format. Each one is checked against its expected
+//! result before it is measured, so a fixture that gullwing and Python
+//! disagree on fails loudly instead of timing the wrong thing.
+//!
+//! The Python side reads the same file: compile each pattern once with
+//! `parse.compile`, then time `pattern.format(*args)`, `p.parse(text)`,
+//! `p.search(text)` or `list(p.findall(text))` with `timeit`.
+//!
+//! ```bash
+//! cargo bench --bench compat_perf
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use gullwing::{Formatter, Parser, Value};
+
+const FIXTURES: &str = include_str!("fixtures/compat_perf.tsv");
+
+/// One line of the fixture file.
+struct Workload {
+    line: usize,
+    kind: &'static str,
+    name: &'static str,
+    pattern: &'static str,
+    input: &'static str,
+    expected: &'static str,
+}
+
+fn workloads() -> Vec<Workload> {
+    FIXTURES
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let columns: Vec<_> = line.split('\t').collect();
+            let [kind, name, pattern, input, expected] = columns[..] else {
+                panic!("line {}: expected 5 tab-separated columns", i + 1);
+            };
+            Workload {
+                line: i + 1,
+                kind,
+                name,
+                pattern,
+                input,
+                expected,
+            }
+        })
+        .collect()
+}
+
+/// Read a format argument the way the Python side does: int, else float, else string.
+fn argument(text: &str) -> Value {
+    if let Ok(i) = text.parse::<i64>() {
+        Value::from(i)
+    } else if let Ok(f) = text.parse::<f64>() {
+        Value::from(f)
+    } else {
+        Value::from(text)
+    }
+}
+
+fn bench_compat(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compat_perf");
+
+    for w in workloads() {
+        let context = format!("line {} ({})", w.line, w.name);
+        if w.kind == "format" {
+            let formatter = Formatter::new(w.pattern).expect(&context);
+            let args: Vec<_> = w.input.split('|').map(argument).collect();
+            let output = formatter.format_positional(&args).expect(&context);
+            assert_eq!(output, w.expected, "{}", context);
+
+            group.bench_function(w.name, |b| {
+                b.iter(|| formatter.format_positional(black_box(&args)))
+            });
+            continue;
+        }
+
+        let parser = Parser::new(w.pattern).expect(&context);
+        let expected: usize = w.expected.parse().expect(&context);
+        let text = w.input;
+        match w.kind {
+            "parse" => {
+                let found = parser.parse(text).expect(&context).is_some();
+                assert_eq!(usize::from(found), expected, "{}", context);
+                group.bench_function(w.name, |b| b.iter(|| parser.parse(black_box(text))));
+            }
+            "search" => {
+                let found = parser.search(text).expect(&context).is_some();
+                assert_eq!(usize::from(found), expected, "{}", context);
+                group.bench_function(w.name, |b| b.iter(|| parser.search(black_box(text))));
+            }
+            "findall" => {
+                assert_eq!(parser.findall(text).count(), expected, "{}", context);
+                group.bench_function(w.name, |b| {
+                    b.iter(|| parser.findall(black_box(text)).collect::<Vec<_>>())
+                });
+            }
+            kind => panic!("{}: unknown kind '{}'", context, kind),
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_compat);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 use gullwing::{Formatter, Value};
 use std::collections::HashMap;
 
@@ -6,7 +6,7 @@ fn bench_format_string_simple(c: &mut Criterion) {
     c.bench_function("format_string_simple", |b| {
         let formatter = Formatter::new("Hello, {name}!").unwrap();
         let mut values = HashMap::new();
-        values.insert("name".to_string(), Value::Str("World".to_string()));
+        values.insert("name".to_string(), Value::from_static("World"));
 
         b.iter(|| formatter.format_map(black_box(&values)))
     });
@@ -32,6 +32,7 @@ fn bench_format_number_with_grouping(c: &mut Criterion) {
     });
 }
 
+#[allow(clippy::approx_constant)]
 fn bench_format_float_precision(c: &mut Criterion) {
     c.bench_function("format_float_precision", |b| {
         let formatter = Formatter::new("{value:.2f}").unwrap();
@@ -46,12 +47,30 @@ fn bench_format_aligned_padded(c: &mut Criterion) {
     c.bench_function("format_aligned_padded", |b| {
         let formatter = Formatter::new("{value:*>20}").unwrap();
         let mut values = HashMap::new();
-        values.insert("value".to_string(), Value::Str("test".to_string()));
+        values.insert("value".to_string(), Value::from_static("test"));
 
         b.iter(|| formatter.format_map(black_box(&values)))
     });
 }
 
+fn bench_format_aligned_wide_pad(c: &mut Criterion) {
+    let mut group = c.benchmark_group("format_aligned_wide_pad");
+    for (name, pattern) in [
+        ("ascii_fill", "{value:*^200}"),
+        ("multibyte_fill", "{value:\u{b7}^200}"),
+        ("zero_pad", "{value:0200.2f}"),
+    ] {
+        let formatter = Formatter::new(pattern).unwrap();
+        let mut values = HashMap::new();
+        values.insert("value".to_string(), Value::Float(-3.25));
+
+        group.bench_function(name, |b| {
+            b.iter(|| formatter.format_map(black_box(&values)))
+        });
+    }
+    group.finish();
+}
+
 fn bench_format_hex_with_prefix(c: &mut Criterion) {
     c.bench_function("format_hex_with_prefix", |b| {
         let formatter = Formatter::new("{value:#x}").unwrap();
@@ -67,7 +86,7 @@ fn bench_format_complex_pattern(c: &mut Criterion) {
         let formatter =
             Formatter::new("Name: {name:<20} | Amount: {amount:>10,.2f} | ID: {id:#06x}").unwrap();
         let mut values = HashMap::new();
-        values.insert("name".to_string(), Value::Str("Alice".to_string()));
+        values.insert("name".to_string(), Value::from_static("Alice"));
         values.insert("amount".to_string(), Value::Float(1234.56));
         values.insert("id".to_string(), Value::Int(42));
 
@@ -87,6 +106,55 @@ fn bench_format_multiple_fields(c: &mut Criterion) {
     });
 }
 
+fn bench_compile_throughput(c: &mut Criterion) {
+    // Templates like a service loads at boot: mostly distinct, with a few
+    // specs shared across many of them
+    let text = ["", ">8", "<12", "^20", "*<6", ".10", "!r", "!s:>4"];
+    let int = ["", ",d", "08d", "#x", "+d", "*>6d", "_b", "=+9d"];
+    let float = ["", ".2f", "08.3f", "+.1%", ",.2f", ".3e", "g", "^10.1f"];
+    let templates: Vec<String> = (0..1000)
+        .map(|i| {
+            format!(
+                "event {i}: user={{user{}}} id={{id_{i}:{}}} took {{ms:{}}}ms [{{tag}}] {{{{raw}}}}",
+                match text[i % text.len()] {
+                    spec if spec.starts_with('!') || spec.is_empty() => spec.to_string(),
+                    spec => format!(":{}", spec),
+                },
+                int[(i / 3) % int.len()],
+                float[(i / 7) % float.len()],
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("compile_throughput");
+    group.throughput(Throughput::Elements(templates.len() as u64));
+    group.bench_function("formatter_new", |b| {
+        b.iter(|| {
+            for template in &templates {
+                black_box(Formatter::new(black_box(template)).unwrap());
+            }
+        })
+    });
+    group.finish();
+
+    // A wide table row: many columns, few distinct specs
+    let wide: Vec<String> = (0..200)
+        .map(|i| match i % 4 {
+            0 => format!("{{name{}:<16}}", i),
+            1 => format!("{{count{}:>10,d}}", i),
+            _ => format!("{{value{}:>10.2f}}", i),
+        })
+        .collect();
+    let wide = wide.join(" | ");
+
+    let mut group = c.benchmark_group("compile_throughput");
+    group.throughput(Throughput::Elements(200));
+    group.bench_function("wide_table", |b| {
+        b.iter(|| black_box(Formatter::new(black_box(&wide)).unwrap()))
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_format_string_simple,
@@ -94,8 +162,10 @@ criterion_group!(
     bench_format_number_with_grouping,
     bench_format_float_precision,
     bench_format_aligned_padded,
+    bench_format_aligned_wide_pad,
     bench_format_hex_with_prefix,
     bench_format_complex_pattern,
-    bench_format_multiple_fields
+    bench_format_multiple_fields,
+    bench_compile_throughput
 );
 criterion_main!(benches);
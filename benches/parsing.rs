@@ -1,5 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use gullwing::Parser;
+use gullwing::parse::{Engine, ParserSet};
+use gullwing::{Parser, Transformer};
 
 fn bench_parse_simple_pattern(c: &mut Criterion) {
     c.bench_function("parse_simple_pattern", |b| {
@@ -60,7 +61,22 @@ fn bench_parse_findall(c: &mut Criterion) {
         let parser = Parser::new("{key}={value}").unwrap();
         let text = "config: name=test, port=8080, host=localhost, debug=true";
 
-        b.iter(|| parser.findall(black_box(text)))
+        b.iter(|| parser.findall(black_box(text)).count())
+    });
+}
+
+fn bench_parse_large_input(c: &mut Criterion) {
+    // About 1 MB of log lines where only the last one matches
+    let mut text = "2024-01-15 INFO [12] request served in 13.5ms\n".repeat(20_000);
+    text.push_str("2024-01-15 FATAL [7] disk full\n");
+    let parser = Parser::new("FATAL [{thread:d}] {message}\n").unwrap();
+
+    c.bench_function("parse_search_large_input", |b| {
+        b.iter(|| parser.search(black_box(&text)))
+    });
+
+    c.bench_function("parse_findall_large_input", |b| {
+        b.iter(|| parser.findall(black_box(&text)).count())
     });
 }
 
@@ -89,6 +105,77 @@ fn bench_parse_pattern_creation(c: &mut Criterion) {
     });
 }
 
+fn bench_parse_hot_loop(c: &mut Criterion) {
+    // The anchored regex is compiled once in `Parser::new`; rebuilding the
+    // parser per line shows what that saves
+    let pattern = "{date} {level} [{thread:d}] {message}";
+    let line = "2024-01-15 WARN [12] slow request to /api/users";
+    let mut group = c.benchmark_group("parse_hot_loop");
+
+    group.bench_function("precompiled", |b| {
+        let parser = Parser::new(pattern)
+            .unwrap()
+            .with_engine(Engine::Regex)
+            .unwrap();
+        b.iter(|| parser.parse(black_box(line)))
+    });
+
+    group.bench_function("compiled_per_call", |b| {
+        b.iter(|| {
+            Parser::new(black_box(pattern))
+                .unwrap()
+                .with_engine(Engine::Regex)
+                .unwrap()
+                .parse(black_box(line))
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_transform(c: &mut Criterion) {
+    let transformer = Transformer::new(
+        "{date} {level} [{thread:d}] {latency:f}ms {message}",
+        "{level:<5} {date} {thread:>4d} {latency:8.2f} {message}",
+    )
+    .unwrap();
+    let line = "2024-01-15 WARN [12] 13.5ms slow request to /api/users";
+
+    c.bench_function("transform", |b| {
+        b.iter(|| transformer.transform(black_box(line)))
+    });
+
+    c.bench_function("transform_into_scratch", |b| {
+        let mut scratch = transformer.scratch();
+        let mut out = String::new();
+        b.iter(|| {
+            out.clear();
+            transformer.transform_into(black_box(line), &mut out, &mut scratch)
+        })
+    });
+}
+
+fn bench_parser_set(c: &mut Criterion) {
+    // Many line formats, with the matching one last
+    let mut patterns: Vec<String> = (0..30)
+        .map(|i| format!("{{ts}} svc{} {{level}} [{{thread:d}}] {{message}}", i))
+        .collect();
+    patterns.push("{ts} gateway {status:d} {path} {latency:f}ms".to_string());
+    let line = "2024-01-15T10:30:00 gateway 503 /api/users 13.5ms";
+
+    let parsers: Vec<Parser> = patterns.iter().map(|p| Parser::new(p).unwrap()).collect();
+    c.bench_function("parser_set_serial", |b| {
+        b.iter(|| {
+            parsers
+                .iter()
+                .find_map(|parser| parser.parse(black_box(line)).unwrap())
+        })
+    });
+
+    let set = ParserSet::new(&patterns).unwrap();
+    c.bench_function("parser_set", |b| b.iter(|| set.parse(black_box(line))));
+}
+
 criterion_group!(
     benches,
     bench_parse_simple_pattern,
@@ -98,8 +185,12 @@ criterion_group!(
     bench_parse_multiple_fields,
     bench_parse_search,
     bench_parse_findall,
+    bench_parse_large_input,
     bench_parse_complex_pattern,
     bench_parse_hex_number,
-    bench_parse_pattern_creation
+    bench_parse_pattern_creation,
+    bench_parse_hot_loop,
+    bench_transform,
+    bench_parser_set
 );
 criterion_main!(benches);
@@ -0,0 +1,398 @@
+//! Derive macros for [gullwing](https://docs.rs/gullwing)'s pattern-bound structs.
+//!
+//! Use these through gullwing's `derive` feature, which re-exports them
+//! alongside the `GullwingFormat` and `GullwingParse` traits they implement.
+//!
+//! The struct names its pattern with `#[gullwing(pattern = "...")]`. Each
+//! named field in the pattern binds to the struct field of the same name, or
+//! to the field marked `#[gullwing(rename = "...")]` with that name. Patterns
+//! are checked when the struct is compiled:
+//!
+//! ```compile_fail
+//! use gullwing::GullwingFormat;
+//!
+//! #[derive(GullwingFormat)]
+//! #[gullwing(pattern = "{host}:{port:d}")]
+//! struct Address {
+//!     host: String,
+//!     // error: pattern field 'port' is not a field of `Address`
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! use gullwing::GullwingParse;
+//!
+//! #[derive(GullwingParse)]
+//! #[gullwing(pattern = "{host}")]
+//! struct Address {
+//!     host: String,
+//!     // error: field `port` is not in the pattern
+//!     port: u16,
+//! }
+//! ```
+//!
+//! Parsing fills fields that aren't in the pattern from their `Default`
+//! when they are marked `#[gullwing(skip)]`:
+//!
+//! ```
+//! use gullwing::GullwingParse;
+//!
+//! #[derive(GullwingParse)]
+//! #[gullwing(pattern = "{host}:{port:d}")]
+//! struct Address {
+//!     host: String,
+//!     port: u16,
+//!     #[gullwing(skip)]
+//!     retries: u32,
+//! }
+//!
+//! let address = Address::parse("example.com:8080").unwrap().unwrap();
+//! assert_eq!((address.host.as_str(), address.port, address.retries), ("example.com", 8080, 0));
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+/// Derive `GullwingFormat` and `ValueProvider` from a `#[gullwing(pattern = "...")]` attribute.
+#[proc_macro_derive(GullwingFormat, attributes(gullwing))]
+pub fn derive_format(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_format(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `GullwingParse` from a `#[gullwing(pattern = "...")]` attribute.
+#[proc_macro_derive(GullwingParse, attributes(gullwing))]
+pub fn derive_parse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_parse(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A struct and the pattern it is bound to.
+struct Binding<'a> {
+    input: &'a DeriveInput,
+    pattern: LitStr,
+    pattern_fields: Vec<PatternField>,
+    fields: Vec<StructField>,
+}
+
+/// A replacement field in the pattern.
+#[derive(Debug)]
+struct PatternField {
+    name: String, // Name before any attribute or index path
+    path: bool,   // Followed by `.attr` or `[index]`
+}
+
+/// A field of the struct.
+struct StructField {
+    ident: Ident,
+    name: String, // Name in the pattern
+    skip: bool,   // Not bound to the pattern
+}
+
+impl<'a> Binding<'a> {
+    /// Read the pattern and fields of a struct deriving `derive`.
+    fn parse(input: &'a DeriveInput, derive: &str) -> syn::Result<Self> {
+        let named = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(named) => named,
+                _ => return Err(named_fields_error(input, derive)),
+            },
+            _ => return Err(named_fields_error(input, derive)),
+        };
+
+        let mut pattern = None;
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("gullwing")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("pattern") {
+                    pattern = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown gullwing attribute; expected `pattern`"))
+                }
+            })?;
+        }
+        let pattern = pattern.ok_or_else(|| {
+            syn::Error::new_spanned(
+                &input.ident,
+                format!(
+                    "#[derive({})] needs a #[gullwing(pattern = \"...\")] attribute",
+                    derive
+                ),
+            )
+        })?;
+        let pattern_fields =
+            scan_pattern(&pattern.value()).map_err(|msg| syn::Error::new(pattern.span(), msg))?;
+
+        let mut fields = Vec::new();
+        for field in &named.named {
+            let ident = field.ident.clone().expect("named fields have identifiers");
+            let mut name = ident.unraw().to_string();
+            let mut skip = false;
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("gullwing")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        skip = true;
+                        Ok(())
+                    } else if meta.path.is_ident("rename") {
+                        name = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown gullwing attribute; expected `skip` or `rename`"))
+                    }
+                })?;
+            }
+            fields.push(StructField { ident, name, skip });
+        }
+
+        Ok(Binding {
+            input,
+            pattern,
+            pattern_fields,
+            fields,
+        })
+    }
+
+    /// Get the struct field a pattern field binds to.
+    fn bound_field(&self, name: &str) -> syn::Result<&StructField> {
+        self.fields
+            .iter()
+            .find(|field| !field.skip && field.name == name)
+            .ok_or_else(|| {
+                syn::Error::new(
+                    self.pattern.span(),
+                    format!(
+                        "pattern field '{}' is not a field of `{}`",
+                        name, self.input.ident
+                    ),
+                )
+            })
+    }
+}
+
+/// Build the error for a derive on something other than a struct with named fields.
+fn named_fields_error(input: &DeriveInput, derive: &str) -> syn::Error {
+    syn::Error::new_spanned(
+        &input.ident,
+        format!(
+            "{} can only be derived for structs with named fields",
+            derive
+        ),
+    )
+}
+
+/// Get the replacement fields of a pattern, checking that its braces match.
+///
+/// Only names are extracted; specs are left for gullwing to check when the
+/// pattern is first used.
+fn scan_pattern(pattern: &str) -> Result<Vec<PatternField>, String> {
+    let mut fields = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let mut field = String::new();
+                let mut depth = 0;
+                loop {
+                    match chars.next() {
+                        None => return Err("unclosed '{' in pattern".to_string()),
+                        Some('}') if depth == 0 => break,
+                        Some(c) => {
+                            match c {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            field.push(c);
+                        }
+                    }
+                }
+
+                // The name ends at a conversion or spec, unless inside an index
+                let mut in_index = false;
+                let end = field
+                    .char_indices()
+                    .find(|&(_, c)| {
+                        match c {
+                            '[' => in_index = true,
+                            ']' => in_index = false,
+                            '!' | ':' => return !in_index,
+                            _ => {}
+                        }
+                        false
+                    })
+                    .map_or(field.len(), |(i, _)| i);
+                let name = &field[..end];
+                let root = &name[..name.find(['.', '[']).unwrap_or(name.len())];
+                if root.is_empty() || root.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(format!(
+                        "positional field '{{{}}}' can't be bound to a struct field; give it a name",
+                        field
+                    ));
+                }
+                fields.push(PatternField {
+                    name: root.to_string(),
+                    path: root.len() < name.len(),
+                });
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '}' => return Err("single '}' in pattern; write '}}' for a literal brace".to_string()),
+            _ => {}
+        }
+    }
+    Ok(fields)
+}
+
+fn expand_format(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let binding = Binding::parse(input, "GullwingFormat")?;
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut arms = Vec::new();
+    for field in &binding.pattern_fields {
+        if names.contains(&field.name.as_str()) {
+            continue;
+        }
+        names.push(&field.name);
+        let name = &field.name;
+        let ident = &binding.bound_field(name)?.ident;
+        arms.push(quote! {
+            #name => ::std::option::Option::Some(::gullwing::ToValue::to_value(&self.#ident)),
+        });
+    }
+
+    let ident = &input.ident;
+    let pattern = &binding.pattern;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::gullwing::ValueProvider for #ident #ty_generics #where_clause {
+            fn get(&self, name: &str) -> ::std::option::Option<::gullwing::Value> {
+                match name {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        impl #impl_generics ::gullwing::GullwingFormat for #ident #ty_generics #where_clause {
+            const PATTERN: &'static str = #pattern;
+
+            fn formatter() -> ::gullwing::Result<&'static ::gullwing::Formatter> {
+                static FORMATTER: ::std::sync::OnceLock<::gullwing::Result<::gullwing::Formatter>> =
+                    ::std::sync::OnceLock::new();
+                FORMATTER
+                    .get_or_init(|| ::gullwing::Formatter::new(#pattern))
+                    .as_ref()
+                    .map_err(::std::clone::Clone::clone)
+            }
+        }
+    })
+}
+
+fn expand_parse(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let binding = Binding::parse(input, "GullwingParse")?;
+
+    for field in &binding.pattern_fields {
+        if field.path {
+            return Err(syn::Error::new(
+                binding.pattern.span(),
+                format!(
+                    "parse pattern field '{}' can't have an attribute or index path",
+                    field.name
+                ),
+            ));
+        }
+        binding.bound_field(&field.name)?;
+    }
+
+    let mut inits = Vec::new();
+    for field in &binding.fields {
+        let ident = &field.ident;
+        let name = &field.name;
+        if field.skip {
+            inits.push(quote! { #ident: ::std::default::Default::default(), });
+        } else if binding.pattern_fields.iter().any(|f| &f.name == name) {
+            inits.push(quote! {
+                #ident: ::gullwing::FromValue::from_field(result.get(#name), #name)?,
+            });
+        } else {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "field `{}` is not in the pattern; mark it #[gullwing(skip)] to fill it with its Default",
+                    ident
+                ),
+            ));
+        }
+    }
+
+    let ident = &input.ident;
+    let pattern = &binding.pattern;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::gullwing::GullwingParse for #ident #ty_generics #where_clause {
+            const PATTERN: &'static str = #pattern;
+
+            fn parser() -> ::gullwing::Result<&'static ::gullwing::Parser> {
+                static PARSER: ::std::sync::OnceLock<::gullwing::Result<::gullwing::Parser>> =
+                    ::std::sync::OnceLock::new();
+                PARSER
+                    .get_or_init(|| ::gullwing::Parser::new(#pattern))
+                    .as_ref()
+                    .map_err(::std::clone::Clone::clone)
+            }
+
+            fn from_result(result: &::gullwing::ParseResult) -> ::gullwing::Result<Self> {
+                ::std::result::Result::Ok(Self {
+                    #(#inits)*
+                })
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(pattern: &str) -> Vec<(String, bool)> {
+        scan_pattern(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|field| (field.name, field.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_pattern() {
+        assert_eq!(
+            names("{{literal}} {a} {b:>{w}} {c!r:^10} {d.e} {f[x:y]:s}"),
+            [
+                ("a".to_string(), false),
+                ("b".to_string(), false),
+                ("c".to_string(), false),
+                ("d".to_string(), true),
+                ("f".to_string(), true),
+            ]
+        );
+        assert!(names("no fields }} {{").is_empty());
+    }
+
+    #[test]
+    fn test_scan_pattern_errors() {
+        assert!(scan_pattern("{a").unwrap_err().contains("unclosed"));
+        assert!(scan_pattern("a}").unwrap_err().contains("single '}'"));
+        assert!(scan_pattern("{} {a}").unwrap_err().contains("positional"));
+        assert!(scan_pattern("{0:d}").unwrap_err().contains("positional"));
+    }
+}
@@ -0,0 +1,411 @@
+//! Python bindings for [gullwing](https://docs.rs/gullwing)'s formatter and parser.
+//!
+//! Built with `maturin`, this crate is the `gullwing` Python module. It runs
+//! the same Rust implementation as the crate, so code moving off Python's
+//! `parse` package or `str.format` can check, from Python and against the
+//! same inputs, that both give byte-for-byte identical results:
+//!
+//! ```python
+//! import parse, gullwing
+//!
+//! pattern, text = "{name} is {age:d}", "Alice is 30"
+//! assert gullwing.parse(pattern, text).named == parse.parse(pattern, text).named
+//! assert gullwing.format("{:>8.2f}", 3.14159) == "{:>8.2f}".format(3.14159)
+//! ```
+//!
+//! The module follows `parse` where it can: `parse`, `search`, `findall` and
+//! `compile` functions, and `Result` objects with `named` and `spans`. Spans
+//! are in characters, to index Python strings. Invalid patterns raise
+//! `ValueError`, and missing fields `KeyError`, as `str.format` does.
+
+use gullwing::{ErrorKind, Formatter, ParseResult, Parser, Value};
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyString, PyTuple};
+use pyo3::IntoPyObjectExt;
+use std::collections::HashMap;
+
+/// Raise a gullwing error as the exception Python would raise.
+fn to_py_err(err: gullwing::Error) -> PyErr {
+    match err.kind() {
+        ErrorKind::MissingField => PyKeyError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// A pattern compiled for formatting, like `str.format` with a fixed string.
+#[pyclass(name = "Formatter", module = "gullwing", frozen)]
+#[derive(Debug)]
+struct PyFormatter {
+    inner: Formatter,
+}
+
+#[pymethods]
+impl PyFormatter {
+    #[new]
+    fn new(pattern: &str) -> PyResult<Self> {
+        let inner = Formatter::new(pattern).map_err(to_py_err)?;
+        Ok(PyFormatter { inner })
+    }
+
+    /// The pattern this formatter was compiled from.
+    #[getter]
+    fn pattern(&self) -> &str {
+        self.inner.pattern()
+    }
+
+    /// Format positional or keyword arguments, like `str.format`.
+    #[pyo3(signature = (*args, **kwargs))]
+    fn format(
+        &self,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<String> {
+        format_args(&self.inner, args, kwargs)
+    }
+
+    /// Format the items of a mapping, like `str.format_map`.
+    fn format_map(&self, mapping: &Bound<'_, PyDict>) -> PyResult<String> {
+        let values = map_from_py(mapping)?;
+        self.inner.format_map(&values).map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Formatter {:?}>", self.inner.pattern())
+    }
+}
+
+/// A pattern compiled for parsing, like `parse.compile`.
+#[pyclass(name = "Parser", module = "gullwing", frozen)]
+#[derive(Debug)]
+struct PyParser {
+    inner: Parser,
+}
+
+#[pymethods]
+impl PyParser {
+    #[new]
+    fn new(pattern: &str) -> PyResult<Self> {
+        let inner = Parser::new(pattern).map_err(to_py_err)?;
+        Ok(PyParser { inner })
+    }
+
+    /// The pattern this parser was compiled from.
+    #[getter]
+    fn pattern(&self) -> &str {
+        self.inner.pattern()
+    }
+
+    /// Match the whole of `text`, giving `None` if it doesn't match.
+    fn parse(&self, text: &str) -> PyResult<Option<PyParseResult>> {
+        let result = self.inner.parse(text).map_err(to_py_err)?;
+        Ok(result.map(PyParseResult::from))
+    }
+
+    /// Find the first match anywhere in `text`, giving `None` if there is none.
+    fn search(&self, text: &str) -> PyResult<Option<PyParseResult>> {
+        let result = self.inner.search(text).map_err(to_py_err)?;
+        Ok(result.map(PyParseResult::from))
+    }
+
+    /// Find every non-overlapping match in `text`.
+    fn findall(&self, text: &str) -> PyResult<Vec<PyParseResult>> {
+        self.inner
+            .findall(text)
+            .map(|result| result.map(PyParseResult::from).map_err(to_py_err))
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Parser {:?}>", self.inner.pattern())
+    }
+}
+
+/// The fields of a match, like the `Result` of `parse`.
+#[pyclass(name = "Result", module = "gullwing", frozen)]
+#[derive(Debug)]
+struct PyParseResult {
+    inner: ParseResult,
+}
+
+impl From<ParseResult> for PyParseResult {
+    fn from(inner: ParseResult) -> Self {
+        PyParseResult { inner }
+    }
+}
+
+#[pymethods]
+impl PyParseResult {
+    /// The fields, by name, in pattern order.
+    #[getter]
+    fn named<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let named = PyDict::new(py);
+        for (name, value) in self.inner.fields() {
+            named.set_item(name, value_to_py(py, value)?)?;
+        }
+        Ok(named)
+    }
+
+    /// The `(start, end)` of each field in the text, in characters.
+    #[getter]
+    fn spans<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let text = self.inner.text();
+        let spans = PyDict::new(py);
+        for (name, _) in self.inner.fields() {
+            if let Some(span) = self.inner.span(name) {
+                let start = text[..span.start].chars().count();
+                let end = start + text[span].chars().count();
+                spans.set_item(name, (start, end))?;
+            }
+        }
+        Ok(spans)
+    }
+
+    /// The text that was matched.
+    #[getter]
+    fn text(&self) -> &str {
+        self.inner.text()
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+        match self.inner.get(name) {
+            Some(value) => value_to_py(py, value),
+            None => Err(PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.inner.get(name).is_some()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("<Result {}>", self.named(py)?.repr()?))
+    }
+}
+
+/// Format `args` or `kwargs` with a formatter.
+fn format_args(
+    formatter: &Formatter,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    match kwargs {
+        Some(kwargs) if !kwargs.is_empty() => {
+            if !args.is_empty() {
+                return Err(PyTypeError::new_err(
+                    "positional and keyword arguments can't be mixed",
+                ));
+            }
+            let values = map_from_py(kwargs)?;
+            formatter.format_map(&values).map_err(to_py_err)
+        }
+        _ => {
+            let values = args
+                .iter()
+                .map(|arg| value_from_py(&arg))
+                .collect::<PyResult<Vec<_>>>()?;
+            formatter.format_positional(&values).map_err(to_py_err)
+        }
+    }
+}
+
+/// Convert the items of a dict into values by name.
+fn map_from_py(dict: &Bound<'_, PyDict>) -> PyResult<HashMap<String, Value>> {
+    dict.iter()
+        .map(|(key, value)| Ok((key.extract::<String>()?, value_from_py(&value)?)))
+        .collect()
+}
+
+/// Convert a Python object into a value.
+///
+/// `int`s become the smallest integer variant holding them, so
+/// `2**100` formats exactly. Lists and tuples become lists and dicts maps.
+fn value_from_py(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::Null)
+    } else if obj.is_instance_of::<PyBool>() {
+        Ok(Value::Bool(obj.extract()?))
+    } else if obj.is_instance_of::<PyString>() {
+        Ok(Value::from(obj.extract::<String>()?))
+    } else if obj.is_instance_of::<PyFloat>() {
+        Ok(Value::Float(obj.extract()?))
+    } else if let Ok(i) = obj.extract::<i64>() {
+        Ok(Value::Int(i))
+    } else if let Ok(u) = obj.extract::<u64>() {
+        Ok(Value::UInt(u))
+    } else if let Ok(i) = obj.extract::<i128>() {
+        Ok(Value::Int128(i))
+    } else if let Ok(u) = obj.extract::<u128>() {
+        Ok(Value::UInt128(u))
+    } else if let Ok(dict) = obj.cast::<PyDict>() {
+        Ok(Value::Map(map_from_py(dict)?))
+    } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        obj.try_iter()?
+            .map(|item| value_from_py(&item?))
+            .collect::<PyResult<_>>()
+            .map(Value::List)
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "can't format a value of type {}",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+/// Convert a value into a Python object.
+fn value_to_py(py: Python<'_>, value: &Value) -> PyResult<Py<PyAny>> {
+    match value {
+        Value::Null => Ok(py.None()),
+        Value::Str(s) => s.into_py_any(py),
+        Value::Int(i) => i.into_py_any(py),
+        Value::UInt(u) => u.into_py_any(py),
+        Value::Int128(i) => i.into_py_any(py),
+        Value::UInt128(u) => u.into_py_any(py),
+        Value::Float(f) => f.into_py_any(py),
+        Value::Bool(b) => b.into_py_any(py),
+        Value::Char(c) => c.into_py_any(py),
+        Value::List(items) => items
+            .iter()
+            .map(|item| value_to_py(py, item))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_py_any(py),
+        Value::Map(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, value_to_py(py, value)?)?;
+            }
+            dict.into_py_any(py)
+        }
+        // Decimals and datetimes, when another crate in the build turns on
+        // gullwing's `rust_decimal` or `chrono` feature
+        #[allow(unreachable_patterns)]
+        other => other.to_string().into_py_any(py),
+    }
+}
+
+/// Format arguments with a pattern, like `pattern.format(*args, **kwargs)`.
+#[pyfunction]
+#[pyo3(signature = (pattern, *args, **kwargs))]
+fn format(
+    pattern: &str,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let formatter = Formatter::new(pattern).map_err(to_py_err)?;
+    format_args(&formatter, args, kwargs)
+}
+
+/// Compile a pattern for parsing, like `parse.compile`.
+#[pyfunction]
+fn compile(pattern: &str) -> PyResult<PyParser> {
+    PyParser::new(pattern)
+}
+
+/// Match the whole of `text` against `pattern`, like `parse.parse`.
+#[pyfunction]
+fn parse(pattern: &str, text: &str) -> PyResult<Option<PyParseResult>> {
+    PyParser::new(pattern)?.parse(text)
+}
+
+/// Find the first match of `pattern` in `text`, like `parse.search`.
+#[pyfunction]
+fn search(pattern: &str, text: &str) -> PyResult<Option<PyParseResult>> {
+    PyParser::new(pattern)?.search(text)
+}
+
+/// Find every match of `pattern` in `text`, like `parse.findall`.
+#[pyfunction]
+fn findall(pattern: &str, text: &str) -> PyResult<Vec<PyParseResult>> {
+    PyParser::new(pattern)?.findall(text)
+}
+
+/// The `gullwing` Python module.
+#[pymodule(name = "gullwing")]
+fn gullwing_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFormatter>()?;
+    m.add_class::<PyParser>()?;
+    m.add_class::<PyParseResult>()?;
+    m.add_function(wrap_pyfunction!(format, m)?)?;
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(search, m)?)?;
+    m.add_function(wrap_pyfunction!(findall, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// Run Python code with the module imported as `gullwing`.
+    fn run(code: &CStr) {
+        Python::attach(|py| {
+            let module = pyo3::wrap_pymodule!(gullwing_py)(py);
+            let globals = PyDict::new(py);
+            globals.set_item("gullwing", module).unwrap();
+            if let Err(err) = py.run(code, Some(&globals), None) {
+                err.print(py);
+                panic!("Python code failed: {err}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_format_matches_str_format() {
+        run(c"
+cases = [
+    ('{:>8.2f}', (3.14159,), {}),
+    ('{0:+,d}|{1!r:^9}', (1234567, 'x'), {}),
+    ('{name:_<6}{n:#x}', (), {'name': 'ab', 'n': 255}),
+    ('{:e} {:%} {:g}', (12345.678, 0.25, 1e-7), {}),
+    ('{:d}', (2**100,), {}),
+    ('{v[0]}{v[1]}', (), {'v': [1, 'a']}),
+]
+for pattern, args, kwargs in cases:
+    assert gullwing.format(pattern, *args, **kwargs) == pattern.format(*args, **kwargs), pattern
+
+formatter = gullwing.Formatter('{a}-{b:03d}')
+assert formatter.pattern == '{a}-{b:03d}'
+assert formatter.format_map({'a': None, 'b': 7}) == 'None-007'
+");
+    }
+
+    #[test]
+    fn test_parse() {
+        run(c"
+result = gullwing.parse('{name} is {age:d}', 'Zoë is 30')
+assert result.named == {'name': 'Zoë', 'age': 30}
+assert result['age'] == 30 and 'name' in result and 'x' not in result
+assert result.spans == {'name': (0, 3), 'age': (7, 9)}
+assert result.text[slice(*result.spans['age'])] == '30'
+assert gullwing.parse('{:d}', 'x') is None
+
+parser = gullwing.compile('<{tag}>')
+assert parser.search('a <b> c')['tag'] == 'b'
+assert [r['tag'] for r in parser.findall('<i><em>')] == ['i', 'em']
+assert [r['tag'] for r in gullwing.findall('<{tag}>', '<p>')] == ['p']
+assert repr(parser) == '<Parser \"<{tag}>\">'
+");
+    }
+
+    #[test]
+    fn test_errors() {
+        run(c"
+def raises(exception, f, *args, **kwargs):
+    try:
+        f(*args, **kwargs)
+    except exception:
+        return
+    raise AssertionError(f'{f} didn\\'t raise {exception}')
+
+raises(ValueError, gullwing.Formatter, '{x')
+raises(ValueError, gullwing.compile, '{x:q}')
+raises(KeyError, gullwing.format, '{x}', y=1)
+raises(ValueError, gullwing.format, '{:d}', 'x')
+raises(TypeError, gullwing.format, '{}', object())
+raises(TypeError, gullwing.format, '{}{y}', 1, y=2)
+raises(KeyError, lambda: gullwing.parse('{a}', 'b')['c'])
+");
+    }
+}
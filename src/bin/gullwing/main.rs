@@ -0,0 +1,423 @@
+//! `gullwing`: parse and reformat structured text from the command line.
+//!
+//! Install it with `cargo install gullwing --features cli`; the binary is
+//! only built with the `cli` feature.
+//!
+//! # Usage
+//!
+//! ```bash
+//! echo "2024-01-15 INFO Hello" | gullwing "{date} {level} {message}" "{level}: {message}"
+//! # Output: INFO: Hello
+//! ```
+//!
+//! `gullwing --output shell INPUT_FORMAT` prints the fields of each record as
+//! shell variable assignments instead, for `eval` in a script:
+//!
+//! ```bash
+//! echo "Alice 30" | gullwing --output shell "{name} {age:d}"
+//! # Output: name='Alice'; age=30
+//! ```
+//!
+//! With `--output json` each record becomes a JSON object on its own line,
+//! ready for `jq` and log shippers:
+//!
+//! ```bash
+//! echo "Alice 30" | gullwing --output json "{name} {age:d}"
+//! # Output: {"name":"Alice","age":30}
+//! ```
+//!
+//! `gullwing --check INPUT_FORMAT OUTPUT_FORMAT` tries the formats on the first
+//! records and reports what became of each, without transforming the input.
+//!
+//! `gullwing repl SAMPLES` opens an interactive session for developing the
+//! two patterns against sample lines.
+
+mod repl;
+
+use gullwing::transform::{Progress, SampleReport, Scratch};
+use gullwing::{Parser, Transformer};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+/// Number of records `--check` tries.
+const CHECK_RECORDS: usize = 10;
+
+const USAGE: &str = "\
+Usage: gullwing [OPTIONS] INPUT_FORMAT OUTPUT_FORMAT
+       gullwing [OPTIONS] --output shell|json INPUT_FORMAT
+       gullwing [OPTIONS] --check INPUT_FORMAT OUTPUT_FORMAT
+       gullwing repl SAMPLES [INPUT_FORMAT [OUTPUT_FORMAT]]
+
+Parse each record of the input with INPUT_FORMAT and write it out with
+OUTPUT_FORMAT, print its fields as shell variable assignments with
+--output shell or as a JSON object with --output json. --check tries the
+formats on the first records and reports on each instead, and 'repl'
+develops them interactively against the lines of SAMPLES.
+
+Options:
+  --skip               drop records that don't match INPUT_FORMAT (default)
+  --passthrough        copy records that don't match to the output unchanged
+  --error              stop with an error at the first record that doesn't match
+  --input-file FILE    read records from FILE instead of stdin
+  --output-file FILE   write to FILE instead of stdout
+  --errors-to FILE     also write records that match but fail to convert or
+                       format to FILE; they are always reported on stderr
+  --stop-on-error      stop with an error at the first record that fails to
+                       convert or format, instead of reporting it and going on
+  --null               end records with NUL instead of newline, in and out
+  --output MODE        write each record's fields in MODE, 'shell' or 'json'
+  --check              report what becomes of the first 10 records and fail if
+                       any can't be transformed, or doesn't match with --error
+  -h, --help           show this help
+
+Set GULLWING_PROGRESS=1 to print byte, record and match counts to stderr.
+
+Example:
+  echo '2024-01-15 INFO Hello' | gullwing '{date} {level} {message}' '{level}: {message}'
+  Output: INFO: Hello";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("repl") {
+        process::exit(repl::run(&args[1..]));
+    }
+
+    let options = match Options::parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("gullwing: {}", e);
+            eprintln!("Try 'gullwing --help' for more information.");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("gullwing: {}", e);
+        process::exit(1);
+    }
+}
+
+/// What happens to a record that doesn't match the input pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unmatched {
+    Skip,
+    Passthrough,
+    Error,
+}
+
+/// The command-line options, apart from the `repl` subcommand.
+#[derive(Debug)]
+struct Options {
+    unmatched: Unmatched,
+    input_file: Option<String>,
+    output_file: Option<String>,
+    errors_file: Option<String>, // Where failed records go, besides stderr
+    stop_on_error: bool,         // Stop at the first failed record
+    delimiter: u8,               // Byte ending each record, in and out
+    mode: Option<String>,        // Output mode instead of an output pattern
+    check: bool,                 // Report on the first records instead of transforming
+    patterns: Vec<String>,
+}
+
+impl Options {
+    /// Read the options from the arguments, or `None` if help was asked for.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut options = Options {
+            unmatched: Unmatched::Skip,
+            input_file: None,
+            output_file: None,
+            errors_file: None,
+            stop_on_error: false,
+            delimiter: b'\n',
+            mode: None,
+            check: false,
+            patterns: Vec::new(),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("option '{}' needs a value", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--skip" => options.unmatched = Unmatched::Skip,
+                "--passthrough" => options.unmatched = Unmatched::Passthrough,
+                "--error" => options.unmatched = Unmatched::Error,
+                "--input-file" => options.input_file = Some(value()?),
+                "--output-file" => options.output_file = Some(value()?),
+                "--errors-to" => options.errors_file = Some(value()?),
+                "--stop-on-error" => options.stop_on_error = true,
+                "--null" => options.delimiter = b'\0',
+                "--output" => options.mode = Some(value()?),
+                "--check" => options.check = true,
+                "--" => {
+                    options.patterns.extend(args.by_ref().cloned());
+                    break;
+                }
+                option if option.starts_with("--") => {
+                    return Err(format!("unknown option '{}'", option));
+                }
+                pattern => options.patterns.push(pattern.to_string()),
+            }
+        }
+
+        let expected = match options.mode.as_deref() {
+            None => 2,
+            Some("shell" | "json") => 1,
+            Some(mode) => {
+                return Err(format!(
+                    "unknown output mode '{}'; the modes are 'shell' and 'json'",
+                    mode
+                ))
+            }
+        };
+        if options.stop_on_error && options.errors_file.is_some() {
+            return Err("'--stop-on-error' and '--errors-to' can't be used together".to_string());
+        }
+        if options.check && options.mode.is_some() {
+            return Err("'--check' needs an OUTPUT_FORMAT instead of '--output'".to_string());
+        }
+        if options.patterns.len() != expected {
+            return Err(match expected {
+                1 => "expected INPUT_FORMAT".to_string(),
+                _ => "expected INPUT_FORMAT and OUTPUT_FORMAT".to_string(),
+            });
+        }
+        Ok(Some(options))
+    }
+}
+
+/// How each matching record is written out.
+enum Output {
+    Format(Box<(Transformer, Scratch)>),
+    Shell(Box<Parser>),
+    Json(Box<Parser>),
+}
+
+/// Transform every record of the input, returning an error message on failure.
+fn run(options: &Options) -> Result<(), String> {
+    let input = &options.patterns[0];
+    let mut output = match options.patterns.get(1) {
+        Some(output) => Transformer::new(input, output).map(|transformer| {
+            let scratch = transformer.scratch();
+            Output::Format(Box::new((transformer, scratch)))
+        }),
+        None => Parser::new(input).map(|parser| match options.mode.as_deref() {
+            Some("json") => Output::Json(Box::new(parser)),
+            _ => Output::Shell(Box::new(parser)),
+        }),
+    }
+    .map_err(|e| format!("error in formats: {}", e))?;
+
+    let mut reader: Box<dyn BufRead> = match &options.input_file {
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut writer: Box<dyn Write> = match &options.output_file {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    if let (true, Output::Format(format)) = (options.check, &output) {
+        return check(&format.0, &mut *reader, &mut *writer, options);
+    }
+
+    let mut errors = match &options.errors_file {
+        Some(path) => Some(BufWriter::new(
+            File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => None,
+    };
+
+    let show_progress = env::var_os("GULLWING_PROGRESS").is_some();
+    let delimiter = options.delimiter;
+    let mut record = Vec::new();
+    let mut out = String::new();
+    let mut totals = Progress::default();
+
+    while let Some((read, text)) =
+        read_record(&mut *reader, delimiter, &mut record, totals.lines + 1)?
+    {
+        totals.bytes += read as u64;
+        totals.lines += 1;
+
+        out.clear();
+        let matched = match &mut output {
+            Output::Format(format) => {
+                let (transformer, scratch) = &mut **format;
+                transformer.transform_into(text, &mut out, scratch)
+            }
+            Output::Shell(parser) => parser
+                .parse(text)
+                .map(|result| result.map(|r| out.push_str(&r.to_env())).is_some()),
+            Output::Json(parser) => parser
+                .parse(text)
+                .map(|result| result.map(|r| out.push_str(&r.to_json())).is_some()),
+        }
+        .map_err(|e| format!("record {}: {}", totals.lines, e));
+
+        // Report a failed record and go on, setting it aside if asked to
+        let matched = match matched {
+            Ok(matched) => matched,
+            Err(message) if options.stop_on_error => return Err(message),
+            Err(message) => {
+                eprintln!("gullwing: {}", message);
+                if let Some(errors) = &mut errors {
+                    errors
+                        .write_all(text.as_bytes())
+                        .and_then(|()| errors.write_all(&[delimiter]))
+                        .map_err(|e| format!("error writing failed records: {}", e))?;
+                }
+                progress(show_progress, totals, false);
+                continue;
+            }
+        };
+
+        let write = if matched {
+            totals.matches += 1;
+            true
+        } else {
+            match options.unmatched {
+                Unmatched::Skip => false,
+                Unmatched::Passthrough => {
+                    out.push_str(text);
+                    true
+                }
+                Unmatched::Error => {
+                    return Err(format!(
+                        "record {} doesn't match the input format",
+                        totals.lines
+                    ))
+                }
+            }
+        };
+        if write {
+            out.push(char::from(delimiter));
+            writer
+                .write_all(out.as_bytes())
+                .map_err(|e| format!("error writing output: {}", e))?;
+        }
+        progress(show_progress, totals, false);
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("error writing output: {}", e))?;
+    if let Some(errors) = &mut errors {
+        errors
+            .flush()
+            .map_err(|e| format!("error writing failed records: {}", e))?;
+    }
+    progress(show_progress, totals, true);
+    Ok(())
+}
+
+/// Read the next record into `record`, returning the number of bytes read
+/// and its text, or `None` at the end of the input.
+///
+/// `number` is the 1-based number of the record, for error messages.
+fn read_record<'r>(
+    reader: &mut dyn BufRead,
+    delimiter: u8,
+    record: &'r mut Vec<u8>,
+    number: u64,
+) -> Result<Option<(usize, &'r str)>, String> {
+    record.clear();
+    let read = reader
+        .read_until(delimiter, record)
+        .map_err(|e| format!("error reading input: {}", e))?;
+    if read == 0 {
+        return Ok(None);
+    }
+
+    // Strip the delimiter, and a `\r` before a newline
+    let mut bytes = record.strip_suffix(&[delimiter]).unwrap_or(record);
+    if delimiter == b'\n' {
+        bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    }
+    let text =
+        std::str::from_utf8(bytes).map_err(|_| format!("record {} is not valid UTF-8", number))?;
+    Ok(Some((read, text)))
+}
+
+/// Try the transformation on the first records, writing a line about each
+/// and a summary, and fail if any record failed.
+fn check(
+    transformer: &Transformer,
+    reader: &mut dyn BufRead,
+    writer: &mut dyn Write,
+    options: &Options,
+) -> Result<(), String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    while records.len() < CHECK_RECORDS {
+        let number = records.len() as u64 + 1;
+        match read_record(reader, options.delimiter, &mut record, number)? {
+            Some((_, text)) => records.push(text.to_string()),
+            None => break,
+        }
+    }
+
+    let report = transformer.sample(&records, CHECK_RECORDS);
+    write_report(writer, &report).map_err(|e| format!("error writing output: {}", e))?;
+
+    let mut failed = report.failed();
+    if options.unmatched == Unmatched::Error {
+        failed += report.unmatched();
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!(
+            "{} of {} records failed the check",
+            failed,
+            report.lines.len()
+        )),
+    }
+}
+
+/// Write a line for each sampled record, then the counts.
+fn write_report(writer: &mut dyn Write, report: &SampleReport) -> io::Result<()> {
+    for (number, sample) in (1..).zip(&report.lines) {
+        match &sample.output {
+            Ok(Some(output)) => writeln!(writer, "{}: ok: {}", number, output)?,
+            Ok(None) => writeln!(writer, "{}: no match: {}", number, sample.line)?,
+            Err(e) => writeln!(writer, "{}: error: {}", number, e)?,
+        }
+    }
+    writeln!(
+        writer,
+        "{} records: {} transformed, {} didn't match, {} failed",
+        report.lines.len(),
+        report.transformed(),
+        report.unmatched(),
+        report.failed()
+    )?;
+    writer.flush()
+}
+
+/// Print the running totals to stderr every 10,000 records, and at the end.
+fn progress(show: bool, totals: Progress, done: bool) {
+    if show && (done || totals.lines.is_multiple_of(10_000)) {
+        eprint!(
+            "\r{} bytes, {} records, {} matches{}",
+            totals.bytes,
+            totals.lines,
+            totals.matches,
+            if done { "\n" } else { "" }
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! `gullwing repl`: develop the input and output patterns against sample lines.
+//!
+//! Every change runs the patterns over all samples again and prints what each
+//! line parses to, with typed values and the formatted output, or why it
+//! doesn't match.
+
+use gullwing::{Error, Formatter, Parser};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+const HELP: &str = "\
+Commands:
+  in PATTERN     set the input pattern
+  out [PATTERN]  set the output pattern, or clear it
+  add LINE       add a sample line
+  load FILE      replace the samples with the lines of FILE
+  show           run the patterns over the samples again
+  export [FILE]  print the gullwing command for the patterns, or write it to FILE
+  help           show this help
+  quit           leave the repl";
+
+/// Run the repl with the arguments after `repl`, returning the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let Some(samples) = args.first() else {
+        eprintln!("Usage: gullwing repl SAMPLES [INPUT_FORMAT [OUTPUT_FORMAT]]");
+        return 1;
+    };
+
+    let mut session = Session {
+        samples: Vec::new(),
+        input: args.get(1).cloned().unwrap_or_default(),
+        output: args.get(2).cloned().unwrap_or_default(),
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = session.load(samples).and_then(|()| {
+        writeln!(
+            out,
+            "{} sample lines; type 'help' for commands",
+            session.samples.len()
+        )?;
+        session.show(&mut out)?;
+        session.repl(io::stdin().lock(), &mut out)
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// The patterns being developed and the lines they're tried on.
+struct Session {
+    samples: Vec<String>,
+    input: String,
+    output: String, // Empty to only show parse results
+}
+
+impl Session {
+    /// Replace the samples with the lines of a file.
+    fn load(&mut self, path: &str) -> io::Result<()> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        self.samples = text.lines().map(String::from).collect();
+        Ok(())
+    }
+
+    /// Read and run commands until `quit` or the end of the input.
+    fn repl(&mut self, commands: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        let interactive = io::stdin().is_terminal();
+        let mut commands = commands.lines();
+        loop {
+            if interactive {
+                write!(out, "gullwing> ")?;
+                out.flush()?;
+            }
+            let Some(line) = commands.next().transpose()? else {
+                return Ok(());
+            };
+
+            // Patterns are taken verbatim after the first space
+            let (command, rest) = line.split_once(' ').unwrap_or((&line, ""));
+            match command {
+                "" => {}
+                "in" => {
+                    self.input = rest.to_string();
+                    self.show(out)?;
+                }
+                "out" => {
+                    self.output = rest.to_string();
+                    self.show(out)?;
+                }
+                "add" => {
+                    self.samples.push(rest.to_string());
+                    self.show(out)?;
+                }
+                "load" => match self.load(rest) {
+                    Ok(()) => self.show(out)?,
+                    Err(e) => writeln!(out, "error: {}", e)?,
+                },
+                "show" => self.show(out)?,
+                "export" => self.export(rest, out)?,
+                "help" => writeln!(out, "{}", HELP)?,
+                "quit" | "exit" => return Ok(()),
+                _ => writeln!(
+                    out,
+                    "unknown command '{}'; type 'help' for commands",
+                    command
+                )?,
+            }
+        }
+    }
+
+    /// Run the patterns over every sample and print the results.
+    fn show(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.input.is_empty() {
+            return writeln!(out, "no input pattern; set one with 'in PATTERN'");
+        }
+        let parser = match Parser::new(&self.input) {
+            Ok(parser) => parser,
+            Err(e) => return pattern_error(out, "input", &self.input, &e),
+        };
+        let formatter = match self.output.as_str() {
+            "" => None,
+            pattern => match Formatter::new(pattern) {
+                Ok(formatter) => Some(formatter),
+                Err(e) => return pattern_error(out, "output", pattern, &e),
+            },
+        };
+
+        let mut matched = 0;
+        for (i, line) in self.samples.iter().enumerate() {
+            writeln!(out, "[{}] {}", i + 1, line)?;
+            let result = match parser.parse(line) {
+                Ok(Some(result)) => result,
+                Ok(None) => {
+                    writeln!(out, "    no match: {}", mismatch(&parser, line))?;
+                    continue;
+                }
+                Err(e) => {
+                    writeln!(out, "    error: {}", e)?;
+                    continue;
+                }
+            };
+            matched += 1;
+
+            let mut fields: Vec<_> = result.spans().iter().collect();
+            fields.sort_by_key(|(_, span)| span.start);
+            let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            for (name, _) in fields {
+                if let Some(value) = result.get(name) {
+                    writeln!(out, "    {:<width$} = {:?}", name, value, width = width)?;
+                }
+            }
+
+            if let Some(formatter) = &formatter {
+                match formatter.format_map(result.values()) {
+                    Ok(text) => writeln!(out, "    -> {}", text)?,
+                    Err(e) => writeln!(out, "    output error: {}", e)?,
+                }
+            }
+        }
+        writeln!(out, "{} of {} lines match", matched, self.samples.len())
+    }
+
+    /// Print the gullwing command for the patterns, or write it to a file.
+    fn export(&self, path: &str, out: &mut impl Write) -> io::Result<()> {
+        let command = format!(
+            "gullwing {} {}",
+            shell_quote(&self.input),
+            shell_quote(&self.output)
+        );
+        if path.is_empty() {
+            return writeln!(out, "{}", command);
+        }
+        match fs::write(path, format!("{}\n", command)) {
+            Ok(()) => writeln!(out, "wrote {}", path),
+            Err(e) => writeln!(out, "error: {}: {}", path, e),
+        }
+    }
+}
+
+/// Print an error in a pattern, pointing at where it was found.
+fn pattern_error(
+    out: &mut impl Write,
+    which: &str,
+    pattern: &str,
+    error: &Error,
+) -> io::Result<()> {
+    writeln!(out, "{} pattern error: {}", which, error)?;
+    if let Some(position) = error.position() {
+        let column = pattern.get(..position).map_or(0, |s| s.chars().count());
+        writeln!(out, "    {}", pattern)?;
+        writeln!(out, "    {}^", " ".repeat(column))?;
+    }
+    Ok(())
+}
+
+/// Explain why a line doesn't match, as far as the parser can tell.
+fn mismatch(parser: &Parser, line: &str) -> String {
+    if !line.starts_with(parser.literal_prefix()) {
+        format!("doesn't start with {:?}", parser.literal_prefix())
+    } else if !line.ends_with(parser.literal_suffix()) {
+        format!("doesn't end with {:?}", parser.literal_suffix())
+    } else if let Ok(Some(found)) = parser.search(line) {
+        let span = found.match_span();
+        format!("only bytes {}..{} match", span.start, span.end)
+    } else {
+        "the fields don't fit the text between the literals".to_string()
+    }
+}
+
+/// Quote a string for a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
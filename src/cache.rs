@@ -0,0 +1,205 @@
+//! Process-wide caches of compiled patterns.
+//!
+//! Code that receives the same pattern strings over and over, such as a
+//! service formatting with per-request templates, can fetch them here
+//! instead of compiling each time: [`formatter`] and [`parser`] compile a
+//! pattern on first use and share it afterwards. Each cache holds the
+//! [`CAPACITY`] most recently used patterns, dropping the least recently
+//! used one to make room.
+//!
+//! Nothing is cached unless these functions are called; [`Formatter::new`]
+//! and [`Parser::new`](crate::Parser::new) always compile.
+//!
+//! # Examples
+//!
+//! ```
+//! use gullwing::cache;
+//! use std::collections::HashMap;
+//!
+//! let values = HashMap::from([("user", "ann")]);
+//! for _ in 0..3 {
+//!     // Compiled on the first pass only
+//!     let formatter = cache::formatter("hello, {user}").unwrap();
+//!     assert_eq!(formatter.format_map(&values).unwrap(), "hello, ann");
+//! }
+//! ```
+
+use crate::error::Result;
+use crate::format::Formatter;
+#[cfg(feature = "parse")]
+use crate::parse::Parser;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// The most patterns each cache keeps.
+pub const CAPACITY: usize = 256;
+
+/// Get the formatter for `pattern`, compiling it if it isn't cached.
+///
+/// Fails if the pattern is invalid; invalid patterns aren't cached.
+pub fn formatter(pattern: &str) -> Result<Arc<Formatter>> {
+    get_or_compile(formatters(), pattern, Formatter::new)
+}
+
+/// Get the parser for `pattern`, compiling it if it isn't cached.
+///
+/// Fails if the pattern is invalid; invalid patterns aren't cached.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::cache;
+///
+/// let parser = cache::parser("{key}={value:d}").unwrap();
+/// let result = parser.parse("retries=3").unwrap().unwrap();
+/// assert_eq!(result.get("value").unwrap().as_int(), Some(3));
+/// ```
+#[cfg(feature = "parse")]
+pub fn parser(pattern: &str) -> Result<Arc<Parser>> {
+    get_or_compile(parsers(), pattern, Parser::new)
+}
+
+/// Drop every cached formatter and parser.
+///
+/// Formatters and parsers already handed out stay usable.
+pub fn clear() {
+    lock(formatters()).clear();
+    #[cfg(feature = "parse")]
+    lock(parsers()).clear();
+}
+
+/// The cache behind [`formatter`].
+fn formatters() -> &'static Mutex<Lru<Formatter>> {
+    static FORMATTERS: OnceLock<Mutex<Lru<Formatter>>> = OnceLock::new();
+    FORMATTERS.get_or_init(|| Mutex::new(Lru::new(CAPACITY)))
+}
+
+/// The cache behind [`parser`].
+#[cfg(feature = "parse")]
+fn parsers() -> &'static Mutex<Lru<Parser>> {
+    static PARSERS: OnceLock<Mutex<Lru<Parser>>> = OnceLock::new();
+    PARSERS.get_or_init(|| Mutex::new(Lru::new(CAPACITY)))
+}
+
+/// Lock a cache, carrying on if another thread panicked holding it.
+fn lock<T>(cache: &Mutex<Lru<T>>) -> MutexGuard<'_, Lru<T>> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Get `pattern` from `cache`, compiling and caching it on a miss.
+fn get_or_compile<T>(
+    cache: &Mutex<Lru<T>>,
+    pattern: &str,
+    compile: impl FnOnce(&str) -> Result<T>,
+) -> Result<Arc<T>> {
+    if let Some(compiled) = lock(cache).get(pattern) {
+        event!(TRACE, pattern, "pattern cache hit");
+        return Ok(compiled);
+    }
+    event!(TRACE, pattern, "pattern cache miss");
+
+    // Compile outside the lock, so other patterns aren't held up
+    let compiled = Arc::new(compile(pattern)?);
+    Ok(lock(cache).insert(pattern, compiled))
+}
+
+/// Compiled patterns by their text, dropping the least recently used when
+/// full.
+#[derive(Debug)]
+struct Lru<T> {
+    entries: HashMap<String, (Arc<T>, u64)>, // Value and when it was last used
+    clock: u64,
+    capacity: usize,
+}
+
+impl<T> Lru<T> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            entries: HashMap::new(),
+            clock: 0,
+            capacity,
+        }
+    }
+
+    /// Get a cached value, marking it as just used.
+    fn get(&mut self, key: &str) -> Option<Arc<T>> {
+        self.clock += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        *used = self.clock;
+        Some(Arc::clone(value))
+    }
+
+    /// Cache a value, making room if needed, and return the cached value.
+    ///
+    /// If another thread cached the same key first, its value is kept, so
+    /// every caller shares one.
+    fn insert(&mut self, key: &str, value: Arc<T>) -> Arc<T> {
+        if let Some(cached) = self.get(key) {
+            return cached;
+        }
+        if self.capacity == 0 {
+            return value;
+        }
+        if self.entries.len() >= self.capacity {
+            // Only on misses, and over a few hundred entries at most
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries
+            .insert(key.to_string(), (Arc::clone(&value), self.clock));
+        value
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", Arc::new(1));
+        lru.insert("b", Arc::new(2));
+        assert_eq!(lru.get("a").as_deref(), Some(&1));
+
+        // "b" was used longest ago
+        lru.insert("c", Arc::new(3));
+        assert!(lru.get("b").is_none());
+        assert_eq!(lru.get("a").as_deref(), Some(&1));
+        assert_eq!(lru.get("c").as_deref(), Some(&3));
+
+        // The first value cached for a key wins
+        let kept = lru.insert("c", Arc::new(4));
+        assert_eq!(*kept, 3);
+
+        lru.clear();
+        assert!(lru.get("a").is_none());
+        assert_eq!(*Lru::new(0).insert("a", Arc::new(5)), 5);
+    }
+
+    #[test]
+    fn test_shared_compiles() {
+        let first = formatter("{cache_test:^9}").unwrap();
+        let second = formatter(&String::from("{cache_test:^9}")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(formatter("{cache_test").is_err());
+
+        #[cfg(feature = "parse")]
+        {
+            let first = parser("{cache_test:d}").unwrap();
+            assert!(Arc::ptr_eq(&first, &parser("{cache_test:d}").unwrap()));
+            assert!(parser("{cache_test:q}").is_err());
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Parsing and writing CSV, with a pattern per column.
+//!
+//! [`CsvParser`] reads records by their header: each column becomes a field
+//! named after its header, unless a pattern is given for the column, whose
+//! fields are parsed from the cell instead. [`CsvWriter`] goes the other
+//! way, formatting each column of a row with its own pattern and quoting
+//! cells as CSV requires.
+//!
+//! # Examples
+//!
+//! ```
+//! use gullwing::csv::{CsvParser, CsvWriter};
+//!
+//! let input = "host,latency,path\ndb1,12ms,/a\nweb,3ms,\"/b,c\"\n";
+//! let parser = CsvParser::new().with_column("latency", "{ms:d}ms").unwrap();
+//!
+//! let mut writer = CsvWriter::new(Vec::new(), [("server", "{host:>4}"), ("ms", "{ms:03d}")]).unwrap();
+//! for record in parser.records(input.as_bytes()).unwrap() {
+//!     let result = record.unwrap().unwrap();
+//!     writer.write_record(result.values()).unwrap();
+//! }
+//!
+//! let output = writer.into_inner().unwrap();
+//! assert_eq!(output, b"server,ms\n db1,012\n web,003\n");
+//! ```
+
+use crate::error::{Error, Result};
+use crate::format::{Formatter, ValueProvider};
+use crate::parse::{ParseResult, Parser};
+use crate::types::Value;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+/// Parses CSV records into [`ParseResult`]s, one field per column.
+///
+/// The first record is the header. A column without a pattern becomes a
+/// string field named after its header; a column given a pattern with
+/// [`with_column`](Self::with_column) is parsed with it, and contributes the
+/// pattern's fields instead.
+#[derive(Debug, Clone, Default)]
+pub struct CsvParser {
+    patterns: Vec<(String, Parser)>, // Column header and its parser
+}
+
+impl CsvParser {
+    /// Create a parser that makes a string field of every column.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the cells of the column headed `header` with `pattern`.
+    ///
+    /// Fails if the pattern is invalid. A record whose cell doesn't match
+    /// the pattern yields `None`.
+    pub fn with_column(self, header: &str, pattern: &str) -> Result<Self> {
+        Ok(self.with_column_parser(header, Parser::new(pattern)?))
+    }
+
+    /// Parse the cells of the column headed `header` with a parser built
+    /// elsewhere, such as with custom types.
+    pub fn with_column_parser(mut self, header: &str, parser: Parser) -> Self {
+        self.patterns.retain(|(name, _)| name != header);
+        self.patterns.push((header.to_string(), parser));
+        self
+    }
+
+    /// Read the header of `reader` and iterate over its records.
+    ///
+    /// Fails if the header can't be read or lacks a column given a pattern.
+    pub fn records<R: io::Read>(&self, reader: R) -> Result<CsvRecords<'_, R>> {
+        let mut reader = ::csv::Reader::from_reader(reader);
+        let headers = reader.headers().map_err(Error::write)?;
+
+        if let Some((header, _)) = self
+            .patterns
+            .iter()
+            .find(|(header, _)| !headers.iter().any(|h| h == header))
+        {
+            return Err(Error::missing(header.as_str()));
+        }
+
+        let mut names = Vec::new();
+        let columns = headers
+            .iter()
+            .map(
+                |header| match self.patterns.iter().find(|(h, _)| h == header) {
+                    Some((_, parser)) => {
+                        names.extend_from_slice(parser.field_names());
+                        Column::Pattern(parser)
+                    }
+                    None => {
+                        names.push(header.to_string());
+                        Column::Field(header.to_string())
+                    }
+                },
+            )
+            .collect();
+
+        Ok(CsvRecords {
+            reader,
+            record: ::csv::StringRecord::new(),
+            columns,
+            names: names.into(),
+        })
+    }
+}
+
+/// How one column of a record becomes fields.
+#[derive(Debug)]
+enum Column<'p> {
+    Field(String),       // A string field named after the header
+    Pattern(&'p Parser), // The fields of a pattern matched against the cell
+}
+
+/// An iterator over the records of a CSV input, from [`CsvParser::records`].
+///
+/// Yields `Ok(None)` for a record with a cell that doesn't match its
+/// column's pattern, and an error for a cell that fails to convert or a
+/// record that isn't valid CSV, such as one with too few cells.
+///
+/// Each result's [`text()`](ParseResult::text) is the record's cells joined
+/// with commas, unquoted, and its spans point into that text.
+#[derive(Debug)]
+pub struct CsvRecords<'p, R> {
+    reader: ::csv::Reader<R>,
+    record: ::csv::StringRecord, // Reused for every record
+    columns: Vec<Column<'p>>,
+    names: Arc<[String]>, // Field names, in column order
+}
+
+impl<R: io::Read> CsvRecords<'_, R> {
+    /// Turn the current record into a result.
+    fn parse_record(&self) -> Result<Option<ParseResult>> {
+        let mut text = String::new();
+        let mut values = HashMap::with_capacity(self.names.len());
+        let mut spans = HashMap::with_capacity(self.names.len());
+
+        for (i, (cell, column)) in self.record.iter().zip(&self.columns).enumerate() {
+            if i > 0 {
+                text.push(',');
+            }
+            let start = text.len();
+            text.push_str(cell);
+
+            match column {
+                Column::Field(name) => {
+                    values.insert(name.clone(), Value::from(cell));
+                    spans.insert(name.clone(), start..text.len());
+                }
+                Column::Pattern(parser) => {
+                    let Some(result) = parser.parse(cell)? else {
+                        return Ok(None);
+                    };
+                    let (cell_values, cell_spans) = result.into_parts();
+                    values.extend(cell_values);
+                    spans.extend(
+                        cell_spans
+                            .into_iter()
+                            .map(|(name, span)| (name, span.start + start..span.end + start)),
+                    );
+                }
+            }
+        }
+
+        Ok(Some(ParseResult::from_parts(
+            text,
+            values,
+            spans,
+            Arc::clone(&self.names),
+        )))
+    }
+}
+
+impl<R: io::Read> Iterator for CsvRecords<'_, R> {
+    type Item = Result<Option<ParseResult>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(self.parse_record()),
+            Ok(false) => None,
+            Err(e) => Some(Err(Error::write(e))),
+        }
+    }
+}
+
+/// Writes CSV rows, formatting each column with its own pattern.
+///
+/// The header row is written when the writer is created. Cells holding
+/// commas, quotes or line breaks are quoted.
+#[derive(Debug)]
+pub struct CsvWriter<W: io::Write> {
+    writer: ::csv::Writer<W>,
+    columns: Vec<Formatter>, // Pattern for each column, in order
+}
+
+impl<W: io::Write> CsvWriter<W> {
+    /// Create a writer with a column for each header and pattern, and write
+    /// the header row.
+    ///
+    /// A pattern that is just the field, such as `"{name}"`, copies the
+    /// field's value. Fails if a pattern is invalid or the header row can't
+    /// be written.
+    pub fn new<I, H, P>(writer: W, columns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (H, P)>,
+        H: AsRef<str>,
+        P: AsRef<str>,
+    {
+        let mut headers = Vec::new();
+        let columns = columns
+            .into_iter()
+            .map(|(header, pattern)| {
+                headers.push(header.as_ref().to_string());
+                Formatter::new(pattern.as_ref())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut writer = ::csv::Writer::from_writer(writer);
+        writer.write_record(&headers).map_err(Error::write)?;
+        Ok(CsvWriter { writer, columns })
+    }
+
+    /// Format a row from `values`, such as a [`ParseResult::values`] map,
+    /// and write it.
+    ///
+    /// Fails without writing anything if a column can't be formatted.
+    pub fn write_record<P: ValueProvider + ?Sized>(&mut self, values: &P) -> Result<()> {
+        let cells = self
+            .columns
+            .iter()
+            .map(|column| column.format_with(values))
+            .collect::<Result<Vec<_>>>()?;
+        self.writer.write_record(&cells).map_err(Error::write)
+    }
+
+    /// Write any buffered rows to the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(Error::write)
+    }
+
+    /// Flush the buffered rows and return the underlying writer.
+    pub fn into_inner(self) -> Result<W> {
+        self.writer.into_inner().map_err(Error::write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_records() {
+        let input = "when,level,msg\n2024-01-15 10:00,INFO,\"hello, world\"\n2024-01-16 11:30,WARN,\"say \"\"hi\"\"\"\nyesterday,INFO,x\n";
+        let parser = CsvParser::new()
+            .with_column("when", "{date} {hour:d}:{minute:d}")
+            .unwrap();
+        let records: Vec<_> = parser
+            .records(input.as_bytes())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+
+        let first = records[0].as_ref().unwrap();
+        let names: Vec<_> = first.fields().map(|(name, _)| name).collect();
+        assert_eq!(names, ["date", "hour", "minute", "level", "msg"]);
+        assert_eq!(first.get("hour"), Some(&Value::from(10)));
+        assert_eq!(first.get("msg"), Some(&Value::from("hello, world")));
+        assert_eq!(first.text(), "2024-01-15 10:00,INFO,hello, world");
+        assert_eq!(&first.text()[first.span("minute").unwrap()], "00");
+        assert_eq!(&first.text()[first.span("msg").unwrap()], "hello, world");
+
+        let second = records[1].as_ref().unwrap();
+        assert_eq!(second.get("msg"), Some(&Value::from("say \"hi\"")));
+
+        // A cell that doesn't match its pattern
+        assert!(records[2].is_none());
+    }
+
+    #[test]
+    fn test_record_errors() {
+        let parser = CsvParser::new().with_column("n", "{n:d}").unwrap();
+        let err = parser.records("a,b\n1,2\n".as_bytes()).unwrap_err();
+        assert_eq!(err, Error::missing("n"));
+
+        let mut records = parser.records("n,m\n1,2\n3\n".as_bytes()).unwrap();
+        assert!(records.next().unwrap().unwrap().is_some());
+        assert_eq!(
+            records.next().unwrap().unwrap_err().kind(),
+            ErrorKind::WriteError
+        );
+
+        assert!(CsvParser::new().with_column("n", "{n").is_err());
+
+        // A later pattern for the same column replaces the earlier one
+        let parser = parser.with_column("n", "{x}").unwrap();
+        let result = parser.records("n\nabc\n".as_bytes()).unwrap().next();
+        assert_eq!(
+            result.unwrap().unwrap().unwrap().get("x"),
+            Some(&Value::from("abc"))
+        );
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut writer = CsvWriter::new(
+            Vec::new(),
+            [
+                ("name", "{name}"),
+                ("score", "{score:.1f}"),
+                ("note", "{name}: {note}"),
+            ],
+        )
+        .unwrap();
+        let row = HashMap::from([
+            ("name", Value::from("Ann")),
+            ("score", Value::from(9.25)),
+            ("note", Value::from("said \"ok\", left\nearly")),
+        ]);
+        writer.write_record(&row).unwrap();
+
+        // A failed row writes nothing
+        let err = writer
+            .write_record(&HashMap::from([("name", Value::from("Bo"))]))
+            .unwrap_err();
+        assert_eq!(err, Error::missing("score"));
+
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "name,score,note\nAnn,9.2,\"Ann: said \"\"ok\"\", left\nearly\"\n"
+        );
+
+        // Rows written from parsed records read back the same
+        let parser = CsvParser::new();
+        let records: Vec<_> = parser.records(output.as_bytes()).unwrap().collect();
+        let result = records[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(
+            result.get("note"),
+            Some(&Value::from("Ann: said \"ok\", left\nearly"))
+        );
+
+        assert!(CsvWriter::new(Vec::new(), [("a", "{a")]).is_err());
+    }
+}
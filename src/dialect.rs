@@ -0,0 +1,230 @@
+//! Dialects: which pattern features a [`Formatter`](crate::Formatter) or
+//! [`Parser`](crate::Parser) accepts.
+//!
+//! gullwing reads a superset of Python's pattern syntax. Teams that share
+//! templates with Python, or with C code, can lock a formatter or parser to
+//! the features the other side understands by building it with a
+//! [`Dialect`]. The dialect only decides which patterns are accepted; a
+//! pattern accepted by a stricter dialect behaves the same in all of them.
+//!
+//! Each feature belongs to one tier:
+//!
+//! | Feature | [`Printf`](Dialect::Printf) | [`Python312`](Dialect::Python312) | [`GullwingExtended`](Dialect::GullwingExtended) |
+//! |---------|:---:|:---:|:---:|
+//! | Width, precision, `<` and `>` alignment, `+` and space signs, `#`, `0` | ✓ | ✓ | ✓ |
+//! | Types `s c d o x X e E f F g G` | ✓ | ✓ | ✓ |
+//! | Fill characters, `^` and `=` alignment, `z`, `,` and `_` grouping | | ✓ | ✓ |
+//! | Types `b`, `n` and `%` | | ✓ | ✓ |
+//! | Conversion flags and attribute or item paths (`{user.name!r}`) | | ✓ | ✓ |
+//! | Datetime formats (`{ts:%Y-%m-%d}`) | | ✓ | ✓ |
+//! | Parse types, built in (`{ts:ti}`) or registered | | ✓ | ✓ |
+//! | Inline parse regexes (`{id:/[A-Z]+\d+/}`) | | | ✓ |
+//! | Parse choice sets (`{status:(OK\|FAIL)}`) | | | ✓ |
+//!
+//! Moving a feature to a stricter tier is a breaking change, and is listed
+//! in the changelog like one.
+
+use crate::error::{Error, Result};
+use crate::spec::{Alignment, TypeSpec};
+use crate::template::{Template, TemplateField};
+use std::fmt;
+
+/// A set of pattern features a formatter or parser accepts.
+///
+/// See the [module documentation](self) for the features of each dialect.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::dialect::Dialect;
+/// use gullwing::Formatter;
+///
+/// assert!(Formatter::new_with_dialect("{n:>8.2f}", Dialect::Printf).is_ok());
+///
+/// let err = Formatter::new_with_dialect("{n:,d}", Dialect::Printf).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "invalid format specification: grouping is not allowed in the printf dialect at position 1"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// What Python 3.12's `str.format` and the `parse` package accept
+    Python312,
+    /// Everything gullwing supports
+    #[default]
+    GullwingExtended,
+    /// Only what C's `printf` can express
+    Printf,
+}
+
+impl Dialect {
+    /// Check that every field of `template` uses only features of this dialect.
+    ///
+    /// Fails with [`Error::InvalidFormatSpec`] at the first field that
+    /// doesn't, naming the feature.
+    pub fn check(self, template: &Template) -> Result<()> {
+        for field in template.fields() {
+            if let Some(feature) = self.unsupported(field) {
+                return Err(Error::spec_at(
+                    format!("{} is not allowed in the {} dialect", feature, self),
+                    field.offset(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a description of the first feature of `field` this dialect lacks.
+    fn unsupported(self, field: &TemplateField) -> Option<&'static str> {
+        #[cfg(feature = "parse")]
+        if field.regex().is_some() && self != Dialect::GullwingExtended {
+            return Some("an inline regex");
+        }
+        #[cfg(feature = "parse")]
+        if field.choices().is_some() && self != Dialect::GullwingExtended {
+            return Some("a choice set");
+        }
+        if self != Dialect::Printf {
+            return None;
+        }
+        #[cfg(feature = "chrono")]
+        if field.strftime().is_some() {
+            return Some("a datetime format");
+        }
+        #[cfg(feature = "parse")]
+        if field.custom_type().is_some() {
+            return Some("a parse type");
+        }
+
+        let spec = field.spec();
+        let feature = if field.conversion().is_some() {
+            "a conversion flag"
+        } else if !field.path().is_empty() {
+            "an attribute or item path"
+        } else if spec.fill.is_some_and(|fill| fill != ' ') {
+            "a fill character"
+        } else if matches!(spec.align, Some(Alignment::Center | Alignment::AfterSign)) {
+            "this alignment"
+        } else if spec.zero_flag {
+            "negative zero coercion (z)"
+        } else if spec.grouping.is_some() {
+            "grouping"
+        } else if matches!(
+            spec.type_spec,
+            Some(TypeSpec::Binary | TypeSpec::Number | TypeSpec::Percentage)
+        ) {
+            "this type"
+        } else {
+            return None;
+        };
+        Some(feature)
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dialect::Python312 => "Python 3.12",
+            Dialect::GullwingExtended => "gullwing",
+            Dialect::Printf => "printf",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(dialect: Dialect, pattern: &str) -> Result<()> {
+        dialect.check(&Template::parse(pattern)?)
+    }
+
+    #[test]
+    fn test_printf() {
+        for pattern in [
+            "{:>8.2f}",
+            "{a:<5s}|{b:+#010x}",
+            "{: >4d}",
+            "{:e}{:G}{:c}{:o}",
+            "x",
+        ] {
+            assert!(check(Dialect::Printf, pattern).is_ok(), "{}", pattern);
+        }
+        for (pattern, feature) in [
+            ("{a!r}", "a conversion flag"),
+            ("{a.b}", "an attribute or item path"),
+            ("{a:*>5}", "a fill character"),
+            ("{a:^5}", "this alignment"),
+            ("{a:=5d}", "this alignment"),
+            ("{a:z.1f}", "negative zero coercion (z)"),
+            ("{a:_d}", "grouping"),
+            ("{a:b}", "this type"),
+            ("{a:.1%}", "this type"),
+        ] {
+            let err = check(Dialect::Printf, pattern).unwrap_err();
+            assert!(err.to_string().contains(feature), "{}: {}", pattern, err);
+            assert!(check(Dialect::Python312, pattern).is_ok(), "{}", pattern);
+        }
+
+        let err = check(Dialect::Printf, "ab {c:,} {d:n}").unwrap_err();
+        assert_eq!(
+            err,
+            Error::spec_at("grouping is not allowed in the printf dialect", 4)
+        );
+    }
+
+    #[test]
+    fn test_formatter_dialect() {
+        use crate::{FormatSpec, Formatter};
+
+        assert_eq!(
+            Formatter::new("{a}").unwrap().dialect(),
+            Dialect::GullwingExtended
+        );
+        let formatter = Formatter::new_with_dialect("{a:>5}", Dialect::Printf).unwrap();
+        assert_eq!(formatter.dialect(), Dialect::Printf);
+        assert!(formatter
+            .with_spec("a", FormatSpec::parse("<8").unwrap())
+            .is_ok());
+        let err = formatter
+            .with_spec("a", FormatSpec::parse("^8").unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("this alignment"));
+    }
+
+    #[cfg(feature = "parse")]
+    #[test]
+    fn test_parse_only_features() {
+        let template =
+            Template::parse_with_types(r"{id:/\d+/} {ts:ti}", &|spec| spec == "ti").unwrap();
+        let err = Dialect::Python312.check(&template).unwrap_err();
+        assert!(err.to_string().contains("an inline regex"));
+        assert!(Dialect::GullwingExtended.check(&template).is_ok());
+
+        let template = Template::parse_with_types("{s:(OK|FAIL)}", &|_| false).unwrap();
+        let err = Dialect::Python312.check(&template).unwrap_err();
+        assert!(err.to_string().contains("a choice set"));
+        assert!(Dialect::GullwingExtended.check(&template).is_ok());
+
+        let template = Template::parse_with_types("{ts:ti}", &|spec| spec == "ti").unwrap();
+        assert!(Dialect::Python312.check(&template).is_ok());
+        let err = Dialect::Printf.check(&template).unwrap_err();
+        assert!(err.to_string().contains("a parse type"));
+
+        let builder = crate::Parser::builder().with_type("hex", "[0-9a-f]+", |_| None);
+        assert!(builder
+            .clone()
+            .with_dialect(Dialect::Python312)
+            .build("{a:hex}")
+            .is_ok());
+        let parser = builder
+            .with_dialect(Dialect::Printf)
+            .build("{a:d}")
+            .unwrap();
+        assert_eq!(parser.dialect(), Dialect::Printf);
+        let parser = parser.with_anonymous_skipped(true).unwrap();
+        assert_eq!(parser.dialect(), Dialect::Printf);
+    }
+}
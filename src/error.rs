@@ -1,46 +1,533 @@
 //! Error types for the gullwing library.
 
+use std::fmt;
 use thiserror::Error;
 
 /// Errors that can occur when working with format specifications.
+///
+/// Each variant carries structured data so callers can react to errors
+/// without inspecting messages. Prefer [`Error::kind()`] and the helper
+/// predicates over exhaustive matching: new variants may be added in
+/// minor releases.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::error::ErrorKind;
+/// use gullwing::{Formatter, Value};
+/// use std::collections::HashMap;
+///
+/// let formatter = Formatter::new("{user}").unwrap();
+/// let err = formatter.format_map(&HashMap::<String, Value>::new()).unwrap_err();
+///
+/// assert_eq!(err.kind(), ErrorKind::MissingField);
+/// assert_eq!(err.field_name(), Some("user"));
+/// ```
 #[derive(Debug, Error, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// Invalid format specification syntax.
-    #[error("invalid format specification: {0}")]
-    InvalidFormatSpec(String),
+    #[error("invalid format specification: {message}{}", Position(.position))]
+    InvalidFormatSpec {
+        /// Description of the problem.
+        message: String,
+        /// Byte offset of the problem in the spec or pattern, if known.
+        position: Option<usize>,
+    },
 
     /// Unsupported type specifier.
-    #[error("unsupported type specifier: {0}")]
-    UnsupportedType(String),
+    #[error("unsupported type specifier: {type_spec}")]
+    UnsupportedType {
+        /// The type specifier as written.
+        type_spec: String,
+    },
 
     /// Parse error when matching a string against a format pattern.
-    #[error("parse error: {0}")]
-    ParseError(String),
+    #[cfg(feature = "parse")]
+    #[error("parse error: {message}")]
+    ParseError {
+        /// Description of the problem.
+        message: String,
+    },
 
-    /// Type conversion error when converting parsed strings to typed values.
-    #[error("type conversion error: {0}")]
-    ConversionError(String),
+    /// Type conversion error when converting values for formatting or parsing.
+    #[error("type conversion error: cannot convert {got} to {expected}")]
+    ConversionError {
+        /// The type the value was being converted to.
+        expected: String,
+        /// The offending value, as text.
+        got: String,
+    },
 
     /// Regex compilation or matching error.
-    #[error("regex error: {0}")]
-    RegexError(String),
+    #[cfg(feature = "parse")]
+    #[error("regex error: {message}")]
+    RegexError {
+        /// Description of the problem.
+        message: String,
+    },
 
     /// Missing required field in format operation.
-    #[error("missing field: {0}")]
-    MissingField(String),
+    #[error("missing field: {name}")]
+    MissingField {
+        /// Name (or `position N`) of the missing field.
+        name: String,
+    },
 
     /// Field name used is invalid.
-    #[error("invalid field name: {0}")]
-    InvalidFieldName(String),
+    #[error("invalid field name: {name}{}", Position(.position))]
+    InvalidFieldName {
+        /// The field name as written.
+        name: String,
+        /// Byte offset of the field name in the pattern, if known.
+        position: Option<usize>,
+    },
 
     /// Width or precision value is invalid.
-    #[error("invalid width or precision: {0}")]
-    InvalidWidth(String),
+    #[error("invalid width or precision: {value}")]
+    InvalidWidth {
+        /// The width or precision as written.
+        value: String,
+    },
+
+    /// Width or precision is larger than the formatter allows.
+    ///
+    /// See [`Formatter::with_max_width`](crate::Formatter::with_max_width).
+    #[error("width or precision {value} exceeds the limit of {limit}")]
+    WidthLimitExceeded {
+        /// The width or precision in the spec.
+        value: usize,
+        /// The largest width or precision allowed.
+        limit: usize,
+    },
+
+    /// Formatted output is longer than the formatter allows.
+    ///
+    /// See [`Formatter::with_max_len`](crate::Formatter::with_max_len).
+    #[error("output of {length} bytes exceeds the limit of {limit}")]
+    LengthLimitExceeded {
+        /// The length of the output, in bytes.
+        length: usize,
+        /// The largest length allowed, in bytes.
+        limit: usize,
+    },
 
     /// No match found when parsing.
+    #[cfg(feature = "parse")]
     #[error("no match found")]
     NoMatch,
+
+    /// A value bound with [`Formatter::bind`](crate::Formatter::bind) can't
+    /// be formatted by one of the fields it fills.
+    #[error("cannot bind {name}: {source}")]
+    BindError {
+        /// The field, as written in the pattern.
+        name: String,
+        /// Why the value couldn't be formatted.
+        source: Box<Error>,
+    },
+
+    /// Writing formatted output to a sink failed.
+    #[error("write error: {message}")]
+    WriteError {
+        /// Description of the underlying failure.
+        message: String,
+    },
+
+    /// Deserializing a Rust type from a [`Value`](crate::Value) failed.
+    #[cfg(feature = "serde")]
+    #[error("deserialize error: {message}")]
+    DeserializeError {
+        /// Description of the problem, as reported by `serde`.
+        message: String,
+    },
+
+    /// Serializing a Rust value into a [`Value`](crate::Value) failed.
+    #[cfg(feature = "serde")]
+    #[error("serialize error: {message}")]
+    SerializeError {
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+/// The category of an [`Error`], for programmatic handling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// See [`Error::InvalidFormatSpec`].
+    InvalidFormatSpec,
+    /// See [`Error::UnsupportedType`].
+    UnsupportedType,
+    /// See [`Error::ParseError`].
+    #[cfg(feature = "parse")]
+    ParseError,
+    /// See [`Error::ConversionError`].
+    ConversionError,
+    /// See [`Error::RegexError`].
+    #[cfg(feature = "parse")]
+    RegexError,
+    /// See [`Error::MissingField`].
+    MissingField,
+    /// See [`Error::InvalidFieldName`].
+    InvalidFieldName,
+    /// See [`Error::InvalidWidth`].
+    InvalidWidth,
+    /// See [`Error::WidthLimitExceeded`].
+    WidthLimitExceeded,
+    /// See [`Error::LengthLimitExceeded`].
+    LengthLimitExceeded,
+    /// See [`Error::NoMatch`].
+    #[cfg(feature = "parse")]
+    NoMatch,
+    /// See [`Error::BindError`].
+    BindError,
+    /// See [`Error::WriteError`].
+    WriteError,
+    /// See [`Error::DeserializeError`].
+    #[cfg(feature = "serde")]
+    DeserializeError,
+    /// See [`Error::SerializeError`].
+    #[cfg(feature = "serde")]
+    SerializeError,
+}
+
+impl Error {
+    /// Get the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidFormatSpec { .. } => ErrorKind::InvalidFormatSpec,
+            Error::UnsupportedType { .. } => ErrorKind::UnsupportedType,
+            #[cfg(feature = "parse")]
+            Error::ParseError { .. } => ErrorKind::ParseError,
+            Error::ConversionError { .. } => ErrorKind::ConversionError,
+            #[cfg(feature = "parse")]
+            Error::RegexError { .. } => ErrorKind::RegexError,
+            Error::MissingField { .. } => ErrorKind::MissingField,
+            Error::InvalidFieldName { .. } => ErrorKind::InvalidFieldName,
+            Error::InvalidWidth { .. } => ErrorKind::InvalidWidth,
+            Error::WidthLimitExceeded { .. } => ErrorKind::WidthLimitExceeded,
+            Error::LengthLimitExceeded { .. } => ErrorKind::LengthLimitExceeded,
+            #[cfg(feature = "parse")]
+            Error::NoMatch => ErrorKind::NoMatch,
+            Error::BindError { .. } => ErrorKind::BindError,
+            Error::WriteError { .. } => ErrorKind::WriteError,
+            #[cfg(feature = "serde")]
+            Error::DeserializeError { .. } => ErrorKind::DeserializeError,
+            #[cfg(feature = "serde")]
+            Error::SerializeError { .. } => ErrorKind::SerializeError,
+        }
+    }
+
+    /// Check if this error was caused by an invalid pattern or format spec.
+    ///
+    /// These errors are reported when compiling a [`Formatter`](crate::Formatter)
+    /// or [`Parser`](crate::Parser) and will not go away by retrying with other values.
+    pub fn is_pattern_error(&self) -> bool {
+        match self.kind() {
+            ErrorKind::InvalidFormatSpec
+            | ErrorKind::UnsupportedType
+            | ErrorKind::InvalidFieldName
+            | ErrorKind::InvalidWidth
+            | ErrorKind::WidthLimitExceeded => true,
+            #[cfg(feature = "parse")]
+            ErrorKind::RegexError => true,
+            _ => false,
+        }
+    }
+
+    /// Check if this is a [`Error::MissingField`] error.
+    pub fn is_missing_field(&self) -> bool {
+        self.kind() == ErrorKind::MissingField
+    }
+
+    /// Check if this is a [`Error::ConversionError`] error.
+    pub fn is_conversion_error(&self) -> bool {
+        self.kind() == ErrorKind::ConversionError
+    }
+
+    /// Check if this is a [`Error::NoMatch`] error.
+    #[cfg(feature = "parse")]
+    pub fn is_no_match(&self) -> bool {
+        self.kind() == ErrorKind::NoMatch
+    }
+
+    /// Get the field name involved in this error, if any.
+    pub fn field_name(&self) -> Option<&str> {
+        match self {
+            Error::MissingField { name }
+            | Error::InvalidFieldName { name, .. }
+            | Error::BindError { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get the byte offset where this error was detected, if known.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Error::InvalidFormatSpec { position, .. }
+            | Error::InvalidFieldName { position, .. } => *position,
+            _ => None,
+        }
+    }
+
+    /// Create an [`Error::InvalidFormatSpec`] without position information.
+    pub(crate) fn spec(message: impl Into<String>) -> Self {
+        Error::InvalidFormatSpec {
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    /// Create an [`Error::InvalidFormatSpec`] at a byte offset.
+    pub(crate) fn spec_at(message: impl Into<String>, position: usize) -> Self {
+        Error::InvalidFormatSpec {
+            message: message.into(),
+            position: Some(position),
+        }
+    }
+
+    /// Create an [`Error::ConversionError`].
+    pub(crate) fn conversion(expected: impl Into<String>, got: impl fmt::Debug) -> Self {
+        Error::ConversionError {
+            expected: expected.into(),
+            got: format!("{:?}", got),
+        }
+    }
+
+    /// Create an [`Error::WriteError`] from a sink failure.
+    pub(crate) fn write(error: impl fmt::Display) -> Self {
+        Error::WriteError {
+            message: error.to_string(),
+        }
+    }
+
+    /// Create an [`Error::InvalidFieldName`] for a field whose name part
+    /// (the text before any `:`) is `head`.
+    ///
+    /// Whitespace is never trimmed from names, so when `head` contains any the
+    /// position points at it; otherwise the position is left for the caller.
+    pub(crate) fn invalid_name(name: impl Into<String>, head: &str) -> Self {
+        Error::InvalidFieldName {
+            name: name.into(),
+            position: head.find(char::is_whitespace),
+        }
+    }
+
+    /// Create an [`Error::MissingField`].
+    pub(crate) fn missing(name: impl Into<String>) -> Self {
+        Error::MissingField { name: name.into() }
+    }
+
+    /// Shift a position reported relative to a substring so it is relative
+    /// to the enclosing string starting `offset` bytes earlier.
+    pub(crate) fn offset_by(mut self, offset: usize) -> Self {
+        match &mut self {
+            Error::InvalidFormatSpec { position, .. }
+            | Error::InvalidFieldName { position, .. } => {
+                *position = Some(position.unwrap_or(0) + offset);
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+/// Renders ` at position N` for errors that know where they occurred.
+struct Position<'a>(&'a Option<usize>);
+
+impl fmt::Display for Position<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(position) => write!(f, " at position {}", position),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "miette")]
+impl Error {
+    /// Attach the pattern that produced this error, for rendering with `miette`.
+    ///
+    /// Spans point at the byte offset reported by [`Error::position()`], so
+    /// pass the same string that was given to [`Formatter::new`](crate::Formatter::new),
+    /// [`Parser::new`](crate::Parser::new) or [`FormatSpec::parse`](crate::FormatSpec::parse).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Formatter;
+    ///
+    /// let pattern = "{name:>10q}";
+    /// let report = Formatter::new(pattern).unwrap_err().into_report(pattern);
+    /// assert!(report.source_code().is_some());
+    /// ```
+    pub fn into_report(self, pattern: impl Into<String>) -> miette::Report {
+        miette::Report::new(self).with_source_code(pattern.into())
+    }
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let code = match self.kind() {
+            ErrorKind::InvalidFormatSpec => "gullwing::invalid_format_spec",
+            ErrorKind::UnsupportedType => "gullwing::unsupported_type",
+            #[cfg(feature = "parse")]
+            ErrorKind::ParseError => "gullwing::parse_error",
+            ErrorKind::ConversionError => "gullwing::conversion_error",
+            #[cfg(feature = "parse")]
+            ErrorKind::RegexError => "gullwing::regex_error",
+            ErrorKind::MissingField => "gullwing::missing_field",
+            ErrorKind::InvalidFieldName => "gullwing::invalid_field_name",
+            ErrorKind::InvalidWidth => "gullwing::invalid_width",
+            ErrorKind::WidthLimitExceeded => "gullwing::width_limit_exceeded",
+            ErrorKind::LengthLimitExceeded => "gullwing::length_limit_exceeded",
+            #[cfg(feature = "parse")]
+            ErrorKind::NoMatch => "gullwing::no_match",
+            ErrorKind::BindError => "gullwing::bind_error",
+            ErrorKind::WriteError => "gullwing::write_error",
+            #[cfg(feature = "serde")]
+            ErrorKind::DeserializeError => "gullwing::deserialize_error",
+            #[cfg(feature = "serde")]
+            ErrorKind::SerializeError => "gullwing::serialize_error",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match self {
+            Error::InvalidFormatSpec { message, .. } if message.starts_with("unclosed '{'") => {
+                "close the field with '}' or write a literal brace as '{{'"
+            }
+            Error::InvalidFormatSpec { message, .. } if message.starts_with("unmatched '}'") => {
+                "write a literal brace as '}}'"
+            }
+            Error::WidthLimitExceeded { .. } => {
+                "use a smaller width or raise the limit with Formatter::with_max_width"
+            }
+            Error::LengthLimitExceeded { .. } => {
+                "shorten the values, or truncate with OverflowPolicy::Truncate"
+            }
+            Error::InvalidFieldName { name, .. } if name.contains(char::is_whitespace) => {
+                "field names can't contain spaces; write '{name}' rather than '{ name }'"
+            }
+            Error::InvalidFieldName { .. } => {
+                "field names may only contain letters, digits and '_', optionally followed by '.attr' and '!conversion'"
+            }
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let position = self.position()?;
+        let (label, len) = match self {
+            Error::InvalidFieldName { name, .. } if name.contains(char::is_whitespace) => {
+                ("whitespace in field name", 1)
+            }
+            Error::InvalidFieldName { name, .. } => ("invalid field name", name.len().max(1)),
+            _ => ("here", 1),
+        };
+        Some(Box::new(std::iter::once(miette::LabeledSpan::new(
+            Some(label.to_string()),
+            position,
+            len,
+        ))))
+    }
 }
 
 /// Result type alias for gullwing operations.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_and_predicates() {
+        let err = Error::missing("name");
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert!(err.is_missing_field());
+        assert!(!err.is_pattern_error());
+        assert_eq!(err.field_name(), Some("name"));
+
+        let err = Error::conversion("int", "abc");
+        assert!(err.is_conversion_error());
+        assert_eq!(err.field_name(), None);
+
+        #[cfg(feature = "parse")]
+        assert!(Error::NoMatch.is_no_match());
+        assert!(Error::spec("bad").is_pattern_error());
+
+        let err = Error::WidthLimitExceeded {
+            value: 99,
+            limit: 10,
+        };
+        assert_eq!(err.kind(), ErrorKind::WidthLimitExceeded);
+        assert!(err.is_pattern_error());
+        assert_eq!(
+            err.to_string(),
+            "width or precision 99 exceeds the limit of 10"
+        );
+
+        let err = Error::LengthLimitExceeded {
+            length: 20,
+            limit: 16,
+        };
+        assert_eq!(err.kind(), ErrorKind::LengthLimitExceeded);
+        assert!(!err.is_pattern_error());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Error::spec("bad").to_string(),
+            "invalid format specification: bad"
+        );
+        assert_eq!(
+            Error::spec_at("bad", 3).to_string(),
+            "invalid format specification: bad at position 3"
+        );
+        assert_eq!(
+            Error::conversion("int", "abc").to_string(),
+            "type conversion error: cannot convert \"abc\" to int"
+        );
+        assert_eq!(Error::missing("x").to_string(), "missing field: x");
+    }
+
+    #[test]
+    fn test_offset_by() {
+        let err = Error::spec_at("bad", 2).offset_by(10);
+        assert_eq!(err.position(), Some(12));
+
+        let err = Error::spec("bad").offset_by(4);
+        assert_eq!(err.position(), Some(4));
+
+        let err = Error::missing("x").offset_by(4);
+        assert_eq!(err.position(), None);
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn test_diagnostic_labels() {
+        use miette::Diagnostic;
+
+        let err = crate::Formatter::new("id={value:>5q}").unwrap_err();
+        assert_eq!(
+            err.code().unwrap().to_string(),
+            "gullwing::invalid_format_spec"
+        );
+        let labels: Vec<_> = err.labels().unwrap().collect();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].offset(), 12);
+        assert_eq!(labels[0].len(), 1);
+
+        let err = crate::Formatter::new("x {a-b}").unwrap_err();
+        let labels: Vec<_> = err.labels().unwrap().collect();
+        assert_eq!((labels[0].offset(), labels[0].len()), (3, 3));
+        assert!(err.help().is_some());
+
+        assert!(Error::missing("x").labels().is_none());
+    }
+}
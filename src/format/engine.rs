@@ -1,12 +1,41 @@
 //! Core formatting engine.
 
+use super::fallback::FormatterChain;
+use super::provider::ValueProvider;
+#[cfg(feature = "atomic-stats")]
+use super::stats::{Counters, FormatStats};
+use crate::dialect::Dialect;
 use crate::error::{Error, Result};
-use crate::spec::{Alignment, FormatSpec, TypeSpec};
-use crate::types::Value;
+use crate::locale::Locale;
+use crate::spec::{Alignment, Conversion, FormatSpec, TypeSpec};
+use crate::template::{Access, Template};
+use crate::types::{ToValue, Value};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::{fmt, io};
+
+/// Estimated rendered size of a field without an explicit width.
+const DEFAULT_FIELD_SIZE: usize = 8;
+
+/// Number of fill characters written per call when padding.
+const FILL_CHUNK: usize = 32;
+
+/// Pseudo-field holding the 1-based index of a record made by [`Formatter::format_parts`].
+pub const PART_FIELD: &str = "__part";
+
+/// Pseudo-field holding the number of records made by [`Formatter::format_parts`].
+pub const PARTS_FIELD: &str = "__parts";
 
 /// A formatter that can format values according to a format string.
 ///
+/// # Sharing
+///
+/// A formatter is `Send` and `Sync`, and cloning one is cheap: clones share
+/// the compiled pattern rather than copying it, so each worker thread can be
+/// handed its own clone.
+///
 /// # Examples
 ///
 /// ```
@@ -20,25 +49,111 @@ use std::collections::HashMap;
 /// ```
 #[derive(Debug, Clone)]
 pub struct Formatter {
-    #[allow(dead_code)]
-    pattern: String,
-    fields: Vec<Field>,
+    template: Arc<Template>,
+    fields: Arc<[Field]>, // Compiled from `template`, plus one for the text after it
+    slots: usize,         // Number of distinct named fields
+    shared_slots: bool,   // Some name is used by more than one field
+    cache_lookups: bool,  // Look up each named field once per call
+    locale: Arc<Locale>,  // Conventions for the `n` type
+    separators: Option<(char, char)>, // Decimal point and `,` grouping for other numbers
+    exponent: Option<i32>, // Fixed exponent for `e` and `E` fields
+    max_width: usize,     // Largest width or precision a field may use
+    null: NullPolicy,     // How `Value::Null` renders
+    float_repr: FloatRepr, // How floats without a precision or type render
+    max_len: Option<usize>, // Longest output allowed, in bytes
+    overflow: OverflowPolicy, // What happens to output longer than `max_len`
+    dialect: Dialect,     // Features the pattern may use
+    #[cfg(feature = "atomic-stats")]
+    stats: Arc<Counters>, // Shared by clones
+}
+
+/// How a [`Formatter`] renders [`Value::Null`].
+///
+/// The text is padded to the field's width and alignment; the rest of the
+/// spec, such as the presentation type or precision, is not applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NullPolicy {
+    /// Render `None`, like Python's `str(None)`
+    #[default]
+    None,
+    /// Render nothing
+    Empty,
+    /// Fail with [`Error::ConversionError`]
+    Error,
+}
+
+/// How a [`Formatter`] renders a float whose field has no precision or
+/// presentation type, as in `{}` or `{:>10}`.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FloatRepr {
+    /// Like the `g` type with six significant digits, so `123456789.0`
+    /// renders as `1.23457e+08` and `2.0` as `2`
+    #[default]
+    General,
+    /// The shortest text that reads back as the same float, like Python's
+    /// `str()`: `123456789.0`, `2.0`, `0.1` and `1e+16`
+    Python,
+}
+
+/// What a [`Formatter`] does with output longer than its
+/// [`max_len`](Formatter::max_len).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum OverflowPolicy {
+    /// Fail with [`Error::LengthLimitExceeded`]
+    #[default]
+    Error,
+    /// Cut the output at a character boundary and append `marker`, so the
+    /// whole is at most the limit
+    Truncate {
+        /// Text appended to cut output, such as `"..."`; cut itself if it
+        /// doesn't fit
+        marker: String,
+    },
+    /// Spread the text of one field over several records with
+    /// [`format_parts`](Formatter::format_parts), appending `marker` to each
+    /// record but the last; methods producing a single record fail as with
+    /// [`Error`](OverflowPolicy::Error)
+    Split {
+        /// Name of the field whose text is split, such as the message
+        field: String,
+        /// Text appended to every record but the last, such as `"+"`
+        marker: String,
+    },
 }
 
 #[derive(Debug, Clone)]
 struct Field {
-    prefix: String,       // Text before the field
-    name: Option<String>, // Field name (None for positional)
-    index: Option<usize>, // Positional index
-    spec: FormatSpec,     // Format specification
+    prefix: String,                 // Text before the field
+    name: Option<String>,           // Field name (None for positional)
+    index: Option<usize>,           // Positional index
+    slot: Option<usize>,            // Lookup cache slot shared by fields with the same name
+    path: Vec<Access>,              // Attribute and item path (`{name.a[0]}`)
+    conversion: Option<Conversion>, // Conversion flag (!s, !r, !a)
+    spec: Arc<FormatSpec>,          // Format specification
+    write: Option<WriteFn>,         // Writer for `spec`, or None to pick one per value
+    #[cfg(feature = "chrono")]
+    strftime: Option<String>, // Datetime format used instead of `spec`
 }
 
+/// The most fields for which lookup slots are assigned by a linear scan.
+const LINEAR_SLOTS: usize = 16;
+
+/// Renders a value according to a spec whose presentation type is known.
+type WriteFn = fn(&mut String, &Value, &FormatSpec, &Locale) -> Result<()>;
+
 impl Formatter {
+    /// The default for [`with_max_width`](Self::with_max_width).
+    pub const DEFAULT_MAX_WIDTH: usize = 1 << 20;
+
     /// Create a new formatter from a format pattern.
     ///
     /// The pattern may contain:
     /// - Named fields: `{name}` or `{name:spec}`
     /// - Positional fields: `{}` or `{:spec}` or `{0:spec}`
+    /// - Attribute access into [`Value::Map`] values: `{user.name}`
+    /// - Item access into [`Value::List`] and [`Value::Map`] values: `{tags[0]}`
+    /// - Conversion flags: `{name!r}` or `{name!s:>10}` (`!s`, `!r`, `!a`)
     /// - Literal braces: `{{` and `}}`
     ///
     /// # Examples
@@ -49,56 +164,432 @@ impl Formatter {
     /// let f = Formatter::new("{name} is {age:d} years old").unwrap();
     /// ```
     pub fn new(pattern: &str) -> Result<Self> {
-        let fields = parse_format_string(pattern)?;
-        Ok(Formatter {
-            pattern: pattern.to_string(),
-            fields,
-        })
+        Self::new_with_dialect(pattern, Dialect::default())
+    }
+
+    /// Create a new formatter from a pattern that may only use the features
+    /// of `dialect`.
+    ///
+    /// Specs set later with [`with_spec`](Self::with_spec) are held to the
+    /// same dialect. See [`Dialect`] for an example.
+    pub fn new_with_dialect(pattern: &str, dialect: Dialect) -> Result<Self> {
+        span!(DEBUG, "compile", kind = "formatter", pattern);
+        let template = Template::parse(pattern)?;
+        dialect.check(&template)?;
+        let mut formatter = Formatter {
+            template: Arc::new(template),
+            fields: Arc::new([]),
+            slots: 0,
+            shared_slots: false,
+            cache_lookups: true,
+            locale: Arc::new(Locale::c()),
+            separators: None,
+            exponent: None,
+            max_width: Self::DEFAULT_MAX_WIDTH,
+            null: NullPolicy::default(),
+            float_repr: FloatRepr::default(),
+            max_len: None,
+            overflow: OverflowPolicy::default(),
+            dialect,
+            #[cfg(feature = "atomic-stats")]
+            stats: Arc::default(),
+        };
+        formatter.compile();
+        Ok(formatter)
+    }
+
+    /// Rebuild the fields from the template.
+    fn compile(&mut self) {
+        // Most patterns have a handful of names, for which a linear scan beats
+        // hashing them; wide ones get a map so compiling stays linear
+        let count = self.template.fields().len();
+        let mut slots: Vec<&str> = Vec::new();
+        let mut index: Option<HashMap<&str, usize>> =
+            (count > LINEAR_SLOTS).then(|| HashMap::with_capacity(count));
+        let mut uses = 0;
+        let mut fields = Vec::with_capacity(count + 1);
+        fields.extend(self.template.fields().iter().map(|f| {
+            let slot = f.name().map(|name| {
+                uses += 1;
+                let next = slots.len();
+                let slot = match &mut index {
+                    Some(index) => *index.entry(name).or_insert(next),
+                    None => slots.iter().position(|&s| s == name).unwrap_or(next),
+                };
+                if slot == next {
+                    slots.push(name);
+                }
+                slot
+            });
+            Field {
+                prefix: f.prefix().to_string(),
+                name: f.name().map(str::to_string),
+                index: f.index(),
+                slot,
+                path: f.path().to_vec(),
+                conversion: f.conversion(),
+                spec: Arc::clone(f.shared_spec()),
+                write: writer_for(f.spec()),
+                #[cfg(feature = "chrono")]
+                strftime: f.strftime().map(str::to_string),
+            }
+        }));
+        // A trailing field holds the text after the last placeholder (even if
+        // empty), which simplifies the formatting loops.
+        fields.push(Field {
+            prefix: self.template.tail().to_string(),
+            name: None,
+            index: None,
+            slot: None,
+            path: Vec::new(),
+            conversion: None,
+            spec: FormatSpec::empty(),
+            write: None,
+            #[cfg(feature = "chrono")]
+            strftime: None,
+        });
+        self.fields = fields.into();
+        self.slots = slots.len();
+        self.shared_slots = uses > slots.len();
+    }
+
+    /// Get the pattern this formatter was created from.
+    ///
+    /// After [`with_spec`](Self::with_spec),
+    /// [`with_literal_replaced`](Self::with_literal_replaced) or
+    /// [`bind`](Self::bind), this is an equivalent pattern with the edits
+    /// applied. Positional fields are then
+    /// written with explicit indices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Formatter;
+    ///
+    /// let formatter = Formatter::new("{} -> {name!r:>8}").unwrap();
+    /// assert_eq!(formatter.pattern(), "{} -> {name!r:>8}");
+    /// assert_eq!(formatter.to_string(), formatter.pattern());
+    ///
+    /// let edited = formatter.with_literal_replaced("->", "=>");
+    /// assert_eq!(edited.pattern(), "{0} => {name!r:>8}");
+    /// ```
+    pub fn pattern(&self) -> &str {
+        self.template.pattern()
+    }
+
+    /// Get the parsed pattern.
+    ///
+    /// [`Parser::template`](crate::Parser::template) returns the same
+    /// [`Template`] for the same pattern.
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
+    /// Get the dialect the pattern was checked against.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
     }
 
-    /// Format values from a HashMap.
+    /// Format values from a map.
+    ///
+    /// Takes a `HashMap` or `BTreeMap` holding [`Value`]s or anything else
+    /// that implements [`ToValue`], such as plain integers and strings. Each
+    /// value is converted when its field is written;
+    /// [`format_into`](Self::format_into) borrows `Value`s instead. Keys can
+    /// be anything that borrows as `str`, so `HashMap<&str, _>` works without
+    /// allocating owned keys. Any other [`ValueProvider`] works too, as with
+    /// [`format_with`](Self::format_with).
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::{Formatter, Value};
-    /// use std::collections::HashMap;
+    /// use std::collections::{BTreeMap, HashMap};
     ///
     /// let formatter = Formatter::new("{name:>10}").unwrap();
     /// let mut values = HashMap::new();
     /// values.insert("name".to_string(), Value::from("Alice"));
     /// let result = formatter.format_map(&values).unwrap();
     /// assert_eq!(result, "     Alice");
+    ///
+    /// let scores = HashMap::from([("alice".to_string(), 92), ("bob".to_string(), 7)]);
+    /// let formatter = Formatter::new("{alice:>3}|{bob:>3}").unwrap();
+    /// assert_eq!(formatter.format_map(&scores).unwrap(), " 92|  7");
+    ///
+    /// let borrowed = HashMap::from([("alice", Value::from(92)), ("bob", Value::from(7))]);
+    /// assert_eq!(formatter.format_map(&borrowed).unwrap(), " 92|  7");
+    ///
+    /// let sorted = BTreeMap::from([("alice", 92), ("bob", 7)]);
+    /// assert_eq!(formatter.format_map(&sorted).unwrap(), " 92|  7");
     /// ```
-    pub fn format_map(&self, values: &HashMap<String, Value>) -> Result<String> {
-        let mut result = String::new();
+    pub fn format_map<M>(&self, values: &M) -> Result<String>
+    where
+        M: ValueProvider + ?Sized,
+    {
+        self.format_named("format_map", |name| values.get(name))
+    }
 
-        for field in &self.fields {
-            // Append prefix text
-            result.push_str(&field.prefix);
+    /// Format values from a map, appending to an existing buffer.
+    ///
+    /// Reusing one buffer across calls avoids allocating a new `String` for
+    /// every record, which matters in logging and telemetry hot paths. The
+    /// buffer is grown by at most [`size_hint()`](Self::size_hint) up front.
+    /// On error, `buf` may hold partial output.
+    ///
+    /// Values come from any [`ValueProvider`], as with
+    /// [`format_map`](Self::format_map), and are looked up with
+    /// [`ValueProvider::get_ref`], so maps of `Value`s are written without
+    /// copying their values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let formatter = Formatter::new("{level:<5} {message}").unwrap();
+    /// let mut values = HashMap::new();
+    /// let mut buf = String::new();
+    ///
+    /// for (level, message) in [("INFO", "started"), ("WARN", "slow")] {
+    ///     values.insert("level".to_string(), Value::from(level));
+    ///     values.insert("message".to_string(), Value::from(message));
+    ///
+    ///     buf.clear();
+    ///     formatter.format_into(&mut buf, &values).unwrap();
+    ///     println!("{}", buf);
+    /// }
+    /// assert_eq!(buf, "WARN  slow");
+    /// ```
+    pub fn format_into<M>(&self, buf: &mut String, values: &M) -> Result<()>
+    where
+        M: ValueProvider + ?Sized,
+    {
+        let start = buf.len();
+        buf.reserve(self.size_hint());
 
-            // Skip if this is the trailing field (no name or index)
-            if field.name.is_none() && field.index.is_none() {
-                continue;
+        with_scratch(|scratch| {
+            for field in self.fields.iter() {
+                // Append prefix text
+                buf.push_str(&field.prefix);
+
+                // Skip if this is the trailing field (no name or index)
+                if field.name.is_none() && field.index.is_none() {
+                    continue;
+                }
+
+                // Get the value
+                let root = if let Some(name) = &field.name {
+                    values
+                        .get_ref(name)
+                        .ok_or_else(|| Error::missing(name.clone()))?
+                } else {
+                    return Err(Error::spec(
+                        "positional fields not supported with format_map".to_string(),
+                    ));
+                };
+
+                // Format the value
+                self.write_field(buf, scratch, resolve_attrs(&root, field)?, field)?;
             }
 
-            // Get the value
-            let value = if let Some(name) = &field.name {
-                values
-                    .get(name)
-                    .ok_or_else(|| Error::MissingField(name.clone()))?
-            } else {
-                return Err(Error::InvalidFormatSpec(
-                    "positional fields not supported with format_map".to_string(),
-                ));
-            };
+            Ok(())
+        })?;
+
+        self.limit_len(buf, start)
+    }
+
+    /// Estimate the length of the formatted output, in bytes.
+    ///
+    /// This is the literal text plus each field's width, or a small default
+    /// for fields without one. It is a capacity hint, not a bound.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Formatter;
+    ///
+    /// let formatter = Formatter::new("id={id:>12}").unwrap();
+    /// assert!(formatter.size_hint() >= "id=".len() + 12);
+    /// ```
+    pub fn size_hint(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| {
+                let value = if field.name.is_some() || field.index.is_some() {
+                    field
+                        .spec
+                        .width
+                        .unwrap_or(0)
+                        .clamp(DEFAULT_FIELD_SIZE, self.max_width.max(DEFAULT_FIELD_SIZE))
+                } else {
+                    0
+                };
+                field.prefix.len() + value
+            })
+            .sum()
+    }
+
+    /// Get the length in bytes of the output for `values`, without building it.
+    ///
+    /// Unlike [`size_hint()`](Self::size_hint) this is exact: each field is
+    /// rendered and counted, so callers sizing fixed buffers, datagrams or
+    /// database columns can check that a record fits before producing it.
+    /// Fails like [`format_map`](Self::format_map) would, including when the
+    /// output is over the [`max_len`](Self::max_len).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let formatter = Formatter::new("{name:<8}|{score:,}").unwrap();
+    /// let values = HashMap::from([("name", Value::from("Åsa")), ("score", Value::from(1234567))]);
+    ///
+    /// let len = formatter.estimate_len(&values).unwrap();
+    /// assert_eq!(len, formatter.format_map(&values).unwrap().len());
+    /// assert!(len <= 64);
+    /// ```
+    pub fn estimate_len<M>(&self, values: &M) -> Result<usize>
+    where
+        M: ValueProvider + ?Sized,
+    {
+        let mut counter = ByteCounter(0);
+        self.write_named(&mut counter, "estimate_len", |name| values.get(name), None)?;
+        match self.max_len {
+            // Where cut output ends depends on the characters around the cut
+            Some(max_len) if counter.0 > max_len => self.format_map(values).map(|s| s.len()),
+            _ => Ok(counter.0),
+        }
+    }
+
+    /// Format values from a map into as many records as it takes to stay
+    /// within the [`max_len`](Self::max_len).
+    ///
+    /// With [`OverflowPolicy::Split`], output over the limit is spread over
+    /// several records: each repeats the rest of the pattern around a piece
+    /// of the split field's formatted text, and all but the last end the
+    /// piece with the marker. Pieces never split a UTF-8 character. The
+    /// pseudo-fields [`__part`](PART_FIELD) and [`__parts`](PARTS_FIELD)
+    /// hold the 1-based record number and the record count, so a pattern can
+    /// number its records, as with `{__part:d}/{__parts:d}`. Output that
+    /// fits is a single record numbered `1/1`; with other policies, output
+    /// over the limit is handled as in [`format_map`](Self::format_map).
+    ///
+    /// Fails with [`Error::LengthLimitExceeded`] if the rest of the pattern
+    /// leaves no room for the split field, and with
+    /// [`Error::InvalidFormatSpec`] unless the split field is used exactly
+    /// once in the pattern.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, OverflowPolicy, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let policy = OverflowPolicy::Split {
+    ///     field: "msg".to_string(),
+    ///     marker: "+".to_string(),
+    /// };
+    /// let formatter = Formatter::new("<13>{__part}/{__parts} {msg}")
+    ///     .unwrap()
+    ///     .with_max_len(16, policy);
+    ///
+    /// let values = HashMap::from([("msg", Value::from("disk almost full"))]);
+    /// let records = formatter.format_parts(&values).unwrap();
+    /// assert_eq!(records, ["<13>1/3 disk al+", "<13>2/3 most fu+", "<13>3/3 ll"]);
+    /// ```
+    pub fn format_parts<M>(&self, values: &M) -> Result<Vec<String>>
+    where
+        M: ValueProvider + ?Sized,
+    {
+        let lookup = |part: usize, parts: usize| {
+            move |name: &str| match name {
+                PART_FIELD => Some(Value::from(part as i64)),
+                PARTS_FIELD => Some(Value::from(parts as i64)),
+                _ => values.get(name),
+            }
+        };
+
+        let mut whole = String::with_capacity(self.size_hint());
+        self.write_named(&mut whole, "format_parts", lookup(1, 1), None)?;
+        let (max_len, field, marker) = match (&self.overflow, self.max_len) {
+            (OverflowPolicy::Split { field, marker }, Some(max_len)) if whole.len() > max_len => {
+                (max_len, field, marker)
+            }
+            _ => {
+                self.limit_len(&mut whole, 0)?;
+                return Ok(vec![whole]);
+            }
+        };
+
+        let mut uses = self
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.name.as_deref() == Some(field.as_str()));
+        let (index, split) = match (uses.next(), uses.next()) {
+            (Some(first), None) => first,
+            _ => {
+                return Err(Error::spec(format!(
+                    "split field '{}' must appear exactly once in the pattern",
+                    field
+                )))
+            }
+        };
+        let root = lookup(1, 1)(field).ok_or_else(|| Error::missing(field.clone()))?;
+        let mut text = String::new();
+        with_scratch(|scratch| {
+            self.write_field(&mut text, scratch, resolve_attrs(&root, split)?, split)
+        })?;
 
-            // Format the value
-            let formatted = format_value(value, &field.spec)?;
-            result.push_str(&formatted);
+        // More records can mean longer record numbers, and so more records
+        let mut parts = 1;
+        loop {
+            let records = self.split_records(&text, index, max_len, marker, parts, lookup)?;
+            if records.len() == parts {
+                return Ok(records);
+            }
+            parts = records.len();
         }
+    }
 
-        Ok(result)
+    /// Format values from key/value pairs.
+    ///
+    /// Accepts anything that iterates over `&(key, Value)` pairs, such as a
+    /// slice of `(&str, Value)` tuples, so callers don't need to build a
+    /// `HashMap` with owned keys. If a key appears more than once, the last
+    /// pair wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{name:>10} {value:05d}").unwrap();
+    /// let result = formatter
+    ///     .format(&[("name", Value::from("Alice")), ("value", Value::from(42))])
+    ///     .unwrap();
+    /// assert_eq!(result, "     Alice 00042");
+    /// ```
+    pub fn format<'a, I, K>(&self, values: I) -> Result<String>
+    where
+        I: IntoIterator<Item = &'a (K, Value)>,
+        K: AsRef<str> + 'a,
+    {
+        let pairs: Vec<(&str, &Value)> = values
+            .into_iter()
+            .map(|(key, value)| (key.as_ref(), value))
+            .collect();
+
+        self.format_named("format", |name| {
+            pairs
+                .iter()
+                .rev()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).clone())
+        })
     }
 
     /// Format values from a closure that provides values by field name.
@@ -119,287 +610,1230 @@ impl Formatter {
     /// }).unwrap();
     /// assert_eq!(result, "1 + 2 = 3");
     /// ```
-    pub fn format_fn<F>(&self, mut f: F) -> Result<String>
+    pub fn format_fn<F>(&self, f: F) -> Result<String>
     where
         F: FnMut(&str) -> Option<Value>,
     {
-        let mut result = String::new();
+        self.format_named("format_fn", f)
+    }
 
-        for field in &self.fields {
-            result.push_str(&field.prefix);
+    /// Format values looked up from a [`ValueProvider`].
+    ///
+    /// This is the general form of [`format_map`](Self::format_map) and
+    /// [`format_fn`](Self::format_fn): maps of either kind, slices of
+    /// `(name, value)` pairs, closures in an
+    /// [`FnProvider`](crate::format::FnProvider) and types implementing the
+    /// trait themselves all work.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::format::{EnvProvider, ValueProvider};
+    /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut values = HashMap::new();
+    /// values.insert("GULLWING_DOC_USER".to_string(), Value::from("nobody"));
+    ///
+    /// let formatter = Formatter::new("user={GULLWING_DOC_USER}").unwrap();
+    /// let result = formatter.format_with(&EnvProvider.or(&values)).unwrap();
+    /// assert_eq!(result, "user=nobody");
+    ///
+    /// let pairs = [("GULLWING_DOC_USER", "root")];
+    /// assert_eq!(formatter.format_with(&pairs).unwrap(), "user=root");
+    /// ```
+    pub fn format_with<P>(&self, provider: &P) -> Result<String>
+    where
+        P: ValueProvider + ?Sized,
+    {
+        self.format_named("format_with", |name| provider.get(name))
+    }
 
-            // Skip if this is the trailing field (no name or index)
-            if field.name.is_none() && field.index.is_none() {
-                continue;
+    /// Format the fields of any [`Serialize`](serde::Serialize) value, such
+    /// as a struct deriving it.
+    ///
+    /// The value is converted with [`Value::from_serialize`], so `{name}`
+    /// fields look up its fields by their serialized names, and nested
+    /// structs, maps and sequences are reached with `{outer.inner}` and
+    /// `{items[0]}`. Fails with a conversion error if the value doesn't
+    /// serialize as a struct or map, or with [`Error::SerializeError`] if it
+    /// can't be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Formatter;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Request {
+    ///     method: &'static str,
+    ///     status: u16,
+    ///     client: Client,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Client {
+    ///     addr: String,
+    /// }
+    ///
+    /// let request = Request {
+    ///     method: "GET",
+    ///     status: 404,
+    ///     client: Client { addr: "10.0.0.7".to_string() },
+    /// };
+    /// let formatter = Formatter::new("{client.addr} {method:<4} {status:d}").unwrap();
+    /// assert_eq!(formatter.format_serde(&request).unwrap(), "10.0.0.7 GET  404");
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn format_serde<T>(&self, data: &T) -> Result<String>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        match Value::from_serialize(data)? {
+            Value::Map(fields) => {
+                self.format_named("format_serde", |name| fields.get(name).cloned())
             }
-
-            let value = if let Some(name) = &field.name {
-                f(name).ok_or_else(|| Error::MissingField(name.clone()))?
-            } else {
-                return Err(Error::InvalidFormatSpec(
-                    "positional fields not supported with format_fn".to_string(),
-                ));
-            };
-
-            let formatted = format_value(&value, &field.spec)?;
-            result.push_str(&formatted);
+            other => Err(Error::conversion("struct or map", other)),
         }
-
-        Ok(result)
     }
 
-    /// Format positional values.
+    /// Write formatted output into any [`fmt::Write`] sink.
+    ///
+    /// Values are looked up from a [`ValueProvider`], like
+    /// [`format_with`](Self::format_with), but the output goes straight into
+    /// `out` instead of a new `String`. On error, `out` may hold partial output.
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
     ///
-    /// let formatter = Formatter::new("{0} + {1} = {2}").unwrap();
-    /// let values = vec![Value::from(1), Value::from(2), Value::from(3)];
-    /// let result = formatter.format_positional(&values).unwrap();
-    /// assert_eq!(result, "1 + 2 = 3");
+    /// let formatter = Formatter::new("{id:>4}|").unwrap();
+    /// let mut out = String::new();
+    /// for id in 1..=3 {
+    ///     let values = HashMap::from([("id".to_string(), Value::from(id))]);
+    ///     formatter.format_to(&mut out, &values).unwrap();
+    /// }
+    /// assert_eq!(out, "   1|   2|   3|");
     /// ```
-    pub fn format_positional(&self, values: &[Value]) -> Result<String> {
-        let mut result = String::new();
-
-        for field in &self.fields {
-            result.push_str(&field.prefix);
-
-            // Skip if this is the trailing field (no name or index)
-            if field.name.is_none() && field.index.is_none() {
-                continue;
-            }
+    pub fn format_to<W, P>(&self, out: &mut W, values: &P) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+        P: ValueProvider + ?Sized,
+    {
+        if self.max_len.is_none() {
+            return self.write_named(out, "format_to", |name| values.get(name), None);
+        }
 
-            let value = if let Some(index) = field.index {
-                values
-                    .get(index)
-                    .ok_or_else(|| Error::MissingField(format!("position {}", index)))?
-            } else if field.name.is_some() {
-                return Err(Error::InvalidFormatSpec(
-                    "named fields not supported with format_positional".to_string(),
-                ));
-            } else {
-                return Err(Error::InvalidFormatSpec(
-                    "cannot mix auto and manual indexing".to_string(),
-                ));
-            };
+        // Output is only checked against the limit once it is complete
+        let output = self.format_named("format_to", |name| values.get(name))?;
+        out.write_str(&output).map_err(Error::write)
+    }
 
-            let formatted = format_value(value, &field.spec)?;
-            result.push_str(&formatted);
+    /// Write formatted output into any [`io::Write`] sink, such as a file or stdout.
+    ///
+    /// See [`format_to`](Self::format_to). I/O failures are reported as
+    /// [`Error::WriteError`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let formatter = Formatter::new("{level}: {message}\n").unwrap();
+    /// let values = HashMap::from([
+    ///     ("level".to_string(), Value::from("INFO")),
+    ///     ("message".to_string(), Value::from("started")),
+    /// ]);
+    ///
+    /// let mut out = Vec::new();
+    /// formatter.format_to_io(&mut out, &values).unwrap();
+    /// assert_eq!(out, b"INFO: started\n");
+    /// ```
+    pub fn format_to_io<W, P>(&self, out: &mut W, values: &P) -> Result<()>
+    where
+        W: io::Write + ?Sized,
+        P: ValueProvider + ?Sized,
+    {
+        let mut adapter = IoAdapter {
+            inner: out,
+            error: None,
+        };
+        let result = self.format_to(&mut adapter, values);
+        match adapter.error {
+            Some(e) => Err(Error::write(e)),
+            None => result,
         }
+    }
 
+    /// Format named fields with values produced by a lookup closure.
+    fn format_named<F>(&self, method: &str, lookup: F) -> Result<String>
+    where
+        F: FnMut(&str) -> Option<Value>,
+    {
+        let mut result = String::with_capacity(self.size_hint());
+        self.write_named(&mut result, method, lookup, None)?;
+        self.limit_len(&mut result, 0)?;
         Ok(result)
     }
-}
 
-/// Parse a format string into fields.
-fn parse_format_string(pattern: &str) -> Result<Vec<Field>> {
-    let mut fields = Vec::new();
-    let mut chars = pattern.chars().peekable();
-    let mut prefix = String::new();
-    let mut auto_index = 0;
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            '{' => {
-                if chars.peek() == Some(&'{') {
-                    // Escaped brace
-                    chars.next();
-                    prefix.push('{');
-                } else {
-                    // Parse field
-                    let field_str = parse_until_closing_brace(&mut chars)?;
-                    let field = parse_field(&field_str, &mut auto_index)?;
-                    fields.push(Field {
-                        prefix: prefix.clone(),
-                        name: field.0,
-                        index: field.1,
-                        spec: field.2,
-                    });
-                    prefix.clear();
+    /// Write named fields with values produced by a lookup closure.
+    ///
+    /// When lookup caching is enabled, each distinct name is looked up at most
+    /// once, so `{x}` and `{x:>10}` share one value. `raw` replaces the
+    /// output of the field at that index with the given text.
+    fn write_named<W, F>(
+        &self,
+        out: &mut W,
+        method: &str,
+        mut lookup: F,
+        raw: Option<(usize, &str)>,
+    ) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+        F: FnMut(&str) -> Option<Value>,
+    {
+        span!(TRACE, "format", method);
+        let mut cache: Vec<Option<Value>> = if self.cache_lookups && self.shared_slots {
+            vec![None; self.slots]
+        } else {
+            Vec::new()
+        };
+
+        with_scratch(|scratch| {
+            let mut uncached;
+            for (i, field) in self.fields.iter().enumerate() {
+                out.write_str(&field.prefix).map_err(Error::write)?;
+
+                // Skip if this is the trailing field (no name or index)
+                if field.name.is_none() && field.index.is_none() {
+                    continue;
                 }
-            }
-            '}' => {
-                if chars.peek() == Some(&'}') {
-                    // Escaped brace
-                    chars.next();
-                    prefix.push('}');
-                } else {
-                    return Err(Error::InvalidFormatSpec(
-                        "unmatched '}' in format string".to_string(),
-                    ));
+
+                if let Some((_, text)) = raw.filter(|&(index, _)| index == i) {
+                    out.write_str(text).map_err(Error::write)?;
+                    continue;
                 }
-            }
-            _ => prefix.push(ch),
-        }
-    }
 
-    // Always add a trailing field to represent text after the last placeholder
-    // (even if empty). This simplifies formatting logic.
-    fields.push(Field {
-        prefix,
-        name: None,
-        index: None,
-        spec: FormatSpec::default(),
-    });
+                let name = match &field.name {
+                    Some(name) => name,
+                    None => {
+                        return Err(Error::spec(format!(
+                            "positional fields not supported with {}",
+                            method
+                        )))
+                    }
+                };
 
-    Ok(fields)
-}
+                let root = match field.slot.and_then(|slot| cache.get_mut(slot)) {
+                    Some(Some(value)) => &*value,
+                    Some(entry) => {
+                        &*entry.insert(lookup(name).ok_or_else(|| Error::missing(name.clone()))?)
+                    }
+                    None => {
+                        uncached = lookup(name).ok_or_else(|| Error::missing(name.clone()))?;
+                        &uncached
+                    }
+                };
 
-/// Parse until we find a closing brace.
-fn parse_until_closing_brace(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String> {
-    let mut result = String::new();
-    let mut depth = 0;
-
-    while let Some(&ch) = chars.peek() {
-        if ch == '{' {
-            depth += 1;
-        } else if ch == '}' {
-            if depth == 0 {
-                chars.next(); // consume the '}'
-                return Ok(result);
+                let value = resolve_attrs(root, field)?;
+                self.write_field(out, scratch, value, field)?;
             }
-            depth -= 1;
-        }
-        result.push(ch);
-        chars.next();
+
+            Ok(())
+        })
     }
 
-    Err(Error::InvalidFormatSpec(
-        "unclosed '{' in format string".to_string(),
-    ))
-}
+    /// Write a field's value into `out`, applying its conversion flag first.
+    ///
+    /// `scratch` is a reusable buffer for the unpadded rendering.
+    fn write_field<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        scratch: &mut String,
+        value: &Value,
+        field: &Field,
+    ) -> Result<()> {
+        let spec = &field.spec;
+        if let Some(value) = spec
+            .width
+            .max(spec.precision)
+            .filter(|&n| n > self.max_width)
+        {
+            return Err(Error::WidthLimitExceeded {
+                value,
+                limit: self.max_width,
+            });
+        }
 
-/// Parse a field specification.
-/// Returns (name, index, spec)
-fn parse_field(
-    field: &str,
-    auto_index: &mut usize,
-) -> Result<(Option<String>, Option<usize>, FormatSpec)> {
-    // Split on ':'
-    let parts: Vec<&str> = field.splitn(2, ':').collect();
-    let name_part = parts[0];
-    let spec_part = parts.get(1).copied().unwrap_or("");
-
-    // Parse the name/index part
-    let (name, index) = if name_part.is_empty() {
-        // Auto-numbered positional field
-        let idx = *auto_index;
-        *auto_index += 1;
-        (None, Some(idx))
-    } else if let Ok(idx) = name_part.parse::<usize>() {
-        // Explicit positional field
-        (None, Some(idx))
-    } else if name_part.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        // Named field
-        (Some(name_part.to_string()), None)
-    } else {
-        return Err(Error::InvalidFieldName(name_part.to_string()));
-    };
+        let result = if value.is_null() {
+            match self.null {
+                NullPolicy::None => write_aligned(out, "None", spec, false).map_err(Error::write),
+                NullPolicy::Empty => write_aligned(out, "", spec, false).map_err(Error::write),
+                NullPolicy::Error => Err(Error::conversion("a non-null value", value)),
+            }
+        } else {
+            match field.conversion {
+                Some(conversion) => {
+                    self.write_value(out, scratch, &convert_value(value, conversion), field)
+                }
+                None => self.write_value(out, scratch, value, field),
+            }
+        };
+        #[cfg(feature = "atomic-stats")]
+        self.stats.record_field(&result);
+        result
+    }
 
-    // Parse the format spec
-    let spec = FormatSpec::parse(spec_part)?;
+    /// Write a field's value according to its format specification.
+    fn write_value<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        scratch: &mut String,
+        value: &Value,
+        field: &Field,
+    ) -> Result<()> {
+        use super::writer::*;
 
-    Ok((name, index, spec))
-}
+        #[cfg(feature = "chrono")]
+        if let Some(format) = &field.strftime {
+            let datetime = value
+                .as_datetime()
+                .ok_or_else(|| Error::conversion("datetime", value))?;
+            return write!(out, "{}", datetime.format(format)).map_err(Error::write);
+        }
 
-/// Format a value according to a format specification.
-fn format_value(value: &Value, spec: &FormatSpec) -> Result<String> {
-    use super::writer::*;
+        let spec = &field.spec;
+        scratch.clear();
+        match (field.write, self.exponent) {
+            (_, Some(exponent))
+                if matches!(
+                    spec.type_spec,
+                    Some(TypeSpec::ExponentLower | TypeSpec::ExponentUpper)
+                ) =>
+            {
+                write_pinned_exponent(scratch, value, spec, exponent)?
+            }
+            (Some(write), _) => write(scratch, value, spec, &self.locale)?,
+            // Default type based on value
+            (None, _) => match value {
+                Value::Null | Value::Str(_) | Value::Char(_) | Value::List(_) | Value::Map(_) => {
+                    write_string(scratch, value, spec)?
+                }
+                Value::Int(_)
+                | Value::UInt(_)
+                | Value::Int128(_)
+                | Value::UInt128(_)
+                | Value::Bool(_) => {
+                    spec.validate(Some(TypeSpec::Decimal))?;
+                    write_decimal(scratch, value, spec)?
+                }
+                Value::Float(_)
+                    if self.float_repr == FloatRepr::Python && spec.precision.is_none() =>
+                {
+                    write_repr(scratch, value, spec)?
+                }
+                Value::Float(_) => write_general(scratch, value, spec)?,
+                #[cfg(feature = "chrono")]
+                Value::DateTime(_) => write_string(scratch, value, spec)?,
+                #[cfg(feature = "rust_decimal")]
+                Value::Decimal(_) => write_general(scratch, value, spec)?,
+            },
+        }
 
-    // Determine the type of formatting to perform
-    let type_spec = spec.type_spec.unwrap_or({
-        // Default type based on value
-        match value {
-            Value::Str(_) | Value::Char(_) => TypeSpec::String,
-            Value::Int(_) | Value::UInt(_) | Value::Bool(_) => TypeSpec::Decimal,
-            Value::Float(_) => TypeSpec::GeneralLower,
+        if let Some((decimal_point, thousands_sep)) = self.separators {
+            // Only decimal numbers; `n` has the locale and other types no decimal point
+            let decimal = match spec.type_spec {
+                Some(ty) => ty == TypeSpec::Decimal || ty.is_float(),
+                None => value.as_float().is_some(),
+            };
+            if decimal && scratch.contains(['.', ',']) {
+                let rendered = std::mem::take(scratch);
+                scratch.extend(rendered.chars().map(|c| match c {
+                    '.' => decimal_point,
+                    ',' => thousands_sep,
+                    c => c,
+                }));
+            }
         }
-    });
 
-    // Format according to type
-    let formatted = match type_spec {
-        TypeSpec::String => format_string(value, spec)?,
-        TypeSpec::Decimal => format_decimal(value, spec)?,
-        TypeSpec::Binary => format_binary(value, spec)?,
-        TypeSpec::Octal => format_octal(value, spec)?,
-        TypeSpec::HexLower => format_hex(value, spec, false)?,
-        TypeSpec::HexUpper => format_hex(value, spec, true)?,
-        TypeSpec::FixedLower | TypeSpec::FixedUpper => format_fixed(value, spec)?,
-        TypeSpec::ExponentLower | TypeSpec::ExponentUpper => format_exponent(value, spec)?,
-        TypeSpec::GeneralLower | TypeSpec::GeneralUpper => format_general(value, spec)?,
-        TypeSpec::Percentage => format_percentage(value, spec)?,
-        TypeSpec::Character => format_character(value)?,
-        TypeSpec::Number => format_decimal(value, spec)?, // TODO: locale-aware
-    };
+        // Apply alignment and padding; numbers are right-aligned by default
+        // whatever their presentation type, as in Python
+        let numeric = spec.is_numeric() || value.as_float().is_some() || value.as_bool().is_some();
+        write_aligned(out, scratch, spec, numeric).map_err(Error::write)
+    }
 
-    // Apply alignment and padding
-    let result = apply_alignment(&formatted, spec);
+    /// Get the distinct named fields, indexed by lookup slot.
+    #[cfg(feature = "parse")]
+    pub(crate) fn slot_names(&self) -> Vec<&str> {
+        let mut names = vec![""; self.slots];
+        for field in self.fields.iter() {
+            if let (Some(name), Some(slot)) = (&field.name, field.slot) {
+                names[slot] = name;
+            }
+        }
+        names
+    }
 
-    Ok(result)
+    /// Write named fields with values borrowed by lookup slot.
+    ///
+    /// This is the allocation-free path used by pipelines that keep their
+    /// values and `scratch` buffer alive across records.
+    #[cfg(feature = "parse")]
+    pub(crate) fn write_slots<'v, W, F>(
+        &self,
+        out: &mut W,
+        scratch: &mut String,
+        mut get: F,
+    ) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+        F: FnMut(usize) -> Option<&'v Value>,
+    {
+        span!(TRACE, "format", method = "transform");
+        for field in self.fields.iter() {
+            out.write_str(&field.prefix).map_err(Error::write)?;
+
+            // Skip if this is the trailing field (no name or index)
+            if field.name.is_none() && field.index.is_none() {
+                continue;
+            }
+
+            let (name, slot) = match (&field.name, field.slot) {
+                (Some(name), Some(slot)) => (name, slot),
+                _ => {
+                    return Err(Error::spec(
+                        "positional fields not supported in output patterns".to_string(),
+                    ))
+                }
+            };
+
+            let root = get(slot).ok_or_else(|| Error::missing(name.clone()))?;
+            let value = resolve_attrs(root, field)?;
+            self.write_field(out, scratch, value, field)?;
+        }
+
+        Ok(())
+    }
+
+    /// Format positional values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{0} + {1} = {2}").unwrap();
+    /// let values = vec![Value::from(1), Value::from(2), Value::from(3)];
+    /// let result = formatter.format_positional(&values).unwrap();
+    /// assert_eq!(result, "1 + 2 = 3");
+    /// ```
+    pub fn format_positional(&self, values: &[Value]) -> Result<String> {
+        span!(TRACE, "format", method = "format_positional");
+        let mut result = String::with_capacity(self.size_hint());
+
+        with_scratch(|scratch| {
+            for field in self.fields.iter() {
+                result.push_str(&field.prefix);
+
+                // Skip if this is the trailing field (no name or index)
+                if field.name.is_none() && field.index.is_none() {
+                    continue;
+                }
+
+                let value = if let Some(index) = field.index {
+                    let root = values
+                        .get(index)
+                        .ok_or_else(|| Error::missing(format!("position {}", index)))?;
+                    resolve_attrs(root, field)?
+                } else if field.name.is_some() {
+                    return Err(Error::spec(
+                        "named fields not supported with format_positional".to_string(),
+                    ));
+                } else {
+                    return Err(Error::spec(
+                        "cannot mix auto and manual indexing".to_string(),
+                    ));
+                };
+
+                self.write_field(&mut result, scratch, value, field)?;
+            }
+
+            Ok(())
+        })?;
+
+        self.limit_len(&mut result, 0)?;
+        Ok(result)
+    }
+
+    /// Return a copy of this formatter with lookup caching enabled or disabled.
+    ///
+    /// By default, [`format_fn`](Self::format_fn), [`format_with`](Self::format_with)
+    /// and [`format`](Self::format) look up each distinct field name once per
+    /// call, even if the pattern uses it several times with different specs.
+    /// Disable caching when the lookup has side effects that should run for
+    /// every occurrence, such as a counter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{n} {n:>3}").unwrap();
+    ///
+    /// let mut calls = 0;
+    /// let result = formatter.format_fn(|_| { calls += 1; Some(Value::from(calls)) }).unwrap();
+    /// assert_eq!(result, "1   1");
+    ///
+    /// let mut calls = 0;
+    /// let uncached = formatter.with_lookup_cache(false);
+    /// let result = uncached.format_fn(|_| { calls += 1; Some(Value::from(calls)) }).unwrap();
+    /// assert_eq!(result, "1   2");
+    /// ```
+    pub fn with_lookup_cache(&self, enabled: bool) -> Self {
+        let mut formatter = self.clone();
+        formatter.cache_lookups = enabled;
+        formatter
+    }
+
+    /// Return a copy of this formatter that formats `n` fields with `locale`.
+    ///
+    /// The `n` type uses the locale's decimal point and digit grouping, like
+    /// Python's `n` after `locale.setlocale()`. Other types are unaffected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::locale::Locale;
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{:>12n}|{:,d}").unwrap();
+    /// let values = [Value::from(1234567), Value::from(1234567)];
+    ///
+    /// let plain = formatter.format_positional(&values).unwrap();
+    /// assert_eq!(plain, "     1234567|1,234,567");
+    ///
+    /// let german = formatter.with_locale(Locale::de_de());
+    /// assert_eq!(german.format_positional(&values).unwrap(), "   1.234.567|1,234,567");
+    /// ```
+    pub fn with_locale(&self, locale: Locale) -> Self {
+        let mut formatter = self.clone();
+        formatter.locale = Arc::new(locale);
+        formatter
+    }
+
+    /// Get the locale used for `n` fields.
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    /// Return a copy of this formatter that writes numbers with the given separators.
+    ///
+    /// Decimal numbers formatted with the `d`, `e`, `f`, `g` and `%` types,
+    /// or without a type, use `decimal_point` instead of `.` and
+    /// `thousands_sep` instead of the `,` grouping separator. The `_`
+    /// grouping separator, binary, octal and hex output, and `n` fields,
+    /// which follow the [locale](Self::with_locale), are unaffected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{:,.2f} {:.1%} {:_d}").unwrap();
+    /// let values = [Value::from(1234.5), Value::from(0.125), Value::from(10000)];
+    ///
+    /// let european = formatter.with_separators(',', '.');
+    /// assert_eq!(european.format_positional(&values).unwrap(), "1.234,50 12,5% 10_000");
+    /// assert_eq!(european.separators(), (',', '.'));
+    /// ```
+    pub fn with_separators(&self, decimal_point: char, thousands_sep: char) -> Self {
+        let mut formatter = self.clone();
+        formatter.separators = Some((decimal_point, thousands_sep)).filter(|&s| s != ('.', ','));
+        formatter
+    }
+
+    /// Get the decimal point and thousands separator used for numbers.
+    ///
+    /// This is `('.', ',')` unless changed with
+    /// [`with_separators`](Self::with_separators).
+    pub fn separators(&self) -> (char, char) {
+        self.separators.unwrap_or(('.', ','))
+    }
+
+    /// Return a copy of this formatter that writes `e` and `E` fields with a fixed exponent.
+    ///
+    /// Python always normalizes the mantissa to one digit before the point.
+    /// With a fixed exponent, the mantissa is scaled instead and keeps the
+    /// field's precision as decimals, so a column of readings lines up by
+    /// magnitude. Parse the output back with
+    /// [`Parser::with_exponent`](crate::Parser::with_exponent).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{:>11.4e}|").unwrap().with_exponent(6);
+    /// for (reading, row) in [(2_500_000.0, " 2.5000e+06|"), (731.25, " 0.0007e+06|")] {
+    ///     assert_eq!(formatter.format_positional(&[Value::from(reading)]).unwrap(), row);
+    /// }
+    /// assert_eq!(formatter.exponent(), Some(6));
+    /// ```
+    pub fn with_exponent(&self, exponent: i32) -> Self {
+        let mut formatter = self.clone();
+        formatter.exponent = Some(exponent);
+        formatter
+    }
+
+    /// Get the fixed exponent for `e` and `E` fields, if one was set with
+    /// [`with_exponent`](Self::with_exponent).
+    pub fn exponent(&self) -> Option<i32> {
+        self.exponent
+    }
+
+    /// Return a copy of this formatter that rejects widths and precisions above `max_width`.
+    ///
+    /// A spec like `{x:999999999}` would otherwise pad a single field with a
+    /// gigabyte of fill. Formatting a field whose width or precision exceeds
+    /// the limit fails with [`Error::WidthLimitExceeded`] before anything is
+    /// rendered. The default, [`DEFAULT_MAX_WIDTH`](Self::DEFAULT_MAX_WIDTH),
+    /// is generous enough for any real layout; lower it for services that
+    /// accept templates from untrusted users.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Error, Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{:>50}").unwrap().with_max_width(40);
+    /// let err = formatter.format_positional(&[Value::from("x")]).unwrap_err();
+    /// assert_eq!(err, Error::WidthLimitExceeded { value: 50, limit: 40 });
+    /// ```
+    pub fn with_max_width(&self, max_width: usize) -> Self {
+        let mut formatter = self.clone();
+        formatter.max_width = max_width;
+        formatter
+    }
+
+    /// Get the largest width or precision a field may use.
+    pub fn max_width(&self) -> usize {
+        self.max_width
+    }
+
+    /// Return a copy of this formatter that renders [`Value::Null`] according to `policy`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, NullPolicy, Value};
+    ///
+    /// let formatter = Formatter::new("[{:>6.2f}]").unwrap();
+    /// let values = [Value::Null];
+    /// assert_eq!(formatter.format_positional(&values).unwrap(), "[  None]");
+    ///
+    /// let blank = formatter.with_null_policy(NullPolicy::Empty);
+    /// assert_eq!(blank.format_positional(&values).unwrap(), "[      ]");
+    ///
+    /// let strict = formatter.with_null_policy(NullPolicy::Error);
+    /// assert!(strict.format_positional(&values).is_err());
+    /// ```
+    pub fn with_null_policy(&self, policy: NullPolicy) -> Self {
+        let mut formatter = self.clone();
+        formatter.null = policy;
+        formatter
+    }
+
+    /// Get how [`Value::Null`] is rendered.
+    pub fn null_policy(&self) -> NullPolicy {
+        self.null
+    }
+
+    /// Return a copy of this formatter that renders floats without a
+    /// precision or presentation type according to `repr`.
+    ///
+    /// [`FloatRepr::Python`] matches Python's output for such fields exactly;
+    /// the default keeps gullwing's six significant digits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{FloatRepr, Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{} {:>8} {:.3}").unwrap();
+    /// let values = [Value::from(0.1), Value::from(2.0), Value::from(1e16)];
+    /// assert_eq!(formatter.format_positional(&values).unwrap(), "0.1        2 1e+16");
+    ///
+    /// let python = formatter.with_float_repr(FloatRepr::Python);
+    /// assert_eq!(python.format_positional(&values).unwrap(), "0.1      2.0 1e+16");
+    /// let values = [Value::from(123456789.0), Value::from(1e-5), Value::from(1.0)];
+    /// assert_eq!(
+    ///     python.format_positional(&values).unwrap(),
+    ///     "123456789.0    1e-05 1"
+    /// );
+    /// ```
+    pub fn with_float_repr(&self, repr: FloatRepr) -> Self {
+        let mut formatter = self.clone();
+        formatter.float_repr = repr;
+        formatter
+    }
+
+    /// Get how floats without a precision or presentation type are rendered.
+    pub fn float_repr(&self) -> FloatRepr {
+        self.float_repr
+    }
+
+    /// Return a copy of this formatter whose output is at most `max_len` bytes.
+    ///
+    /// The limit applies to each complete record, after all fields are
+    /// assembled, as needed for syslog's 1024 or 8192 byte messages or a
+    /// fixed database column. Longer output fails or is truncated according
+    /// to `policy`; truncation never splits a UTF-8 character. With a limit,
+    /// [`format_to`](Self::format_to) buffers each record before writing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::format::OverflowPolicy;
+    /// use gullwing::{Error, Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("msg={}").unwrap();
+    /// let values = [Value::from("häßlich")];
+    ///
+    /// let strict = formatter.with_max_len(10, OverflowPolicy::Error);
+    /// let err = strict.format_positional(&values).unwrap_err();
+    /// assert_eq!(err, Error::LengthLimitExceeded { length: 13, limit: 10 });
+    ///
+    /// let marker = "~".to_string();
+    /// let cut = formatter.with_max_len(10, OverflowPolicy::Truncate { marker });
+    /// assert_eq!(cut.format_positional(&values).unwrap(), "msg=häß~");
+    /// ```
+    pub fn with_max_len(&self, max_len: usize, policy: OverflowPolicy) -> Self {
+        let mut formatter = self.clone();
+        formatter.max_len = Some(max_len);
+        formatter.overflow = policy;
+        formatter
+    }
+
+    /// Get the longest output allowed, in bytes, if a limit was set with
+    /// [`with_max_len`](Self::with_max_len).
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    /// Get what happens to output longer than the [`max_len`](Self::max_len).
+    pub fn overflow_policy(&self) -> &OverflowPolicy {
+        &self.overflow
+    }
+
+    /// Get counts of the fields this formatter has written, the conversion
+    /// errors it has hit and the outputs it has truncated.
+    ///
+    /// The counters are atomic, so a formatter shared between threads counts
+    /// every call; clones, including those made by the `with_` methods,
+    /// count into the same counters. Needs the `atomic-stats` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{id:d} {name}").unwrap();
+    /// formatter.format(&[("id", Value::from(1)), ("name", Value::from("a"))]).unwrap();
+    /// formatter.format(&[("id", Value::from("x")), ("name", Value::from("b"))]).unwrap_err();
+    ///
+    /// let stats = formatter.stats();
+    /// assert_eq!(stats.fields, 2);
+    /// assert_eq!(stats.conversion_errors, 1);
+    /// ```
+    #[cfg(feature = "atomic-stats")]
+    pub fn stats(&self) -> FormatStats {
+        self.stats.snapshot()
+    }
+
+    /// Spread `text` over records of at most `max_len` bytes, written in
+    /// place of the field at `index` and numbered out of `parts`.
+    fn split_records<F>(
+        &self,
+        text: &str,
+        index: usize,
+        max_len: usize,
+        marker: &str,
+        parts: usize,
+        lookup: impl Fn(usize, usize) -> F,
+    ) -> Result<Vec<String>>
+    where
+        F: FnMut(&str) -> Option<Value>,
+    {
+        let mut records = Vec::with_capacity(parts);
+        let mut rest = text;
+        let mut piece = String::new();
+        loop {
+            let part = records.len() + 1;
+            let mut record = String::new();
+            self.write_named(
+                &mut record,
+                "format_parts",
+                lookup(part, parts),
+                Some((index, "")),
+            )?;
+
+            let room = max_len.saturating_sub(record.len());
+            let end = if rest.len() <= room {
+                rest.len()
+            } else {
+                floor_char_boundary(rest, room.saturating_sub(marker.len()))
+            };
+            if end == 0 && !rest.is_empty() || record.len() > max_len {
+                let next = rest.chars().next().map_or(0, char::len_utf8);
+                return Err(Error::LengthLimitExceeded {
+                    length: record.len() + next + marker.len(),
+                    limit: max_len,
+                });
+            }
+
+            piece.clear();
+            piece.push_str(&rest[..end]);
+            rest = &rest[end..];
+            if !rest.is_empty() {
+                piece.push_str(marker);
+            }
+            record.clear();
+            self.write_named(
+                &mut record,
+                "format_parts",
+                lookup(part, parts),
+                Some((index, &piece)),
+            )?;
+            records.push(record);
+
+            if rest.is_empty() {
+                return Ok(records);
+            }
+        }
+    }
+
+    /// Apply the length limit to the record written to `out` from `start` on.
+    pub(crate) fn limit_len(&self, out: &mut String, start: usize) -> Result<()> {
+        let Some(max_len) = self.max_len else {
+            return Ok(());
+        };
+        let length = out.len() - start;
+        if length <= max_len {
+            return Ok(());
+        }
+
+        match &self.overflow {
+            OverflowPolicy::Error | OverflowPolicy::Split { .. } => {
+                Err(Error::LengthLimitExceeded {
+                    length,
+                    limit: max_len,
+                })
+            }
+            OverflowPolicy::Truncate { marker } => {
+                let marker = &marker[..floor_char_boundary(marker, max_len)];
+                let keep = floor_char_boundary(&out[start..], max_len - marker.len());
+                out.truncate(start + keep);
+                out.push_str(marker);
+                #[cfg(feature = "atomic-stats")]
+                self.stats.record_truncation();
+                Ok(())
+            }
+        }
+    }
+
+    /// Return a copy of this formatter with the spec of a field replaced.
+    ///
+    /// `field` is either a field name or a positional index (`"0"`), optionally
+    /// followed by an attribute path (`"user.name"`). Every
+    /// occurrence of the field is updated, which makes it possible to tweak
+    /// width or precision of a user-supplied template (e.g. to auto-fit a
+    /// column) without rewriting the pattern string. The spec is checked
+    /// with [`FormatSpec::validate`] first, as if it had been parsed, and
+    /// must fit the formatter's [`dialect`](Self::dialect).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, FormatSpec, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let formatter = Formatter::new("[{name:>5}]").unwrap();
+    /// let wider = formatter
+    ///     .with_spec("name", FormatSpec::parse(">8").unwrap())
+    ///     .unwrap();
+    ///
+    /// let mut values = HashMap::new();
+    /// values.insert("name".to_string(), Value::from("Bob"));
+    /// assert_eq!(wider.format_map(&values).unwrap(), "[     Bob]");
+    /// ```
+    pub fn with_spec(&self, field: &str, spec: FormatSpec) -> Result<Self> {
+        spec.validate(None)?;
+        let mut formatter = self.clone();
+        formatter.template = Arc::new(self.template.with_spec(field, &spec)?);
+        self.dialect.check(&formatter.template)?;
+        formatter.compile();
+        Ok(formatter)
+    }
+
+    /// Return a copy of this formatter with literal text replaced.
+    ///
+    /// Only the literal text between fields is affected; field names and
+    /// specs are left untouched. Occurrences spanning a field are not replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// let formatter = Formatter::new("{0} | {1}").unwrap();
+    /// let csv = formatter.with_literal_replaced(" | ", ",");
+    ///
+    /// let values = vec![Value::from(1), Value::from(2)];
+    /// assert_eq!(csv.format_positional(&values).unwrap(), "1,2");
+    /// ```
+    pub fn with_literal_replaced(&self, from: &str, to: &str) -> Self {
+        let mut formatter = self.clone();
+        formatter.template = Arc::new(self.template.with_literal_replaced(from, to));
+        formatter.compile();
+        formatter
+    }
+
+    /// Return a copy of this formatter with a field filled in ahead of time.
+    ///
+    /// `field` is a field name or a positional index (`"0"`). Every field
+    /// using it, including ones with an attribute path such as
+    /// `{user.name}`, is formatted once with this formatter's current
+    /// settings and becomes literal text, so the returned formatter no longer
+    /// asks for it.
+    ///
+    /// Because the value is formatted here, a value the spec can't take fails
+    /// now with [`Error::BindError`] naming the field, instead of on every
+    /// later call. Binding a field the pattern doesn't use is an
+    /// [`Error::MissingField`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let formatter = Formatter::new("[{app}] {level:>5}: {msg}").unwrap();
+    /// let bound = formatter.bind("app", "api").unwrap();
+    /// assert_eq!(bound.pattern(), "[api] {level:>5}: {msg}");
+    ///
+    /// let mut values = HashMap::new();
+    /// values.insert("level".to_string(), Value::from("WARN"));
+    /// values.insert("msg".to_string(), Value::from("disk low"));
+    /// assert_eq!(bound.format_map(&values).unwrap(), "[api]  WARN: disk low");
+    ///
+    /// // A string can't be formatted with `d`
+    /// let err = Formatter::new("{port:d}").unwrap().bind("port", "http").unwrap_err();
+    /// assert_eq!(err.field_name(), Some("port"));
+    /// ```
+    pub fn bind(&self, field: &str, value: impl ToValue) -> Result<Self> {
+        let value = value.to_value();
+        let mut scratch = String::new();
+        let template = self.template.with_bound(field, |i| {
+            let compiled = &self.fields[i];
+            let mut text = String::new();
+            resolve_attrs(&value, compiled)
+                .and_then(|value| self.write_field(&mut text, &mut scratch, value, compiled))
+                .map_err(|e| Error::BindError {
+                    name: self.template.fields()[i].display_name(),
+                    source: Box::new(e),
+                })?;
+            Ok(text)
+        })?;
+
+        let mut formatter = self.clone();
+        formatter.template = Arc::new(template);
+        formatter.compile();
+        Ok(formatter)
+    }
+
+    /// Chain this formatter with a fallback used when it fails, such as a
+    /// plain `"{__raw}"` template behind a rich one.
+    ///
+    /// See [`FormatterChain`] for an example.
+    pub fn or_else(self, fallback: Formatter) -> FormatterChain {
+        FormatterChain::new(self, fallback)
+    }
+}
+
+/// Formatters are equal when they format every input the same way.
+///
+/// Patterns are compared after parsing, so `{}` and `{0}`, `{x}` and `{x:}`,
+/// or `{x:05d}` and `{x:005d}` compare equal. Settings such as the locale,
+/// separators, exponent, width limit, length limit and dialect are compared
+/// too; `atomic-stats` counters are not.
+impl PartialEq for Formatter {
+    fn eq(&self, other: &Self) -> bool {
+        self.template == other.template
+            && self.cache_lookups == other.cache_lookups
+            && self.locale == other.locale
+            && self.separators == other.separators
+            && self.exponent == other.exponent
+            && self.max_width == other.max_width
+            && self.null == other.null
+            && self.float_repr == other.float_repr
+            && self.max_len == other.max_len
+            && self.overflow == other.overflow
+            && self.dialect == other.dialect
+    }
+}
+
+impl Eq for Formatter {}
+
+impl Hash for Formatter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.template.hash(state);
+        self.cache_lookups.hash(state);
+        self.locale.hash(state);
+        self.separators.hash(state);
+        self.exponent.hash(state);
+        self.max_width.hash(state);
+        self.null.hash(state);
+        self.float_repr.hash(state);
+        self.max_len.hash(state);
+        self.overflow.hash(state);
+        self.dialect.hash(state);
+    }
+}
+
+impl fmt::Display for Formatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.template.pattern())
+    }
+}
+
+/// Follow a field's attribute and item path into nested composite values.
+fn resolve_attrs<'v>(root: &'v Value, field: &Field) -> Result<&'v Value> {
+    let mut value = root;
+    for (i, access) in field.path.iter().enumerate() {
+        let next = match access {
+            Access::Attr(name) => value.get_attr(name),
+            Access::Item(key) => value.get_item(key),
+        };
+        value = next.ok_or_else(|| {
+            let mut name = match (&field.name, field.index) {
+                (Some(name), _) => name.clone(),
+                (None, Some(index)) => index.to_string(),
+                (None, None) => String::new(),
+            };
+            for access in &field.path[..=i] {
+                name.push_str(&access.to_string());
+            }
+            Error::missing(name)
+        })?;
+    }
+    Ok(value)
+}
+
+/// Run `f` with this thread's reusable buffer for rendering fields.
+///
+/// Falls back to a fresh buffer if the scratch is already in use, e.g. when a
+/// lookup closure formats another template.
+fn with_scratch<T>(f: impl FnOnce(&mut String) -> T) -> T {
+    thread_local! {
+        static SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
+    }
+
+    SCRATCH.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut scratch) => f(&mut scratch),
+        Err(_) => f(&mut String::new()),
+    })
+}
+
+/// Apply a conversion flag, producing a string value.
+///
+/// `!r` and `!a` quote strings and characters like Python's `repr()` and
+/// `ascii()`; other values render the same as with `!s`.
+fn convert_value(value: &Value, conversion: Conversion) -> Value {
+    match value {
+        Value::Str(s) => Value::from(conversion.quote(s)),
+        Value::Char(c) => Value::from(conversion.quote(c.encode_utf8(&mut [0; 4]))),
+        _ => Value::from(value.to_string()),
+    }
 }
 
-/// Apply alignment and padding to a formatted value.
-fn apply_alignment(s: &str, spec: &FormatSpec) -> String {
-    let width = match spec.width {
-        Some(w) if w > s.len() => w,
-        _ => return s.to_string(),
+/// Pick the writer for a spec with an explicit presentation type.
+///
+/// Resolved once per field when the pattern is compiled, so formatting a
+/// record doesn't have to dispatch on the type again.
+fn writer_for(spec: &FormatSpec) -> Option<WriteFn> {
+    use super::writer::*;
+
+    let write: WriteFn = match spec.type_spec? {
+        TypeSpec::String => |out, value, spec, _| write_string(out, value, spec),
+        TypeSpec::Decimal => |out, value, spec, _| write_decimal(out, value, spec),
+        TypeSpec::Binary => |out, value, spec, _| write_binary(out, value, spec),
+        TypeSpec::Octal => |out, value, spec, _| write_octal(out, value, spec),
+        TypeSpec::HexLower => |out, value, spec, _| write_hex(out, value, spec, false),
+        TypeSpec::HexUpper => |out, value, spec, _| write_hex(out, value, spec, true),
+        TypeSpec::FixedLower | TypeSpec::FixedUpper => {
+            |out, value, spec, _| write_fixed(out, value, spec)
+        }
+        TypeSpec::ExponentLower | TypeSpec::ExponentUpper => {
+            |out, value, spec, _| write_exponent(out, value, spec)
+        }
+        TypeSpec::GeneralLower | TypeSpec::GeneralUpper => {
+            |out, value, spec, _| write_general(out, value, spec)
+        }
+        TypeSpec::Percentage => |out, value, spec, _| write_percentage(out, value, spec),
+        TypeSpec::Character => |out, value, _, _| write_character(out, value),
+        TypeSpec::Number => write_number,
     };
+    Some(write)
+}
+
+/// Write `n` copies of `fill`.
+///
+/// The fill is repeated into a small stack buffer first, so wide padding
+/// takes one `write_str` per [`FILL_CHUNK`] characters rather than one call
+/// per character.
+fn write_fill<W: fmt::Write + ?Sized>(out: &mut W, fill: char, n: usize) -> fmt::Result {
+    let mut buf = [0; FILL_CHUNK * 4];
+    let len = fill.encode_utf8(&mut buf).len();
+    let chunk_len = len * FILL_CHUNK.min(n);
+    if len == 1 {
+        let byte = buf[0];
+        buf[..chunk_len].fill(byte);
+    } else {
+        // Double the repeated run until it covers the chunk
+        let mut filled = len;
+        while filled < chunk_len {
+            let copy = filled.min(chunk_len - filled);
+            buf.copy_within(..copy, filled);
+            filled += copy;
+        }
+    }
+    let chunk =
+        std::str::from_utf8(&buf[..chunk_len]).expect("a repeated character is valid UTF-8");
+
+    let mut remaining = n;
+    while remaining >= FILL_CHUNK {
+        out.write_str(chunk)?;
+        remaining -= FILL_CHUNK;
+    }
+    out.write_str(&chunk[..remaining * len])
+}
+
+/// Write a formatted value with alignment and padding applied.
+///
+/// Width is measured in characters (Unicode scalar values), the same metric
+/// used for string precision, so multi-byte text pads like Python does.
+/// Without an alignment, `numeric` text is right-aligned and anything else
+/// left-aligned.
+fn write_aligned<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    s: &str,
+    spec: &FormatSpec,
+    numeric: bool,
+) -> fmt::Result {
+    // Only count characters when there's a width to pad to
+    let Some(width) = spec.width else {
+        return out.write_str(s);
+    };
+    let len = s.chars().count();
+    if len >= width {
+        return out.write_str(s);
+    }
 
     let fill = spec.fill_char();
-    let padding_needed = width - s.len();
+    let padding_needed = width - len;
 
-    let align = spec.align.unwrap_or(
-        // Default alignment depends on type
-        if spec.is_numeric() {
-            Alignment::Right
-        } else {
-            Alignment::Left
-        },
-    );
+    let align = spec.align.unwrap_or(if numeric {
+        Alignment::Right
+    } else {
+        Alignment::Left
+    });
+
+    let pad = |out: &mut W, n: usize| write_fill(out, fill, n);
 
     match align {
         Alignment::Left => {
-            let mut result = s.to_string();
-            result.push_str(&fill.to_string().repeat(padding_needed));
-            result
+            out.write_str(s)?;
+            pad(out, padding_needed)
         }
         Alignment::Right => {
-            let mut result = fill.to_string().repeat(padding_needed);
-            result.push_str(s);
-            result
+            pad(out, padding_needed)?;
+            out.write_str(s)
         }
         Alignment::Center => {
             let left_pad = padding_needed / 2;
-            let right_pad = padding_needed - left_pad;
-            let mut result = fill.to_string().repeat(left_pad);
-            result.push_str(s);
-            result.push_str(&fill.to_string().repeat(right_pad));
-            result
+            pad(out, left_pad)?;
+            out.write_str(s)?;
+            pad(out, padding_needed - left_pad)
         }
         Alignment::AfterSign => {
-            // Insert padding after sign for numeric values
-            if let Some(first_char) = s.chars().next() {
-                if first_char == '+' || first_char == '-' || first_char == ' ' {
-                    let mut result = String::new();
-                    result.push(first_char);
-                    result.push_str(&fill.to_string().repeat(padding_needed));
-                    result.push_str(&s[1..]);
-                    return result;
-                }
-            }
-            // No sign, just right-align
-            let mut result = fill.to_string().repeat(padding_needed);
-            result.push_str(s);
-            result
+            // Insert padding after the sign and any `#` radix prefix
+            let sign_len = usize::from(s.starts_with(['+', '-', ' ']));
+            let prefix_len = match &s.as_bytes()[sign_len..] {
+                [b'0', b'b' | b'B' | b'o' | b'O' | b'x' | b'X', ..] if spec.alternate => 2,
+                _ => 0,
+            };
+            let (head, digits) = s.split_at(sign_len + prefix_len);
+            out.write_str(head)?;
+            pad(out, padding_needed)?;
+            out.write_str(digits)
         }
     }
 }
 
+/// Adapts an [`io::Write`] sink to [`fmt::Write`], keeping the underlying I/O error.
+struct IoAdapter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write + ?Sized> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Get the largest index of at most `index` that falls on a character boundary of `s`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    (0..=index)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+/// A [`fmt::Write`] sink that only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl fmt::Write for ByteCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorKind;
+
+    fn parse_format_string(pattern: &str) -> Result<Vec<Field>> {
+        Formatter::new(pattern).map(|f| f.fields.to_vec())
+    }
+
+    fn aligned(s: &str, spec: &FormatSpec) -> String {
+        let mut out = String::new();
+        write_aligned(&mut out, s, spec, false).unwrap();
+        out
+    }
 
     #[test]
     fn test_parse_simple_pattern() {
@@ -424,7 +1858,7 @@ mod tests {
         let fields = parse_format_string("{value:05d}").unwrap();
         assert_eq!(fields[0].name, Some("value".to_string()));
         assert_eq!(fields[0].spec.width, Some(5));
-        assert_eq!(fields[0].spec.zero_pad, true);
+        assert!(fields[0].spec.zero_pad);
     }
 
     #[test]
@@ -432,4 +1866,1184 @@ mod tests {
         let fields = parse_format_string("{{escaped}}").unwrap();
         assert_eq!(fields[0].prefix, "{escaped}");
     }
+
+    #[test]
+    fn test_parse_attributes() {
+        let fields = parse_format_string("{user.name} {0.id:>4} {.x}").unwrap();
+        assert_eq!(fields[0].name, Some("user".to_string()));
+        assert_eq!(fields[0].path, vec![Access::Attr("name".to_string())]);
+        assert_eq!(fields[1].index, Some(0));
+        assert_eq!(fields[1].path, vec![Access::Attr("id".to_string())]);
+        assert_eq!(fields[2].index, Some(0));
+        assert_eq!(fields[2].path, vec![Access::Attr("x".to_string())]);
+
+        assert!(parse_format_string("{user.}").is_err());
+        assert!(parse_format_string("{user..name}").is_err());
+        assert!(parse_format_string("{user.na-me}").is_err());
+    }
+
+    #[test]
+    fn test_parse_items() {
+        let fields = parse_format_string("{a[0].b[key x]!r:>4} {[:]}").unwrap();
+        assert_eq!(fields[0].name, Some("a".to_string()));
+        assert_eq!(
+            fields[0].path,
+            vec![
+                Access::Item("0".to_string()),
+                Access::Attr("b".to_string()),
+                Access::Item("key x".to_string()),
+            ]
+        );
+        assert_eq!(fields[0].conversion, Some(Conversion::Repr));
+        assert_eq!(fields[0].spec.width, Some(4));
+        assert_eq!(fields[1].index, Some(0));
+        assert_eq!(fields[1].path, vec![Access::Item(":".to_string())]);
+
+        assert!(parse_format_string("{a[]}").is_err());
+        assert!(parse_format_string("{a[0}").is_err());
+        assert!(parse_format_string("{a[0]x}").is_err());
+    }
+
+    #[test]
+    fn test_format_items() {
+        let mut point = HashMap::new();
+        point.insert("x".to_string(), Value::from(1.5));
+        let mut values = HashMap::new();
+        values.insert(
+            "tags".to_string(),
+            Value::from(vec![Value::from("a"), Value::from(2), Value::from(point)]),
+        );
+
+        let formatter =
+            Formatter::new("{tags[0]!r} {tags[1]:03} {tags[2][x]:.2f} {tags[2].x}").unwrap();
+        assert_eq!(formatter.format_map(&values).unwrap(), "'a' 002 1.50 1.5");
+
+        // Composites without a presentation type render like Python's repr()
+        let formatter = Formatter::new("{tags} {tags[2]:>12}").unwrap();
+        assert_eq!(
+            formatter.format_map(&values).unwrap(),
+            "['a', 2, {'x': 1.5}]   {'x': 1.5}"
+        );
+        assert!(Formatter::new("{tags:d}")
+            .unwrap()
+            .format_map(&values)
+            .is_err());
+
+        let missing = Formatter::new("{tags[3]}").unwrap();
+        assert_eq!(missing.format_map(&values), Err(Error::missing("tags[3]")));
+
+        let edited = Formatter::new("{tags[1]}")
+            .unwrap()
+            .with_spec("tags[1]", FormatSpec::parse("+").unwrap())
+            .unwrap();
+        assert_eq!(edited.pattern(), "{tags[1]:+}");
+        assert_eq!(edited.format_map(&values).unwrap(), "+2");
+    }
+
+    #[test]
+    fn test_format_attributes() {
+        let mut address = HashMap::new();
+        address.insert("city".to_string(), Value::from("Oslo"));
+        let mut user = HashMap::new();
+        user.insert("name".to_string(), Value::from("Alice"));
+        user.insert("address".to_string(), Value::from(address));
+        let mut values = HashMap::new();
+        values.insert("user".to_string(), Value::from(user));
+
+        let formatter = Formatter::new("{user.name:>6} lives in {user.address.city!r}").unwrap();
+        assert_eq!(
+            formatter.format_map(&values).unwrap(),
+            " Alice lives in 'Oslo'"
+        );
+        assert_eq!(
+            formatter.format_with(&values).unwrap(),
+            " Alice lives in 'Oslo'"
+        );
+
+        let positional = Formatter::new("{0.name}").unwrap();
+        let list = vec![values["user"].clone()];
+        assert_eq!(positional.format_positional(&list).unwrap(), "Alice");
+
+        let missing = Formatter::new("{user.address.zip}").unwrap();
+        assert_eq!(
+            missing.format_map(&values),
+            Err(Error::missing("user.address.zip"))
+        );
+
+        let edited = formatter
+            .with_spec("user.name", FormatSpec::parse("<6").unwrap())
+            .unwrap();
+        assert_eq!(
+            edited.format_map(&values).unwrap(),
+            "Alice  lives in 'Oslo'"
+        );
+        assert!(formatter.with_spec("user", FormatSpec::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_conversion() {
+        let fields = parse_format_string("{name!r:>20} {0!s} {!a}").unwrap();
+        assert_eq!(fields[0].name, Some("name".to_string()));
+        assert_eq!(fields[0].conversion, Some(Conversion::Repr));
+        assert_eq!(fields[0].spec.width, Some(20));
+        assert_eq!(fields[1].index, Some(0));
+        assert_eq!(fields[1].conversion, Some(Conversion::Str));
+        assert_eq!(fields[2].conversion, Some(Conversion::Ascii));
+
+        assert!(parse_format_string("{name!x}").is_err());
+        assert!(parse_format_string("{name!}").is_err());
+        assert!(parse_format_string("{name!rr}").is_err());
+    }
+
+    #[test]
+    fn test_format_conversion() {
+        let cases = [
+            ("{v!r}", Value::from("hi"), "'hi'"),
+            ("{v!r}", Value::from("it's"), "\"it's\""),
+            (
+                "{v!r}",
+                Value::from("say \"hi\" it's"),
+                "'say \"hi\" it\\'s'",
+            ),
+            ("{v!r}", Value::from("tab\there\n"), "'tab\\there\\n'"),
+            ("{v!r}", Value::from("\u{7}\u{7f}"), "'\\x07\\x7f'"),
+            ("{v!r}", Value::from("café 日 😀"), "'café 日 😀'"),
+            (
+                "{v!a}",
+                Value::from("café 日 😀"),
+                "'caf\\xe9 \\u65e5 \\U0001f600'",
+            ),
+            ("{v!r:>8}", Value::from("hi"), "    'hi'"),
+            ("{v!r}", Value::from('x'), "'x'"),
+            ("{v!s:>5}", Value::from(42), "   42"),
+            ("{v!r}", Value::from(42), "42"),
+        ];
+
+        for (pattern, value, expected) in cases {
+            let formatter = Formatter::new(pattern).unwrap();
+            let mut values = HashMap::new();
+            values.insert("v".to_string(), value);
+            assert_eq!(
+                formatter.format_map(&values).unwrap(),
+                expected,
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_repeated_field_looked_up_once() {
+        let formatter = Formatter::new("{x}|{x:>6}|{y}|{x:.1f}|{y!r}").unwrap();
+        assert_eq!(formatter.slots, 2);
+
+        let mut lookups = Vec::new();
+        let result = formatter
+            .format_fn(|name| {
+                lookups.push(name.to_string());
+                match name {
+                    "x" => Some(Value::from(2.5)),
+                    "y" => Some(Value::from("a")),
+                    _ => None,
+                }
+            })
+            .unwrap();
+        assert_eq!(result, "2.5|   2.5|a|2.5|'a'");
+        assert_eq!(lookups, vec!["x", "y"]);
+
+        let mut count = 0;
+        formatter
+            .with_lookup_cache(false)
+            .format_fn(|_| {
+                count += 1;
+                Some(Value::from(1))
+            })
+            .unwrap();
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn test_format_to_sinks() {
+        let formatter = Formatter::new("[{a:^7}|{b:+.1f}]").unwrap();
+        let mut values = HashMap::new();
+        values.insert("a".to_string(), Value::from("mid"));
+        values.insert("b".to_string(), Value::from(2.25));
+
+        let mut out = String::from(">");
+        formatter.format_to(&mut out, &values).unwrap();
+        assert_eq!(out, ">[  mid  |+2.2]");
+
+        let mut bytes = Vec::new();
+        formatter.format_to_io(&mut bytes, &values).unwrap();
+        assert_eq!(bytes, b"[  mid  |+2.2]");
+
+        // Formatting errors are reported as-is
+        let mut out = String::new();
+        let err = formatter
+            .format_to(&mut out, &HashMap::<String, Value>::new())
+            .unwrap_err();
+        assert!(err.is_missing_field());
+    }
+
+    #[test]
+    fn test_format_to_io_error() {
+        struct Full;
+        impl io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let formatter = Formatter::new("x").unwrap();
+        let err = formatter
+            .format_to_io(&mut Full, &HashMap::<String, Value>::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::WriteError);
+        assert!(err.to_string().contains("disk full"));
+    }
+
+    #[test]
+    fn test_format_pairs() {
+        let formatter = Formatter::new("{a}-{b:>3}").unwrap();
+        assert_eq!(
+            formatter
+                .format(&[("a", Value::from(1)), ("b", Value::from("x"))])
+                .unwrap(),
+            "1-  x"
+        );
+
+        // Owned keys work too, and later pairs override earlier ones
+        let pairs = vec![
+            ("a".to_string(), Value::from(1)),
+            ("b".to_string(), Value::from(2)),
+            ("a".to_string(), Value::from(3)),
+        ];
+        assert_eq!(formatter.format(&pairs).unwrap(), "3-  2");
+
+        assert_eq!(
+            formatter.format(&[("a", Value::from(1))]),
+            Err(Error::missing("b"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_format_serde() {
+        use std::collections::BTreeMap;
+
+        #[derive(serde::Serialize)]
+        struct Job {
+            name: String,
+            retries: Option<u8>,
+            tags: Vec<&'static str>,
+            limits: BTreeMap<&'static str, f64>,
+        }
+
+        let job = Job {
+            name: "backup".to_string(),
+            retries: None,
+            tags: vec!["nightly", "db"],
+            limits: BTreeMap::from([("cpu", 0.5)]),
+        };
+        let formatter = Formatter::new("{name}:{tags[1]}:{limits.cpu:.0%}:{retries}").unwrap();
+        assert_eq!(formatter.format_serde(&job).unwrap(), "backup:db:50%:None");
+
+        let formatter = Formatter::new("{missing}").unwrap();
+        assert_eq!(formatter.format_serde(&job), Err(Error::missing("missing")));
+        assert!(formatter
+            .format_serde(&[1, 2])
+            .unwrap_err()
+            .is_conversion_error());
+        let bad_keys = BTreeMap::from([(vec![1], 2)]);
+        let err = formatter.format_serde(&bad_keys).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SerializeError);
+    }
+
+    #[test]
+    fn test_format_map_key_and_map_types() {
+        use std::collections::BTreeMap;
+
+        let formatter = Formatter::new("{a}-{b:>3}").unwrap();
+        let borrowed: HashMap<&str, i64> = HashMap::from([("a", 1), ("b", 2)]);
+        assert_eq!(formatter.format_map(&borrowed).unwrap(), "1-  2");
+        let owned = BTreeMap::from([("a".to_string(), "x"), ("b".to_string(), "y")]);
+        assert_eq!(formatter.format_map(&owned).unwrap(), "x-  y");
+        let partial = BTreeMap::from([("a", Value::from(1))]);
+        assert_eq!(formatter.format_map(&partial), Err(Error::missing("b")));
+
+        // The other map-taking methods accept the same providers
+        assert_eq!(formatter.estimate_len(&owned).unwrap(), 5);
+        assert_eq!(formatter.format_parts(&borrowed).unwrap(), ["1-  2"]);
+        assert_eq!(formatter.estimate_len(&partial), Err(Error::missing("b")));
+        let mut buf = String::new();
+        formatter.format_into(&mut buf, &owned).unwrap();
+        formatter.format_into(&mut buf, &borrowed).unwrap();
+        assert_eq!(buf, "x-  y1-  2");
+    }
+
+    #[test]
+    fn test_format_into_appends() {
+        let formatter = Formatter::new("[{n:>3}]").unwrap();
+        let mut values = HashMap::new();
+        let mut buf = String::from("> ");
+
+        for n in [1, 22] {
+            values.insert("n".to_string(), Value::from(n));
+            formatter.format_into(&mut buf, &values).unwrap();
+        }
+        assert_eq!(buf, "> [  1][ 22]");
+
+        // Reusing a cleared buffer doesn't reallocate
+        let capacity = buf.capacity();
+        buf.clear();
+        formatter.format_into(&mut buf, &values).unwrap();
+        assert_eq!(buf, "[ 22]");
+        assert_eq!(buf.capacity(), capacity);
+
+        values.clear();
+        assert_eq!(
+            formatter.format_into(&mut buf, &values),
+            Err(Error::missing("n"))
+        );
+
+        // Borrowed keys work without allocating
+        let borrowed = HashMap::from([("n", Value::from(5))]);
+        buf.clear();
+        formatter.format_into(&mut buf, &borrowed).unwrap();
+        assert_eq!(buf, "[  5]");
+        assert_eq!(formatter.format_map(&borrowed).unwrap(), "[  5]");
+    }
+
+    #[test]
+    fn test_size_hint() {
+        assert_eq!(Formatter::new("plain text").unwrap().size_hint(), 10);
+        assert_eq!(
+            Formatter::new("a={a:>20} b={b}").unwrap().size_hint(),
+            2 + 20 + 3 + DEFAULT_FIELD_SIZE
+        );
+        assert_eq!(Formatter::new("").unwrap().size_hint(), 0);
+
+        // Oversized fields will fail to format, so they don't reserve their width
+        let huge = Formatter::new("{:999999999}").unwrap();
+        assert_eq!(huge.size_hint(), Formatter::DEFAULT_MAX_WIDTH);
+    }
+
+    #[test]
+    fn test_estimate_len() {
+        let formatter = Formatter::new("{a:*^7} {b:#010x} {c:.3e} {a!r}").unwrap();
+        let values = HashMap::from([
+            ("a", Value::from("ü")),
+            ("b", Value::from(255)),
+            ("c", Value::from(-1.5)),
+        ]);
+        let output = formatter.format_map(&values).unwrap();
+        assert_eq!(formatter.estimate_len(&values).unwrap(), output.len());
+        assert_ne!(output.len(), output.chars().count());
+
+        let err = formatter
+            .estimate_len(&HashMap::<&str, Value>::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        let err = Formatter::new("{}")
+            .unwrap()
+            .estimate_len(&values)
+            .unwrap_err();
+        assert!(err.to_string().contains("estimate_len"));
+    }
+
+    #[test]
+    fn test_max_len() {
+        let formatter = Formatter::new("<13>{host} {msg}").unwrap();
+        let values = HashMap::from([("host", Value::from("db1")), ("msg", Value::from("a→b→c"))]);
+        assert_eq!(formatter.format_map(&values).unwrap().len(), 17);
+        assert_eq!(formatter.max_len(), None);
+
+        let strict = formatter.with_max_len(16, OverflowPolicy::Error);
+        let err = strict.format_map(&values).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthLimitExceeded);
+        assert_eq!(
+            err.to_string(),
+            "output of 17 bytes exceeds the limit of 16"
+        );
+        assert!(strict.estimate_len(&values).is_err());
+        assert_ne!(strict, formatter);
+        assert_eq!(
+            formatter
+                .with_max_len(17, OverflowPolicy::Error)
+                .format_map(&values)
+                .unwrap()
+                .len(),
+            17
+        );
+
+        // Truncation backs off to a character boundary before the marker
+        let marker = "...".to_string();
+        let cut = formatter.with_max_len(14, OverflowPolicy::Truncate { marker });
+        assert_eq!(cut.format_map(&values).unwrap(), "<13>db1 a...");
+        assert_eq!(cut.estimate_len(&values).unwrap(), 12);
+        let mut out = String::new();
+        cut.format_to(&mut out, &values).unwrap();
+        assert_eq!(out, "<13>db1 a...");
+
+        // Only the record counts against the limit, not what's already in the buffer
+        let mut buf = String::from("prefix: ");
+        cut.format_into(&mut buf, &values).unwrap();
+        assert_eq!(buf, "prefix: <13>db1 a...");
+
+        // A marker longer than the limit is cut too
+        let marker = "→→".to_string();
+        let tiny = formatter.with_max_len(4, OverflowPolicy::Truncate { marker });
+        assert_eq!(tiny.format_map(&values).unwrap(), "<→");
+        assert_eq!(
+            tiny.overflow_policy(),
+            &OverflowPolicy::Truncate {
+                marker: "→→".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_format_parts() {
+        let split = |field: &str| OverflowPolicy::Split {
+            field: field.to_string(),
+            marker: "…".to_string(),
+        };
+        let formatter = Formatter::new("[{__part}/{__parts}] {msg!r}")
+            .unwrap()
+            .with_max_len(16, split("msg"));
+
+        let values = HashMap::from([("msg", Value::from("ok"))]);
+        assert_eq!(formatter.format_parts(&values).unwrap(), ["[1/1] 'ok'"]);
+
+        // The field's formatted text is split, between characters
+        let values = HashMap::from([("msg", Value::from("añoañoañoaño"))]);
+        let records = formatter.format_parts(&values).unwrap();
+        assert_eq!(records, ["[1/3] 'añoa…", "[2/3] ñoaño…", "[3/3] año'"]);
+        assert!(records.iter().all(|r| r.len() <= 16));
+        let single = Formatter::new("{msg}")
+            .unwrap()
+            .with_max_len(8, split("msg"));
+        let err = single.format_map(&values).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthLimitExceeded);
+        assert_eq!(single.format_parts(&values).unwrap().len(), 3);
+
+        // Reaching ten records makes every record number longer
+        let values = HashMap::from([("msg", Value::from("x".repeat(100)))]);
+        let records = formatter.format_parts(&values).unwrap();
+        assert_eq!(records.len(), 18);
+        assert_eq!(records[0], "[1/18] 'xxxxx…");
+        assert_eq!(records[17], "[18/18] xxxxxxx'");
+        assert!(records.iter().all(|r| r.len() <= 16));
+
+        // Other policies make a single record
+        let cut = formatter.with_max_len(12, OverflowPolicy::default());
+        assert!(cut.format_parts(&values).is_err());
+
+        let err = formatter
+            .with_max_len(9, split("msg"))
+            .format_parts(&values)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthLimitExceeded);
+        let err = formatter
+            .with_max_len(16, split("other"))
+            .format_parts(&values)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidFormatSpec);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_datetime_values() {
+        use chrono::{FixedOffset, NaiveDate, TimeZone, Timelike};
+
+        let utc = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .unwrap()
+            .and_hms_opt(10, 30, 5)
+            .unwrap();
+        let offset = FixedOffset::west_opt(5 * 3600 + 1800)
+            .unwrap()
+            .from_local_datetime(&utc.with_nanosecond(123_000_000).unwrap())
+            .unwrap();
+        let values = [Value::from(utc), Value::from(offset)];
+
+        let cases = [
+            ("{0:%Y-%m-%d %H:%M:%S}", "2024-01-15 10:30:05"),
+            ("[{1:%H:%M:%S%.3f %z}]", "[10:30:05.123 -0530]"),
+            ("{0:%d/%m/%y} {{%}}", "15/01/24 {%}"),
+            // Without a datetime format, like Python's `str()`
+            ("{0}", "2024-01-15 10:30:05+00:00"),
+            ("{1:>34}", "  2024-01-15 10:30:05.123000-05:30"),
+            ("{0:%}", "n/a"),
+        ];
+        for (pattern, expected) in cases {
+            let formatter = Formatter::new(pattern).unwrap();
+            let result = formatter.format_positional(&values);
+            if expected == "n/a" {
+                // `%` alone is still the percentage type
+                assert!(result.unwrap_err().is_conversion_error());
+            } else {
+                assert_eq!(result.unwrap(), expected, "{}", pattern);
+            }
+        }
+
+        let list = Value::from(vec![values[0].clone(), values[1].clone()]);
+        assert_eq!(
+            list.to_string(),
+            "[datetime.datetime(2024, 1, 15, 10, 30, 5, tzinfo=datetime.timezone.utc), \
+             datetime.datetime(2024, 1, 15, 10, 30, 5, 123000, \
+             tzinfo=datetime.timezone(datetime.timedelta(days=-1, seconds=66600)))]"
+        );
+
+        let formatter = Formatter::new("{ts:%Y}").unwrap();
+        assert_eq!(formatter.pattern(), "{ts:%Y}");
+        assert_eq!(formatter.template().fields()[0].strftime(), Some("%Y"));
+        let err = formatter.format_fn(|_| Some(Value::from(1))).unwrap_err();
+        assert!(err.is_conversion_error());
+
+        let err = Formatter::new("at {ts:%Y-%Q}").unwrap_err();
+        assert_eq!(err, Error::spec_at("invalid datetime format '%Y-%Q'", 7));
+    }
+
+    #[test]
+    fn test_128_bit_integers() {
+        let u64_max = Value::from(u64::MAX);
+        let i128_min = Value::from(i128::MIN);
+        let u128_max = Value::from(u128::MAX);
+        let cases = [
+            ("d", &u64_max, "18446744073709551615"),
+            (",d", &u64_max, "18,446,744,073,709,551,615"),
+            ("+_d", &u64_max, "+18_446_744_073_709_551_615"),
+            ("x", &u64_max, "ffffffffffffffff"),
+            ("n", &u64_max, "18446744073709551615"),
+            ("", &i128_min, "-170141183460469231731687303715884105728"),
+            (
+                "045,d",
+                &i128_min,
+                "-170,141,183,460,469,231,731,687,303,715,884,105,728",
+            ),
+            ("#X", &u128_max, "0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"),
+            (
+                "o",
+                &u128_max,
+                "3777777777777777777777777777777777777777777",
+            ),
+        ];
+        for (spec, value, expected) in cases {
+            let formatter = Formatter::new(&format!("{{:{}}}", spec)).unwrap();
+            let values = [value.clone()];
+            assert_eq!(
+                formatter.format_positional(&values).unwrap(),
+                expected,
+                "{:?}",
+                spec
+            );
+        }
+
+        let formatter = Formatter::new("{:x}").unwrap();
+        assert_eq!(
+            formatter.format_positional(&[i128_min]).unwrap(),
+            "-80000000000000000000000000000000"
+        );
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_decimal_values() {
+        use rust_decimal::Decimal;
+
+        // Expected output from Python's `format(Decimal(...), spec)`
+        let cases = [
+            ("", "1.50", "1.50"),
+            (".1f", "2.25", "2.2"),
+            (".0f", "3.5", "4"),
+            (",.2f", "1234567.895", "1,234,567.90"),
+            ("015,.2f", "-1234.5", "-000,001,234.50"),
+            ("z.2f", "-0.001", "0.00"),
+            (".3e", "1.5", "1.500e+0"),
+            ("E", "1234.5", "1.2345E+3"),
+            (".2g", "1234.5", "1.2e+3"),
+            ("", "0.0000001", "1E-7"),
+            (".3g", "99.97", "100"),
+            ("%", "0.125", "12.5%"),
+            ("^+10.1%", "-5", " -500.0%  "),
+            ("n", "1234.5", "1234.5"),
+            (
+                "f",
+                "79228162514264337593543950335",
+                "79228162514264337593543950335",
+            ),
+        ];
+        for (spec, text, expected) in cases {
+            let formatter = Formatter::new(&format!("{{:{}}}", spec)).unwrap();
+            let values = [Value::from(text.parse::<Decimal>().unwrap())];
+            assert_eq!(
+                formatter.format_positional(&values).unwrap(),
+                expected,
+                "{:?} {}",
+                spec,
+                text
+            );
+        }
+
+        // Integer types still need an integer
+        let formatter = Formatter::new("{:d}").unwrap();
+        assert!(formatter
+            .format_positional(&[Value::from(Decimal::ONE)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_after_sign_alignment() {
+        // Padding goes between the sign (and `#` prefix) and the digits. Only
+        // a `0` fill is grouped along with the digits, like the `0` flag
+        let cases = [
+            ("=+12,.2f", Value::from(-1234.0), "-   1,234.00"),
+            ("=+12,.2f", Value::from(1234.5), "+   1,234.50"),
+            ("=12,.2f", Value::from(-0.5), "-       0.50"),
+            ("=+12,.2f", Value::from(12345678.0), "+12,345,678.00"),
+            ("0=+12,.2f", Value::from(-1234.0), "-0,001,234.00"),
+            ("0=+12,.2f", Value::from(-0.5), "-0,000,000.50"),
+            ("012,.2f", Value::from(-1234.0), "-0,001,234.00"),
+            ("*=+12,.2f", Value::from(-1234.0), "-***1,234.00"),
+            ("x=+15_.1f", Value::from(12345678.0), "+xx12_345_678.0"),
+            ("0=+14,.3e", Value::from(-1234.0), "-0,001.234e+03"),
+            ("0=12,d", Value::from(-1234), "-000,001,234"),
+            ("=010,d", Value::from(-1234), "-0,001,234"),
+            ("0=+14_d", Value::from(12345678), "+0_012_345_678"),
+            ("=+14_x", Value::from(12345678), "+      bc_614e"),
+            ("0=14_x", Value::from(-1234), "-000_0000_04d2"),
+            ("*=+#9x", Value::from(255), "+0x****ff"),
+            ("=#8x", Value::from(-255), "-0x   ff"),
+            ("#010_x", Value::from(-255), "-0x00_00ff"),
+            ("0=+#12_b", Value::from(-5), "-0b0000_0101"),
+            (" #o", Value::from(-8), "-0o10"),
+            ("<08d", Value::from(-42), "-4200000"),
+            ("8", Value::from(65), "      65"),
+            ("8c", Value::from(65), "       A"),
+        ];
+        for (spec, value, expected) in cases {
+            let formatter = Formatter::new(&format!("{{:{}}}", spec)).unwrap();
+            assert_eq!(
+                formatter.format_positional(&[value]).unwrap(),
+                expected,
+                "{:?}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_separators() {
+        let formatter = Formatter::new("{:,.2f}|{:e}|{:,d}|{:,}|{:x}|{:n}|{!s}").unwrap();
+        let values = [
+            Value::from(-1234567.891),
+            Value::from(1.5),
+            Value::from(1234),
+            Value::from(0.5),
+            Value::from(255),
+            Value::from(1.5),
+            Value::from(2.5),
+        ];
+        assert_eq!(
+            formatter.format_positional(&values).unwrap(),
+            "-1,234,567.89|1.500000e+00|1,234|0.5|ff|1.5|2.5"
+        );
+
+        let swiss = formatter.with_separators('.', '\'');
+        assert_eq!(
+            swiss.format_positional(&values).unwrap(),
+            "-1'234'567.89|1.500000e+00|1'234|0.5|ff|1.5|2.5"
+        );
+
+        // Multi-byte separators still pad to the width in characters
+        let french = Formatter::new("{:>12,.1f}|{:012,.1f}")
+            .unwrap()
+            .with_separators(',', '\u{202f}');
+        let values = [Value::from(1234567.25), Value::from(-1234.25)];
+        assert_eq!(
+            french.format_positional(&values).unwrap(),
+            " 1\u{202f}234\u{202f}567,2|-0\u{202f}001\u{202f}234,2"
+        );
+
+        assert_eq!(formatter.with_separators('.', ','), formatter);
+        assert_ne!(swiss, formatter);
+    }
+
+    #[test]
+    fn test_pinned_exponent() {
+        let formatter = Formatter::new("{:.3e}|{:+011.2E}|{:.0e}|{:#.0e}|{:g}")
+            .unwrap()
+            .with_exponent(3);
+        let row = |values: [f64; 5]| {
+            formatter
+                .format_positional(&values.map(Value::from))
+                .unwrap()
+        };
+        assert_eq!(
+            row([1234.5, -0.5, 2500.0, 3500.0, 1234.5]),
+            "1.234e+03|-000.00E+03|2e+03|4.e+03|1234.5"
+        );
+        assert_eq!(
+            row([999999.0, 12.0, 1e-9, 0.0, 1e9]),
+            "999.999e+03|+000.01E+03|0e+03|0.e+03|1e+09"
+        );
+
+        // Ties round half to even on the exact value
+        let formatter = Formatter::new("{:.1e}").unwrap().with_exponent(-1);
+        let values = [0.125, 0.375, 0.1251].map(Value::from);
+        let rendered: Vec<_> = values
+            .iter()
+            .map(|v| {
+                formatter
+                    .format_positional(std::slice::from_ref(v))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(rendered, ["1.2e-01", "3.8e-01", "1.3e-01"]);
+
+        let formatter = Formatter::new("{:.0e}").unwrap().with_exponent(4);
+        let rendered: Vec<_> = [15000.0, 25000.0, 25000.5, 99999.0]
+            .iter()
+            .map(|&v| formatter.format_positional(&[Value::from(v)]).unwrap())
+            .collect();
+        assert_eq!(rendered, ["2e+04", "2e+04", "3e+04", "10e+04"]);
+
+        assert_eq!(formatter.exponent(), Some(4));
+        assert_ne!(formatter, Formatter::new("{:.0e}").unwrap());
+    }
+
+    #[test]
+    fn test_bind() {
+        let formatter = Formatter::new("{host}:{port:05d} {host.len} {host!r}").unwrap();
+        let mut user = HashMap::new();
+        user.insert("len".to_string(), Value::from(3));
+        let bound = formatter.bind("host", Value::Map(user)).unwrap();
+        assert_eq!(bound.pattern(), "{{'len': 3}}:{port:05d} 3 {{'len': 3}}");
+        assert_eq!(
+            bound.format_fn(|_| Some(Value::from(80))).unwrap(),
+            "{'len': 3}:00080 3 {'len': 3}"
+        );
+
+        // Bound text uses the formatter's settings and may contain braces
+        let formatter = Formatter::new("{0:,.1f} {1}")
+            .unwrap()
+            .with_separators(',', '.');
+        let bound = formatter.bind("0", 1234.5).unwrap();
+        assert_eq!(bound.pattern(), "1.234,5 {1}");
+        let bound = bound.bind("1", "{x}").unwrap();
+        assert_eq!(bound.pattern(), "1.234,5 {{x}}");
+        assert_eq!(bound.format_positional(&[]).unwrap(), "1.234,5 {x}");
+
+        let formatter = Formatter::new("{name} {port:d} {user.id:x}").unwrap();
+        let err = formatter.bind("port", "http").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BindError);
+        assert_eq!(err.field_name(), Some("port"));
+        assert_eq!(
+            err.to_string(),
+            "cannot bind port: type conversion error: cannot convert Str(\"http\") to int"
+        );
+
+        let err = formatter.bind("user", Value::Null).unwrap_err();
+        assert_eq!(err.field_name(), Some("user.id"));
+        assert_eq!(
+            formatter.bind("other", 1).unwrap_err(),
+            Error::missing("other")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "atomic-stats")]
+    fn test_stats() {
+        let formatter = Formatter::new("{n:d}|{s:>3}").unwrap();
+        assert_eq!(formatter.stats(), FormatStats::default());
+
+        // Clones count into the same counters, from any thread
+        let cut = formatter.with_max_len(
+            4,
+            OverflowPolicy::Truncate {
+                marker: String::new(),
+            },
+        );
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let values = HashMap::from([("n", Value::from(7)), ("s", Value::from("x"))]);
+                    assert_eq!(cut.format_map(&values).unwrap(), "7|  ");
+                });
+            }
+        });
+        assert_eq!(formatter.stats().fields, 8);
+        assert_eq!(formatter.stats().truncations, 4);
+
+        // A bad value counts as a conversion error, a missing one doesn't
+        let values = HashMap::from([("n", Value::from("seven")), ("s", Value::from("x"))]);
+        assert!(formatter.format_map(&values).is_err());
+        assert!(formatter
+            .format_map(&HashMap::<&str, Value>::new())
+            .is_err());
+        let nulls = formatter.with_null_policy(NullPolicy::Error);
+        assert!(nulls
+            .format_map(&HashMap::from([("n", Value::Null)]))
+            .is_err());
+        assert_eq!(
+            cut.stats(),
+            FormatStats {
+                fields: 8,
+                conversion_errors: 2,
+                truncations: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_float_repr() {
+        let formatter = Formatter::new("{}").unwrap();
+        assert_eq!(formatter.float_repr(), FloatRepr::General);
+        let python = formatter.with_float_repr(FloatRepr::Python);
+        assert_eq!(python.float_repr(), FloatRepr::Python);
+        assert_ne!(python, formatter);
+
+        // Python's str() of each value, and with `#`, `,`, `012` and `+` specs
+        let cases: &[(f64, [&str; 5])] = &[
+            (1e16, ["1e+16", "1.e+16", "1e+16", "00000001e+16", "+1e+16"]),
+            (2.0, ["2.0", "2.0", "2.0", "0000000002.0", "+2.0"]),
+            (
+                1.5e-7,
+                ["1.5e-07", "1.5e-07", "1.5e-07", "000001.5e-07", "+1.5e-07"],
+            ),
+            (0.1, ["0.1", "0.1", "0.1", "0000000000.1", "+0.1"]),
+            (-0.0, ["-0.0", "-0.0", "-0.0", "-000000000.0", "-0.0"]),
+            (
+                123456789.0,
+                [
+                    "123456789.0",
+                    "123456789.0",
+                    "123,456,789.0",
+                    "0123456789.0",
+                    "+123456789.0",
+                ],
+            ),
+            (
+                5e-324,
+                ["5e-324", "5.e-324", "5e-324", "0000005e-324", "+5e-324"],
+            ),
+            (
+                0.0001,
+                ["0.0001", "0.0001", "0.0001", "0000000.0001", "+0.0001"],
+            ),
+            (1e-5, ["1e-05", "1.e-05", "1e-05", "00000001e-05", "+1e-05"]),
+            (
+                1e15,
+                [
+                    "1000000000000000.0",
+                    "1000000000000000.0",
+                    "1,000,000,000,000,000.0",
+                    "1000000000000000.0",
+                    "+1000000000000000.0",
+                ],
+            ),
+            (
+                f64::MAX,
+                [
+                    "1.7976931348623157e+308",
+                    "1.7976931348623157e+308",
+                    "1.7976931348623157e+308",
+                    "1.7976931348623157e+308",
+                    "+1.7976931348623157e+308",
+                ],
+            ),
+        ];
+        for (value, expected) in cases {
+            for (spec, expected) in ["", "#", ",", "012", "+"].iter().zip(expected) {
+                let formatter = Formatter::new(&format!("{{:{}}}", spec))
+                    .unwrap()
+                    .with_float_repr(FloatRepr::Python);
+                let result = formatter.format_positional(&[Value::from(*value)]).unwrap();
+                assert_eq!(&result, expected, "{:?} with {:?}", value, spec);
+            }
+        }
+
+        // A precision, a type or a non-float keeps the usual rules
+        let python = Formatter::new("{:.3} {:g} {} {}")
+            .unwrap()
+            .with_float_repr(FloatRepr::Python);
+        let values = [0.12345.into(), 2.0.into(), 7.into(), f64::NAN.into()];
+        assert_eq!(python.format_positional(&values).unwrap(), "0.123 2 7 nan");
+    }
+
+    #[test]
+    fn test_null_policy() {
+        let formatter = Formatter::new("{a}|{a:*^6d}|{a!r}|{b:.1f}").unwrap();
+        let mut values = HashMap::new();
+        values.insert("a".to_string(), Value::Null);
+        values.insert("b".to_string(), Value::from(None::<f64>));
+        assert_eq!(formatter.null_policy(), NullPolicy::None);
+        assert_eq!(
+            formatter.format_map(&values).unwrap(),
+            "None|*None*|None|None"
+        );
+
+        let blank = formatter.with_null_policy(NullPolicy::Empty);
+        assert_eq!(blank.format_map(&values).unwrap(), "|******||");
+        assert_ne!(blank, formatter);
+
+        let strict = formatter.with_null_policy(NullPolicy::Error);
+        let err = strict.format_map(&values).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConversionError);
+
+        values.insert("b".to_string(), Value::from(Some(2.25)));
+        values.insert("a".to_string(), Value::from(1));
+        assert_eq!(strict.format_map(&values).unwrap(), "1|**1***|1|2.2");
+    }
+
+    #[test]
+    fn test_max_width() {
+        let values = vec![Value::from(1.5)];
+        let wide = Formatter::new("{:999999999}").unwrap();
+        assert_eq!(
+            wide.format_positional(&values).unwrap_err(),
+            Error::WidthLimitExceeded {
+                value: 999999999,
+                limit: Formatter::DEFAULT_MAX_WIDTH
+            }
+        );
+
+        // Precision is limited too, and the limit itself is allowed
+        let precise = Formatter::new("{:.20f}").unwrap();
+        assert!(precise
+            .with_max_width(19)
+            .format_positional(&values)
+            .is_err());
+        assert_eq!(
+            precise
+                .with_max_width(20)
+                .format_positional(&values)
+                .unwrap(),
+            "1.50000000000000000000"
+        );
+
+        let raised = wide.with_max_width(usize::MAX);
+        assert_eq!(raised.max_width(), usize::MAX);
+        assert_eq!(
+            Formatter::new("{a:>12}")
+                .unwrap()
+                .with_max_width(4)
+                .format(&[("a", Value::from(1))])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::WidthLimitExceeded
+        );
+    }
+
+    #[test]
+    fn test_zero_width_percentage() {
+        // A zero-padded width smaller than the % sign must not underflow
+        let values = vec![Value::from(0.5)];
+        for template in ["{:00%}", "{:0%}", "{:01%}"] {
+            assert_eq!(
+                Formatter::new(template)
+                    .unwrap()
+                    .format_positional(&values)
+                    .unwrap(),
+                "50.000000%",
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_format_in_lookup() {
+        // A lookup that formats another template must not clash with the
+        // shared scratch buffer of the outer call
+        let inner = Formatter::new("{x:*^5}").unwrap();
+        let outer = Formatter::new("<{a:>7}|{b}>").unwrap();
+        let result = outer
+            .format_fn(|name| {
+                let values = HashMap::from([("x".to_string(), Value::from(name))]);
+                inner.format_map(&values).ok().map(Value::from)
+            })
+            .unwrap();
+        assert_eq!(result, "<  **a**|**b**>");
+    }
+
+    #[test]
+    fn test_alignment_counts_characters() {
+        let spec = FormatSpec::parse(">5").unwrap();
+        assert_eq!(aligned("日本語", &spec), "  日本語");
+
+        let spec = FormatSpec::parse("*<5").unwrap();
+        assert_eq!(aligned("👍🏽ok", &spec), "👍🏽ok*");
+
+        let spec = FormatSpec::parse("^6").unwrap();
+        assert_eq!(aligned("日本", &spec), "  日本  ");
+
+        // Combining marks count as separate characters, as in Python
+        let spec = FormatSpec::parse(">3").unwrap();
+        assert_eq!(aligned("e\u{301}", &spec), " e\u{301}");
+    }
+
+    #[test]
+    fn test_wide_fill() {
+        // Padding is written in chunks, so check widths around the chunk size
+        for n in [
+            FILL_CHUNK - 1,
+            FILL_CHUNK,
+            FILL_CHUNK + 1,
+            3 * FILL_CHUNK + 5,
+        ] {
+            let mut out = String::new();
+            write_fill(&mut out, '\u{b7}', n).unwrap();
+            assert_eq!(out, "\u{b7}".repeat(n));
+        }
+
+        let spec = FormatSpec::parse("\u{1f44d}^75").unwrap();
+        let expected = format!("{0}x{0}", "\u{1f44d}".repeat(37));
+        assert_eq!(aligned("x", &spec), expected);
+    }
+
+    #[test]
+    fn test_precision_and_width_share_metric() {
+        let formatter = Formatter::new("[{s:^6.2}]").unwrap();
+        let mut values = HashMap::new();
+        values.insert("s".to_string(), Value::from("日本語"));
+        assert_eq!(formatter.format_map(&values).unwrap(), "[  日本  ]");
+
+        values.insert("s".to_string(), Value::from("😀😀😀"));
+        assert_eq!(formatter.format_map(&values).unwrap(), "[  😀😀  ]");
+    }
+
+    #[test]
+    fn test_number_precision() {
+        // Like Python, `n` takes a precision for floats but not for integers
+        let formatter = Formatter::new("{:.2n}").unwrap();
+        assert_eq!(
+            formatter.format_positional(&[Value::from(1.2345)]).unwrap(),
+            "1.2"
+        );
+        assert_eq!(
+            formatter.format_positional(&[Value::from(5)]),
+            Err(Error::spec(
+                "precision not allowed in integer format specifier".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_with_spec() {
+        let formatter = Formatter::new("{a:>4}|{b}|{a}").unwrap();
+        let spec = FormatSpec::parse("<6").unwrap();
+        let edited = formatter.with_spec("a", spec.clone()).unwrap();
+
+        assert_eq!(*edited.fields[0].spec, spec);
+        assert_eq!(*edited.fields[1].spec, FormatSpec::default());
+        assert_eq!(*edited.fields[2].spec, spec);
+        // The original is left untouched
+        assert_eq!(formatter.fields[0].spec.width, Some(4));
+
+        let positional = Formatter::new("{0:d} {1:d}").unwrap();
+        let edited = positional
+            .with_spec("1", FormatSpec::parse("03d").unwrap())
+            .unwrap();
+        let values = vec![Value::from(1), Value::from(2)];
+        assert_eq!(edited.format_positional(&values).unwrap(), "1 002");
+
+        // Changing the presentation type switches the field's writer
+        let edited = positional
+            .with_spec("0", FormatSpec::parse("#x").unwrap())
+            .unwrap();
+        let values = vec![Value::from(255), Value::from(2)];
+        assert_eq!(edited.format_positional(&values).unwrap(), "0xff 2");
+
+        assert!(matches!(
+            formatter.with_spec("missing", FormatSpec::default()),
+            Err(Error::MissingField { .. })
+        ));
+    }
+
+    #[test]
+    // The `atomic-stats` counters aren't hashed or compared
+    #[cfg_attr(feature = "atomic-stats", allow(clippy::mutable_key_type))]
+    fn test_equality_and_hash() {
+        use std::collections::HashSet;
+
+        let a = Formatter::new("{} {x:05d}").unwrap();
+        let b = Formatter::new("{0} {x:005d}").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, Formatter::new("{} {x:5d}").unwrap());
+        assert_ne!(a, a.with_locale(Locale::de_de()));
+        assert_eq!(
+            a.with_spec("x", FormatSpec::parse("5d").unwrap()).unwrap(),
+            Formatter::new("{} {x:5d}").unwrap()
+        );
+
+        // The dialect decides which specs `with_spec` accepts
+        let printf = Formatter::new_with_dialect("{} {x:05d}", Dialect::Printf).unwrap();
+        assert_ne!(a, printf);
+
+        let unique: HashSet<Formatter> = [a.clone(), b, a.with_max_width(10), printf]
+            .into_iter()
+            .collect();
+        assert_eq!(unique.len(), 3);
+    }
+
+    #[test]
+    fn test_pattern_tracks_edits() {
+        let formatter = Formatter::new("{{{a.b!r:>4}}} {} {c}").unwrap();
+        assert_eq!(formatter.pattern(), "{{{a.b!r:>4}}} {} {c}");
+
+        // Edits regenerate an equivalent pattern
+        let edited = formatter
+            .with_spec("c", FormatSpec::parse("08.3f").unwrap())
+            .unwrap();
+        assert_eq!(edited.pattern(), "{{{a.b!r:>4}}} {0} {c:08.3f}");
+        assert_eq!(formatter.pattern(), "{{{a.b!r:>4}}} {} {c}");
+
+        let edited = formatter.with_literal_replaced(" ", "{");
+        assert_eq!(edited.to_string(), "{{{a.b!r:>4}}}{{{0}{{{c}");
+        let reparsed = Formatter::new(edited.pattern()).unwrap();
+        assert_eq!(reparsed.pattern(), edited.pattern());
+    }
+
+    #[test]
+    fn test_with_literal_replaced() {
+        let formatter = Formatter::new("{a}, {b}, end").unwrap();
+        let edited = formatter.with_literal_replaced(", ", ";");
+
+        let mut values = HashMap::new();
+        values.insert("a".to_string(), Value::from("x"));
+        values.insert("b".to_string(), Value::from("y"));
+        assert_eq!(edited.format_map(&values).unwrap(), "x;y;end");
+
+        // Empty search strings are a no-op
+        let same = formatter.with_literal_replaced("", "!");
+        assert_eq!(same.format_map(&values).unwrap(), "x, y, end");
+    }
+
+    #[test]
+    fn test_clones_share_compiled_pattern() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Formatter>();
+
+        let formatter = Formatter::new("{n:>4d}|{s}").unwrap();
+        let clone = formatter.clone();
+        assert!(Arc::ptr_eq(&formatter.fields, &clone.fields));
+        assert!(Arc::ptr_eq(&formatter.template, &clone.template));
+
+        // Edits compile their own copy, leaving the original's alone
+        let edited = formatter.with_literal_replaced("|", ",");
+        assert!(!Arc::ptr_eq(&formatter.fields, &edited.fields));
+
+        std::thread::scope(|scope| {
+            for n in 0..4 {
+                let formatter = formatter.clone();
+                scope.spawn(move || {
+                    let values = HashMap::from([("n", Value::from(n)), ("s", Value::from("x"))]);
+                    assert_eq!(formatter.format_map(&values).unwrap(), format!("   {n}|x"));
+                });
+            }
+        });
+    }
 }
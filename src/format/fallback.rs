@@ -0,0 +1,173 @@
+//! Formatters tried in turn until one succeeds.
+
+use super::engine::Formatter;
+use super::provider::ValueProvider;
+use crate::error::{Error, Result};
+use crate::types::ToValue;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// Formatters tried in order, the first to succeed giving the output.
+///
+/// Made with [`Formatter::or_else`]. A rich template can be paired with a
+/// plainer one that needs fewer fields, so a record missing a field or
+/// holding a value of the wrong type still produces a line instead of an
+/// error at the call site. Any error moves on to the next formatter; if
+/// every one fails, the last one's error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::{Formatter, Value};
+/// use std::collections::HashMap;
+///
+/// let chain = Formatter::new("{ts} {level:<5} {code:04d} {msg}")
+///     .unwrap()
+///     .or_else(Formatter::new("{ts} {__raw}").unwrap());
+///
+/// let full = HashMap::from([
+///     ("ts", Value::from("12:00")),
+///     ("level", Value::from("WARN")),
+///     ("code", Value::from(42)),
+///     ("msg", Value::from("slow")),
+/// ]);
+/// assert_eq!(chain.format_map(&full).unwrap(), "12:00 WARN  0042 slow");
+///
+/// let raw = HashMap::from([("ts", Value::from("12:01")), ("__raw", Value::from("?? garbled"))]);
+/// assert_eq!(chain.format_map(&raw).unwrap(), "12:01 ?? garbled");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FormatterChain {
+    formatters: Vec<Formatter>, // Never empty
+}
+
+impl FormatterChain {
+    /// Create a chain of a formatter and its first fallback.
+    pub(super) fn new(first: Formatter, fallback: Formatter) -> Self {
+        FormatterChain {
+            formatters: vec![first, fallback],
+        }
+    }
+
+    /// Append another fallback, tried after every formatter already in the chain.
+    pub fn or_else(mut self, fallback: Formatter) -> Self {
+        self.formatters.push(fallback);
+        self
+    }
+
+    /// Get the formatters, in the order they are tried.
+    pub fn formatters(&self) -> &[Formatter] {
+        &self.formatters
+    }
+
+    /// Format values from a HashMap with the first formatter that succeeds.
+    ///
+    /// See [`Formatter::format_map`].
+    pub fn format_map<K, V, S>(&self, values: &HashMap<K, V, S>) -> Result<String>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: ToValue,
+        S: BuildHasher,
+    {
+        self.format_with(values)
+    }
+
+    /// Format values looked up from a [`ValueProvider`] with the first
+    /// formatter that succeeds.
+    ///
+    /// See [`Formatter::format_with`].
+    pub fn format_with<P>(&self, provider: &P) -> Result<String>
+    where
+        P: ValueProvider + ?Sized,
+    {
+        self.format_indexed(provider).map(|(_, output)| output)
+    }
+
+    /// Format values like [`format_with`](Self::format_with), also returning
+    /// the index of the formatter that succeeded.
+    ///
+    /// This lets a pipeline count how often it falls back.
+    pub fn format_indexed<P>(&self, provider: &P) -> Result<(usize, String)>
+    where
+        P: ValueProvider + ?Sized,
+    {
+        let (last, first) = self
+            .formatters
+            .split_last()
+            .expect("chains are never empty");
+        for (index, formatter) in first.iter().enumerate() {
+            if let Ok(output) = formatter.format_with(provider) {
+                return Ok((index, output));
+            }
+        }
+        last.format_with(provider)
+            .map(|output| (first.len(), output))
+    }
+
+    /// Write the output of the first formatter that succeeds into any
+    /// [`fmt::Write`] sink.
+    ///
+    /// Unlike [`Formatter::format_to`], nothing is written on error.
+    pub fn format_to<W, P>(&self, out: &mut W, values: &P) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+        P: ValueProvider + ?Sized,
+    {
+        let output = self.format_with(values)?;
+        out.write_str(&output).map_err(Error::write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::types::Value;
+
+    #[test]
+    fn test_fallback_order() {
+        let chain = Formatter::new("{n:d} items")
+            .unwrap()
+            .or_else(Formatter::new("{n} (raw)").unwrap())
+            .or_else(Formatter::new("nothing").unwrap());
+        assert_eq!(chain.formatters().len(), 3);
+
+        let values = HashMap::from([("n", Value::from(3))]);
+        assert_eq!(
+            chain.format_indexed(&values).unwrap(),
+            (0, "3 items".to_string())
+        );
+
+        // A conversion error falls back to the next formatter
+        let values = HashMap::from([("n", Value::from("three"))]);
+        assert_eq!(
+            chain.format_indexed(&values).unwrap(),
+            (1, "three (raw)".to_string())
+        );
+
+        // So does a missing field
+        let values = HashMap::<&str, Value>::new();
+        assert_eq!(chain.format_map(&values).unwrap(), "nothing");
+
+        let mut out = String::from("> ");
+        chain.format_to(&mut out, &values).unwrap();
+        assert_eq!(out, "> nothing");
+    }
+
+    #[test]
+    fn test_all_fail() {
+        let chain = Formatter::new("{a:d}")
+            .unwrap()
+            .or_else(Formatter::new("{b}").unwrap());
+        let values = HashMap::from([("a", Value::from("x"))]);
+
+        // The last formatter's error is returned and nothing is written
+        assert_eq!(chain.format_map(&values).unwrap_err(), Error::missing("b"));
+        let mut out = String::new();
+        let err = chain.format_to(&mut out, &values).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert!(out.is_empty());
+    }
+}
@@ -1,6 +1,14 @@
 //! Runtime string formatting with format specifications.
 
 mod engine;
+mod fallback;
+mod provider;
+#[cfg(feature = "atomic-stats")]
+mod stats;
 mod writer;
 
-pub use engine::Formatter;
+pub use engine::{FloatRepr, Formatter, NullPolicy, OverflowPolicy, PARTS_FIELD, PART_FIELD};
+pub use fallback::FormatterChain;
+pub use provider::{ChainProvider, EnvProvider, FnProvider, ValueProvider};
+#[cfg(feature = "atomic-stats")]
+pub use stats::FormatStats;
@@ -0,0 +1,405 @@
+//! Value providers for looking up field values by name.
+
+use crate::types::{ToValue, Value};
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// A source of values for named fields.
+///
+/// Providers are consulted by [`Formatter::format_with`](crate::Formatter::format_with)
+/// once per named field. Returning `None` reports the field as missing.
+///
+/// Maps, slices of `(name, value)` pairs and [`EnvProvider`] are providers
+/// as they are; closures are wrapped in an [`FnProvider`]. A struct can be
+/// formatted directly by implementing `get` over its fields.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::format::{EnvProvider, ValueProvider};
+/// use gullwing::{Formatter, Value};
+/// use std::collections::HashMap;
+///
+/// let mut defaults = HashMap::new();
+/// defaults.insert("GULLWING_DOC_HOST".to_string(), Value::from("localhost"));
+///
+/// let formatter = Formatter::new("http://{GULLWING_DOC_HOST}/").unwrap();
+/// let result = formatter.format_with(&EnvProvider.or(&defaults)).unwrap();
+/// assert_eq!(result, "http://localhost/");
+/// ```
+pub trait ValueProvider {
+    /// Look up the value of a field by name.
+    fn get(&self, name: &str) -> Option<Value>;
+
+    /// Look up the value of a field by name, borrowing it if the provider
+    /// holds one.
+    ///
+    /// [`Formatter::format_into`](crate::Formatter::format_into) looks fields
+    /// up with this, so maps of [`Value`]s are written without copying. The
+    /// default wraps [`get`](Self::get).
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.get(name).map(Cow::Owned)
+    }
+
+    /// Chain this provider with a fallback consulted when this one has no value.
+    fn or<'a, P>(self, other: P) -> ChainProvider<'a>
+    where
+        Self: Sized + 'a,
+        P: ValueProvider + 'a,
+    {
+        ChainProvider::new().with(self).with(other)
+    }
+}
+
+impl<P: ValueProvider + ?Sized> ValueProvider for &P {
+    fn get(&self, name: &str) -> Option<Value> {
+        (**self).get(name)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        (**self).get_ref(name)
+    }
+}
+
+impl<K, V, S> ValueProvider for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: ToValue,
+    S: BuildHasher,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        HashMap::get(self, name).map(ToValue::to_value)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        HashMap::get(self, name).map(ToValue::to_value_ref)
+    }
+}
+
+impl<K, V> ValueProvider for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: ToValue,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        BTreeMap::get(self, name).map(ToValue::to_value)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        BTreeMap::get(self, name).map(ToValue::to_value_ref)
+    }
+}
+
+/// Look up fields in `(name, value)` pairs; a name given twice takes its
+/// last value.
+impl<K, V> ValueProvider for [(K, V)]
+where
+    K: AsRef<str>,
+    V: ToValue,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        self.get_ref(name).map(Cow::into_owned)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.iter()
+            .rev()
+            .find(|(key, _)| key.as_ref() == name)
+            .map(|(_, value)| value.to_value_ref())
+    }
+}
+
+impl<K, V, const N: usize> ValueProvider for [(K, V); N]
+where
+    K: AsRef<str>,
+    V: ToValue,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        ValueProvider::get(self.as_slice(), name)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.as_slice().get_ref(name)
+    }
+}
+
+impl<K, V> ValueProvider for Vec<(K, V)>
+where
+    K: AsRef<str>,
+    V: ToValue,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        ValueProvider::get(self.as_slice(), name)
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.as_slice().get_ref(name)
+    }
+}
+
+/// Look up fields in a JSON object, converting each value found with `Value::from`.
+#[cfg(feature = "json")]
+impl ValueProvider for serde_json::Map<String, serde_json::Value> {
+    fn get(&self, name: &str) -> Option<Value> {
+        serde_json::Map::get(self, name).cloned().map(Value::from)
+    }
+}
+
+/// Provider that reads field values from environment variables.
+///
+/// The field name is used verbatim as the variable name, and values are
+/// returned as [`Value::Str`]. Variables that are unset or not valid
+/// Unicode are reported as missing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvProvider;
+
+impl ValueProvider for EnvProvider {
+    fn get(&self, name: &str) -> Option<Value> {
+        std::env::var(name).ok().map(Value::from)
+    }
+}
+
+/// Provider backed by a closure.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::format::FnProvider;
+/// use gullwing::{Formatter, Value};
+///
+/// let provider = FnProvider::new(|name: &str| Some(Value::from(name.len())));
+/// let formatter = Formatter::new("{abc} {de}").unwrap();
+/// assert_eq!(formatter.format_with(&provider).unwrap(), "3 2");
+/// ```
+#[derive(Clone, Copy)]
+pub struct FnProvider<F> {
+    f: F,
+}
+
+impl<F> FnProvider<F>
+where
+    F: Fn(&str) -> Option<Value>,
+{
+    /// Create a provider from a closure.
+    pub fn new(f: F) -> Self {
+        FnProvider { f }
+    }
+}
+
+impl<F> ValueProvider for FnProvider<F>
+where
+    F: Fn(&str) -> Option<Value>,
+{
+    fn get(&self, name: &str) -> Option<Value> {
+        (self.f)(name)
+    }
+}
+
+impl<F> fmt::Debug for FnProvider<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnProvider").finish_non_exhaustive()
+    }
+}
+
+/// Provider that consults several providers in order and returns the first value found.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::format::{ChainProvider, FnProvider};
+/// use gullwing::{Formatter, Value};
+/// use std::collections::HashMap;
+///
+/// let mut overrides = HashMap::new();
+/// overrides.insert("level".to_string(), Value::from("DEBUG"));
+///
+/// let provider = ChainProvider::new()
+///     .with(&overrides)
+///     .with(FnProvider::new(|_: &str| Some(Value::from("default"))));
+///
+/// let formatter = Formatter::new("{level} {target}").unwrap();
+/// assert_eq!(formatter.format_with(&provider).unwrap(), "DEBUG default");
+/// ```
+#[derive(Default)]
+pub struct ChainProvider<'a> {
+    providers: Vec<Box<dyn ValueProvider + 'a>>,
+}
+
+impl<'a> ChainProvider<'a> {
+    /// Create an empty chain.
+    pub fn new() -> Self {
+        ChainProvider {
+            providers: Vec::new(),
+        }
+    }
+
+    /// Append a provider to the end of the chain.
+    pub fn with<P: ValueProvider + 'a>(mut self, provider: P) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Get the number of providers in the chain.
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    /// Check if the chain has no providers.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+impl ValueProvider for ChainProvider<'_> {
+    fn get(&self, name: &str) -> Option<Value> {
+        self.providers.iter().find_map(|p| p.get(name))
+    }
+
+    fn get_ref(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.providers.iter().find_map(|p| p.get_ref(name))
+    }
+}
+
+impl fmt::Debug for ChainProvider<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainProvider")
+            .field("len", &self.providers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashmap_provider() {
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), Value::from(1));
+        assert_eq!(ValueProvider::get(&map, "a"), Some(Value::from(1)));
+        assert_eq!(ValueProvider::get(&map, "b"), None);
+
+        let plain = HashMap::from([("a".to_string(), "text")]);
+        assert_eq!(ValueProvider::get(&plain, "a"), Some(Value::from("text")));
+
+        let borrowed = HashMap::from([("a", 2)]);
+        assert_eq!(ValueProvider::get(&borrowed, "a"), Some(Value::from(2)));
+
+        // Stored `Value`s are lent out, other types are converted
+        assert!(matches!(map.get_ref("a"), Some(Cow::Borrowed(_))));
+        assert!(matches!(plain.get_ref("a"), Some(Cow::Owned(_))));
+        let chain = (&plain).or(&map);
+        assert!(matches!(chain.get_ref("a"), Some(Cow::Owned(_))));
+        assert!(matches!(
+            (&map).or(&plain).get_ref("a"),
+            Some(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_btreemap_provider() {
+        let map = BTreeMap::from([("a", Value::from(1)), ("b", Value::from("x"))]);
+        assert_eq!(ValueProvider::get(&map, "b"), Some(Value::from("x")));
+        assert_eq!(ValueProvider::get(&map, "c"), None);
+
+        let owned = BTreeMap::from([("a".to_string(), 3)]);
+        assert_eq!(ValueProvider::get(&owned, "a"), Some(Value::from(3)));
+    }
+
+    #[test]
+    fn test_pair_providers() {
+        let pairs = [("a", 1), ("b", 2), ("a", 3)];
+        assert_eq!(ValueProvider::get(&pairs, "a"), Some(Value::from(3)));
+        assert_eq!(ValueProvider::get(&pairs[..2], "a"), Some(Value::from(1)));
+        assert_eq!(ValueProvider::get(&pairs, "c"), None);
+
+        let owned = vec![("name".to_string(), Value::from("x"))];
+        assert_eq!(ValueProvider::get(&owned, "name"), Some(Value::from("x")));
+    }
+
+    #[test]
+    fn test_struct_provider() {
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        impl ValueProvider for Point {
+            fn get(&self, name: &str) -> Option<Value> {
+                match name {
+                    "x" => Some(Value::from(self.x)),
+                    "y" => Some(Value::from(self.y)),
+                    _ => None,
+                }
+            }
+        }
+
+        let formatter = crate::Formatter::new("({x}, {y:+})").unwrap();
+        let point = Point { x: 3, y: -4 };
+        assert_eq!(formatter.format_with(&point).unwrap(), "(3, -4)");
+        let fallback = [("z", 0)];
+        let chained = (&point).or(fallback);
+        assert_eq!(chained.get("z"), Some(Value::from(0)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_provider() {
+        let object = match serde_json::json!({"host": "localhost", "port": 8080}) {
+            serde_json::Value::Object(object) => object,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            object.get("port").cloned().map(Value::from),
+            Some(Value::Int(8080))
+        );
+        assert_eq!(
+            ValueProvider::get(&object, "host"),
+            Some(Value::from("localhost"))
+        );
+        assert_eq!(ValueProvider::get(&object, "user"), None);
+    }
+
+    #[test]
+    fn test_env_provider() {
+        std::env::set_var("GULLWING_TEST_ENV_PROVIDER", "from-env");
+        assert_eq!(
+            EnvProvider.get("GULLWING_TEST_ENV_PROVIDER"),
+            Some(Value::from("from-env"))
+        );
+        assert_eq!(EnvProvider.get("GULLWING_TEST_ENV_PROVIDER_UNSET"), None);
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let mut first = HashMap::new();
+        first.insert("x".to_string(), Value::from("first"));
+        let mut second = HashMap::new();
+        second.insert("x".to_string(), Value::from("second"));
+        second.insert("y".to_string(), Value::from("second"));
+
+        let chain = (&first).or(&second);
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.get("x"), Some(Value::from("first")));
+        assert_eq!(chain.get("y"), Some(Value::from("second")));
+        assert_eq!(chain.get("z"), None);
+
+        assert!(ChainProvider::new().is_empty());
+        assert_eq!(ChainProvider::new().get("x"), None);
+    }
+
+    #[test]
+    fn test_fn_provider() {
+        let provider = FnProvider::new(|name: &str| {
+            if name == "answer" {
+                Some(Value::from(42))
+            } else {
+                None
+            }
+        });
+        assert_eq!(provider.get("answer"), Some(Value::from(42)));
+        assert_eq!(provider.get("question"), None);
+    }
+}
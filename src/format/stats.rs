@@ -0,0 +1,60 @@
+//! Usage counters kept by a formatter.
+
+use crate::error::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts of what a [`Formatter`](super::Formatter) has done, from
+/// [`Formatter::stats`](super::Formatter::stats).
+///
+/// The counters only go up, so a service can sample them periodically and
+/// alert when the difference in `conversion_errors` grows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FormatStats {
+    /// Fields written successfully, by any method including
+    /// [`estimate_len`](super::Formatter::estimate_len).
+    pub fields: u64,
+    /// Fields whose value couldn't be rendered with their spec, such as a
+    /// string in a `d` field.
+    pub conversion_errors: u64,
+    /// Outputs cut to the length limit by
+    /// [`OverflowPolicy::Truncate`](super::OverflowPolicy::Truncate).
+    pub truncations: u64,
+}
+
+/// The live counters behind [`FormatStats`], shared by clones of a formatter.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    fields: AtomicU64,
+    conversion_errors: AtomicU64,
+    truncations: AtomicU64,
+}
+
+impl Counters {
+    /// Count the outcome of writing one field.
+    pub(crate) fn record_field(&self, result: &Result<()>) {
+        match result {
+            Ok(()) => self.fields.fetch_add(1, Ordering::Relaxed),
+            Err(e) if e.is_conversion_error() => {
+                self.conversion_errors.fetch_add(1, Ordering::Relaxed)
+            }
+            Err(_) => return,
+        };
+    }
+
+    /// Count an output cut to the length limit.
+    pub(crate) fn record_truncation(&self) {
+        self.truncations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the counters.
+    ///
+    /// Each counter is read on its own, so a snapshot taken while other
+    /// threads format may be a few fields apart between counters.
+    pub(crate) fn snapshot(&self) -> FormatStats {
+        FormatStats {
+            fields: self.fields.load(Ordering::Relaxed),
+            conversion_errors: self.conversion_errors.load(Ordering::Relaxed),
+            truncations: self.truncations.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,368 +1,1052 @@
 //! Low-level formatting functions for different value types.
+//!
+//! Each `write_*` function appends the rendered value, without alignment
+//! padding, to the end of `out`.
 
 use crate::error::{Error, Result};
-use crate::spec::{FormatSpec, Grouping, Sign, TypeSpec};
+use crate::locale::Locale;
+use crate::spec::{Alignment, FormatSpec, Grouping, Sign, TypeSpec};
 use crate::types::Value;
+use std::fmt::Write;
 
-/// Format a value as a string.
-pub fn format_string(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let s = match value {
-        Value::Str(s) => s.clone(),
-        Value::Char(c) => c.to_string(),
-        _ => value.to_string(),
-    };
+/// Write a value as a string.
+pub fn write_string(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    // Specs without a type are only checked against strings here
+    spec.validate(Some(TypeSpec::String))?;
+
+    let start = out.len();
+    match value {
+        Value::Str(s) => out.push_str(s),
+        Value::Char(c) => out.push(*c),
+        _ => write!(out, "{}", value).expect("writing to a String cannot fail"),
+    }
 
     // Apply precision (max length for strings)
-    let s = if let Some(precision) = spec.precision {
-        s.chars().take(precision).collect()
-    } else {
-        s
-    };
+    if let Some(precision) = spec.precision {
+        if let Some((end, _)) = out[start..].char_indices().nth(precision) {
+            out.truncate(start + end);
+        }
+    }
 
-    Ok(s)
+    Ok(())
 }
 
-/// Format a value as a decimal integer.
-pub fn format_decimal(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let num = value.to_int()?;
+/// Write a value as a decimal integer.
+pub fn write_decimal(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    let (negative, num) = value.int_parts()?;
+
+    // Fast path: write sign and digits straight into the output
+    if spec.grouping.is_none() && !spec.zero_pad {
+        out.push_str(int_sign(negative, spec));
+        out.push_str(itoa::Buffer::new().format(num));
+        return Ok(());
+    }
 
-    let mut result = num.abs().to_string();
+    let mut result = itoa::Buffer::new().format(num).to_string();
 
-    // Apply grouping
+    // Apply grouping, zero-padding the digits first if requested
     if let Some(grouping) = spec.grouping {
-        result = apply_grouping(&result, grouping, 3);
+        let min_width = zero_pad_width(spec).saturating_sub(int_sign(negative, spec).len());
+        result = apply_grouping(&result, grouping, group_size(spec), min_width);
     }
 
     // Add sign
-    result = add_sign(&result, num, spec);
+    result = add_sign(&result, negative, spec);
 
     // Apply zero padding (only if no explicit alignment)
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
             result = apply_zero_padding(&result, width);
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
 }
 
-/// Format a value as a binary integer.
-pub fn format_binary(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let num = value.to_uint()?;
-    let mut result = format!("{:b}", num);
+/// Write a value as a binary integer.
+pub fn write_binary(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    let (negative, num) = value.int_parts()?;
+    let mut buffer = [0; 128];
+    write_radix(
+        out,
+        negative,
+        "0b",
+        radix_digits(&mut buffer, num, 1, false),
+        spec,
+    );
+    Ok(())
+}
 
-    // Apply grouping
-    if let Some(grouping) = spec.grouping {
-        result = apply_grouping(&result, grouping, 4);
-    }
+/// Write a value as an octal integer.
+pub fn write_octal(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    let (negative, num) = value.int_parts()?;
+    let mut buffer = [0; 128];
+    write_radix(
+        out,
+        negative,
+        "0o",
+        radix_digits(&mut buffer, num, 3, false),
+        spec,
+    );
+    Ok(())
+}
 
-    // Add alternate form prefix
-    if spec.alternate && num != 0 {
-        result = format!("0b{}", result);
+/// Write a value as a hexadecimal integer.
+pub fn write_hex(
+    out: &mut String,
+    value: &Value,
+    spec: &FormatSpec,
+    uppercase: bool,
+) -> Result<()> {
+    let (negative, num) = value.int_parts()?;
+    let mut buffer = [0; 128];
+    let digits = radix_digits(&mut buffer, num, 4, uppercase);
+    let prefix = if uppercase { "0X" } else { "0x" };
+    write_radix(out, negative, prefix, digits, spec);
+    Ok(())
+}
+
+/// Render `num` in base `2^bits` into the end of `buffer`, returning the digits.
+///
+/// Replaces `{:x}` and friends, which go through the formatting machinery for
+/// what is a shift-and-mask loop. 128 bytes hold `u128::MAX` in binary.
+fn radix_digits(buffer: &mut [u8; 128], mut num: u128, bits: u32, uppercase: bool) -> &str {
+    let table: &[u8; 16] = if uppercase {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mask = (1 << bits) - 1;
+    let mut start = buffer.len();
+    while num > u128::from(u64::MAX) {
+        start -= 1;
+        buffer[start] = table[(num & u128::from(mask)) as usize];
+        num >>= bits;
     }
+    // Most values fit in 64 bits, where shifting is cheaper
+    let mut num = num as u64;
+    loop {
+        start -= 1;
+        buffer[start] = table[(num & mask) as usize];
+        num >>= bits;
+        if num == 0 {
+            break;
+        }
+    }
+    std::str::from_utf8(&buffer[start..]).expect("digits are ASCII")
+}
 
-    // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
-        if let Some(width) = spec.width {
-            let prefix_len = if spec.alternate && num != 0 { 2 } else { 0 };
-            if result.len() < width {
-                if prefix_len > 0 {
-                    result = format!("0b{:0>width$}", &result[2..], width = width - 2);
-                } else {
-                    result = format!("{:0>width$}", result, width = width);
-                }
+/// Write the digits of a binary, octal or hex integer after its sign and prefix.
+///
+/// Like Python, the sign comes first, then the `#` prefix, then any zero
+/// padding, so -255 with `#08x` renders as `-0x000ff`.
+fn write_radix(out: &mut String, negative: bool, prefix: &str, digits: &str, spec: &FormatSpec) {
+    let sign = int_sign(negative, spec);
+    out.push_str(sign);
+    if spec.alternate {
+        out.push_str(prefix);
+    }
+    let reserved = sign.len() + if spec.alternate { prefix.len() } else { 0 };
+    let min_width = zero_pad_width(spec).saturating_sub(reserved);
+
+    match spec.grouping {
+        // Fast path: write the digits straight into the output
+        None => {
+            if digits.len() < min_width {
+                out.extend(std::iter::repeat_n('0', min_width - digits.len()));
             }
+            out.push_str(digits);
+        }
+        Some(grouping) => {
+            out.push_str(&apply_grouping(
+                digits,
+                grouping,
+                group_size(spec),
+                min_width,
+            ));
         }
     }
-
-    Ok(result)
 }
 
-/// Format a value as an octal integer.
-pub fn format_octal(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let num = value.to_uint()?;
-    let mut result = format!("{:o}", num);
-
-    // Apply grouping
-    if let Some(grouping) = spec.grouping {
-        result = apply_grouping(&result, grouping, 4);
+/// Write a value as a fixed-point float.
+pub fn write_fixed(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    #[cfg(feature = "rust_decimal")]
+    if let Value::Decimal(num) = value {
+        return write_exact(out, num, spec);
     }
-
-    // Add alternate form prefix
-    if spec.alternate && num != 0 {
-        result = format!("0o{}", result);
+    let num = value.to_float()?;
+    if !num.is_finite() {
+        let uppercase = matches!(spec.type_spec, Some(TypeSpec::FixedUpper));
+        write_non_finite(out, num, spec, uppercase, "");
+        return Ok(());
     }
 
-    // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
-        if let Some(width) = spec.width {
-            let prefix_len = if spec.alternate && num != 0 { 2 } else { 0 };
-            if result.len() < width {
-                if prefix_len > 0 {
-                    result = format!("0o{:0>width$}", &result[2..], width = width - 2);
-                } else {
-                    result = format!("{:0>width$}", result, width = width);
-                }
-            }
+    let precision = spec.precision.unwrap_or(6);
+
+    // Fast path: write the magnitude straight into the output, then the sign
+    if spec.grouping.is_none() && !spec.zero_pad {
+        let start = out.len();
+        write_fixed_digits(out, num.abs(), precision);
+        if spec.alternate && precision == 0 {
+            out.push('.');
         }
+        let sign = float_sign(&out[start..], num, spec);
+        out.insert_str(start, sign);
+        return Ok(());
     }
 
-    Ok(result)
-}
-
-/// Format a value as a hexadecimal integer.
-pub fn format_hex(value: &Value, spec: &FormatSpec, uppercase: bool) -> Result<String> {
-    let num = value.to_uint()?;
-    let mut result = if uppercase {
-        format!("{:X}", num)
-    } else {
-        format!("{:x}", num)
-    };
-
-    // Apply grouping
-    if let Some(grouping) = spec.grouping {
-        result = apply_grouping(&result, grouping, 4);
+    let mut result = String::new();
+    write_fixed_digits(&mut result, num.abs(), precision);
+    if spec.alternate && precision == 0 {
+        result.push('.');
     }
 
-    // Add alternate form prefix
-    if spec.alternate && num != 0 {
-        let prefix = if uppercase { "0X" } else { "0x" };
-        result = format!("{}{}", prefix, result);
+    // Add sign, then group the integer part
+    let sign = float_sign(&result, num, spec);
+    if let Some(grouping) = spec.grouping {
+        result = group_integer_part(&result, spec, grouping, sign.len());
     }
+    result.insert_str(0, sign);
 
     // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
-            let prefix_len = if spec.alternate && num != 0 { 2 } else { 0 };
-            if result.len() < width {
-                if prefix_len > 0 {
-                    let prefix = if uppercase { "0X" } else { "0x" };
-                    result = format!("{}{:0>width$}", prefix, &result[2..], width = width - 2);
-                } else {
-                    result = format!("{:0>width$}", result, width = width);
-                }
-            }
+            result = apply_zero_padding(&result, width);
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
 }
 
-/// Format a value as a fixed-point float.
-pub fn format_fixed(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let mut num = value.to_float()?;
-
-    // Handle zero flag (coerce -0.0 to 0.0)
-    if spec.zero_flag && num == 0.0 && num.is_sign_negative() {
-        num = 0.0;
+/// Write a value in scientific notation.
+pub fn write_exponent(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    #[cfg(feature = "rust_decimal")]
+    if let Value::Decimal(num) = value {
+        return write_exact(out, num, spec);
     }
+    write_scientific(out, value.to_float()?, spec, None)
+}
 
+/// Write a value in scientific notation with the exponent fixed at `exponent`.
+///
+/// The mantissa keeps `precision` decimals whatever its size, so `1234.5`
+/// pinned to `e+03` is `1.234500e+03` and pinned to `e+06` is
+/// `0.001234e+06`. Decimals are converted to `f64` first.
+pub fn write_pinned_exponent(
+    out: &mut String,
+    value: &Value,
+    spec: &FormatSpec,
+    exponent: i32,
+) -> Result<()> {
+    write_scientific(out, value.to_float()?, spec, Some(exponent))
+}
+
+fn write_scientific(
+    out: &mut String,
+    num: f64,
+    spec: &FormatSpec,
+    pinned: Option<i32>,
+) -> Result<()> {
     let precision = spec.precision.unwrap_or(6);
+    let uppercase = matches!(spec.type_spec, Some(TypeSpec::ExponentUpper));
+    if !num.is_finite() {
+        write_non_finite(out, num, spec, uppercase, "");
+        return Ok(());
+    }
 
-    let abs_num = num.abs();
-    let mut result = format!("{:.precision$}", abs_num, precision = precision);
+    let mut result = match pinned {
+        Some(exponent) => {
+            pinned_exponent_digits(num.abs(), exponent, precision, uppercase, spec.alternate)
+        }
+        None => exponent_digits(num.abs(), precision, uppercase, spec.alternate),
+    };
 
-    // Apply grouping to integer part
+    // Add sign, then group the integer part
+    let sign = float_sign(&result, num, spec);
     if let Some(grouping) = spec.grouping {
-        if let Some(dot_pos) = result.find('.') {
-            let int_part = &result[..dot_pos];
-            let frac_part = &result[dot_pos..];
-            result = format!("{}{}", apply_grouping(int_part, grouping, 3), frac_part);
-        }
+        result = group_integer_part(&result, spec, grouping, sign.len());
     }
-
-    // Add sign
-    result = add_sign_float(&result, num, spec);
+    result.insert_str(0, sign);
 
     // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
             result = apply_zero_padding(&result, width);
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
 }
 
-/// Format a value in scientific notation.
-pub fn format_exponent(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let mut num = value.to_float()?;
-
-    // Handle zero flag
-    if spec.zero_flag && num == 0.0 && num.is_sign_negative() {
-        num = 0.0;
+/// Write a value using general format (automatically choose fixed or exponent).
+pub fn write_general(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    #[cfg(feature = "rust_decimal")]
+    if let Value::Decimal(num) = value {
+        return write_exact(out, num, spec);
     }
+    let num = value.to_float()?;
 
     let precision = spec.precision.unwrap_or(6);
-    let uppercase = matches!(spec.type_spec, Some(TypeSpec::ExponentUpper));
+    let uppercase = matches!(spec.type_spec, Some(TypeSpec::GeneralUpper));
+    if !num.is_finite() {
+        write_non_finite(out, num, spec, uppercase, "");
+        return Ok(());
+    }
 
-    let abs_num = num.abs();
-    let mut result = if uppercase {
-        format!("{:.precision$E}", abs_num, precision = precision)
-    } else {
-        format!("{:.precision$e}", abs_num, precision = precision)
-    };
+    let mut result = general_digits(num.abs(), precision, uppercase, spec.alternate);
 
-    // Add sign
-    result = add_sign_float(&result, num, spec);
+    // Add sign, then group the integer part
+    let sign = float_sign(&result, num, spec);
+    if let Some(grouping) = spec.grouping {
+        result = group_integer_part(&result, spec, grouping, sign.len());
+    }
+    result.insert_str(0, sign);
 
     // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
             result = apply_zero_padding(&result, width);
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
 }
 
-/// Format a value using general format (automatically choose fixed or exponent).
-pub fn format_general(value: &Value, spec: &FormatSpec) -> Result<String> {
-    let mut num = value.to_float()?;
-
-    // Handle zero flag
-    if spec.zero_flag && num == 0.0 && num.is_sign_negative() {
-        num = 0.0;
+/// Write a float in its shortest round-tripping form, like Python's `repr()`.
+///
+/// This is what Python writes for a float without a precision or
+/// presentation type; the sign, grouping and padding options still apply.
+pub fn write_repr(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    let num = value.to_float()?;
+    if !num.is_finite() {
+        write_non_finite(out, num, spec, false, "");
+        return Ok(());
     }
 
-    let precision = spec.precision.unwrap_or(6);
+    let mut result = repr_digits(num.abs(), spec.alternate);
 
-    // For general format, let Rust's formatting decide
-    let abs_num = num.abs();
-    let mut result = format!("{:.precision$}", abs_num, precision = precision);
-
-    // Add sign
-    result = add_sign_float(&result, num, spec);
+    // Add sign, then group the integer part
+    let sign = float_sign(&result, num, spec);
+    if let Some(grouping) = spec.grouping {
+        result = group_integer_part(&result, spec, grouping, sign.len());
+    }
+    result.insert_str(0, sign);
 
     // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
             result = apply_zero_padding(&result, width);
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
 }
 
-/// Format a value as a percentage.
-pub fn format_percentage(value: &Value, spec: &FormatSpec) -> Result<String> {
+/// Write a value as a percentage.
+pub fn write_percentage(out: &mut String, value: &Value, spec: &FormatSpec) -> Result<()> {
+    #[cfg(feature = "rust_decimal")]
+    if let Value::Decimal(num) = value {
+        return write_exact(out, num, spec);
+    }
     let num = value.to_float()? * 100.0;
+    if !num.is_finite() {
+        write_non_finite(out, num, spec, false, "%");
+        return Ok(());
+    }
 
     let precision = spec.precision.unwrap_or(6);
-    let mut result = format!("{:.precision$}", num.abs(), precision = precision);
+    let mut result = String::new();
+    write_fixed_digits(&mut result, num.abs(), precision);
+    if spec.alternate && precision == 0 {
+        result.push('.');
+    }
 
-    // Add sign
-    result = add_sign_float(&result, num, spec);
+    // Add sign, then group the integer part
+    let sign = float_sign(&result, num, spec);
+    if let Some(grouping) = spec.grouping {
+        let reserved = sign.len() + '%'.len_utf8();
+        result = group_integer_part(&result, spec, grouping, reserved);
+    }
+    result.insert_str(0, sign);
 
     // Add percentage symbol
     result.push('%');
 
     // Apply zero padding
-    if spec.zero_pad && spec.align.is_none() {
+    if zero_pads(spec) {
         if let Some(width) = spec.width {
             // Remove % before padding, add back after
             result.pop();
-            result = apply_zero_padding(&result, width - 1);
+            result = apply_zero_padding(&result, width.saturating_sub(1));
             result.push('%');
         }
     }
 
-    Ok(result)
+    out.push_str(&result);
+    Ok(())
+}
+
+/// Write an exact decimal with one of the float presentation types.
+#[cfg(feature = "rust_decimal")]
+fn write_exact(out: &mut String, num: &rust_decimal::Decimal, spec: &FormatSpec) -> Result<()> {
+    let mut result = decimal_digits(num, spec);
+    let suffix = match spec.type_spec {
+        Some(TypeSpec::Percentage) => "%",
+        _ => "",
+    };
+
+    // Add sign, then group the integer part
+    let sign = magnitude_sign(&result, num.is_sign_negative(), spec);
+    if let Some(grouping) = spec.grouping {
+        let reserved = sign.len() + suffix.len();
+        result = group_integer_part(&result, spec, grouping, reserved);
+    }
+    result.insert_str(0, sign);
+
+    // Apply zero padding
+    if zero_pads(spec) {
+        if let Some(width) = spec.width {
+            result = apply_zero_padding(&result, width.saturating_sub(suffix.len()));
+        }
+    }
+
+    out.push_str(&result);
+    out.push_str(suffix);
+    Ok(())
 }
 
-/// Format a value as a character.
-pub fn format_character(value: &Value) -> Result<String> {
+/// Write a value using the locale-aware `n` type.
+///
+/// Integers are written like `d` and floats like `g`, with the locale's
+/// decimal point and digit grouping.
+pub fn write_number(
+    out: &mut String,
+    value: &Value,
+    spec: &FormatSpec,
+    locale: &Locale,
+) -> Result<()> {
+    let start = out.len();
     match value {
-        Value::Char(c) => Ok(c.to_string()),
-        Value::Int(i) if *i >= 0 && *i <= 0x10FFFF => {
-            let c = char::from_u32(*i as u32)
-                .ok_or_else(|| Error::ConversionError(format!("invalid character code: {}", i)))?;
-            Ok(c.to_string())
+        Value::Float(num) if !num.is_finite() => {
+            write_non_finite(out, *num, spec, false, "");
+            return Ok(());
+        }
+        Value::Float(num) => {
+            let precision = spec.precision.unwrap_or(6);
+            let digits = general_digits(num.abs(), precision, false, spec.alternate);
+            out.push_str(float_sign(&digits, *num, spec));
+            locale.write_number(out, &digits);
+        }
+        #[cfg(feature = "rust_decimal")]
+        Value::Decimal(num) => {
+            let digits = decimal_digits(num, spec);
+            out.push_str(magnitude_sign(&digits, num.is_sign_negative(), spec));
+            locale.write_number(out, &digits);
+        }
+        _ => {
+            let (negative, num) = value.int_parts()?;
+            if spec.precision.is_some() {
+                return Err(Error::spec(
+                    "precision not allowed in integer format specifier".to_string(),
+                ));
+            }
+            out.push_str(int_sign(negative, spec));
+            locale.write_number(out, itoa::Buffer::new().format(num));
+        }
+    }
+
+    // Apply zero padding, counting characters since separators may be multi-byte
+    if let (true, None, Some(width)) = (spec.zero_pad, spec.align, spec.width) {
+        let len = out[start..].chars().count();
+        if len < width {
+            let sign_len = out[start..]
+                .find(|c: char| !matches!(c, '+' | '-' | ' '))
+                .unwrap_or(0);
+            insert_zeros(out, start + sign_len, width - len);
         }
-        Value::Str(s) if s.len() == 1 => Ok(s.clone()),
-        _ => Err(Error::ConversionError(format!(
-            "cannot format {:?} as character",
-            value
-        ))),
     }
+
+    Ok(())
 }
 
-/// Apply grouping separators to a numeric string.
-fn apply_grouping(s: &str, grouping: Grouping, group_size: usize) -> String {
-    let sep = match grouping {
-        Grouping::Comma => ',',
-        Grouping::Underscore => '_',
+/// Write a value as a character.
+pub fn write_character(out: &mut String, value: &Value) -> Result<()> {
+    let c = match value {
+        Value::Char(c) => *c,
+        Value::Int(i) if *i >= 0 && *i <= 0x10FFFF => {
+            char::from_u32(*i as u32).ok_or_else(|| Error::conversion("character", i))?
+        }
+        _ => value
+            .as_char()
+            .ok_or_else(|| Error::conversion("character", value))?,
     };
+    out.push(c);
+    Ok(())
+}
 
-    let chars: Vec<char> = s.chars().collect();
-    let mut result = String::new();
+/// Write a non-negative finite float with `precision` decimals, like
+/// `{:.precision$}`.
+///
+/// Starts from the float's shortest round-tripping digits, from ryu, which
+/// is much cheaper than exact digit generation. Rounding those digits gives
+/// the correctly rounded result unless they end on a tie, and padding them
+/// with zeros does when the gap to the next float is too small to show in
+/// `precision` decimals. Everything else goes through `core::fmt`.
+fn write_fixed_digits(out: &mut String, abs: f64, precision: usize) {
+    // Below 2^53 the integer part is exact and at most 16 digits
+    if precision <= 17 && abs < 9_007_199_254_740_992.0 {
+        let mut buffer = ryu::Buffer::new();
+        let shortest = buffer.format_finite(abs);
+        if let Some((int, frac)) = shortest.split_once('.').filter(|(_, f)| !f.contains('e')) {
+            let frac = frac.trim_end_matches('0');
+            let usable = if frac.len() <= precision {
+                let gap = f64::from_bits(abs.to_bits() + 1) - abs;
+                // Half the bound, to stay clear of rounding in the check itself
+                gap * 10f64.powi(precision as i32) <= 0.5
+            } else {
+                frac.len() > precision + 1 || !frac.ends_with('5')
+            };
+            if usable {
+                write_rounded_digits(out, int, frac, precision);
+                return;
+            }
+        }
+    }
+    write!(out, "{:.precision$}", abs, precision = precision)
+        .expect("writing to a String cannot fail");
+}
+
+/// Write `int.frac` rounded or zero-padded to `precision` decimals.
+///
+/// `int` has at most 16 digits and `precision` is at most 17; the first byte
+/// of the buffer takes a carry out of the integer part.
+fn write_rounded_digits(out: &mut String, int: &str, frac: &str, precision: usize) {
+    let mut digits = [b'0'; 40];
+    let mut end = 1 + int.len();
+    digits[1..end].copy_from_slice(int.as_bytes());
+    if precision > 0 {
+        let kept = frac.len().min(precision);
+        digits[end] = b'.';
+        digits[end + 1..end + 1 + kept].copy_from_slice(&frac.as_bytes()[..kept]);
+        end += 1 + precision;
+    }
 
-    for (i, &c) in chars.iter().enumerate() {
-        if i > 0 && (chars.len() - i).is_multiple_of(group_size) {
-            result.push(sep);
+    let mut begin = 1;
+    if frac.as_bytes().get(precision).is_some_and(|&d| d >= b'5') {
+        // Propagate the carry from the last digit
+        let mut i = end;
+        loop {
+            i -= 1;
+            match digits[i] {
+                b'.' => {}
+                b'9' => digits[i] = b'0',
+                _ => {
+                    digits[i] += 1;
+                    break;
+                }
+            }
         }
-        result.push(c);
+        begin = begin.min(i);
     }
+    out.push_str(std::str::from_utf8(&digits[begin..end]).expect("digits are ASCII"));
+}
+
+/// Render a non-negative float in scientific notation the way Python does.
+///
+/// Rust prints exponents as `1.5e3`; Python always signs the exponent and
+/// uses at least two digits (`1.5e+03`). With `alternate`, a decimal point
+/// is kept even when no digits follow it.
+fn exponent_digits(abs: f64, precision: usize, uppercase: bool, alternate: bool) -> String {
+    if !abs.is_finite() {
+        return abs.to_string();
+    }
+
+    let rendered = format!("{:.precision$e}", abs, precision = precision);
+    let (mantissa, exponent) = rendered.split_once('e').unwrap_or((&rendered, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
 
+    let mut result = String::with_capacity(mantissa.len() + 5);
+    result.push_str(mantissa);
+    if alternate && !mantissa.contains('.') {
+        result.push('.');
+    }
+    result.push(if uppercase { 'E' } else { 'e' });
+    result.push(if exponent < 0 { '-' } else { '+' });
+    if exponent.abs() < 10 {
+        result.push('0');
+    }
+    result.push_str(itoa::Buffer::new().format(exponent.unsigned_abs()));
     result
 }
 
-/// Add sign to a formatted integer.
-fn add_sign(s: &str, num: i64, spec: &FormatSpec) -> String {
-    let sign = match spec.sign {
-        Some(Sign::Plus) => {
-            if num >= 0 {
-                "+"
-            } else {
-                "-"
+/// Render a non-negative float as `mantissa` times ten to `exponent`.
+///
+/// The mantissa is rounded half-to-even on the exact binary value, like the
+/// other float types, to `precision` decimals.
+fn pinned_exponent_digits(
+    abs: f64,
+    exponent: i32,
+    precision: usize,
+    uppercase: bool,
+    alternate: bool,
+) -> String {
+    // The last kept digit is worth 10^scale in `abs`
+    let scale = exponent as i64 - precision as i64;
+    let mut digits = if scale <= 0 {
+        let mut fixed = format!("{:.*}", scale.unsigned_abs() as usize, abs);
+        fixed.retain(|c| c != '.');
+        fixed
+    } else {
+        round_integer(
+            &format!("{:.0}", abs.trunc()),
+            scale as usize,
+            abs.fract() != 0.0,
+        )
+    };
+
+    // Strip leading zeros, then put the decimal point `precision` digits from the end
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    digits.drain(..leading.min(digits.len().saturating_sub(precision + 1)));
+    if digits.len() < precision + 1 {
+        digits.insert_str(0, &"0".repeat(precision + 1 - digits.len()));
+    }
+    let mut result = digits;
+    if precision > 0 {
+        result.insert(result.len() - precision, '.');
+    } else if alternate {
+        result.push('.');
+    }
+
+    result.push(if uppercase { 'E' } else { 'e' });
+    result.push(if exponent < 0 { '-' } else { '+' });
+    if exponent.unsigned_abs() < 10 {
+        result.push('0');
+    }
+    result.push_str(itoa::Buffer::new().format(exponent.unsigned_abs()));
+    result
+}
+
+/// Round the decimal integer `digits` to a multiple of `10^places`, half to
+/// even, returning the quotient's digits.
+///
+/// `sticky` records that the true value is slightly above `digits`, which
+/// breaks ties upwards.
+fn round_integer(digits: &str, places: usize, sticky: bool) -> String {
+    if places > digits.len() {
+        return "0".to_string();
+    }
+    let (head, tail) = digits.split_at(digits.len() - places);
+    let mut head = if head.is_empty() { "0" } else { head }.to_string();
+
+    let first = tail.bytes().next().unwrap_or(b'0');
+    let rest_zero = tail.bytes().skip(1).all(|b| b == b'0') && !sticky;
+    let odd = head.bytes().last().is_some_and(|b| (b - b'0') % 2 == 1);
+    let round_up = first > b'5' || (first == b'5' && (!rest_zero || odd));
+    if round_up {
+        // Propagate the carry from the last digit
+        let mut bytes = head.into_bytes();
+        let mut i = bytes.len();
+        loop {
+            if i == 0 {
+                bytes.insert(0, b'1');
+                break;
             }
-        }
-        Some(Sign::Space) => {
-            if num >= 0 {
-                " "
+            i -= 1;
+            if bytes[i] == b'9' {
+                bytes[i] = b'0';
             } else {
-                "-"
+                bytes[i] += 1;
+                break;
             }
         }
-        Some(Sign::Minus) | None => {
-            if num < 0 {
-                "-"
+        head = String::from_utf8(bytes).expect("digits are ASCII");
+    }
+    head
+}
+
+/// Render a non-negative float using Python's general (`g`) rules.
+///
+/// The value is rounded to `precision` significant digits and printed in
+/// fixed-point when the decimal exponent is in `-4..precision`, otherwise in
+/// scientific notation. Trailing zeros are removed unless `alternate` is set.
+fn general_digits(abs: f64, precision: usize, uppercase: bool, alternate: bool) -> String {
+    if !abs.is_finite() {
+        return abs.to_string();
+    }
+
+    let precision = precision.max(1);
+    let exponent = if abs == 0.0 {
+        0
+    } else {
+        let rendered = format!("{:.precision$e}", abs, precision = precision - 1);
+        rendered
+            .split_once('e')
+            .and_then(|(_, e)| e.parse::<i32>().ok())
+            .unwrap_or(0)
+    };
+
+    let mut result = if exponent >= -4 && exponent < precision as i32 {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        let mut fixed = String::new();
+        write_fixed_digits(&mut fixed, abs, decimals);
+        fixed
+    } else {
+        exponent_digits(abs, precision - 1, uppercase, false)
+    };
+
+    // Split off any exponent so trailing zeros can be handled in the mantissa
+    let exp_pos = result.find(['e', 'E']).unwrap_or(result.len());
+    let exp_part = result.split_off(exp_pos);
+    if alternate {
+        if !result.contains('.') {
+            result.push('.');
+        }
+    } else if result.contains('.') {
+        let trimmed = result.trim_end_matches('0').trim_end_matches('.').len();
+        result.truncate(trimmed);
+    }
+    result.push_str(&exp_part);
+    result
+}
+
+/// Render a non-negative float in its shortest round-tripping form.
+///
+/// As in Python's `repr()`, fixed-point is used when the decimal exponent is
+/// in `-4..16`, always with a digit after the point, and scientific notation
+/// with at least two exponent digits otherwise. With `alternate`, a mantissa
+/// without a point gets one, so `1e+16` becomes `1.e+16`.
+fn repr_digits(abs: f64, alternate: bool) -> String {
+    // ryu renders the shortest digits that round-trip, in fixed-point for
+    // exponents in -5..16 and as `1.5e-7` otherwise
+    let mut buffer = ryu::Buffer::new();
+    let shortest = buffer.format_finite(abs);
+    let (mantissa, exponent): (String, i32) = match shortest.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa.to_string(), exponent.parse().unwrap_or(0)),
+        // Python switches to scientific notation one decade sooner
+        None if shortest.starts_with("0.0000") => {
+            let (head, tail) = shortest[6..].split_at(1);
+            let mantissa = if tail.is_empty() {
+                head.to_string()
             } else {
-                ""
-            }
+                format!("{}.{}", head, tail)
+            };
+            (mantissa, -5)
+        }
+        None => return shortest.to_string(),
+    };
+    format!(
+        "{}{}e{}{:02}",
+        mantissa,
+        if alternate && !mantissa.contains('.') {
+            "."
+        } else {
+            ""
+        },
+        if exponent < 0 { '-' } else { '+' },
+        exponent.unsigned_abs()
+    )
+}
+
+/// Render the magnitude of an exact decimal for a float presentation type.
+///
+/// Follows Python's `Decimal.__format__` rather than the float rules: the
+/// value is rounded half to even, and without a precision every digit the
+/// decimal carries is kept, so `1.50` formats as `1.50` with `f` and `g`
+/// alike. Exponents use as few digits as they need (`1.5e+3`), and a spec
+/// without a type behaves like `G`.
+#[cfg(feature = "rust_decimal")]
+fn decimal_digits(num: &rust_decimal::Decimal, spec: &FormatSpec) -> String {
+    use TypeSpec::{
+        ExponentLower, ExponentUpper, FixedLower, FixedUpper, GeneralUpper, Percentage,
+    };
+
+    let mut digits = itoa::Buffer::new()
+        .format(num.mantissa().unsigned_abs())
+        .to_string();
+    let mut exp = -(num.scale() as i32);
+    let fixed = matches!(spec.type_spec, Some(FixedLower | FixedUpper | Percentage));
+    let exponent = matches!(spec.type_spec, Some(ExponentLower | ExponentUpper));
+    if spec.type_spec == Some(Percentage) {
+        exp += 2;
+    }
+
+    match spec.precision {
+        Some(precision) if exponent => round_significant(&mut digits, &mut exp, precision + 1),
+        Some(precision) if fixed => rescale(&mut digits, &mut exp, -(precision as i32)),
+        Some(precision) if digits.len() > precision.max(1) => {
+            round_significant(&mut digits, &mut exp, precision.max(1))
+        }
+        _ => {}
+    }
+    let is_zero = digits == "0";
+    if is_zero && exp > 0 && fixed {
+        exp = 0;
+    }
+
+    // Place the decimal point, leaving the rest for the exponent
+    let left_digits = exp + digits.len() as i32;
+    let dot = if exponent {
+        match spec.precision {
+            Some(precision) if is_zero => 1 - precision as i32,
+            _ => 1,
         }
+    } else if fixed || (exp <= 0 && left_digits > -6) {
+        left_digits
+    } else {
+        1
     };
 
-    format!("{}{}", sign, s)
+    let mut result = String::with_capacity(digits.len() + 8);
+    if dot <= 0 {
+        result.push('0');
+        result.push('.');
+        result.extend(std::iter::repeat_n('0', dot.unsigned_abs() as usize));
+        result.push_str(&digits);
+    } else if dot as usize >= digits.len() {
+        result.push_str(&digits);
+        result.extend(std::iter::repeat_n('0', dot as usize - digits.len()));
+        if spec.alternate {
+            result.push('.');
+        }
+    } else {
+        let (int_part, frac_part) = digits.split_at(dot as usize);
+        result.push_str(int_part);
+        result.push('.');
+        result.push_str(frac_part);
+    }
+
+    let exp = left_digits - dot;
+    if exp != 0 || exponent {
+        let uppercase = matches!(spec.type_spec, Some(ExponentUpper | GeneralUpper) | None);
+        result.push(if uppercase { 'E' } else { 'e' });
+        write!(result, "{:+}", exp).expect("writing to a String cannot fail");
+    }
+    result
+}
+
+/// Round the decimal `digits × 10^exp` to `places` significant digits,
+/// half to even. Zero is left as it is.
+#[cfg(feature = "rust_decimal")]
+fn round_significant(digits: &mut String, exp: &mut i32, places: usize) {
+    if digits == "0" {
+        return;
+    }
+    rescale(digits, exp, *exp + digits.len() as i32 - places as i32);
+    // Rounding 99.97 to 3 digits carries into a fourth
+    if digits.len() > places {
+        rescale(digits, exp, *exp + 1);
+    }
 }
 
-/// Add sign to a formatted float.
-fn add_sign_float(s: &str, num: f64, spec: &FormatSpec) -> String {
-    let sign = match spec.sign {
-        Some(Sign::Plus) => {
-            if num >= 0.0 {
-                "+"
-            } else {
-                "-"
+/// Round or extend the decimal `digits × 10^exp` to exponent `new_exp`,
+/// rounding half to even.
+#[cfg(feature = "rust_decimal")]
+fn rescale(digits: &mut String, exp: &mut i32, new_exp: i32) {
+    if digits == "0" {
+        *exp = new_exp;
+        return;
+    }
+
+    if new_exp <= *exp {
+        digits.extend(std::iter::repeat_n('0', (*exp - new_exp) as usize));
+    } else {
+        let dropped = (new_exp - *exp) as usize;
+        let round_up = match digits.len().checked_sub(dropped) {
+            Some(keep) => {
+                let rest = &digits.as_bytes()[keep..];
+                let odd = keep > 0 && digits.as_bytes()[keep - 1] % 2 == 1;
+                let round_up = match rest[0] {
+                    b'6'..=b'9' => true,
+                    b'5' => odd || rest[1..].iter().any(|&b| b != b'0'),
+                    _ => false,
+                };
+                digits.truncate(keep);
+                round_up
             }
-        }
-        Some(Sign::Space) => {
-            if num >= 0.0 {
-                " "
-            } else {
-                "-"
+            // Every digit is below the new last place, so it rounds to zero
+            None => {
+                digits.clear();
+                false
             }
-        }
-        Some(Sign::Minus) | None => {
-            if num < 0.0 {
-                "-"
-            } else {
-                ""
+        };
+
+        if round_up {
+            let nines = digits.bytes().rev().take_while(|&b| b == b'9').count();
+            digits.truncate(digits.len() - nines);
+            match digits.pop() {
+                Some(last) => digits.push((last as u8 + 1) as char),
+                None => digits.push('1'),
             }
+            digits.extend(std::iter::repeat_n('0', nines));
+        }
+        if digits.is_empty() {
+            digits.push('0');
+        }
+    }
+    *exp = new_exp;
+}
+
+/// Apply grouping separators to a string of digits.
+///
+/// Like Python, if the grouped digits are narrower than `min_width`, leading
+/// zeros are added and grouped too until the result is at least that wide,
+/// so `08,d` renders 42 as `0,000,042`. The result never starts with a
+/// separator, so it can end up one character wider than `min_width`.
+fn apply_grouping(digits: &str, grouping: Grouping, group_size: usize, min_width: usize) -> String {
+    let sep = grouping.to_char();
+    let mut digits = digits.chars().rev();
+    let mut reversed = String::new();
+    let mut count = 0;
+
+    loop {
+        let digit = match digits.next() {
+            Some(digit) => digit,
+            None if reversed.len() < min_width => '0',
+            None => break,
+        };
+        if count > 0 && count % group_size == 0 {
+            reversed.push(sep);
         }
+        reversed.push(digit);
+        count += 1;
+    }
+
+    reversed.chars().rev().collect()
+}
+
+/// Get the number of digits per group for a spec's presentation type.
+///
+/// Python groups binary, octal and hex digits in fours and everything else,
+/// including the integer part of floats, in threes.
+fn group_size(spec: &FormatSpec) -> usize {
+    match spec.type_spec {
+        Some(TypeSpec::Binary | TypeSpec::Octal | TypeSpec::HexLower | TypeSpec::HexUpper) => 4,
+        _ => 3,
+    }
+}
+
+/// Group the integer part of a formatted float magnitude.
+///
+/// When the spec zero-pads, the digits are padded so the whole result,
+/// including `reserved` bytes for the sign and any suffix, fills the width.
+fn group_integer_part(s: &str, spec: &FormatSpec, grouping: Grouping, reserved: usize) -> String {
+    let int_len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (int_part, rest) = s.split_at(int_len);
+    let min_width = zero_pad_width(spec).saturating_sub(reserved + rest.len());
+    let mut result = apply_grouping(int_part, grouping, group_size(spec), min_width);
+    result.push_str(rest);
+    result
+}
+
+/// Check whether a spec pads numbers with zeros between the sign and the digits.
+///
+/// That's the `0` flag without an alignment, or `=` alignment with a `0`
+/// fill, which Python treats the same way: the zeros are grouped along with
+/// the digits, unlike other fill characters.
+fn zero_pads(spec: &FormatSpec) -> bool {
+    match spec.align {
+        None => spec.zero_pad,
+        Some(Alignment::AfterSign) => spec.fill_char() == '0',
+        Some(_) => false,
+    }
+}
+
+/// Get the width to zero-pad numbers to, or 0 if they aren't zero-padded.
+fn zero_pad_width(spec: &FormatSpec) -> usize {
+    if zero_pads(spec) {
+        spec.width.unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Write a NaN or infinity the way Python does.
+///
+/// The magnitude is `nan` or `inf` (`NAN` or `INF` for the uppercase types),
+/// followed by `suffix`. Signs and zero padding apply as for finite numbers,
+/// except that NaN is never shown as negative.
+fn write_non_finite(out: &mut String, num: f64, spec: &FormatSpec, uppercase: bool, suffix: &str) {
+    let magnitude = match (num.is_nan(), uppercase) {
+        (true, false) => "nan",
+        (true, true) => "NAN",
+        (false, false) => "inf",
+        (false, true) => "INF",
     };
+    let mut result = add_sign_float(magnitude, num, spec);
+
+    if zero_pads(spec) {
+        if let Some(width) = spec.width {
+            result = apply_zero_padding(&result, width.saturating_sub(suffix.len()));
+        }
+    }
 
-    format!("{}{}", sign, s)
+    out.push_str(&result);
+    out.push_str(suffix);
+}
+
+/// Add sign to a formatted integer.
+fn add_sign(s: &str, negative: bool, spec: &FormatSpec) -> String {
+    format!("{}{}", int_sign(negative, spec), s)
+}
+
+/// Get the sign to print before an integer.
+fn int_sign(negative: bool, spec: &FormatSpec) -> &'static str {
+    match (negative, spec.sign) {
+        (true, _) => "-",
+        (false, Some(Sign::Plus)) => "+",
+        (false, Some(Sign::Space)) => " ",
+        (false, Some(Sign::Minus) | None) => "",
+    }
+}
+
+/// Add sign to a formatted float.
+///
+/// `s` is the formatted magnitude. The sign follows the sign bit of `num`, so
+/// negative zero and negative values that round to zero keep their `-` like
+/// in Python. With the `z` flag, a result that rounded to zero is treated as
+/// positive instead. NaN is always treated as positive.
+fn add_sign_float(s: &str, num: f64, spec: &FormatSpec) -> String {
+    format!("{}{}", float_sign(s, num, spec), s)
+}
+
+/// Get the sign to print before a float whose magnitude was rendered as `s`.
+fn float_sign(s: &str, num: f64, spec: &FormatSpec) -> &'static str {
+    if num.is_finite() {
+        magnitude_sign(s, num.is_sign_negative(), spec)
+    } else {
+        int_sign(num.is_sign_negative() && !num.is_nan(), spec)
+    }
+}
+
+/// Get the sign to print before a finite number whose magnitude was rendered as `s`.
+///
+/// With the `z` flag, a negative number that rounded to zero is treated as positive.
+fn magnitude_sign(s: &str, negative: bool, spec: &FormatSpec) -> &'static str {
+    int_sign(negative && !(spec.zero_flag && is_zero_magnitude(s)), spec)
+}
+
+/// Check whether a formatted magnitude has only zero digits before any exponent.
+fn is_zero_magnitude(s: &str) -> bool {
+    s.chars()
+        .take_while(|c| !matches!(c, 'e' | 'E'))
+        .filter(|c| c.is_ascii_digit())
+        .all(|c| c == '0')
+}
+
+/// Insert `n` zeros into `out` at byte offset `at`, without a temporary string.
+fn insert_zeros(out: &mut String, at: usize, n: usize) {
+    const ZEROS: &str = "0000000000000000";
+    let mut remaining = n;
+    while remaining > 0 {
+        let count = remaining.min(ZEROS.len());
+        out.insert_str(at, &ZEROS[..count]);
+        remaining -= count;
+    }
 }
 
 /// Apply zero padding to a numeric string.
@@ -371,93 +1055,588 @@ fn apply_zero_padding(s: &str, width: usize) -> String {
         return s.to_string();
     }
 
-    // Check if there's a sign or prefix
-    let (prefix, rest) = if let Some(first) = s.chars().next() {
-        if first == '+' || first == '-' || first == ' ' {
-            (first.to_string(), &s[1..])
-        } else if s.len() >= 2
-            && (s.starts_with("0x")
-                || s.starts_with("0X")
-                || s.starts_with("0b")
-                || s.starts_with("0B")
-                || s.starts_with("0o")
-                || s.starts_with("0O"))
-        {
-            (s[..2].to_string(), &s[2..])
-        } else {
-            (String::new(), s)
-        }
+    // Keep a sign or radix prefix in front of the zeros
+    let prefix_len = if s.starts_with(['+', '-', ' ']) {
+        1
+    } else if ["0x", "0X", "0b", "0B", "0o", "0O"]
+        .iter()
+        .any(|prefix| s.starts_with(prefix))
+    {
+        2
     } else {
-        (String::new(), s)
+        0
     };
 
-    let padding_needed = width.saturating_sub(s.len());
-    format!(
-        "{}{:0>width$}",
-        prefix,
-        rest,
-        width = rest.len() + padding_needed
-    )
+    let (prefix, rest) = s.split_at(prefix_len);
+    let mut result = String::with_capacity(width);
+    result.push_str(prefix);
+    result.extend(std::iter::repeat_n('0', width - s.len()));
+    result.push_str(rest);
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Run a writer function into a fresh string.
+    fn render(write: impl FnOnce(&mut String) -> Result<()>) -> Result<String> {
+        let mut out = String::new();
+        write(&mut out)?;
+        Ok(out)
+    }
+
     #[test]
     fn test_format_string() {
         let value = Value::from("hello");
         let spec = FormatSpec::default();
-        assert_eq!(format_string(&value, &spec).unwrap(), "hello");
+        assert_eq!(
+            render(|out| write_string(out, &value, &spec)).unwrap(),
+            "hello"
+        );
+
+        let spec = FormatSpec {
+            precision: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_string(out, &value, &spec)).unwrap(),
+            "hel"
+        );
+    }
+
+    #[test]
+    fn test_format_string_truncates_on_char_boundaries() {
+        let spec = FormatSpec {
+            precision: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_string(out, &Value::from("日本語"), &spec)).unwrap(),
+            "日本"
+        );
+        assert_eq!(
+            render(|out| write_string(out, &Value::from("😀😀😀"), &spec)).unwrap(),
+            "😀😀"
+        );
+        // A base letter plus combining accent is two characters
+        let spec = FormatSpec {
+            precision: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_string(out, &Value::from("e\u{301}x"), &spec)).unwrap(),
+            "e"
+        );
+    }
 
-        let mut spec = FormatSpec::default();
-        spec.precision = Some(3);
-        assert_eq!(format_string(&value, &spec).unwrap(), "hel");
+    #[test]
+    fn test_format_character_multibyte() {
+        assert_eq!(
+            render(|out| write_character(out, &Value::from("é"))).unwrap(),
+            "é"
+        );
+        assert!(render(|out| write_character(out, &Value::from("ab"))).is_err());
     }
 
     #[test]
     fn test_format_decimal() {
         let value = Value::from(42);
         let spec = FormatSpec::default();
-        assert_eq!(format_decimal(&value, &spec).unwrap(), "42");
-
-        let mut spec = FormatSpec::default();
-        spec.sign = Some(Sign::Plus);
-        assert_eq!(format_decimal(&value, &spec).unwrap(), "+42");
+        assert_eq!(
+            render(|out| write_decimal(out, &value, &spec)).unwrap(),
+            "42"
+        );
+
+        let spec = FormatSpec {
+            sign: Some(Sign::Plus),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_decimal(out, &value, &spec)).unwrap(),
+            "+42"
+        );
 
         let value = Value::from(-42);
-        assert_eq!(format_decimal(&value, &spec).unwrap(), "-42");
+        assert_eq!(
+            render(|out| write_decimal(out, &value, &spec)).unwrap(),
+            "-42"
+        );
+    }
+
+    #[test]
+    fn test_number_matches_python_c_locale() {
+        let c = Locale::c();
+        let cases = [
+            ("n", Value::from(1234567), "1234567"),
+            ("+n", Value::from(42), "+42"),
+            ("012n", Value::from(-1234567), "-00001234567"),
+            ("n", Value::from(1234.5678), "1234.57"),
+            ("n", Value::from(1234567.0), "1.23457e+06"),
+            ("012n", Value::from(1234.5678), "000001234.57"),
+        ];
+        for (spec, value, expected) in cases {
+            let spec = FormatSpec::parse(spec).unwrap();
+            assert_eq!(
+                render(|out| write_number(out, &value, &spec, &c)).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_number_uses_locale() {
+        let fr = Locale::fr_fr();
+        let spec = FormatSpec::parse("n").unwrap();
+        assert_eq!(
+            render(|out| write_number(out, &Value::from(-1234567), &spec, &fr)).unwrap(),
+            "-1\u{202f}234\u{202f}567"
+        );
+        assert_eq!(
+            render(|out| write_number(out, &Value::from(0.5), &spec, &fr)).unwrap(),
+            "0,5"
+        );
+
+        // Zero padding counts the multi-byte separator as one character
+        let spec = FormatSpec::parse("08n").unwrap();
+        assert_eq!(
+            render(|out| write_number(out, &Value::from(-1234), &spec, &fr)).unwrap(),
+            "-001\u{202f}234"
+        );
     }
 
     #[test]
     fn test_format_binary() {
         let value = Value::from(10);
         let spec = FormatSpec::default();
-        assert_eq!(format_binary(&value, &spec).unwrap(), "1010");
-
-        let mut spec = FormatSpec::default();
-        spec.alternate = true;
-        assert_eq!(format_binary(&value, &spec).unwrap(), "0b1010");
+        assert_eq!(
+            render(|out| write_binary(out, &value, &spec)).unwrap(),
+            "1010"
+        );
+
+        let spec = FormatSpec {
+            alternate: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_binary(out, &value, &spec)).unwrap(),
+            "0b1010"
+        );
     }
 
     #[test]
     fn test_format_hex() {
         let value = Value::from(255);
         let spec = FormatSpec::default();
-        assert_eq!(format_hex(&value, &spec, false).unwrap(), "ff");
-        assert_eq!(format_hex(&value, &spec, true).unwrap(), "FF");
+        assert_eq!(
+            render(|out| write_hex(out, &value, &spec, false)).unwrap(),
+            "ff"
+        );
+        assert_eq!(
+            render(|out| write_hex(out, &value, &spec, true)).unwrap(),
+            "FF"
+        );
+
+        let spec = FormatSpec {
+            alternate: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_hex(out, &value, &spec, false)).unwrap(),
+            "0xff"
+        );
+        assert_eq!(
+            render(|out| write_hex(out, &value, &spec, true)).unwrap(),
+            "0XFF"
+        );
+    }
+
+    #[test]
+    fn test_alternate_form_matrix() {
+        let alt = |type_char: char, precision: Option<usize>| FormatSpec {
+            alternate: true,
+            precision,
+            type_spec: TypeSpec::from_char(type_char),
+            ..Default::default()
+        };
+
+        // Integers: prefixes (also for zero), no-op for decimal
+        assert_eq!(
+            render(|out| write_decimal(out, &Value::from(5), &alt('d', None))).unwrap(),
+            "5"
+        );
+        assert_eq!(
+            render(|out| write_hex(out, &Value::from(0), &alt('x', None), false)).unwrap(),
+            "0x0"
+        );
+        assert_eq!(
+            render(|out| write_binary(out, &Value::from(0), &alt('b', None))).unwrap(),
+            "0b0"
+        );
+        assert_eq!(
+            render(|out| write_octal(out, &Value::from(0), &alt('o', None))).unwrap(),
+            "0o0"
+        );
+
+        // Floats: the decimal point is always kept
+        let one = Value::from(1.0);
+        assert_eq!(
+            render(|out| write_fixed(out, &one, &alt('f', Some(0)))).unwrap(),
+            "1."
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &one, &alt('e', Some(0)))).unwrap(),
+            "1.e+00"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &one, &alt('E', Some(0)))).unwrap(),
+            "1.E+00"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &one, &alt('g', None))).unwrap(),
+            "1.00000"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &one, &alt('g', Some(3)))).unwrap(),
+            "1.00"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &Value::from(100000.0), &alt('g', None))).unwrap(),
+            "100000."
+        );
+        assert_eq!(
+            render(|out| write_percentage(out, &Value::from(1.5), &alt('%', Some(0)))).unwrap(),
+            "150.%"
+        );
+
+        // Strings reject the flag
+        assert!(render(|out| write_string(out, &Value::from("x"), &alt('s', None))).is_err());
+    }
+
+    #[test]
+    fn test_exponent_matches_python() {
+        let spec = |precision: Option<usize>| FormatSpec {
+            precision,
+            type_spec: Some(TypeSpec::ExponentLower),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(1.0), &spec(None))).unwrap(),
+            "1.000000e+00"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(1234.0), &spec(Some(2)))).unwrap(),
+            "1.23e+03"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(0.00012), &spec(Some(1)))).unwrap(),
+            "1.2e-04"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(1e123), &spec(Some(0)))).unwrap(),
+            "1e+123"
+        );
+    }
 
-        let mut spec = FormatSpec::default();
-        spec.alternate = true;
-        assert_eq!(format_hex(&value, &spec, false).unwrap(), "0xff");
-        assert_eq!(format_hex(&value, &spec, true).unwrap(), "0XFF");
+    #[test]
+    fn test_general_matches_python() {
+        let spec = |precision: Option<usize>| FormatSpec {
+            precision,
+            type_spec: Some(TypeSpec::GeneralLower),
+            ..Default::default()
+        };
+        let cases = [
+            (1.0, None, "1"),
+            (0.0, None, "0"),
+            (2.5, None, "2.5"),
+            (123456.0, None, "123456"),
+            (1234567.0, None, "1.23457e+06"),
+            (0.0001, None, "0.0001"),
+            (0.00001, None, "1e-05"),
+            (1234.5678, Some(2), "1.2e+03"),
+            (0.5, Some(0), "0.5"),
+            (9.99, Some(2), "10"),
+        ];
+        for (num, precision, expected) in cases {
+            assert_eq!(
+                render(|out| write_general(out, &Value::from(num), &spec(precision))).unwrap(),
+                expected,
+                "{} with precision {:?}",
+                num,
+                precision
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_finite_matches_python() {
+        let nan = Value::from(f64::NAN);
+        let neg_nan = Value::from(-f64::NAN);
+        let inf = Value::from(f64::INFINITY);
+        let neg_inf = Value::from(f64::NEG_INFINITY);
+
+        let cases = [
+            ("f", &nan, "nan"),
+            ("f", &neg_nan, "nan"),
+            ("F", &inf, "INF"),
+            ("e", &neg_inf, "-inf"),
+            ("E", &nan, "NAN"),
+            ("g", &inf, "inf"),
+            ("G", &neg_inf, "-INF"),
+            ("%", &nan, "nan%"),
+            ("+f", &nan, "+nan"),
+            ("+f", &neg_nan, "+nan"),
+            (" e", &inf, " inf"),
+            ("z.1f", &neg_inf, "-inf"),
+            ("010f", &nan, "0000000nan"),
+            ("010%", &nan, "000000nan%"),
+            ("+08.1E", &neg_inf, "-0000INF"),
+        ];
+        for (spec, value, expected) in cases {
+            let spec = FormatSpec::parse(spec).unwrap();
+            let rendered = render(|out| match spec.type_spec {
+                Some(TypeSpec::FixedLower | TypeSpec::FixedUpper) => write_fixed(out, value, &spec),
+                Some(TypeSpec::ExponentLower | TypeSpec::ExponentUpper) => {
+                    write_exponent(out, value, &spec)
+                }
+                Some(TypeSpec::Percentage) => write_percentage(out, value, &spec),
+                _ => write_general(out, value, &spec),
+            });
+            assert_eq!(rendered.unwrap(), expected, "spec {:?}", spec);
+        }
+
+        let spec = FormatSpec::parse("n").unwrap();
+        assert_eq!(
+            render(|out| write_number(out, &neg_inf, &spec, &Locale::de_de())).unwrap(),
+            "-inf"
+        );
+    }
+
+    #[test]
+    fn test_sign_of_zero() {
+        let spec = |s: &str| FormatSpec::parse(s).unwrap();
+        let neg_zero = Value::from(-0.0);
+
+        // Integers have no negative zero
+        assert_eq!(
+            render(|out| write_decimal(out, &Value::from(0), &spec("+d"))).unwrap(),
+            "+0"
+        );
+        assert_eq!(
+            render(|out| write_decimal(out, &Value::from(-0), &spec(" d"))).unwrap(),
+            " 0"
+        );
+
+        // Floats keep the sign bit, as in Python
+        assert_eq!(
+            render(|out| write_fixed(out, &neg_zero, &spec("+.1f"))).unwrap(),
+            "-0.0"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &neg_zero, &spec(" .1f"))).unwrap(),
+            "-0.0"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &Value::from(0.0), &spec("+.1f"))).unwrap(),
+            "+0.0"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &Value::from(-0.04), &spec(".1f"))).unwrap(),
+            "-0.0"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &neg_zero, &spec("+e"))).unwrap(),
+            "-0.000000e+00"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &neg_zero, &spec("+g"))).unwrap(),
+            "-0"
+        );
+        assert_eq!(
+            render(|out| write_percentage(out, &neg_zero, &spec("+.0%"))).unwrap(),
+            "-0%"
+        );
+        assert_eq!(
+            render(|out| write_percentage(out, &Value::from(-0.001), &spec(".0%"))).unwrap(),
+            "-0%"
+        );
+
+        // The z flag coerces anything that rounds to zero
+        assert_eq!(
+            render(|out| write_fixed(out, &neg_zero, &spec("z.1f"))).unwrap(),
+            "0.0"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &Value::from(-0.04), &spec("z.1f"))).unwrap(),
+            "0.0"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &Value::from(-0.04), &spec("+z.1f"))).unwrap(),
+            "+0.0"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &neg_zero, &spec("zg"))).unwrap(),
+            "0"
+        );
+        assert_eq!(
+            render(|out| write_percentage(out, &Value::from(-0.001), &spec("z.0%"))).unwrap(),
+            "0%"
+        );
+        // ...but not values that are merely small
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(-1e-9), &spec("z.2e"))).unwrap(),
+            "-1.00e-09"
+        );
+        assert_eq!(
+            render(|out| write_fixed(out, &Value::from(-0.06), &spec("z.1f"))).unwrap(),
+            "-0.1"
+        );
     }
 
     #[test]
     fn test_grouping() {
-        assert_eq!(apply_grouping("1000", Grouping::Comma, 3), "1,000");
-        assert_eq!(apply_grouping("1000000", Grouping::Comma, 3), "1,000,000");
-        assert_eq!(apply_grouping("1111", Grouping::Underscore, 4), "1111");
-        assert_eq!(apply_grouping("11111", Grouping::Underscore, 4), "1_1111");
+        assert_eq!(apply_grouping("1000", Grouping::Comma, 3, 0), "1,000");
+        assert_eq!(
+            apply_grouping("1000000", Grouping::Comma, 3, 0),
+            "1,000,000"
+        );
+        assert_eq!(apply_grouping("1111", Grouping::Underscore, 4, 0), "1111");
+        assert_eq!(
+            apply_grouping("11111", Grouping::Underscore, 4, 0),
+            "1_1111"
+        );
+
+        // Zero padding is grouped, and never leaves a leading separator
+        assert_eq!(apply_grouping("42", Grouping::Comma, 3, 7), "000,042");
+        assert_eq!(apply_grouping("42", Grouping::Comma, 3, 8), "0,000,042");
+        assert_eq!(
+            apply_grouping("1234567", Grouping::Comma, 3, 8),
+            "1,234,567"
+        );
+    }
+
+    #[test]
+    fn test_group_size_by_type() {
+        let spec = |s| FormatSpec::parse(s).unwrap();
+        let value = Value::from(123456789);
+        assert_eq!(
+            render(|out| write_decimal(out, &value, &spec("_d"))).unwrap(),
+            "123_456_789"
+        );
+        assert_eq!(
+            render(|out| write_hex(out, &value, &spec("_x"), false)).unwrap(),
+            "75b_cd15"
+        );
+        assert_eq!(
+            render(|out| write_general(out, &Value::from(-1234.5), &spec(",g"))).unwrap(),
+            "-1,234.5"
+        );
+        assert_eq!(
+            render(|out| write_exponent(out, &Value::from(1234.5), &spec("_e"))).unwrap(),
+            "1.234500e+03"
+        );
+
+        // Strings can't be grouped, which is only known once the value is seen
+        assert_eq!(
+            render(|out| write_string(out, &Value::from("abc"), &spec(","))).unwrap_err(),
+            Error::spec("cannot specify ',' with 's'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_grouped_zero_padding_matches_python() {
+        let cases = [
+            ("08,d", Value::from(42), "0,000,042"),
+            ("08,d", Value::from(-42), "-000,042"),
+            ("+08,d", Value::from(42), "+000,042"),
+            ("010,d", Value::from(1234567), "01,234,567"),
+            ("05,d", Value::from(-42), "-0,042"),
+            ("08_d", Value::from(42), "0_000_042"),
+            ("08_x", Value::from(1234567), "012_d687"),
+            ("#010_x", Value::from(42), "0x000_002a"),
+            ("#012_b", Value::from(42), "0b0_0010_1010"),
+            ("010_o", Value::from(42), "0_0000_0052"),
+            ("010,.2f", Value::from(1234.5), "001,234.50"),
+            ("012,.2f", Value::from(-3.25), "-0,000,003.25"),
+            ("+012,.1f", Value::from(1234.5), "+0,001,234.5"),
+            ("09,.1%", Value::from(-3.25), "-0,325.0%"),
+            ("09,.1%", Value::from(1234.5), "123,450.0%"),
+            ("015,g", Value::from(1234.5), "0,000,001,234.5"),
+            ("015,g", Value::from(1234567.0), "0,001.23457e+06"),
+            ("015,e", Value::from(-1234.5), "-001.234500e+03"),
+        ];
+        for (spec, value, expected) in cases {
+            let parsed = FormatSpec::parse(spec).unwrap();
+            let rendered = render(|out| match parsed.type_spec {
+                Some(TypeSpec::Decimal) => write_decimal(out, &value, &parsed),
+                Some(TypeSpec::HexLower) => write_hex(out, &value, &parsed, false),
+                Some(TypeSpec::Binary) => write_binary(out, &value, &parsed),
+                Some(TypeSpec::Octal) => write_octal(out, &value, &parsed),
+                Some(TypeSpec::FixedLower) => write_fixed(out, &value, &parsed),
+                Some(TypeSpec::GeneralLower) => write_general(out, &value, &parsed),
+                Some(TypeSpec::ExponentLower) => write_exponent(out, &value, &parsed),
+                _ => write_percentage(out, &value, &parsed),
+            });
+            assert_eq!(rendered.unwrap(), expected, "spec {:?}", spec);
+        }
+    }
+
+    #[test]
+    fn test_digits_match_core_fmt() {
+        // A fixed xorshift sequence of floats across many magnitudes
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut floats = vec![
+            0.0,
+            0.5,
+            2.5,
+            0.125,
+            0.1,
+            2.675,
+            9.995,
+            99.5,
+            1e-7,
+            1.5e-5,
+            9007199254740991.0,
+        ];
+        for _ in 0..2000 {
+            let bits = next();
+            let scale = 10f64.powi((bits % 24) as i32 - 8);
+            floats.push((bits >> 11) as f64 / (1u64 << 53) as f64 * scale);
+            // Short decimals, which take the ryu path
+            floats.push((bits % 1_000_000) as f64 / 1000.0);
+        }
+        for abs in floats {
+            for precision in [0, 1, 2, 3, 6, 12, 17, 20] {
+                let mut out = String::new();
+                write_fixed_digits(&mut out, abs, precision);
+                assert_eq!(out, format!("{:.*}", precision, abs), "{:?}", abs);
+            }
+        }
+
+        for num in [0, 1, 255, u64::MAX as u128, u128::MAX, next() as u128 * 3] {
+            let mut buffer = [0; 128];
+            assert_eq!(
+                radix_digits(&mut buffer, num, 1, false),
+                format!("{:b}", num)
+            );
+            assert_eq!(
+                radix_digits(&mut buffer, num, 3, false),
+                format!("{:o}", num)
+            );
+            assert_eq!(
+                radix_digits(&mut buffer, num, 4, false),
+                format!("{:x}", num)
+            );
+            assert_eq!(
+                radix_digits(&mut buffer, num, 4, true),
+                format!("{:X}", num)
+            );
+        }
     }
 }
@@ -0,0 +1,21 @@
+//! `tracing` spans and events, compiled in by the `instrument` feature.
+//!
+//! The macros take the arguments of `tracing::span!` and `tracing::event!`
+//! after a level name, and expand to nothing without the feature, so the
+//! engine's hot paths carry no `cfg` attributes of their own.
+
+/// Enter a span at `$level` until the end of the enclosing block.
+macro_rules! span {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "instrument")]
+        let _span = ::tracing::span!(::tracing::Level::$level, $($arg)+).entered();
+    };
+}
+
+/// Record an event at `$level`.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "instrument")]
+        ::tracing::event!(::tracing::Level::$level, $($arg)+);
+    };
+}
@@ -21,41 +21,114 @@
 //!
 //! ### Formatting
 //!
-//! ```rust,ignore
+//! ```rust
 //! use gullwing::{Formatter, Value};
 //!
 //! let formatter = Formatter::new("{name:>10} {value:05d}")?;
 //! let output = formatter.format(&[
-//!     ("name", Value::Str("Alice")),
-//!     ("value", Value::Int(42))
+//!     ("name", Value::from("Alice")),
+//!     ("value", Value::from(42)),
 //! ])?;
-//! // Output: "     Alice 00042"
+//! assert_eq!(output, "     Alice 00042");
+//! # Ok::<(), gullwing::Error>(())
 //! ```
 //!
 //! ### Parsing
 //!
-//! ```rust,ignore
-//! use gullwing::Parser;
+//! ```rust
+//! # #[cfg(feature = "parse")] {
+//! use gullwing::{Parser, Value};
 //!
 //! let parser = Parser::new("{name} is {age:d} years old")?;
-//! let result = parser.parse("Alice is 30 years old")?;
+//! let result = parser.parse("Alice is 30 years old")?.unwrap();
 //!
-//! assert_eq!(result.get("name"), Some("Alice"));
-//! assert_eq!(result.get("age"), Some(30));
+//! assert_eq!(result.get("name"), Some(&Value::from("Alice")));
+//! assert_eq!(result.get("age"), Some(&Value::from(30)));
+//! # }
+//! # Ok::<(), gullwing::Error>(())
 //! ```
+//!
+//! ## Feature flags
+//!
+//! - `regex` (default): the `parse` and `transform` modules, matching with
+//!   `regex`. Turn off default features for formatting alone.
+//! - `regex-lite`: the same modules, matching with the much smaller but slower
+//!   `regex-lite`. Its `\d` only matches ASCII digits.
+//! - `std` (default): reserved for `no_std` support.
+//! - `miette`: implement `miette::Diagnostic` for [`Error`].
+//! - `chrono`: `Value::DateTime`, formatted with strftime-style specs such as
+//!   `{ts:%Y-%m-%d %H:%M:%S}`.
+//! - `rust_decimal`: add `Value::Decimal` for exact decimal values, formatted
+//!   like Python's `decimal.Decimal` and parsed back exactly with
+//!   `Parser::with_decimals`.
+//! - `json`: convert between [`Value`] and `serde_json::Value`, and format
+//!   straight from a JSON object with [`Formatter::format_with`].
+//! - `serde`: deserialize structs straight from a [`ParseResult`] or [`Value`],
+//!   and format any `Serialize` value with [`Formatter::format_serde`].
+//! - `derive`: `#[derive(GullwingFormat, GullwingParse)]` for structs bound
+//!   to a pattern; see the [`pattern`] module.
+//! - `atomic-stats`: count the fields each [`Formatter`] writes, its
+//!   conversion errors and truncations, read with `Formatter::stats`.
+//! - `csv`: the `csv` module, parsing CSV records into [`ParseResult`]s
+//!   by header, with a pattern per column, and writing formatted CSV rows.
+//!   It turns on `regex` for the per-column patterns.
+//! - `wasm`: the `wasm` module, exporting [`Formatter`], [`Parser`] and
+//!   [`ParseResult`] to JavaScript as classes through `wasm-bindgen`. It
+//!   turns on `regex-lite`; build with `default-features = false` to leave
+//!   `regex` out of the module.
+//! - `instrument`: `tracing` spans around compiling patterns and formatting
+//!   or parsing each record, with events for the engine chosen and spec
+//!   and pattern cache hits, to profile pipelines in production.
+//! - `cli`: the `gullwing` command-line tool, which parses and reformats
+//!   text records; install it with `cargo install gullwing --features cli`.
+//!   It adds nothing to the library but `regex` and `json`.
 
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
 
+// First, so its macros are in scope in every module
+#[macro_use]
+mod instrument;
+
+pub mod cache;
+#[cfg(feature = "csv")]
+pub mod csv;
+pub mod dialect;
 pub mod error;
 pub mod format;
+pub mod locale;
+mod macros;
+#[cfg(feature = "parse")]
 pub mod parse;
+pub mod pattern;
+#[cfg(feature = "parse")]
+pub mod replay;
 pub mod spec;
+pub mod template;
+#[cfg(feature = "parse")]
+pub mod transform;
 pub mod types;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 // Re-export commonly used types
-pub use error::{Error, Result};
-pub use format::Formatter;
+pub use error::{Error, ErrorKind, Result};
+pub use format::{FloatRepr, Formatter, NullPolicy, OverflowPolicy, ValueProvider};
+#[cfg(feature = "parse")]
 pub use parse::{ParseResult, Parser};
-pub use spec::{Alignment, FormatSpec, Grouping, Sign, TypeSpec};
-pub use types::Value;
+pub use pattern::GullwingFormat;
+#[cfg(feature = "parse")]
+pub use pattern::GullwingParse;
+pub use spec::{Alignment, Conversion, FormatSpec, Grouping, Sign, TypeSpec};
+pub use template::Template;
+#[cfg(feature = "parse")]
+pub use transform::Transformer;
+pub use types::{FromValue, ToValue, Value};
+
+#[doc(hidden)]
+pub use macros::format_cached as __format_cached;
+
+#[cfg(feature = "derive")]
+pub use gullwing_derive::GullwingFormat;
+#[cfg(all(feature = "derive", feature = "parse"))]
+pub use gullwing_derive::GullwingParse;
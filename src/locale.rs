@@ -0,0 +1,176 @@
+//! Locale conventions for the `n` presentation type.
+//!
+//! Python's `n` type formats numbers using the process-wide C locale. gullwing
+//! doesn't read the environment; instead a [`Locale`] is attached to a
+//! [`Formatter`](crate::Formatter) with
+//! [`with_locale`](crate::Formatter::with_locale). Without one, `n` behaves
+//! like Python in the `C` locale: `d` for integers and `g` for floats.
+
+/// Number formatting conventions: decimal point, thousands separator and digit grouping.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::locale::Locale;
+/// use gullwing::{Formatter, Value};
+///
+/// let formatter = Formatter::new("{:n} / {:n}").unwrap().with_locale(Locale::de_de());
+/// let values = [Value::from(1234567), Value::from(1234.5)];
+/// assert_eq!(formatter.format_positional(&values).unwrap(), "1.234.567 / 1.234,5");
+///
+/// // Indian numbering groups by three, then by two
+/// let lakh = Locale::new(".", ",", [3, 2]);
+/// let formatter = Formatter::new("{:n}").unwrap().with_locale(lakh);
+/// assert_eq!(formatter.format_positional(&[Value::from(12345678)]).unwrap(), "1,23,45,678");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Locale {
+    decimal_point: String,
+    thousands_sep: String,
+    grouping: Vec<usize>,
+}
+
+impl Locale {
+    /// Create a locale from its separators and group sizes.
+    ///
+    /// `grouping` lists the sizes of the digit groups, starting next to the
+    /// decimal point; the last size repeats for the remaining digits, and a
+    /// size of zero stops grouping. An empty list disables grouping.
+    pub fn new(
+        decimal_point: impl Into<String>,
+        thousands_sep: impl Into<String>,
+        grouping: impl Into<Vec<usize>>,
+    ) -> Self {
+        Locale {
+            decimal_point: decimal_point.into(),
+            thousands_sep: thousands_sep.into(),
+            grouping: grouping.into(),
+        }
+    }
+
+    /// The `C` (POSIX) locale: `.` as the decimal point and no grouping.
+    pub fn c() -> Self {
+        Locale::new(".", "", [])
+    }
+
+    /// US English: `1,234,567.89`.
+    pub fn en_us() -> Self {
+        Locale::new(".", ",", [3])
+    }
+
+    /// German: `1.234.567,89`.
+    pub fn de_de() -> Self {
+        Locale::new(",", ".", [3])
+    }
+
+    /// French: `1 234 567,89`, grouped with a narrow no-break space.
+    pub fn fr_fr() -> Self {
+        Locale::new(",", "\u{202f}", [3])
+    }
+
+    /// Swiss German: `1’234’567.89`.
+    pub fn de_ch() -> Self {
+        Locale::new(".", "\u{2019}", [3])
+    }
+
+    /// Get the decimal point.
+    pub fn decimal_point(&self) -> &str {
+        &self.decimal_point
+    }
+
+    /// Get the thousands separator.
+    pub fn thousands_sep(&self) -> &str {
+        &self.thousands_sep
+    }
+
+    /// Get the digit group sizes.
+    pub fn grouping(&self) -> &[usize] {
+        &self.grouping
+    }
+
+    /// Append `number`, an ASCII-rendered magnitude such as `1234.5` or
+    /// `1.5e+06`, to `out` with this locale's separators.
+    pub(crate) fn write_number(&self, out: &mut String, number: &str) {
+        let int_len = number.bytes().take_while(|b| b.is_ascii_digit()).count();
+        let (digits, rest) = number.split_at(int_len);
+
+        // Check whether a separator goes before the digit `from_right` places
+        // from the end of the integer part
+        let is_break = |from_right: usize| {
+            let mut sizes = self.grouping.iter().copied();
+            let (mut size, mut at) = (0, 0);
+            while at < from_right {
+                size = sizes.next().unwrap_or(size);
+                if size == 0 {
+                    return false;
+                }
+                at += size;
+            }
+            at == from_right
+        };
+
+        for (i, digit) in digits.char_indices() {
+            if i > 0 && !self.thousands_sep.is_empty() && is_break(digits.len() - i) {
+                out.push_str(&self.thousands_sep);
+            }
+            out.push(digit);
+        }
+
+        match rest.strip_prefix('.') {
+            Some(fraction) => {
+                out.push_str(&self.decimal_point);
+                out.push_str(fraction);
+            }
+            None => out.push_str(rest),
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::c()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localized(locale: &Locale, number: &str) -> String {
+        let mut out = String::new();
+        locale.write_number(&mut out, number);
+        out
+    }
+
+    #[test]
+    fn test_grouping() {
+        let en = Locale::en_us();
+        assert_eq!(localized(&en, "1"), "1");
+        assert_eq!(localized(&en, "999"), "999");
+        assert_eq!(localized(&en, "1000"), "1,000");
+        assert_eq!(localized(&en, "1234567.125"), "1,234,567.125");
+        assert_eq!(localized(&en, "1.5e+06"), "1.5e+06");
+
+        let de = Locale::de_de();
+        assert_eq!(localized(&de, "1234567.5"), "1.234.567,5");
+        assert_eq!(localized(&Locale::fr_fr(), "12345"), "12\u{202f}345");
+    }
+
+    #[test]
+    fn test_irregular_grouping() {
+        let lakh = Locale::new(".", ",", [3, 2]);
+        assert_eq!(localized(&lakh, "1234"), "1,234");
+        assert_eq!(localized(&lakh, "12345678"), "1,23,45,678");
+
+        // A zero size stops grouping after the groups before it
+        let once = Locale::new(".", ",", [3, 0]);
+        assert_eq!(localized(&once, "1234567"), "1234,567");
+    }
+
+    #[test]
+    fn test_c_locale_is_plain() {
+        let c = Locale::default();
+        assert_eq!(c, Locale::c());
+        assert_eq!(localized(&c, "1234567.25"), "1234567.25");
+    }
+}
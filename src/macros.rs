@@ -0,0 +1,76 @@
+//! The [`rtformat!`](crate::rtformat) macro.
+
+use crate::cache;
+use crate::error::Result;
+use crate::types::Value;
+
+/// Format named values with a runtime pattern, like `format!`.
+///
+/// The pattern is any `&str` or `String` expression, and each argument is
+/// written `name = value`, where the value is anything with
+/// [`ToValue`](crate::ToValue), such as numbers and strings. Returns a
+/// [`Result`](crate::Result), failing if the pattern is invalid, names a
+/// field that isn't given, or can't render a value with its spec.
+///
+/// Each pattern is compiled the first time it is used and kept in the
+/// [`cache`](crate::cache), so formatting in a loop costs little more than
+/// with a [`Formatter`](crate::Formatter) held by the caller.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::rtformat;
+///
+/// let pattern = String::from("{name:>6}|{score:.1%}");
+/// let row = rtformat!(pattern, name = "Ann", score = 0.875).unwrap();
+/// assert_eq!(row, "   Ann|87.5%");
+///
+/// assert!(rtformat!("{missing}", name = "Ann").is_err());
+/// assert_eq!(rtformat!("no fields").unwrap(), "no fields");
+/// ```
+#[macro_export]
+macro_rules! rtformat {
+    ($pattern:expr $(,)?) => {
+        $crate::__format_cached(::core::convert::AsRef::<str>::as_ref(&$pattern), &[])
+    };
+    ($pattern:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::__format_cached(
+            ::core::convert::AsRef::<str>::as_ref(&$pattern),
+            &[$((::core::stringify!($name), $crate::ToValue::to_value(&$value))),+],
+        )
+    };
+}
+
+/// Format `values` with the cached formatter for `pattern`.
+#[doc(hidden)]
+pub fn format_cached(pattern: &str, values: &[(&str, Value)]) -> Result<String> {
+    cache::formatter(pattern)?.format(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorKind};
+
+    #[test]
+    fn test_rtformat() {
+        let count = 255;
+        let text = rtformat!("{a:>7}|{b!r}|{a:x}", a = count, b = "q").unwrap();
+        assert_eq!(text, "    255|'q'|ff");
+
+        // Trailing commas, and values of any ToValue type
+        let values = vec![Value::from(1), Value::from("two")];
+        assert_eq!(
+            rtformat!("{v[1]} {n}", v = values, n = 3u8,).unwrap(),
+            "two 3"
+        );
+
+        let err = rtformat!("{x:d}", x = "text").unwrap_err();
+        assert!(err.is_conversion_error());
+        assert_eq!(rtformat!("{x}", y = 1).unwrap_err(), Error::missing("x"));
+        assert_eq!(
+            rtformat!("{x", x = 1).unwrap_err().kind(),
+            ErrorKind::InvalidFormatSpec
+        );
+    }
+}
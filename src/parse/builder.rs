@@ -1,119 +1,326 @@
 //! Build regex patterns from format strings.
 
+use super::custom::CustomType;
+#[cfg(feature = "chrono")]
+use super::datetime::DateTimeType;
+use super::re::Regex;
 use crate::error::{Error, Result};
-use crate::spec::{FormatSpec, TypeSpec};
+use crate::spec::{Alignment, FormatSpec, TypeSpec};
+use crate::template::{Template, TemplateField};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Information about a capture group in a regex pattern.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CaptureInfo {
     pub name: String,
-    pub spec: FormatSpec,
-    #[allow(dead_code)]
+    pub spec: Arc<FormatSpec>,
     pub group_index: usize,
+    pub kind: CaptureKind,
+    pub groups: usize,       // Capture groups inside the field's own regex
+    pub skip: bool,          // Matched without a capture group, so not in results
+    pub repeats: Vec<usize>, // Groups of later fields with the same name
 }
 
-/// Build a regex pattern from a format string.
+/// Where a capture's regex and conversion come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureKind {
+    /// The field's format spec
+    Spec,
+    /// A custom type, by index into the parser's types
+    Custom(usize),
+    /// An inline `/regex/`, captured as a string
+    Regex,
+    /// A `(a|b)` choice set, captured as a string
+    Choice,
+    /// A datetime type such as `ti`
+    #[cfg(feature = "chrono")]
+    DateTime(DateTimeType),
+}
+
+/// Check whether a spec names a built-in parse type, such as the `ti`
+/// datetime type.
+pub(crate) fn is_builtin_type(name: &str) -> bool {
+    builtin_type(name).is_some()
+}
+
+/// Look up a built-in parse type, returning its capture kind and regex.
+#[cfg_attr(not(feature = "chrono"), allow(unused_variables))]
+fn builtin_type(name: &str) -> Option<(CaptureKind, String)> {
+    #[cfg(feature = "chrono")]
+    if let Some(ty) = DateTimeType::from_spec(name) {
+        return Some((CaptureKind::DateTime(ty), ty.pattern()));
+    }
+    None
+}
+
+/// Build a regex pattern from a parsed pattern.
+///
+/// Returns the regex pattern and information about capture groups. With an
+/// `exponent`, `e` and `E` fields only match numbers written with exactly
+/// that exponent. Fields with a custom type match the regex of the type of
+/// that name in `types`, fields with an inline regex match that regex, and
+/// fields with a choice set match one of its alternatives.
+/// With `skip_anonymous`, positional fields get a non-capturing group.
 ///
-/// Returns the regex pattern and information about capture groups.
-pub fn build_regex_pattern(format_str: &str) -> Result<(String, Vec<CaptureInfo>)> {
+/// A name used again gets no capture info of its own; its group is listed
+/// in the first field's `repeats` instead. Repeats must have the same spec.
+pub fn build_regex_pattern(
+    template: &Template,
+    exponent: Option<i32>,
+    types: &[CustomType],
+    skip_anonymous: bool,
+) -> Result<(String, Vec<CaptureInfo>)> {
     let mut pattern = String::new();
-    let mut captures = Vec::new();
-    let mut chars = format_str.chars().peekable();
-    let mut group_index = 1; // Regex group indices start at 1
-    let mut auto_index = 0;
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            '{' => {
-                if chars.peek() == Some(&'{') {
-                    // Escaped brace
-                    chars.next();
-                    pattern.push_str(r"\{");
-                } else {
-                    // Parse field
-                    let field_str = parse_until_closing_brace(&mut chars)?;
-                    let (field_pattern, capture_info) =
-                        build_field_pattern(&field_str, &mut group_index, &mut auto_index)?;
-                    pattern.push_str(&field_pattern);
-                    if let Some(info) = capture_info {
-                        captures.push(info);
-                    }
-                }
-            }
-            '}' => {
-                if chars.peek() == Some(&'}') {
-                    // Escaped brace
-                    chars.next();
-                    pattern.push_str(r"\}");
-                } else {
-                    return Err(Error::InvalidFormatSpec(
-                        "unmatched '}' in format string".to_string(),
-                    ));
-                }
+    let mut captures: Vec<CaptureInfo> = Vec::new();
+    let mut group_index = 1;
+
+    let mut firsts: HashMap<String, (usize, &TemplateField)> = HashMap::new();
+
+    for field in template.fields() {
+        push_escaped(&mut pattern, field.prefix());
+        let skip = skip_anonymous && field.name().is_none();
+        let (field_pattern, capture_info) =
+            build_field_pattern(field, group_index, exponent, types, skip)?;
+
+        // A repeated name gets an unnamed group, checked against the first
+        // field's text after matching
+        if let Some(&(first, first_field)) = firsts.get(&capture_info.name) {
+            if (
+                field.spec(),
+                field.custom_type(),
+                field.regex(),
+                field.choices(),
+            ) != (
+                first_field.spec(),
+                first_field.custom_type(),
+                first_field.regex(),
+                first_field.choices(),
+            ) {
+                return Err(Error::spec_at(
+                    format!(
+                        "field '{}' is repeated with a different format spec",
+                        capture_info.name
+                    ),
+                    field.offset(),
+                ));
             }
-            // Escape regex special characters
-            '.' | '*' | '+' | '?' | '|' | '(' | ')' | '[' | ']' | '^' | '$' | '\\' => {
-                pattern.push('\\');
-                pattern.push(ch);
+            if !skip {
+                let (field_pattern, _) =
+                    build_field_pattern(field, group_index, exponent, types, true)?;
+                pattern.push_str(&format!("({})", field_pattern));
+                captures[first].repeats.push(group_index);
+            } else {
+                pattern.push_str(&field_pattern);
             }
-            _ => pattern.push(ch),
+            group_index += usize::from(!skip) + capture_info.groups;
+            continue;
         }
+
+        pattern.push_str(&field_pattern);
+        // Groups inside the field's regex come after the field's own
+        group_index += usize::from(!skip) + capture_info.groups;
+        firsts.insert(capture_info.name.clone(), (captures.len(), field));
+        captures.push(capture_info);
     }
+    push_escaped(&mut pattern, template.tail());
 
     Ok((pattern, captures))
 }
 
-/// Parse until we find a closing brace.
-fn parse_until_closing_brace(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String> {
-    let mut result = String::new();
-    let mut depth = 0;
-
-    while let Some(&ch) = chars.peek() {
-        if ch == '{' {
-            depth += 1;
-        } else if ch == '}' {
-            if depth == 0 {
-                chars.next(); // consume the '}'
-                return Ok(result);
-            }
-            depth -= 1;
+/// Append literal text to a regex pattern, escaping regex special characters.
+fn push_escaped(pattern: &mut String, text: &str) {
+    for ch in text.chars() {
+        if matches!(
+            ch,
+            '.' | '*' | '+' | '?' | '|' | '(' | ')' | '[' | ']' | '{' | '}' | '^' | '$' | '\\'
+        ) {
+            pattern.push('\\');
         }
-        result.push(ch);
-        chars.next();
+        pattern.push(ch);
     }
+}
+
+/// Get the fill character and alignment a field is padded with, if any.
+///
+/// Like the formatter, numbers are right-aligned and text is left-aligned
+/// unless the spec says otherwise. Numbers zero-padded without an alignment
+/// have no padding to strip, since the zeros parse as digits.
+pub(crate) fn padding(spec: &FormatSpec) -> Option<(char, Alignment)> {
+    let align = match spec.align {
+        Some(align) => align,
+        None if spec.width.is_none() || (is_numeric(spec) && spec.zero_pad) => return None,
+        None if is_numeric(spec) => Alignment::Right,
+        None => Alignment::Left,
+    };
+    let fill = spec.fill.unwrap_or(if spec.zero_pad { '0' } else { ' ' });
+    Some((fill, align))
+}
 
-    Err(Error::InvalidFormatSpec(
-        "unclosed '{' in format string".to_string(),
-    ))
+/// Strip a field's padding from the text it matched.
+///
+/// For `=` alignment the sign is kept and the fill after it removed. A
+/// number made only of fill characters, like `0` padded with zeros, keeps
+/// its last character.
+pub(crate) fn strip_padding<'a>(text: &'a str, spec: &FormatSpec) -> Cow<'a, str> {
+    let Some((fill, align)) = padding(spec) else {
+        return Cow::Borrowed(text);
+    };
+    let stripped = match align {
+        Alignment::Left => text.trim_end_matches(fill),
+        Alignment::Right => text.trim_start_matches(fill),
+        Alignment::Center => text.trim_matches(fill),
+        Alignment::AfterSign => {
+            let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+            let sign = &text[..text.len() - digits.len()];
+            let digits = keep_last(digits, digits.trim_start_matches(fill));
+            return if sign.is_empty() {
+                Cow::Borrowed(digits)
+            } else {
+                Cow::Owned(format!("{}{}", sign, digits))
+            };
+        }
+    };
+    Cow::Borrowed(if is_numeric(spec) {
+        keep_last(text, stripped)
+    } else {
+        stripped
+    })
+}
+
+/// Check whether a field with this spec is converted to a number.
+fn is_numeric(spec: &FormatSpec) -> bool {
+    !matches!(
+        spec.type_spec,
+        None | Some(TypeSpec::String | TypeSpec::Character)
+    )
+}
+
+/// Get `stripped`, or the last character of `text` if stripping removed
+/// everything.
+fn keep_last<'a>(text: &'a str, stripped: &'a str) -> &'a str {
+    match text.char_indices().last() {
+        Some((i, _)) if stripped.is_empty() => &text[i..],
+        _ => stripped,
+    }
+}
+
+/// Wrap a field's regex in a group named after the field, or in a
+/// non-capturing group if the field is skipped.
+fn field_group(name: &str, regex: &str, skip: bool) -> String {
+    if skip {
+        format!("(?:{})", regex)
+    } else {
+        format!("(?P<{}>{})", name, regex)
+    }
 }
 
 /// Build a regex pattern for a field.
 ///
-/// Returns the pattern and optional capture info.
+/// Returns the pattern and the field's capture info.
 fn build_field_pattern(
-    field: &str,
-    group_index: &mut usize,
-    auto_index: &mut usize,
-) -> Result<(String, Option<CaptureInfo>)> {
-    // Split on ':'
-    let parts: Vec<&str> = field.splitn(2, ':').collect();
-    let name_part = parts[0];
-    let spec_part = parts.get(1).copied().unwrap_or("");
-
-    // Determine field name
-    let name = if name_part.is_empty() {
-        // Auto-numbered field
-        let n = format!("_{}", auto_index);
-        *auto_index += 1;
-        n
-    } else if name_part.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        name_part.to_string()
-    } else {
-        return Err(Error::InvalidFieldName(name_part.to_string()));
+    field: &TemplateField,
+    group_index: usize,
+    exponent: Option<i32>,
+    types: &[CustomType],
+    skip: bool,
+) -> Result<(String, CaptureInfo)> {
+    // Positional fields are captured as `_0`, `_1`, ...; paths and conversion
+    // flags only make sense when formatting
+    if !field.path().is_empty() || field.conversion().is_some() {
+        let mut name = field.display_name();
+        if let Some(conversion) = field.conversion() {
+            name.push('!');
+            name.push(conversion.to_char());
+        }
+        return Err(Error::invalid_name(name, "").offset_by(field.offset()));
+    }
+    #[cfg(feature = "chrono")]
+    if let Some(format) = field.strftime() {
+        return Err(Error::spec_at(
+            format!(
+                "datetime format '{}' can only be used for formatting",
+                format
+            ),
+            field.offset(),
+        ));
+    }
+    let name = match (field.name(), field.index()) {
+        (Some(name), _) => name.to_string(),
+        (None, index) => format!("_{}", index.unwrap_or_default()),
     };
+    let spec = Arc::clone(field.shared_spec());
 
-    // Parse format spec
-    let spec = FormatSpec::parse(spec_part)?;
+    // Registered types take precedence over built-in ones
+    if let Some(type_name) = field.custom_type() {
+        let (kind, type_pattern, groups) = match types.iter().position(|ty| ty.name == type_name) {
+            Some(i) => (
+                CaptureKind::Custom(i),
+                types[i].pattern.clone(),
+                types[i].groups,
+            ),
+            None => {
+                let (kind, type_pattern) = builtin_type(type_name).ok_or_else(|| {
+                    Error::spec_at(format!("unknown type '{}'", type_name), field.offset())
+                })?;
+                (kind, type_pattern, 0)
+            }
+        };
+        let pattern = field_group(&name, &type_pattern, skip);
+        let capture_info = CaptureInfo {
+            name,
+            spec,
+            group_index,
+            kind,
+            groups,
+            skip,
+            repeats: Vec::new(),
+        };
+        return Ok((pattern, capture_info));
+    }
+
+    if let Some(regex) = field.regex() {
+        let groups = Regex::new(regex)
+            .map_err(|e| Error::RegexError {
+                message: format!("invalid regex for field '{}': {}", name, e),
+            })?
+            .captures_len()
+            - 1;
+        let pattern = field_group(&name, regex, skip);
+        let capture_info = CaptureInfo {
+            name,
+            spec,
+            group_index,
+            kind: CaptureKind::Regex,
+            groups,
+            skip,
+            repeats: Vec::new(),
+        };
+        return Ok((pattern, capture_info));
+    }
+
+    // A choice set matches one of its alternatives, tried in order
+    if let Some(choices) = field.choices() {
+        let mut regex = String::new();
+        for (i, choice) in choices.iter().enumerate() {
+            if i > 0 {
+                regex.push('|');
+            }
+            push_escaped(&mut regex, choice);
+        }
+        let pattern = field_group(&name, &format!("(?:{})", regex), skip);
+        let capture_info = CaptureInfo {
+            name,
+            spec,
+            group_index,
+            kind: CaptureKind::Choice,
+            groups: 0,
+            skip,
+            repeats: Vec::new(),
+        };
+        return Ok((pattern, capture_info));
+    }
 
     // Build regex pattern based on type
     let type_spec = spec.type_spec.unwrap_or(TypeSpec::String);
@@ -151,6 +358,20 @@ fn build_field_pattern(
             // Match hex with optional 0x prefix
             r"(?:0[xX])?[0-9a-fA-F]+".to_string()
         }
+        TypeSpec::ExponentLower | TypeSpec::ExponentUpper if exponent.is_some() => {
+            // Match a mantissa followed by exactly the fixed exponent
+            let exponent = exponent.unwrap_or_default();
+            format!(
+                r"[-+]?(?:\d+\.?\d*|\.\d+){}{}{:02}",
+                if type_spec == TypeSpec::ExponentUpper {
+                    'E'
+                } else {
+                    'e'
+                },
+                if exponent < 0 { "-" } else { r"\+" },
+                exponent.unsigned_abs()
+            )
+        }
         TypeSpec::FixedLower
         | TypeSpec::FixedUpper
         | TypeSpec::ExponentLower
@@ -170,27 +391,52 @@ fn build_field_pattern(
         }
     };
 
-    // Wrap in named capture group
-    let pattern = format!(r"(?P<{}>{})", name, regex_pattern);
+    // Text with a width already matches its padding; everything else gets
+    // the fill on the aligned side(s), stripped again when converting
+    let regex_pattern = match padding(&spec) {
+        Some((fill, align)) if !(type_spec == TypeSpec::String && spec.width.is_some()) => {
+            let mut fill_pattern = String::new();
+            push_escaped(&mut fill_pattern, fill.encode_utf8(&mut [0; 4]));
+            fill_pattern.push('*');
+            match align {
+                Alignment::Left => format!("(?:{}){}", regex_pattern, fill_pattern),
+                Alignment::Right => format!("{}(?:{})", fill_pattern, regex_pattern),
+                Alignment::Center => {
+                    format!("{}(?:{}){}", fill_pattern, regex_pattern, fill_pattern)
+                }
+                Alignment::AfterSign => {
+                    format!("[-+]?{}(?:{})", fill_pattern, regex_pattern)
+                }
+            }
+        }
+        _ => regex_pattern,
+    };
+    let pattern = field_group(&name, &regex_pattern, skip);
 
     let capture_info = CaptureInfo {
-        name: name.clone(),
+        name,
         spec,
-        group_index: *group_index,
+        group_index,
+        kind: CaptureKind::Spec,
+        groups: 0,
+        skip,
+        repeats: Vec::new(),
     };
 
-    *group_index += 1;
-
-    Ok((pattern, Some(capture_info)))
+    Ok((pattern, capture_info))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn build(pattern: &str) -> Result<(String, Vec<CaptureInfo>)> {
+        build_regex_pattern(&Template::parse(pattern)?, None, &[], false)
+    }
+
     #[test]
     fn test_simple_pattern() {
-        let (pattern, captures) = build_regex_pattern("{name}").unwrap();
+        let (pattern, captures) = build("{name}").unwrap();
         assert_eq!(pattern, r"(?P<name>.+?)");
         assert_eq!(captures.len(), 1);
         assert_eq!(captures[0].name, "name");
@@ -198,34 +444,103 @@ mod tests {
 
     #[test]
     fn test_multiple_fields() {
-        let (pattern, captures) = build_regex_pattern("{first} {last}").unwrap();
+        let (pattern, captures) = build("{first} {last}").unwrap();
         assert_eq!(pattern, r"(?P<first>.+?) (?P<last>.+?)");
         assert_eq!(captures.len(), 2);
     }
 
     #[test]
     fn test_decimal_field() {
-        let (pattern, captures) = build_regex_pattern("{value:d}").unwrap();
+        let (pattern, captures) = build("{value:d}").unwrap();
         assert!(pattern.contains(r"[-+]?\d+"));
         assert_eq!(captures[0].spec.type_spec, Some(TypeSpec::Decimal));
     }
 
     #[test]
     fn test_float_field() {
-        let (pattern, _) = build_regex_pattern("{value:f}").unwrap();
+        let (pattern, _) = build("{value:f}").unwrap();
         assert!(pattern.contains(r"[-+]?"));
         assert!(pattern.contains(r"\d+"));
     }
 
     #[test]
     fn test_escaped_braces() {
-        let (pattern, _) = build_regex_pattern("{{literal}}").unwrap();
+        let (pattern, _) = build("{{literal}}").unwrap();
         assert_eq!(pattern, r"\{literal\}");
     }
 
     #[test]
     fn test_regex_special_chars() {
-        let (pattern, _) = build_regex_pattern("value = {x}").unwrap();
+        let (pattern, _) = build("value = {x}").unwrap();
         assert!(pattern.contains("value = "));
     }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_datetime_format_rejected() {
+        let err = build("x={ts:%Y-%m-%d}").unwrap_err();
+        assert_eq!(
+            err,
+            Error::spec_at(
+                "datetime format '%Y-%m-%d' can only be used for formatting",
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn test_positional_and_unsupported_fields() {
+        let (pattern, captures) = build("{} {1:d}").unwrap();
+        assert_eq!(pattern, r"(?P<_0>.+?) (?P<_1>[-+]?\d+)");
+        assert_eq!(captures[1].group_index, 2);
+
+        assert_eq!(
+            build("ab{x.y}").unwrap_err(),
+            Error::InvalidFieldName {
+                name: "x.y".to_string(),
+                position: Some(3),
+            }
+        );
+        assert!(build("{x!r}").is_err());
+        assert!(build("{x[0]}").is_err());
+    }
+
+    #[test]
+    fn test_patterns_stay_within_regex_lite() {
+        // Every field type, each constraint on text fields, regex specials in
+        // literals and non-ASCII names must compile with `regex-lite` too
+        let mut patterns: Vec<String> = "sbcdoxXnfFeEgG%"
+            .chars()
+            .map(|t| format!("<{{v:{}}}>", t))
+            .collect();
+        patterns.extend(
+            [
+                "{a:5} {b:.3} {c:2.4} {}",
+                ".*+?|()[]^$\\ {{x}} {x}",
+                "{名前} = {値:d}",
+                "{_0}-{a_b:#x}|{c:,d}",
+            ]
+            .map(String::from),
+        );
+
+        for pattern in &patterns {
+            let (regex_pattern, captures) = build(pattern).unwrap();
+            let lite = regex_lite::Regex::new(&regex_pattern)
+                .unwrap_or_else(|e| panic!("{:?} -> {:?}: {}", pattern, regex_pattern, e));
+            let names: Vec<_> = lite.capture_names().flatten().collect();
+            let expected: Vec<_> = captures.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, expected, "{:?}", pattern);
+
+            let full = crate::parse::re::Regex::new(&regex_pattern).unwrap();
+            for text in ["<x>", "<-12>", "<0x1F>", "<1.5e3>", "<50%>", "名前 = 7"] {
+                assert_eq!(
+                    lite.is_match(text),
+                    full.is_match(text),
+                    "{:?} on {:?}",
+                    pattern,
+                    text
+                );
+            }
+        }
+    }
 }
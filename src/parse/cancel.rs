@@ -0,0 +1,111 @@
+//! Stopping long scans early.
+
+use super::matcher::ParseResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A handle for stopping a scan before it reaches the end of its input.
+///
+/// Clones share one flag, so a clone can be handed to another thread or a
+/// Ctrl-C handler while the scan runs. A deadline stops the scan once it has
+/// passed. Scans check the token between matches, so a match in progress is
+/// never cut short.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::parse::{CancelToken, ScanStatus};
+/// use gullwing::Parser;
+///
+/// let parser = Parser::new("{n:d};").unwrap();
+/// let token = CancelToken::new();
+///
+/// let scan = parser.findall_cancellable("1;2;3;", &token).unwrap();
+/// assert_eq!(scan.status, ScanStatus::Complete);
+/// assert_eq!(scan.results.len(), 3);
+///
+/// token.cancel();
+/// let scan = parser.findall_cancellable("1;2;3;", &token).unwrap();
+/// assert_eq!(scan.status, ScanStatus::Cancelled);
+/// assert!(scan.results.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// Create a token that stops a scan only when [`cancel`](Self::cancel) is called.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a copy of this token that also stops scans at `deadline`.
+    ///
+    /// The copy shares the cancellation flag with this token.
+    pub fn with_deadline(&self, deadline: Instant) -> Self {
+        CancelToken {
+            cancelled: Arc::clone(&self.cancelled),
+            deadline: Some(deadline),
+        }
+    }
+
+    /// Return a copy of this token that also stops scans `timeout` from now.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Stop every scan using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check whether scans using this token should stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// The matches found by a cancellable scan, and whether it finished.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    /// The matches found before the scan finished or was stopped.
+    pub results: Vec<ParseResult>,
+    /// Whether the whole input was scanned.
+    pub status: ScanStatus,
+}
+
+/// How a cancellable scan ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanStatus {
+    /// The whole input was scanned.
+    Complete,
+    /// The scan was stopped by its [`CancelToken`]; later matches are missing.
+    Cancelled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::new();
+        assert!(!token.is_cancelled());
+
+        let expired = token.with_deadline(Instant::now());
+        assert!(expired.is_cancelled());
+        assert!(!token.with_timeout(Duration::from_secs(3600)).is_cancelled());
+
+        // Clones and derived tokens share the flag
+        let clone = token.clone();
+        expired.cancel();
+        assert!(token.is_cancelled());
+        assert!(clone.is_cancelled());
+    }
+}
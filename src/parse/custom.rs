@@ -0,0 +1,260 @@
+//! Parse types registered by the user.
+
+use super::builder::is_builtin_type;
+use super::matcher::Parser;
+use super::re::Regex;
+use crate::dialect::Dialect;
+use crate::error::{Error, Result};
+use crate::template::Template;
+use crate::types::Value;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Converts the text matched by a custom type, or returns `None` to reject it.
+type Converter = Arc<dyn Fn(&str) -> Option<Value> + Send + Sync>;
+
+/// A parse type registered with [`ParserBuilder::with_type`].
+#[derive(Clone)]
+pub(crate) struct CustomType {
+    pub name: String,
+    pub pattern: String,
+    pub groups: usize, // Capture groups in `pattern`
+    convert: Converter,
+}
+
+impl CustomType {
+    /// Convert matched text, failing with [`Error::ConversionError`] if the
+    /// converter rejects it.
+    pub(crate) fn convert(&self, text: &str) -> Result<Value> {
+        (self.convert)(text).ok_or_else(|| Error::conversion(self.name.as_str(), text))
+    }
+}
+
+/// Types are equal when they share a name, a regex and the same converter;
+/// converters are compared by identity, since closures can't be compared.
+impl PartialEq for CustomType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.pattern == other.pattern
+            && converter_addr(&self.convert) == converter_addr(&other.convert)
+    }
+}
+
+impl Eq for CustomType {}
+
+impl Hash for CustomType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.pattern.hash(state);
+        converter_addr(&self.convert).hash(state);
+    }
+}
+
+/// Get the address of a converter's closure, ignoring its vtable.
+fn converter_addr(convert: &Converter) -> usize {
+    Arc::as_ptr(convert).cast::<()>() as usize
+}
+
+impl fmt::Debug for CustomType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomType")
+            .field("name", &self.name)
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builds a [`Parser`] that understands extra type specifiers.
+///
+/// Created with [`Parser::builder`]. Like `extra_types` in Python's `parse`,
+/// each type is a name used as the whole spec of a field (`{addr:ip}`), a
+/// regex the field matches, and a converter from the matched text to a
+/// [`Value`]. A converter that returns `None` fails the parse with
+/// [`Error::ConversionError`]. A registered name takes precedence over a
+/// built-in type of the same name.
+///
+/// Fields with a custom type are always matched with
+/// [`Engine::Regex`](super::Engine::Regex). Parsers compare equal by their
+/// patterns and registered types, with converters compared by identity:
+/// clones of a parser are equal, but parsers built from separate
+/// `with_type` calls are not, even with the same closure.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::{Parser, Value};
+/// use std::net::Ipv4Addr;
+///
+/// let parser = Parser::builder()
+///     .with_type("ip", r"\d+\.\d+\.\d+\.\d+", |s| {
+///         let ip: Ipv4Addr = s.parse().ok()?;
+///         Some(Value::from(ip.to_string()))
+///     })
+///     .build("{addr:ip}:{port:d}")
+///     .unwrap();
+///
+/// let result = parser.parse("10.0.0.1:8080").unwrap().unwrap();
+/// assert_eq!(result.get("addr").unwrap().as_str(), Some("10.0.0.1"));
+///
+/// // Matches the regex, but the converter rejects it
+/// let err = parser.parse("10.0.0.256:8080").unwrap_err();
+/// assert!(err.is_conversion_error());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParserBuilder {
+    types: Vec<CustomType>,
+    dialect: Dialect,
+}
+
+impl ParserBuilder {
+    /// Register a type named `name`, matching `pattern` and converted with `convert`.
+    ///
+    /// Registering a name again replaces the earlier type. The regex is
+    /// checked when the parser is built.
+    pub fn with_type<F>(
+        mut self,
+        name: impl Into<String>,
+        pattern: impl Into<String>,
+        convert: F,
+    ) -> Self
+    where
+        F: Fn(&str) -> Option<Value> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.types.retain(|ty| ty.name != name);
+        self.types.push(CustomType {
+            name,
+            pattern: pattern.into(),
+            groups: 0,
+            convert: Arc::new(convert),
+        });
+        self
+    }
+
+    /// Only accept patterns that use the features of `dialect`.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Build a parser for `pattern` using the registered types.
+    ///
+    /// Fails if a type has an empty name or an invalid regex, or if the
+    /// pattern is invalid or uses features outside the dialect.
+    pub fn build(&self, pattern: &str) -> Result<Parser> {
+        span!(DEBUG, "compile", kind = "parser", pattern);
+        let mut types = self.types.clone();
+        for ty in &mut types {
+            if ty.name.is_empty() {
+                return Err(Error::spec("custom type names can't be empty"));
+            }
+            let regex = Regex::new(&ty.pattern).map_err(|e| Error::RegexError {
+                message: format!("invalid regex for type '{}': {}", ty.name, e),
+            })?;
+            ty.groups = regex.captures_len() - 1;
+        }
+
+        let template = Template::parse_with_types(pattern, &|spec| {
+            types.iter().any(|ty| ty.name == spec) || is_builtin_type(spec)
+        })?;
+        Parser::compile(Arc::new(template), None, types.into(), false, self.dialect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::parse::Engine;
+
+    fn hex_color() -> ParserBuilder {
+        Parser::builder().with_type("color", r"#([0-9a-f]{2})([0-9a-f]{2})([0-9a-f]{2})", |s| {
+            i64::from_str_radix(&s[1..], 16).ok().map(Value::from)
+        })
+    }
+
+    #[test]
+    fn test_custom_type() {
+        // Groups inside the type's regex don't shift later fields
+        let parser = hex_color()
+            .build("{fg:color} on {bg:color} x{n:d}")
+            .unwrap();
+        assert_eq!(parser.engine(), Engine::Regex);
+        assert_eq!(parser.capture_count(), 3);
+        assert_eq!(parser.template().fields()[0].custom_type(), Some("color"));
+
+        let result = parser.parse("#ff0000 on #000001 x3").unwrap().unwrap();
+        assert_eq!(result.get("fg"), Some(&Value::from(0xff0000)));
+        assert_eq!(result.get("bg"), Some(&Value::from(1)));
+        assert_eq!(result.get("n"), Some(&Value::from(3)));
+        assert_eq!(result.span("bg"), Some(11..18));
+        assert!(parser.parse("#ff000 on #000001 x3").unwrap().is_none());
+
+        let found: Vec<_> = parser
+            .findall("#000002 on #000003 x1, #000004 on #000005 x2")
+            .map(|r| r.unwrap().get("bg").cloned())
+            .collect();
+        assert_eq!(found, [Some(Value::from(3)), Some(Value::from(5))]);
+
+        // Registered names win over built-in types
+        let parser = Parser::builder()
+            .with_type("d", "[a-z]+", |s| Some(Value::from(s.len() as i64)))
+            .build("{x:d}")
+            .unwrap();
+        assert_eq!(
+            parser.parse("abc").unwrap().unwrap().get("x"),
+            Some(&Value::from(3))
+        );
+    }
+
+    #[test]
+    fn test_custom_type_errors() {
+        let parser = Parser::builder()
+            .with_type("even", r"\d+", |s| {
+                let n: i64 = s.parse().ok()?;
+                (n % 2 == 0).then(|| Value::from(n))
+            })
+            .build("{n:even}")
+            .unwrap();
+        let err = parser.parse("3").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConversionError);
+        assert!(err.to_string().contains("even"));
+
+        let err = Parser::builder()
+            .with_type("x", "(", |_| None)
+            .build("{a:x}");
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::RegexError);
+        let err = Parser::builder().with_type("", ".", |_| None).build("{a}");
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidFormatSpec);
+
+        // Unregistered names are still invalid specs
+        assert!(hex_color().build("{a:colour}").is_err());
+    }
+
+    #[test]
+    fn test_custom_type_equality() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |parser: &Parser| {
+            let mut hasher = DefaultHasher::new();
+            parser.hash(&mut hasher);
+            hasher.finish()
+        };
+        let builder = hex_color();
+        let parser = builder.build("{fg:color}").unwrap();
+        let same = builder.build("{fg:color}").unwrap();
+        assert_eq!(parser, same);
+        assert_eq!(hash(&parser), hash(&same));
+
+        // Same name and regex, but a converter that parses differently
+        let other = Parser::builder()
+            .with_type("color", r"#([0-9a-f]{2})([0-9a-f]{2})([0-9a-f]{2})", |s| {
+                Some(Value::from(s))
+            })
+            .build("{fg:color}")
+            .unwrap();
+        assert_ne!(parser, other);
+        assert_ne!(hash(&parser), hash(&other));
+    }
+}
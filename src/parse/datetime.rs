@@ -0,0 +1,326 @@
+//! The datetime parse types of Python's `parse`, such as `{ts:ti}`.
+
+use super::re::Regex;
+use crate::error::{Error, Result};
+use crate::types::Value;
+use chrono::{Datelike, FixedOffset, NaiveDate, TimeZone};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAYS: &str = "(?:Mon|Tue|Wed|Thu|Fri|Sat|Sun)";
+const MONTHS: &str = "(?P<b>Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)";
+const ALL_MONTHS: &str = "(?P<b>January|February|March|April|May|June|July|August|September|\
+                          October|November|December|Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec)";
+const TIME: &str = r"(?P<H>\d{1,2}):(?P<M>\d{1,2})(?::(?P<S>\d{1,2})(?:\.(?P<f>\d+))?)?";
+const AM_PM: &str = r"(?:\s+(?P<ampm>[AP]M))";
+const TZ: &str = r"(?:\s+(?P<tz>[-+]\d\d?:?\d\d))";
+
+/// A datetime parse type.
+///
+/// Each matches the same text as the type of the same name in Python's
+/// `parse` and converts it to a [`Value::DateTime`]. Text without a time
+/// zone is taken as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum DateTimeType {
+    /// `ti`: ISO 8601, `1972-01-20T10:21:36Z`; the time and zone are optional
+    Iso,
+    /// `te`: RFC 2822 email, `Mon, 20 Jan 1972 10:21:36 +1000`
+    Email,
+    /// `tg`: day first, `20/1/1972 10:21:36 AM +1:00`; the time is optional
+    Global,
+    /// `ta`: month first, `1/20/1972 10:21:36 PM +10:30`; the time is optional
+    Us,
+    /// `tc`: `ctime()`, `Sun Sep 16 01:03:52 1973`
+    Ctime,
+    /// `th`: HTTP access logs, `21/Nov/2011:00:07:11 +0000`
+    Http,
+    /// `ts`: syslog, `Nov  9 03:37:44`, in the current year
+    Syslog,
+}
+
+const ALL: [DateTimeType; 7] = [
+    DateTimeType::Iso,
+    DateTimeType::Email,
+    DateTimeType::Global,
+    DateTimeType::Us,
+    DateTimeType::Ctime,
+    DateTimeType::Http,
+    DateTimeType::Syslog,
+];
+
+impl DateTimeType {
+    /// Get the type named by a spec, such as `ti`.
+    pub(crate) fn from_spec(spec: &str) -> Option<Self> {
+        ALL.into_iter().find(|ty| ty.name() == spec)
+    }
+
+    /// Get the spec naming this type.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            DateTimeType::Iso => "ti",
+            DateTimeType::Email => "te",
+            DateTimeType::Global => "tg",
+            DateTimeType::Us => "ta",
+            DateTimeType::Ctime => "tc",
+            DateTimeType::Http => "th",
+            DateTimeType::Syslog => "ts",
+        }
+    }
+
+    /// Get the regex for this type, with a named group for each part.
+    fn named_pattern(self) -> String {
+        match self {
+            DateTimeType::Iso => format!(
+                r"(?P<Y>\d{{4}})-(?P<m>\d\d)-(?P<d>\d\d)(?:(?:\s+|T){})?(?P<tz>Z|\s*[-+]\d\d:?\d\d)?",
+                TIME
+            ),
+            DateTimeType::Email => format!(
+                r"(?:{},\s+)?(?P<d>\d{{1,2}})\s+{}\s+(?P<Y>\d{{4}})\s+{}{}",
+                DAYS, MONTHS, TIME, TZ
+            ),
+            DateTimeType::Global => format!(
+                r"(?P<d>\d{{1,2}})[-/](?:(?P<m>\d{{1,2}})|{})[-/](?P<Y>\d{{4}})(?:\s+{})?{}?{}?",
+                ALL_MONTHS, TIME, AM_PM, TZ
+            ),
+            DateTimeType::Us => format!(
+                r"(?:(?P<m>\d{{1,2}})|{})[-/](?P<d>\d{{1,2}})[-/](?P<Y>\d{{4}})(?:\s+{})?{}?{}?",
+                ALL_MONTHS, TIME, AM_PM, TZ
+            ),
+            DateTimeType::Ctime => format!(
+                r"{}\s+{}\s+(?P<d>\d{{1,2}})\s+{}\s+(?P<Y>\d{{4}})",
+                DAYS, MONTHS, TIME
+            ),
+            DateTimeType::Http => format!(
+                r"(?P<d>\d{{1,2}})[-/]{}[-/](?P<Y>\d{{4}}):{}{}",
+                MONTHS, TIME, TZ
+            ),
+            DateTimeType::Syslog => format!(
+                r"{}\s+(?P<d>\d{{1,2}})\s+(?P<H>\d{{1,2}}):(?P<M>\d{{1,2}}):(?P<S>\d{{1,2}})",
+                MONTHS
+            ),
+        }
+    }
+
+    /// Get the regex a field of this type matches, without capture groups.
+    pub(crate) fn pattern(self) -> String {
+        let named = self.named_pattern();
+        let mut pattern = String::with_capacity(named.len());
+        let mut rest = named.as_str();
+        while let Some(start) = rest.find("(?P<") {
+            pattern.push_str(&rest[..start]);
+            pattern.push_str("(?:");
+            let name = &rest[start + 4..];
+            rest = &name[name.find('>').map_or(0, |end| end + 1)..];
+        }
+        pattern.push_str(rest);
+        pattern
+    }
+
+    /// Convert text matched by [`pattern`](Self::pattern).
+    pub(crate) fn convert(self, text: &str) -> Result<Value> {
+        self.to_datetime(text)
+            .ok_or_else(|| Error::conversion(format!("datetime ({})", self.name()), text))
+    }
+
+    fn to_datetime(self, text: &str) -> Option<Value> {
+        static REGEXES: OnceLock<Vec<Regex>> = OnceLock::new();
+        let regexes = REGEXES.get_or_init(|| {
+            ALL.iter()
+                .map(|ty| Regex::new(&format!("^(?:{})$", ty.named_pattern())).unwrap())
+                .collect()
+        });
+        let cap = regexes[self as usize].captures(text)?;
+        let number = |name: &str| cap.name(name).map(|m| m.as_str().parse::<u32>());
+
+        let year = match number("Y") {
+            Some(year) => year.ok()? as i32,
+            None => current_year(),
+        };
+        let month = match (number("m"), cap.name("b")) {
+            (Some(month), _) => month.ok()?,
+            (None, Some(name)) => month_number(name.as_str())?,
+            (None, None) => return None,
+        };
+        let day = number("d")?.ok()?;
+        let mut hour = number("H").transpose().ok()?.unwrap_or(0);
+        let minute = number("M").transpose().ok()?.unwrap_or(0);
+        let second = number("S").transpose().ok()?.unwrap_or(0);
+        let nanos = match cap.name("f") {
+            Some(fraction) => fraction_nanos(fraction.as_str())?,
+            None => 0,
+        };
+        match cap.name("ampm").map(|m| m.as_str()) {
+            Some("AM") if hour == 12 => hour = 0,
+            Some("PM") if hour < 12 => hour += 12,
+            _ => {}
+        }
+        let offset = match cap.name("tz") {
+            Some(tz) => parse_offset(tz.as_str().trim())?,
+            None => 0,
+        };
+
+        let naive = NaiveDate::from_ymd_opt(year, month, day)?
+            .and_hms_nano_opt(hour, minute, second, nanos)?;
+        let dt = FixedOffset::east_opt(offset)?
+            .from_local_datetime(&naive)
+            .single()?;
+        Some(Value::DateTime(dt))
+    }
+}
+
+/// Get the month number for an English month name or its abbreviation.
+fn month_number(name: &str) -> Option<u32> {
+    const ABBREVIATIONS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let position = ABBREVIATIONS
+        .iter()
+        .position(|abbr| name.starts_with(abbr))?;
+    Some(position as u32 + 1)
+}
+
+/// Convert the digits after a decimal point to nanoseconds, dropping any
+/// past the ninth.
+///
+/// Returns `None` for non-ASCII digits, which `\d` also matches.
+fn fraction_nanos(digits: &str) -> Option<u32> {
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = digits
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + u32::from(digit - b'0'));
+    Some(nanos)
+}
+
+/// Parse a time zone offset such as `Z`, `+10:00`, `-0530` or `+1:00`, in seconds.
+fn parse_offset(tz: &str) -> Option<i32> {
+    if tz == "Z" {
+        return Some(0);
+    }
+    let sign = match tz.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: String = tz[1..].chars().filter(|&c| c != ':').collect();
+    if !digits.is_ascii() {
+        return None;
+    }
+    let (hours, minutes) = digits.split_at(digits.len().checked_sub(2)?);
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Get the current year in UTC, for dates written without one.
+fn current_year() -> i32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    chrono::DateTime::from_timestamp(now as i64, 0).map_or(1970, |dt| dt.year())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    fn parse(spec: &str, text: &str) -> chrono::DateTime<FixedOffset> {
+        let ty = DateTimeType::from_spec(spec).unwrap();
+        let regex = Regex::new(&format!("^(?:{})$", ty.pattern())).unwrap();
+        assert!(regex.is_match(text), "{} should match {:?}", spec, text);
+        match ty.convert(text).unwrap() {
+            Value::DateTime(dt) => dt,
+            other => panic!("expected a datetime, got {:?}", other),
+        }
+    }
+
+    fn rfc3339(spec: &str, text: &str) -> String {
+        parse(spec, text).to_rfc3339()
+    }
+
+    #[test]
+    fn test_datetime_types() {
+        assert_eq!(
+            rfc3339("ti", "1972-01-20T10:21:36Z"),
+            "1972-01-20T10:21:36+00:00"
+        );
+        assert_eq!(
+            rfc3339("ti", "1972-01-20 10:21:36.5+0530"),
+            "1972-01-20T10:21:36.500+05:30"
+        );
+        assert_eq!(rfc3339("ti", "1972-01-20"), "1972-01-20T00:00:00+00:00");
+        assert_eq!(
+            rfc3339("te", "Mon, 20 Jan 1972 10:21:36 +1000"),
+            "1972-01-20T10:21:36+10:00"
+        );
+        assert_eq!(
+            rfc3339("te", "20 Jan 1972 10:21 -0100"),
+            "1972-01-20T10:21:00-01:00"
+        );
+        assert_eq!(
+            rfc3339("tg", "20/1/1972 10:21:36 PM +1:00"),
+            "1972-01-20T22:21:36+01:00"
+        );
+        assert_eq!(
+            rfc3339("tg", "20-January-1972"),
+            "1972-01-20T00:00:00+00:00"
+        );
+        assert_eq!(
+            rfc3339("ta", "1/20/1972 12:21:36 AM +10:30"),
+            "1972-01-20T00:21:36+10:30"
+        );
+        assert_eq!(rfc3339("ta", "Sept/20/1972"), "1972-09-20T00:00:00+00:00");
+        assert_eq!(
+            rfc3339("tc", "Sun Sep 16 01:03:52 1973"),
+            "1973-09-16T01:03:52+00:00"
+        );
+        assert_eq!(
+            rfc3339("th", "21/Nov/2011:00:07:11 +0000"),
+            "2011-11-21T00:07:11+00:00"
+        );
+
+        let dt = parse("ts", "Nov  9 03:37:44");
+        assert_eq!((dt.month(), dt.day(), dt.hour()), (11, 9, 3));
+        assert_eq!(dt.year(), current_year());
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let parser = crate::Parser::new("[{when:th}] {level}: {msg}").unwrap();
+        let result = parser
+            .parse("[21/Nov/2011:00:07:11 +0000] WARN: disk at 91%")
+            .unwrap()
+            .unwrap();
+        let when = result.get("when").unwrap().as_datetime().unwrap();
+        assert_eq!(when.to_rfc3339(), "2011-11-21T00:07:11+00:00");
+        assert_eq!(result.get("level").unwrap().as_str(), Some("WARN"));
+
+        let parser = crate::Parser::new("{a:ti}/{b:ti}").unwrap();
+        let found = parser
+            .search("x 2020-01-02/2021-03-04T05:06Z y")
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.get("b").unwrap().as_datetime().unwrap().year(), 2021);
+
+        // Datetime types are only for parsing
+        assert!(crate::Formatter::new("{a:ti}").is_err());
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        let err = DateTimeType::Iso.convert("1972-02-30").unwrap_err();
+        assert!(err.to_string().contains("datetime (ti)"));
+        assert!(DateTimeType::Http
+            .convert("21/Nov/2011:25:07:11 +0000")
+            .is_err());
+        assert!(DateTimeType::Iso
+            .convert("1972-01-20 10:21:36.\u{0665}")
+            .is_err());
+        assert!(DateTimeType::from_spec("tt").is_none());
+        assert_eq!(DateTimeType::Syslog.pattern().matches("(?P<").count(), 0);
+    }
+}
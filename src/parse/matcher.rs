@@ -1,14 +1,55 @@
 //! Parser for extracting structured data from strings.
 
-use super::builder::{build_regex_pattern, CaptureInfo};
+use super::builder::{
+    build_regex_pattern, is_builtin_type, strip_padding, CaptureInfo, CaptureKind,
+};
+use super::cancel::{CancelToken, Scan, ScanStatus};
+use super::custom::{CustomType, ParserBuilder};
+use super::re::{CaptureLocations, Captures, Regex};
+use super::splitter::Splitter;
+use crate::dialect::Dialect;
 use crate::error::{Error, Result};
+use crate::format::Formatter;
 use crate::spec::TypeSpec;
+use crate::template::Template;
 use crate::types::Value;
-use regex::Regex;
+use memchr::memmem;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Shortest literal prefix worth scanning for before running the regex.
+const MIN_PREFILTER_LEN: usize = 3;
 
 /// A parser that extracts structured data from strings using a format pattern.
 ///
+/// # Engines
+///
+/// Patterns are compiled to a regex, which handles every pattern. Simple
+/// patterns, where each field is followed by literal text that can't be part
+/// of the field (or ends the pattern), are also matched by a hand-written
+/// splitter that scans for the literals, which is several times faster.
+/// [`Parser::new`] picks the splitter whenever the pattern qualifies;
+/// [`engine()`](Self::engine) reports the choice and
+/// [`with_engine()`](Self::with_engine) overrides it.
+///
+/// Both engines produce identical results. The splitter mirrors the regex
+/// semantics exactly, including lazy text fields that extend past an early
+/// occurrence of their trailing literal when the rest of the line would
+/// otherwise fail to match. For input it can't decide cheaply (non-ASCII text
+/// or heavy backtracking), it defers to the regex for that call. Only
+/// [`parse`](Self::parse) uses the splitter; [`search`](Self::search) and
+/// [`findall`](Self::findall) always use the regex.
+///
+/// # Sharing
+///
+/// A parser is `Send` and `Sync`, and cloning one is cheap: clones share the
+/// compiled regex and field list rather than copying them, so each worker
+/// thread can be handed its own clone.
+///
 /// # Examples
 ///
 /// ```
@@ -22,229 +63,1433 @@ use std::collections::HashMap;
 /// ```
 #[derive(Debug, Clone)]
 pub struct Parser {
-    #[allow(dead_code)]
-    pattern: String,
+    template: Arc<Template>,
     regex: Regex,
     anchored_regex: Regex,
-    captures: Vec<CaptureInfo>,
+    captures: Arc<[CaptureInfo]>,
+    prefilter: Option<Arc<memmem::Finder<'static>>>, // Literal text every match starts with
+    splitter: Option<Arc<Splitter>>,                 // Regex-free matcher for simple patterns
+    exponent: Option<i32>,                           // Fixed exponent for `e` and `E` fields
+    types: Arc<[CustomType]>,                        // Types registered with `ParserBuilder`
+    skip_anonymous: bool,    // Match positional fields without capturing them
+    dialect: Dialect,        // Features the pattern may use
+    field_order: FieldOrder, // Order results list their fields in
+    names: Arc<[String]>,    // Captured field names, in `field_order`
+    pattern_names: Arc<[String]>, // Captured field names, in pattern order
+    #[cfg(feature = "rust_decimal")]
+    decimals: bool, // Convert float captures to `Value::Decimal`
+}
+
+/// The matching engine used by [`Parser::parse`].
+///
+/// See [Engines](Parser#engines) for how the engine is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Engine {
+    /// Match with the compiled regex
+    Regex,
+    /// Match by scanning for the literal text between fields
+    Splitter,
+}
+
+/// The order [`ParseResult::fields`] lists fields in.
+///
+/// Set with [`Parser::with_field_order`]. Emitters such as
+/// [`ParseResult::to_env`] follow it, so their output is the same from one
+/// run to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum FieldOrder {
+    /// The order the fields appear in the pattern
+    #[default]
+    Pattern,
+    /// Sorted by field name
+    Lexicographic,
+}
+
+/// Reusable match state for [`Parser::parse_into`].
+#[derive(Debug, Clone)]
+pub(crate) struct Locations {
+    regex: CaptureLocations,
+    spans: Vec<(usize, usize)>,
 }
 
 impl Parser {
     /// Create a new parser from a format pattern.
     ///
-    /// The pattern uses the same syntax as formatting, with named or positional fields.
+    /// The pattern uses the same syntax as formatting, with named or positional fields.
+    ///
+    /// Both the anchored regex used by [`parse`](Self::parse) and the
+    /// unanchored one used by [`search`](Self::search) are compiled here,
+    /// so build a parser once and reuse it rather than creating one per
+    /// input.
+    ///
+    /// A spec written between slashes, as in `{token:/[A-Z]{3}-\d+/}`, is
+    /// an inline regex: the field matches it and is captured as a string.
+    /// Braces in the regex must be balanced, as they are in repetitions.
+    ///
+    /// A name used more than once, as in `<{tag}>{body}</{tag}>`, must match
+    /// the same text each time, like a back-reference. Every use needs the
+    /// same spec. The check runs after the regex matches, so the regex
+    /// doesn't backtrack to find a different split of the text where the
+    /// uses agree.
+    ///
+    /// An empty pattern is allowed and matches only empty text:
+    /// [`parse`](Self::parse) accepts `""` alone, [`search`](Self::search)
+    /// finds an empty match at the start of any text, and
+    /// [`findall`](Self::findall) finds nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// // Named fields
+    /// let parser = Parser::new("{name} = {value:d}").unwrap();
+    ///
+    /// // With format specifications
+    /// let parser = Parser::new("{date} {time} {level}").unwrap();
+    ///
+    /// // An empty pattern
+    /// let empty = Parser::new("").unwrap();
+    /// assert!(empty.parse("").unwrap().is_some());
+    /// assert!(empty.parse("text").unwrap().is_none());
+    /// assert_eq!(empty.findall("text").count(), 0);
+    /// ```
+    pub fn new(pattern: &str) -> Result<Self> {
+        Self::new_with_dialect(pattern, Dialect::default())
+    }
+
+    /// Create a new parser from a pattern that may only use the features of
+    /// `dialect`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::dialect::Dialect;
+    /// use gullwing::Parser;
+    ///
+    /// assert!(Parser::new_with_dialect("{id:d} {name}", Dialect::Python312).is_ok());
+    /// assert!(Parser::new_with_dialect(r"{id:/\d+/}", Dialect::Python312).is_err());
+    /// ```
+    pub fn new_with_dialect(pattern: &str, dialect: Dialect) -> Result<Self> {
+        span!(DEBUG, "compile", kind = "parser", pattern);
+        let template = Template::parse_with_types(pattern, &is_builtin_type)?;
+        Self::compile(Arc::new(template), None, Arc::new([]), false, dialect)
+    }
+
+    /// Create a builder for a parser with custom types.
+    ///
+    /// See [`ParserBuilder`] for an example.
+    pub fn builder() -> ParserBuilder {
+        ParserBuilder::default()
+    }
+
+    /// Build the matchers for a template, with `e` and `E` fields pinned to
+    /// `exponent` if set, custom type fields matched by `types` and
+    /// positional fields left out of results if `skip_anonymous` is set.
+    /// Fails if the template uses features outside `dialect`.
+    pub(crate) fn compile(
+        template: Arc<Template>,
+        exponent: Option<i32>,
+        types: Arc<[CustomType]>,
+        skip_anonymous: bool,
+        dialect: Dialect,
+    ) -> Result<Self> {
+        dialect.check(&template)?;
+        let (regex_pattern, captures) =
+            build_regex_pattern(&template, exponent, &types, skip_anonymous)?;
+
+        let regex = Regex::new(&regex_pattern).map_err(|e| Error::RegexError {
+            message: format!("failed to compile regex: {}", e),
+        })?;
+
+        let anchored_pattern = format!("^{}$", regex_pattern);
+        let anchored_regex = Regex::new(&anchored_pattern).map_err(|e| Error::RegexError {
+            message: format!("failed to compile anchored regex: {}", e),
+        })?;
+
+        let literals = template.literals();
+        let prefilter = (literals[0].len() >= MIN_PREFILTER_LEN)
+            .then(|| Arc::new(memmem::Finder::new(literals[0].as_bytes()).into_owned()));
+        // The splitter doesn't check exponents
+        let pinned = exponent.is_some()
+            && captures.iter().any(|info| {
+                matches!(
+                    info.spec.type_spec,
+                    Some(TypeSpec::ExponentLower | TypeSpec::ExponentUpper)
+                )
+            });
+        let splitter = if pinned {
+            None
+        } else {
+            Splitter::new(&literals, &captures).ok().map(Arc::new)
+        };
+
+        let pattern_names = field_names(&captures, FieldOrder::Pattern);
+        let parser = Parser {
+            template,
+            regex,
+            anchored_regex,
+            captures: captures.into(),
+            prefilter,
+            splitter,
+            exponent,
+            types,
+            skip_anonymous,
+            dialect,
+            field_order: FieldOrder::Pattern,
+            names: Arc::clone(&pattern_names),
+            pattern_names,
+            #[cfg(feature = "rust_decimal")]
+            decimals: false,
+        };
+        event!(DEBUG, engine = ?parser.engine(), "engine selected");
+        Ok(parser)
+    }
+
+    /// Get the pattern this parser was created from.
+    ///
+    /// The pattern is also what the parser's [`Display`](fmt::Display) prints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{key}={value:d}").unwrap();
+    /// assert_eq!(parser.pattern(), "{key}={value:d}");
+    /// assert_eq!(parser.to_string(), "{key}={value:d}");
+    /// ```
+    pub fn pattern(&self) -> &str {
+        self.template.pattern()
+    }
+
+    /// Get the parsed pattern.
+    ///
+    /// [`Formatter::template`](crate::Formatter::template) returns the same
+    /// [`Template`] for the same pattern, so each capture has the spec the
+    /// formatter would use for that field.
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
+    /// Get the dialect the pattern was checked against.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Get the number of fields the pattern captures.
+    ///
+    /// Fields skipped with
+    /// [`with_anonymous_skipped`](Self::with_anonymous_skipped) aren't counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// assert_eq!(Parser::new("{key}={value:d}").unwrap().capture_count(), 2);
+    /// assert_eq!(Parser::new("no fields").unwrap().capture_count(), 0);
+    /// ```
+    pub fn capture_count(&self) -> usize {
+        self.captures.iter().filter(|info| !info.skip).count()
+    }
+
+    /// Get the literal text every match starts with.
+    ///
+    /// This is the text before the first field, with escaped braces
+    /// unescaped, or the whole pattern if it has no fields. Text that doesn't
+    /// contain the prefix can't match, so it can be skipped without running
+    /// the regex.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{{ERROR}} [{code:d}] {message}.").unwrap();
+    /// assert_eq!(parser.literal_prefix(), "{ERROR} [");
+    /// assert_eq!(parser.literal_suffix(), ".");
+    /// assert!(parser.is_anchored());
+    ///
+    /// let parser = Parser::new("{key}={value}").unwrap();
+    /// assert_eq!(parser.literal_prefix(), "");
+    /// assert!(!parser.is_anchored());
+    /// ```
+    pub fn literal_prefix(&self) -> &str {
+        match self.template.fields().first() {
+            Some(field) => field.prefix(),
+            None => self.template.tail(),
+        }
+    }
+
+    /// Get the literal text every match ends with.
+    ///
+    /// This is the text after the last field, with escaped braces unescaped,
+    /// or the whole pattern if it has no fields.
+    pub fn literal_suffix(&self) -> &str {
+        self.template.tail()
+    }
+
+    /// Check whether the pattern starts and ends with literal text.
+    ///
+    /// Matches of an anchored pattern are delimited by its
+    /// [`literal_prefix`](Self::literal_prefix) and
+    /// [`literal_suffix`](Self::literal_suffix), so [`search`](Self::search)
+    /// finds the same text whatever surrounds it. A field at either end
+    /// instead stretches as far as its type allows.
+    pub fn is_anchored(&self) -> bool {
+        !self.literal_prefix().is_empty() && !self.literal_suffix().is_empty()
+    }
+
+    /// Get the engine [`parse`](Self::parse) uses for this pattern.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::parse::Engine;
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{date} {level} [{thread:d}] {message}").unwrap();
+    /// assert_eq!(parser.engine(), Engine::Splitter);
+    ///
+    /// // Adjacent fields need the regex to decide where one ends
+    /// let parser = Parser::new("{a}{b}").unwrap();
+    /// assert_eq!(parser.engine(), Engine::Regex);
+    /// ```
+    pub fn engine(&self) -> Engine {
+        match self.splitter {
+            Some(_) => Engine::Splitter,
+            None => Engine::Regex,
+        }
+    }
+
+    /// Return a copy of this parser that uses the given engine.
+    ///
+    /// Forcing [`Engine::Regex`] always succeeds. Forcing
+    /// [`Engine::Splitter`] fails with [`Error::InvalidFormatSpec`]
+    /// explaining why if the pattern doesn't qualify.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::parse::Engine;
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{key}={value:d}").unwrap();
+    /// let regex = parser.with_engine(Engine::Regex).unwrap();
+    /// assert_eq!(regex.engine(), Engine::Regex);
+    /// assert_eq!(
+    ///     regex.parse("x=1").unwrap().unwrap().get("value"),
+    ///     parser.parse("x=1").unwrap().unwrap().get("value"),
+    /// );
+    ///
+    /// let err = Parser::new("{a:>5}|{b}").unwrap().with_engine(Engine::Splitter).unwrap_err();
+    /// assert!(err.to_string().contains("field 'a' has a width"));
+    /// ```
+    pub fn with_engine(&self, engine: Engine) -> Result<Self> {
+        let mut parser = self.clone();
+        parser.splitter = match engine {
+            Engine::Regex => None,
+            Engine::Splitter => match parser.splitter.take() {
+                Some(splitter) => Some(splitter),
+                None => Some(Arc::new(Splitter::new(
+                    &self.template.literals(),
+                    &self.captures,
+                )?)),
+            },
+        };
+        Ok(parser)
+    }
+
+    /// Return a copy of this parser whose `e` and `E` fields require a fixed exponent.
+    ///
+    /// This is the parsing side of
+    /// [`Formatter::with_exponent`](crate::Formatter::with_exponent): a
+    /// number written with any other exponent, or with the other letter case,
+    /// doesn't match. Patterns with such fields always use
+    /// [`Engine::Regex`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Parser, Value};
+    ///
+    /// let parser = Parser::new("{x:e}").unwrap().with_exponent(6).unwrap();
+    /// assert_eq!(parser.exponent(), Some(6));
+    /// assert_eq!(
+    ///     parser.parse("0.0007e+06").unwrap().unwrap().get("x"),
+    ///     Some(&Value::from(700.0))
+    /// );
+    /// assert!(parser.parse("7.0e+02").unwrap().is_none());
+    /// assert!(parser.parse("0.0007E+06").unwrap().is_none());
+    /// ```
+    pub fn with_exponent(&self, exponent: i32) -> Result<Self> {
+        self.recompile(Some(exponent), self.skip_anonymous)
+    }
+
+    /// Get the fixed exponent for `e` and `E` fields, if one was set with
+    /// [`with_exponent`](Self::with_exponent).
+    pub fn exponent(&self) -> Option<i32> {
+        self.exponent
+    }
+
+    /// Return a copy of this parser that matches positional fields without
+    /// capturing them.
+    ///
+    /// Fields like `{}` and `{:d}` still have to match, with the same regex
+    /// as before, but get a non-capturing group and are left out of results,
+    /// so ignored columns cost no conversion and add no `_0`, `_1` keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{} {:d} {status:d} {}").unwrap();
+    /// let parser = parser.with_anonymous_skipped(true).unwrap();
+    /// assert_eq!(parser.capture_count(), 1);
+    ///
+    /// let result = parser.parse("GET 512 404 /index.html").unwrap().unwrap();
+    /// assert_eq!(result.get("status").unwrap().as_int(), Some(404));
+    /// assert!(result.get("_0").is_none());
+    ///
+    /// // Skipped fields are still type-checked
+    /// assert!(parser.parse("GET many 404 /index.html").unwrap().is_none());
+    /// ```
+    pub fn with_anonymous_skipped(&self, enabled: bool) -> Result<Self> {
+        self.recompile(self.exponent, enabled)
+    }
+
+    /// Check whether positional fields are matched without being captured.
+    pub fn anonymous_skipped(&self) -> bool {
+        self.skip_anonymous
+    }
+
+    /// Compile this parser's template again with other settings, keeping
+    /// the engine choice and conversions.
+    fn recompile(&self, exponent: Option<i32>, skip_anonymous: bool) -> Result<Self> {
+        span!(DEBUG, "compile", kind = "parser", pattern = self.pattern());
+        let mut parser = Self::compile(
+            Arc::clone(&self.template),
+            exponent,
+            Arc::clone(&self.types),
+            skip_anonymous,
+            self.dialect,
+        )?;
+        if self.splitter.is_none() {
+            parser.splitter = None;
+        }
+        parser = parser.with_field_order(self.field_order);
+        #[cfg(feature = "rust_decimal")]
+        {
+            parser.decimals = self.decimals;
+        }
+        Ok(parser)
+    }
+
+    /// Return a copy of this parser whose results list their fields in `order`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::parse::FieldOrder;
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{user} {action} {at}").unwrap();
+    /// let result = parser.parse("alice login 09:30").unwrap().unwrap();
+    /// let names: Vec<_> = result.fields().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["user", "action", "at"]);
+    ///
+    /// let parser = parser.with_field_order(FieldOrder::Lexicographic);
+    /// let result = parser.parse("alice login 09:30").unwrap().unwrap();
+    /// let names: Vec<_> = result.fields().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["action", "at", "user"]);
+    /// ```
+    pub fn with_field_order(&self, order: FieldOrder) -> Self {
+        let mut parser = self.clone();
+        parser.field_order = order;
+        parser.names = match order {
+            FieldOrder::Pattern => Arc::clone(&self.pattern_names),
+            _ => field_names(&self.captures, order),
+        };
+        parser
+    }
+
+    /// Get the order results list their fields in.
+    pub fn field_order(&self) -> FieldOrder {
+        self.field_order
+    }
+
+    /// Return a copy of this parser that converts float captures exactly.
+    ///
+    /// With decimals enabled, captures with the `f`, `F`, `e`, `E`, `g`, `G`
+    /// and `%` types become [`Value::Decimal`] instead of going through
+    /// `f64`, so `19.99` parses back to exactly the decimal that was
+    /// formatted. Text a decimal can't hold, such as more than 28
+    /// fractional digits, fails to convert.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Parser, Value};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let parser = Parser::new("{price:.2f} ({change:+.1%})").unwrap().with_decimals(true);
+    /// let result = parser.parse("19.99 (+2.5%)").unwrap().unwrap();
+    /// assert_eq!(result.get("price"), Some(&Value::Decimal(Decimal::new(1999, 2))));
+    /// assert_eq!(result.get("change"), Some(&Value::Decimal(Decimal::new(25, 3))));
+    /// ```
+    #[cfg(feature = "rust_decimal")]
+    pub fn with_decimals(&self, enabled: bool) -> Self {
+        let mut parser = self.clone();
+        parser.decimals = enabled;
+        parser
+    }
+
+    /// Check whether float captures convert to [`Value::Decimal`].
+    fn decimals(&self) -> bool {
+        #[cfg(feature = "rust_decimal")]
+        return self.decimals;
+        #[cfg(not(feature = "rust_decimal"))]
+        false
+    }
+
+    /// Get the names of the captured fields, in the parser's [`FieldOrder`].
+    #[cfg(feature = "csv")]
+    pub(crate) fn field_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Parse a string, matching it exactly against the pattern.
+    ///
+    /// Returns `Ok(Some(result))` if the string matches, `Ok(None)` if it doesn't match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{x:d} + {y:d}").unwrap();
+    /// let result = parser.parse("2 + 3").unwrap().unwrap();
+    ///
+    /// assert_eq!(result.get("x").unwrap().as_int(), Some(2));
+    /// assert_eq!(result.get("y").unwrap().as_int(), Some(3));
+    /// ```
+    pub fn parse(&self, text: &str) -> Result<Option<ParseResult>> {
+        span!(TRACE, "parse", method = "parse");
+        let mut locs = self.capture_locations();
+        if !self.match_spans(text, &mut locs) {
+            return Ok(None);
+        }
+
+        let mut values = HashMap::with_capacity(self.captures.len());
+        let mut spans = HashMap::with_capacity(self.captures.len());
+        for (info, &(start, end)) in self.captures.iter().zip(&locs.spans) {
+            if info.skip {
+                continue;
+            }
+            values.insert(info.name.clone(), self.convert(info, &text[start..end])?);
+            spans.insert(info.name.clone(), start..end);
+        }
+
+        Ok(Some(ParseResult {
+            values,
+            spans,
+            span: 0..text.len(),
+            text: text.to_string(),
+            names: Arc::clone(&self.names),
+            pattern_names: Arc::clone(&self.pattern_names),
+        }))
+    }
+
+    /// Search for the pattern within a string.
+    ///
+    /// Returns the first match found, or `None` if no match is found. If the
+    /// pattern starts with literal text, a SIMD substring scan skips to the
+    /// first occurrence of that text before the regex runs, so large inputs
+    /// with rare matches are cheap to search.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{number:d}").unwrap();
+    /// let result = parser.search("The answer is 42!").unwrap().unwrap();
+    ///
+    /// assert_eq!(result.get("number").unwrap().as_int(), Some(42));
+    /// ```
+    pub fn search(&self, text: &str) -> Result<Option<ParseResult>> {
+        span!(TRACE, "parse", method = "search");
+        let mut from = 0;
+        while let Some(start) = self.next_candidate(text, from) {
+            let Some(cap) = self.regex.captures_at(text, start) else {
+                break;
+            };
+            if self.repeats_agree_in(text, &cap) {
+                return self.result_from(text, &cap).map(Some);
+            }
+            // Try again from just after where the rejected match started
+            match next_start(text, &cap) {
+                Some(next) => from = next,
+                None => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find all occurrences of the pattern in a string.
+    ///
+    /// Returns a lazy iterator over all non-overlapping matches: each match
+    /// is found and converted only when the iterator is advanced, so
+    /// scanning a large input holds one result at a time. A match whose
+    /// fields fail to convert yields an error, and the scan carries on after
+    /// it. Empty matches are never reported, so an empty pattern finds
+    /// nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{number:d}").unwrap();
+    /// let results: Vec<_> = parser
+    ///     .findall("Numbers: 1, 2, 3")
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(results.len(), 3);
+    /// assert_eq!(results[0].get("number").unwrap().as_int(), Some(1));
+    /// assert_eq!(results[1].get("number").unwrap().as_int(), Some(2));
+    /// assert_eq!(results[2].get("number").unwrap().as_int(), Some(3));
+    /// ```
+    pub fn findall<'t>(&self, text: &'t str) -> FindAll<'_, 't> {
+        let state = if self.pattern().is_empty() {
+            // Every position would be an empty match
+            FindState::Done
+        } else {
+            FindState::Searching { pos: 0 }
+        };
+        FindAll {
+            parser: self,
+            text,
+            state,
+        }
+    }
+
+    /// Find all occurrences of the pattern, stopping early if `cancel` fires.
+    ///
+    /// Like [`findall`](Self::findall), but the token is checked before
+    /// searching for each match. A stopped scan still returns the matches
+    /// found so far, with [`ScanStatus::Cancelled`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::parse::{CancelToken, ScanStatus};
+    /// use gullwing::Parser;
+    /// use std::time::Duration;
+    ///
+    /// let parser = Parser::new("ERROR {msg}\n").unwrap();
+    /// let token = CancelToken::new().with_timeout(Duration::from_secs(5));
+    /// let scan = parser.findall_cancellable("ERROR disk full\n", &token).unwrap();
+    ///
+    /// assert_eq!(scan.status, ScanStatus::Complete);
+    /// assert_eq!(scan.results[0].get("msg").unwrap().as_str(), Some("disk full"));
+    /// ```
+    pub fn findall_cancellable(&self, text: &str, cancel: &CancelToken) -> Result<Scan> {
+        let (results, cancelled) = self.scan(text, || cancel.is_cancelled())?;
+        let status = if cancelled {
+            ScanStatus::Cancelled
+        } else {
+            ScanStatus::Complete
+        };
+        Ok(Scan { results, status })
+    }
+
+    /// Rewrite the first match in `text` with `formatter`, fed the parsed fields.
+    ///
+    /// The rest of the text is copied unchanged; see
+    /// [`replace_all`](Self::replace_all).
+    pub fn replace(&self, text: &str, formatter: &Formatter) -> Result<String> {
+        self.replace_n(text, formatter, 1)
+    }
+
+    /// Rewrite every match in `text` with `formatter`, fed the parsed fields.
+    ///
+    /// Matches are found like [`findall`](Self::findall) and text between
+    /// them is copied unchanged. The formatter's fields are looked up by
+    /// name, so positional captures are `{_0}`, `{_1}`, ...; a named field
+    /// the parser doesn't capture fails with [`Error::MissingField`] before
+    /// any matching. A match whose fields fail to convert fails the whole
+    /// call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Parser};
+    ///
+    /// let parser = Parser::new("{amount:d} {currency:/[A-Z]{3}/}").unwrap();
+    /// let formatter = Formatter::new("{currency} {amount:,d}").unwrap();
+    ///
+    /// let text = "Paid 1200 EUR, owed 35000 USD.";
+    /// assert_eq!(
+    ///     parser.replace_all(text, &formatter).unwrap(),
+    ///     "Paid EUR 1,200, owed USD 35,000."
+    /// );
+    /// assert_eq!(
+    ///     parser.replace(text, &formatter).unwrap(),
+    ///     "Paid EUR 1,200, owed 35000 USD."
+    /// );
+    /// ```
+    pub fn replace_all(&self, text: &str, formatter: &Formatter) -> Result<String> {
+        self.replace_n(text, formatter, usize::MAX)
+    }
+
+    /// Rewrite up to `limit` matches in `text` with `formatter`.
+    fn replace_n(&self, text: &str, formatter: &Formatter, limit: usize) -> Result<String> {
+        for name in formatter.slot_names() {
+            if !self.capture_names().any(|capture| capture == Some(name)) {
+                return Err(Error::missing(name));
+            }
+        }
+
+        let mut out = String::with_capacity(text.len());
+        let mut values = HashMap::with_capacity(self.captures.len());
+        let mut last = 0;
+        let mut matches = self.findall(text);
+        for _ in 0..limit {
+            let Some(cap) = matches.next_match() else {
+                break;
+            };
+            let span = cap.get(0).map_or(0..0, |m| m.range());
+            values.clear();
+            self.values_from(&cap, &mut values, None)?;
+            out.push_str(&text[last..span.start]);
+            formatter.format_into(&mut out, &values)?;
+            last = span.end;
+        }
+        out.push_str(&text[last..]);
+        Ok(out)
+    }
+
+    /// Collect all non-overlapping matches, checking `cancelled` before each
+    /// search. Returns the matches and whether the scan was stopped.
+    fn scan<F>(&self, text: &str, mut cancelled: F) -> Result<(Vec<ParseResult>, bool)>
+    where
+        F: FnMut() -> bool,
+    {
+        let mut results = Vec::new();
+        let mut matches = self.findall(text);
+        loop {
+            if cancelled() {
+                return Ok((results, true));
+            }
+            match matches.next() {
+                Some(result) => results.push(result?),
+                None => return Ok((results, false)),
+            }
+        }
+    }
+
+    /// Find the first position at or after `from` where a match could start.
+    ///
+    /// Without a prefilter every position is a candidate, so this is `from`.
+    fn next_candidate(&self, text: &str, from: usize) -> Option<usize> {
+        match &self.prefilter {
+            Some(finder) => finder
+                .find(&text.as_bytes()[from..])
+                .map(|offset| from + offset),
+            None => Some(from),
+        }
+    }
+
+    /// Check that every repeated field matched the same text as the first
+    /// field of its name, given the span of each regex group.
+    fn repeats_agree<F>(&self, text: &str, group: F) -> bool
+    where
+        F: Fn(usize) -> Option<(usize, usize)>,
+    {
+        let matched = |index| group(index).map(|(start, end)| &text[start..end]);
+        self.captures.iter().all(|info| {
+            info.repeats
+                .iter()
+                .all(|&repeat| matched(repeat) == matched(info.group_index))
+        })
+    }
+
+    /// Check [`repeats_agree`](Self::repeats_agree) for a regex match.
+    fn repeats_agree_in(&self, text: &str, cap: &Captures) -> bool {
+        self.repeats_agree(text, |index| cap.get(index).map(|m| (m.start(), m.end())))
+    }
+
+    /// Get the regex that matches whole texts, as [`parse`](Self::parse) does.
+    #[cfg(feature = "regex")]
+    pub(crate) fn anchored_pattern(&self) -> &str {
+        self.anchored_regex.as_str()
+    }
+
+    /// Get the names of the fields, in capture order, or `None` for skipped fields.
+    pub(crate) fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.captures
+            .iter()
+            .map(|info| (!info.skip).then_some(info.name.as_str()))
+    }
+
+    /// Create reusable capture locations for [`parse_into`](Self::parse_into).
+    pub(crate) fn capture_locations(&self) -> Locations {
+        Locations {
+            regex: self.anchored_regex.capture_locations(),
+            spans: Vec::with_capacity(self.captures.len()),
+        }
+    }
+
+    /// Match `text` exactly, storing the span of each capture in `locs.spans`.
+    ///
+    /// Simple patterns are matched by the splitter, falling back to the
+    /// anchored regex when it can't decide.
+    fn match_spans(&self, text: &str, locs: &mut Locations) -> bool {
+        // Most lines fed to a parser that doesn't fit them fail on the literal
+        // ends, which is far cheaper to check than running the matcher
+        if !text.starts_with(self.literal_prefix()) || !text.ends_with(self.literal_suffix()) {
+            return false;
+        }
+
+        if let Some(splitter) = &self.splitter {
+            if let Some(matched) = splitter.split(text, &mut locs.spans) {
+                return matched;
+            }
+            event!(TRACE, "splitter undecided; matching with the regex");
+        }
+
+        if self
+            .anchored_regex
+            .captures_read(&mut locs.regex, text)
+            .is_none()
+            || !self.repeats_agree(text, |group| locs.regex.get(group))
+        {
+            return false;
+        }
+        locs.spans.clear();
+        // Skipped fields have no group; their spans are never read
+        locs.spans.extend(self.captures.iter().map(|info| {
+            (!info.skip)
+                .then(|| locs.regex.get(info.group_index))
+                .flatten()
+                .unwrap_or((0, 0))
+        }));
+        true
+    }
+
+    /// Match `text` exactly and convert the captures into `values`, in capture order.
+    ///
+    /// Existing values are overwritten in place so string buffers are reused
+    /// across calls. Returns `false` if the text doesn't match.
+    pub(crate) fn parse_into(
+        &self,
+        text: &str,
+        locs: &mut Locations,
+        values: &mut Vec<Value>,
+    ) -> Result<bool> {
+        span!(TRACE, "parse", method = "transform");
+        if !self.match_spans(text, locs) {
+            return Ok(false);
+        }
+
+        values.resize_with(self.captures.len(), || Value::Bool(false));
+        for ((info, slot), &(start, end)) in
+            self.captures.iter().zip(values.iter_mut()).zip(&locs.spans)
+        {
+            if !info.skip {
+                self.convert_into(info, &text[start..end], slot)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Convert the text captured for a field.
+    fn convert(&self, info: &CaptureInfo, text: &str) -> Result<Value> {
+        let mut value = Value::Bool(false);
+        self.convert_into(info, text, &mut value)?;
+        Ok(value)
+    }
+
+    /// Convert the text captured for a field into `slot`, reusing its buffer
+    /// where possible.
+    fn convert_into(&self, info: &CaptureInfo, text: &str, slot: &mut Value) -> Result<()> {
+        match info.kind {
+            CaptureKind::Custom(i) => {
+                *slot = self.types[i].convert(text)?;
+                Ok(())
+            }
+            #[cfg(feature = "chrono")]
+            CaptureKind::DateTime(ty) => {
+                *slot = ty.convert(text)?;
+                Ok(())
+            }
+            CaptureKind::Spec => {
+                let text = strip_padding(text, &info.spec);
+                convert_value_into(&text, &info.spec, self.decimals(), slot)
+            }
+            // Inline regex and choice fields have a default spec, so they
+            // stay strings
+            CaptureKind::Regex | CaptureKind::Choice => {
+                convert_value_into(text, &info.spec, self.decimals(), slot)
+            }
+        }
+    }
+
+    /// Extract and convert captured values.
+    fn result_from(&self, text: &str, cap: &Captures) -> Result<ParseResult> {
+        let mut values = HashMap::new();
+        let mut spans = HashMap::new();
+        self.values_from(cap, &mut values, Some(&mut spans))?;
+
+        Ok(ParseResult {
+            values,
+            spans,
+            span: cap.get(0).map_or(0..0, |m| m.range()),
+            text: text.to_string(),
+            names: Arc::clone(&self.names),
+            pattern_names: Arc::clone(&self.pattern_names),
+        })
+    }
+
+    /// Convert the captured fields of a regex match into `values`, and
+    /// record their spans in `spans` if given.
+    fn values_from(
+        &self,
+        cap: &Captures,
+        values: &mut HashMap<String, Value>,
+        mut spans: Option<&mut HashMap<String, Range<usize>>>,
+    ) -> Result<()> {
+        for info in self.captures.iter().filter(|info| !info.skip) {
+            if let Some(matched) = cap.get(info.group_index) {
+                let value = self.convert(info, matched.as_str())?;
+                values.insert(info.name.clone(), value);
+                if let Some(spans) = spans.as_deref_mut() {
+                    spans.insert(info.name.clone(), matched.range());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsers are equal when their patterns compile to the same matcher.
+///
+/// Patterns are compared after parsing, so `{x}` and `{x:}` compare equal.
+/// The engine isn't compared, since both engines give the same results.
+/// Whether float captures convert to decimals is, and so are the custom
+/// types, with their converters compared by identity.
+impl PartialEq for Parser {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+            && self.captures == other.captures
+            && self.types == other.types
+            && self.field_order == other.field_order
+            && self.decimals() == other.decimals()
+    }
+}
+
+impl Eq for Parser {}
+
+impl Hash for Parser {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.regex.as_str().hash(state);
+        self.captures.hash(state);
+        self.types.hash(state);
+        self.field_order.hash(state);
+        self.decimals().hash(state);
+    }
+}
+
+impl fmt::Display for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.pattern())
+    }
+}
+
+/// A lazy iterator over the matches of a [`Parser`] in a string.
+///
+/// Returned by [`Parser::findall`].
+#[derive(Debug)]
+pub struct FindAll<'p, 't> {
+    parser: &'p Parser,
+    text: &'t str,
+    state: FindState,
+}
+
+#[derive(Debug)]
+enum FindState {
+    /// Search from `pos`, skipping ahead with the prefilter if there is one
+    Searching {
+        pos: usize,
+    },
+    Done,
+}
+
+impl<'t> FindAll<'_, 't> {
+    /// Find the next match without converting its fields.
+    fn next_match(&mut self) -> Option<Captures<'t>> {
+        let (parser, text) = (self.parser, self.text);
+        let cap = match &mut self.state {
+            FindState::Searching { pos } => loop {
+                let Some(cap) = parser
+                    .next_candidate(text, *pos)
+                    .and_then(|start| parser.regex.captures_at(text, start))
+                else {
+                    break None;
+                };
+                // Inline regexes such as `{x:/a*/}` can match empty text
+                let whole = cap.get(0).filter(|m| !m.is_empty());
+                if let Some(whole) = whole.filter(|_| parser.repeats_agree_in(text, &cap)) {
+                    *pos = whole.end();
+                    break Some(cap);
+                }
+                // Try again from just after where the rejected match started
+                match next_start(text, &cap) {
+                    Some(next) => *pos = next,
+                    None => break None,
+                }
+            },
+            FindState::Done => None,
+        };
+        if cap.is_none() {
+            self.state = FindState::Done;
+        }
+        cap
+    }
+}
+
+impl Iterator for FindAll<'_, '_> {
+    type Item = Result<ParseResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        span!(TRACE, "parse", method = "findall");
+        let cap = self.next_match()?;
+        Some(self.parser.result_from(self.text, &cap))
+    }
+}
+
+impl std::iter::FusedIterator for FindAll<'_, '_> {}
+
+/// Result of parsing a string.
+///
+/// Contains the extracted values as a map from field names to values.
+#[derive(Debug, Clone)]
+pub struct ParseResult {
+    values: HashMap<String, Value>,
+    spans: HashMap<String, Range<usize>>, // Byte range of each field in `text`
+    span: Range<usize>,                   // Byte range of the whole match in `text`
+    text: String,
+    names: Arc<[String]>,         // Field names in the parser's field order
+    pattern_names: Arc<[String]>, // Field names in pattern order
+}
+
+/// Get the position one character after the start of a match, or `None` at
+/// the end of `text`.
+fn next_start(text: &str, cap: &Captures) -> Option<usize> {
+    let start = cap.get(0)?.start();
+    text[start..].chars().next().map(|c| start + c.len_utf8())
+}
+
+/// Get the names of the captured fields, in `order`.
+fn field_names(captures: &[CaptureInfo], order: FieldOrder) -> Arc<[String]> {
+    let mut names: Vec<_> = captures
+        .iter()
+        .filter(|info| !info.skip)
+        .map(|info| info.name.clone())
+        .collect();
+    if order == FieldOrder::Lexicographic {
+        names.sort_unstable();
+    }
+    names.into()
+}
+
+/// Get the index of a positional field from its `_<index>` capture name.
+fn positional_index(name: &str) -> Option<usize> {
+    let digits = name.strip_prefix('_')?;
+    let index: usize = digits.parse().ok()?;
+    // `_01` is a name someone chose, not a position
+    (digits == index.to_string()).then_some(index)
+}
+
+impl ParseResult {
+    /// Get a value by field name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{name}").unwrap();
+    /// let result = parser.parse("Alice").unwrap().unwrap();
+    ///
+    /// assert_eq!(result.get("name").unwrap().as_str(), Some("Alice"));
+    /// ```
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// Get the value of a positional field by index.
+    ///
+    /// Positional fields (`{}` or `{0:d}`) are stored under the names `_0`,
+    /// `_1`, ...; this looks them up like indexing a Python `parse` result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{} is {:d}").unwrap();
+    /// let result = parser.parse("answer is 42").unwrap().unwrap();
+    ///
+    /// assert_eq!(result.get_index(0).unwrap().as_str(), Some("answer"));
+    /// assert_eq!(result.get_index(1).unwrap().as_int(), Some(42));
+    /// assert!(result.get_index(2).is_none());
+    /// ```
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        self.values.get(&format!("_{}", index))
+    }
+
+    /// Get the values of all positional fields, ordered by index.
+    ///
+    /// Like `fixed` on a Python `parse` result, named fields are left out.
+    pub fn fixed(&self) -> Vec<&Value> {
+        let mut fixed: Vec<_> = self
+            .values
+            .iter()
+            .filter_map(|(name, value)| Some((positional_index(name)?, value)))
+            .collect();
+        fixed.sort_unstable_by_key(|&(index, _)| index);
+        fixed.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Get all values as a HashMap.
+    ///
+    /// The map iterates in no particular order; [`fields`](Self::fields)
+    /// and [`iter_ordered`](Self::iter_ordered) list the same values in a
+    /// stable one.
+    pub fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+
+    /// Iterate over the fields and their values, in the parser's
+    /// [`FieldOrder`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Parser;
+    ///
+    /// let parser = Parser::new("{b}={a:d}").unwrap();
+    /// let result = parser.parse("x=1").unwrap().unwrap();
+    /// let fields: Vec<_> = result.fields().map(|(name, value)| (name, value.to_string())).collect();
+    /// assert_eq!(fields, [("b", "x".to_string()), ("a", "1".to_string())]);
+    /// ```
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> + '_ {
+        self.names
+            .iter()
+            .filter_map(|name| Some((name.as_str(), self.values.get(name)?)))
+    }
+
+    /// Iterate over the fields and their values in the order the fields
+    /// appear in the pattern, whatever the parser's [`FieldOrder`].
+    ///
+    /// A name used more than once comes where it first appears; skipped
+    /// positional fields aren't listed.
     ///
     /// # Examples
     ///
     /// ```
+    /// use gullwing::parse::FieldOrder;
     /// use gullwing::Parser;
     ///
-    /// // Named fields
-    /// let parser = Parser::new("{name} = {value:d}").unwrap();
-    ///
-    /// // With format specifications
-    /// let parser = Parser::new("{date} {time} {level}").unwrap();
+    /// let parser = Parser::new("{b}={a:d}").unwrap().with_field_order(FieldOrder::Lexicographic);
+    /// let result = parser.parse("x=1").unwrap().unwrap();
+    /// let names: Vec<_> = result.iter_ordered().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["b", "a"]);
     /// ```
-    pub fn new(pattern: &str) -> Result<Self> {
-        let (regex_pattern, captures) = build_regex_pattern(pattern)?;
+    pub fn iter_ordered(&self) -> impl Iterator<Item = (&str, &Value)> + '_ {
+        self.pattern_names
+            .iter()
+            .filter_map(|name| Some((name.as_str(), self.values.get(name)?)))
+    }
 
-        let regex = Regex::new(&regex_pattern)
-            .map_err(|e| Error::RegexError(format!("failed to compile regex: {}", e)))?;
+    /// Create a result from fields found in `text` by other means than a
+    /// single parser, such as the cells of a CSV record.
+    #[cfg(feature = "csv")]
+    pub(crate) fn from_parts(
+        text: String,
+        values: HashMap<String, Value>,
+        spans: HashMap<String, Range<usize>>,
+        names: Arc<[String]>,
+    ) -> Self {
+        ParseResult {
+            values,
+            spans,
+            span: 0..text.len(),
+            text,
+            pattern_names: Arc::clone(&names),
+            names,
+        }
+    }
 
-        let anchored_pattern = format!("^{}$", regex_pattern);
-        let anchored_regex = Regex::new(&anchored_pattern)
-            .map_err(|e| Error::RegexError(format!("failed to compile anchored regex: {}", e)))?;
+    /// Take the values and spans out of the result.
+    #[cfg(feature = "csv")]
+    pub(crate) fn into_parts(self) -> (HashMap<String, Value>, HashMap<String, Range<usize>>) {
+        (self.values, self.spans)
+    }
 
-        Ok(Parser {
-            pattern: pattern.to_string(),
-            regex,
-            anchored_regex,
-            captures,
-        })
+    /// Get the original text that was parsed.
+    pub fn text(&self) -> &str {
+        &self.text
     }
 
-    /// Parse a string, matching it exactly against the pattern.
+    /// Check if a field exists in the result.
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Get the byte range a field was captured from in [`text()`](Self::text).
     ///
-    /// Returns `Ok(Some(result))` if the string matches, `Ok(None)` if it doesn't match.
+    /// Like the `spans` of a Python `parse` result, this lets tools
+    /// highlight or replace a field in the original text.
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::Parser;
     ///
-    /// let parser = Parser::new("{x:d} + {y:d}").unwrap();
-    /// let result = parser.parse("2 + 3").unwrap().unwrap();
+    /// let parser = Parser::new("port={port:d}").unwrap();
+    /// let result = parser.search("listen on port=8080 now").unwrap().unwrap();
     ///
-    /// assert_eq!(result.get("x").unwrap().as_int(), Some(2));
-    /// assert_eq!(result.get("y").unwrap().as_int(), Some(3));
+    /// let span = result.span("port").unwrap();
+    /// assert_eq!(span, 15..19);
+    /// assert_eq!(&result.text()[span], "8080");
+    /// assert_eq!(result.match_span(), 10..19);
     /// ```
-    pub fn parse(&self, text: &str) -> Result<Option<ParseResult>> {
-        if let Some(cap) = self.anchored_regex.captures(text) {
-            let values = self.extract_values(&cap)?;
-            Ok(Some(ParseResult {
-                values,
-                text: text.to_string(),
-            }))
-        } else {
-            Ok(None)
-        }
+    pub fn span(&self, name: &str) -> Option<Range<usize>> {
+        self.spans.get(name).cloned()
     }
 
-    /// Search for the pattern within a string.
+    /// Get the byte ranges of all fields, by name.
+    pub fn spans(&self) -> &HashMap<String, Range<usize>> {
+        &self.spans
+    }
+
+    /// Get the byte range of the whole match in [`text()`](Self::text).
     ///
-    /// Returns the first match found, or `None` if no match is found.
+    /// For [`Parser::parse`] this is all of the text; for
+    /// [`search`](Parser::search) and [`findall`](Parser::findall) it is the
+    /// part that matched.
+    pub fn match_span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Render the fields as shell variable assignments, for `eval` in a script.
+    ///
+    /// Fields come in the parser's [`FieldOrder`], separated by `; `.
+    /// Strings are always single-quoted and other values are quoted when
+    /// they contain anything but letters, digits, `.`, `+` and `-`, so the
+    /// output is safe to `eval` whatever the text held. Characters that
+    /// can't appear in a shell variable name become `_`.
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::Parser;
     ///
-    /// let parser = Parser::new("{number:d}").unwrap();
-    /// let result = parser.search("The answer is 42!").unwrap().unwrap();
+    /// let parser = Parser::new("{name} is {age:d}").unwrap();
+    /// let result = parser.parse("O'Brien is 30").unwrap().unwrap();
     ///
-    /// assert_eq!(result.get("number").unwrap().as_int(), Some(42));
+    /// assert_eq!(result.to_env(), r"name='O'\''Brien'; age=30");
     /// ```
-    pub fn search(&self, text: &str) -> Result<Option<ParseResult>> {
-        if let Some(cap) = self.regex.captures(text) {
-            let values = self.extract_values(&cap)?;
-            Ok(Some(ParseResult {
-                values,
-                text: text.to_string(),
-            }))
-        } else {
-            Ok(None)
+    pub fn to_env(&self) -> String {
+        let mut env = String::new();
+        for (name, value) in self.fields() {
+            if !env.is_empty() {
+                env.push_str("; ");
+            }
+            if name.starts_with(|c: char| c.is_ascii_digit()) {
+                env.push('_');
+            }
+            env.extend(name.chars().map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            }));
+            env.push('=');
+            let text = value.to_string();
+            let bare = !matches!(value, Value::Str(_))
+                && !text.is_empty()
+                && text
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-'));
+            if bare {
+                env.push_str(&text);
+            } else {
+                env.push('\'');
+                env.push_str(&text.replace('\'', r"'\''"));
+                env.push('\'');
+            }
         }
+        env
     }
 
-    /// Find all occurrences of the pattern in a string.
+    /// Render the fields as a JSON object on one line, for tools such as `jq`.
     ///
-    /// Returns an iterator over all matches.
+    /// Keys come in the parser's [`FieldOrder`]. Values convert as with
+    /// `serde_json::Value::from`, so numbers stay numbers and non-finite
+    /// floats become `null`. Needs the `json` feature.
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::Parser;
     ///
-    /// let parser = Parser::new("{number:d}").unwrap();
-    /// let results: Vec<_> = parser.findall("Numbers: 1, 2, 3").unwrap().collect();
+    /// let parser = Parser::new("{name} is {age:d}").unwrap();
+    /// let result = parser.parse("O\"Brien is 30").unwrap().unwrap();
     ///
-    /// assert_eq!(results.len(), 3);
-    /// assert_eq!(results[0].get("number").unwrap().as_int(), Some(1));
-    /// assert_eq!(results[1].get("number").unwrap().as_int(), Some(2));
-    /// assert_eq!(results[2].get("number").unwrap().as_int(), Some(3));
+    /// assert_eq!(result.to_json(), r#"{"name":"O\"Brien","age":30}"#);
     /// ```
-    pub fn findall(&self, text: &str) -> Result<impl Iterator<Item = ParseResult> + '_> {
-        let captures: Vec<_> = self.regex.captures_iter(text).collect();
-
-        let results: Result<Vec<_>> = captures
-            .into_iter()
-            .map(|cap| {
-                let values = self.extract_values(&cap)?;
-                Ok(ParseResult {
-                    values,
-                    text: text.to_string(),
-                })
-            })
-            .collect();
-
-        Ok(results?.into_iter())
-    }
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        use std::fmt::Write;
 
-    /// Extract and convert captured values.
-    fn extract_values(&self, cap: &regex::Captures) -> Result<HashMap<String, Value>> {
-        let mut values = HashMap::new();
-
-        for info in &self.captures {
-            if let Some(matched) = cap.name(&info.name) {
-                let text = matched.as_str();
-                let value = convert_value(text, &info.spec)?;
-                values.insert(info.name.clone(), value);
+        let mut json = String::from("{");
+        for (name, value) in self.fields() {
+            if json.len() > 1 {
+                json.push(',');
             }
+            let key = serde_json::Value::from(name);
+            let value = serde_json::Value::from(value.clone());
+            let _ = write!(json, "{}:{}", key, value);
         }
-
-        Ok(values)
+        json.push('}');
+        json
     }
-}
-
-/// Result of parsing a string.
-///
-/// Contains the extracted values as a map from field names to values.
-#[derive(Debug, Clone)]
-pub struct ParseResult {
-    values: HashMap<String, Value>,
-    text: String,
-}
 
-impl ParseResult {
-    /// Get a value by field name.
+    /// Deserialize the captured fields into a struct, by field name.
+    ///
+    /// Untyped captures are strings, which fill number and boolean fields
+    /// when they parse as one; see [`Value::deserialize_into`].
     ///
     /// # Examples
     ///
     /// ```
     /// use gullwing::Parser;
+    /// use serde::Deserialize;
     ///
-    /// let parser = Parser::new("{name}").unwrap();
-    /// let result = parser.parse("Alice").unwrap().unwrap();
+    /// #[derive(Deserialize)]
+    /// struct LogLine {
+    ///     level: String,
+    ///     status: u16,
+    ///     bytes: Option<u64>,
+    /// }
     ///
-    /// assert_eq!(result.get("name").unwrap().as_str(), Some("Alice"));
+    /// let parser = Parser::new("{level} {status:d} {bytes}").unwrap();
+    /// let line: LogLine = parser.parse("INFO 200 512").unwrap().unwrap().into_struct().unwrap();
+    /// assert_eq!((line.level.as_str(), line.status, line.bytes), ("INFO", 200, Some(512)));
     /// ```
-    pub fn get(&self, name: &str) -> Option<&Value> {
-        self.values.get(name)
+    #[cfg(feature = "serde")]
+    pub fn into_struct<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+        Value::Map(self.values).deserialize_into()
     }
+}
 
-    /// Get all values as a HashMap.
-    pub fn values(&self) -> &HashMap<String, Value> {
-        &self.values
+/// Parse an integer, using the narrowest of `Int`, `UInt`, `Int128` and
+/// `UInt128` that holds it.
+fn parse_int(digits: &str, radix: u32) -> Option<Value> {
+    if let Ok(i) = i64::from_str_radix(digits, radix) {
+        return Some(Value::Int(i));
     }
-
-    /// Get the original text that was parsed.
-    pub fn text(&self) -> &str {
-        &self.text
+    match i128::from_str_radix(digits, radix) {
+        Ok(i) => Some(u64::try_from(i).map_or(Value::Int128(i), Value::UInt)),
+        Err(_) => u128::from_str_radix(digits, radix).ok().map(Value::UInt128),
     }
+}
 
-    /// Check if a field exists in the result.
-    pub fn contains(&self, name: &str) -> bool {
-        self.values.contains_key(name)
+/// Parse a float capture, as an exact decimal if `decimals` is set.
+///
+/// `scale` is the power of ten to divide by, 2 for percentages.
+#[cfg_attr(not(feature = "rust_decimal"), allow(unused_variables))]
+fn parse_float(text: &str, decimals: bool, scale: u32) -> Option<Value> {
+    #[cfg(feature = "rust_decimal")]
+    if decimals {
+        let num = if text.contains(['e', 'E']) {
+            rust_decimal::Decimal::from_scientific(text)
+        } else {
+            rust_decimal::Decimal::from_str_exact(text)
+        };
+        return num
+            .ok()
+            .and_then(|num| {
+                rust_decimal::Decimal::try_from_i128_with_scale(num.mantissa(), num.scale() + scale)
+                    .ok()
+            })
+            .map(Value::Decimal);
     }
+    let num = text.parse::<f64>().ok()?;
+    Some(Value::Float(num / 10f64.powi(scale as i32)))
 }
 
-/// Convert a captured string to a typed value based on the format spec.
-fn convert_value(text: &str, spec: &crate::spec::FormatSpec) -> Result<Value> {
+/// Convert a captured string into `slot`, reusing its string buffer if it has one.
+///
+/// Float captures become [`Value::Decimal`] instead of [`Value::Float`]
+/// when `decimals` is set.
+fn convert_value_into(
+    text: &str,
+    spec: &crate::spec::FormatSpec,
+    decimals: bool,
+    slot: &mut Value,
+) -> Result<()> {
     let type_spec = spec.type_spec.unwrap_or(TypeSpec::String);
 
-    match type_spec {
-        TypeSpec::String => Ok(Value::Str(text.to_string())),
+    *slot = match type_spec {
+        TypeSpec::String => {
+            if let Value::Str(Cow::Owned(s)) = slot {
+                s.clear();
+                s.push_str(text);
+            } else {
+                *slot = Value::from(text);
+            }
+            return Ok(());
+        }
 
         TypeSpec::Decimal | TypeSpec::Number => {
-            let cleaned = text.replace([',', '_'], "");
-            cleaned
-                .parse::<i64>()
-                .map(Value::Int)
-                .map_err(|e| Error::ConversionError(format!("failed to parse integer: {}", e)))
+            let parsed = if text.contains([',', '_']) {
+                parse_int(&text.replace([',', '_'], ""), 10)
+            } else {
+                parse_int(text, 10)
+            };
+            parsed.ok_or_else(|| Error::conversion("integer", text))?
         }
 
         TypeSpec::Binary => {
             let cleaned = text.trim_start_matches("0b").trim_start_matches("0B");
-            i64::from_str_radix(cleaned, 2)
-                .map(Value::Int)
-                .map_err(|e| Error::ConversionError(format!("failed to parse binary: {}", e)))
+            parse_int(cleaned, 2).ok_or_else(|| Error::conversion("binary", text))?
         }
 
         TypeSpec::Octal => {
             let cleaned = text.trim_start_matches("0o").trim_start_matches("0O");
-            i64::from_str_radix(cleaned, 8)
-                .map(Value::Int)
-                .map_err(|e| Error::ConversionError(format!("failed to parse octal: {}", e)))
+            parse_int(cleaned, 8).ok_or_else(|| Error::conversion("octal", text))?
         }
 
         TypeSpec::HexLower | TypeSpec::HexUpper => {
-            let cleaned = text
-                .trim_start_matches("0x")
-                .trim_start_matches("0X")
-                .replace('_', "");
-            i64::from_str_radix(&cleaned, 16)
-                .map(Value::Int)
-                .map_err(|e| Error::ConversionError(format!("failed to parse hex: {}", e)))
+            let cleaned = text.trim_start_matches("0x").trim_start_matches("0X");
+            let parsed = if cleaned.contains('_') {
+                parse_int(&cleaned.replace('_', ""), 16)
+            } else {
+                parse_int(cleaned, 16)
+            };
+            parsed.ok_or_else(|| Error::conversion("hex", text))?
         }
 
         TypeSpec::FixedLower
@@ -252,30 +1497,26 @@ fn convert_value(text: &str, spec: &crate::spec::FormatSpec) -> Result<Value> {
         | TypeSpec::ExponentLower
         | TypeSpec::ExponentUpper
         | TypeSpec::GeneralLower
-        | TypeSpec::GeneralUpper => text
-            .parse::<f64>()
-            .map(Value::Float)
-            .map_err(|e| Error::ConversionError(format!("failed to parse float: {}", e))),
+        | TypeSpec::GeneralUpper => {
+            parse_float(text, decimals, 0).ok_or_else(|| Error::conversion("float", text))?
+        }
 
         TypeSpec::Percentage => {
             let cleaned = text.trim_end_matches('%');
-            cleaned
-                .parse::<f64>()
-                .map(|v| Value::Float(v / 100.0))
-                .map_err(|e| Error::ConversionError(format!("failed to parse percentage: {}", e)))
+            parse_float(cleaned, decimals, 2)
+                .ok_or_else(|| Error::conversion("percentage", text))?
         }
 
         TypeSpec::Character => {
-            if text.len() == 1 {
-                Ok(Value::Char(text.chars().next().unwrap()))
+            let mut chars = text.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                Value::Char(c)
             } else {
-                Err(Error::ConversionError(format!(
-                    "expected single character, got: {}",
-                    text
-                )))
+                return Err(Error::conversion("character", text));
             }
         }
-    }
+    };
+    Ok(())
 }
 
 #[cfg(test)]
@@ -300,12 +1541,38 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_parse_floats() {
         let parser = Parser::new("{value:f}").unwrap();
         let result = parser.parse("3.14").unwrap().unwrap();
         assert_eq!(result.get("value").unwrap().as_float(), Some(3.14));
     }
 
+    #[test]
+    fn test_parse_pinned_exponent() {
+        let formatter = crate::Formatter::new("{a:.4e} {b:E}")
+            .unwrap()
+            .with_exponent(-3);
+        let parser = Parser::new("{a:.4e} {b:E}").unwrap();
+        assert_eq!(parser.engine(), Engine::Splitter);
+        let parser = parser.with_exponent(-3).unwrap();
+        assert_eq!(parser.engine(), Engine::Regex);
+
+        let mut values = HashMap::new();
+        values.insert("a".to_string(), Value::from(0.5));
+        values.insert("b".to_string(), Value::from(-0.000125));
+        let text = formatter.format_map(&values).unwrap();
+        assert_eq!(text, "500.0000e-03 -0.125000E-03");
+
+        let result = parser.parse(&text).unwrap().unwrap();
+        assert_eq!(result.get("a").unwrap().as_float(), Some(0.5));
+        assert_eq!(result.get("b").unwrap().as_float(), Some(-0.000125));
+
+        assert!(parser.parse("5.0e-01 -0.125E-03").unwrap().is_none());
+        assert!(parser.parse("500.0e-03 -0.125E-3").unwrap().is_none());
+        assert_ne!(parser, Parser::new("{a:.4e} {b:E}").unwrap());
+    }
+
     #[test]
     fn test_parse_hex() {
         let parser = Parser::new("{value:x}").unwrap();
@@ -316,6 +1583,65 @@ mod tests {
         assert_eq!(result.get("value").unwrap().as_int(), Some(255));
     }
 
+    #[test]
+    fn test_parse_wide_integers() {
+        let parser = Parser::new("{a:d} {b:d} {c:x} {d:d}").unwrap();
+        let result = parser
+            .parse(
+                "18446744073709551615 -170141183460469231731687303715884105728 \
+                 0xffffffffffffffffffffffffffffffff -9223372036854775808",
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.get("a"), Some(&Value::UInt(u64::MAX)));
+        assert_eq!(result.get("b"), Some(&Value::Int128(i128::MIN)));
+        assert_eq!(result.get("c"), Some(&Value::UInt128(u128::MAX)));
+        assert_eq!(result.get("d"), Some(&Value::Int(i64::MIN)));
+
+        // Past the 128-bit range is a conversion error
+        let parser = Parser::new("{a:d}").unwrap();
+        assert!(parser
+            .parse("340282366920938463463374607431768211456")
+            .is_err());
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_parse_decimals() {
+        use rust_decimal::Decimal;
+
+        let parser = Parser::new("{a:f} {b:e} {c:%} {d:d}").unwrap();
+        let text = "0.10 1.25e+3 12.5% 7";
+        let result = parser.parse(text).unwrap().unwrap();
+        assert_eq!(result.get("a"), Some(&Value::Float(0.1)));
+
+        let exact = parser.with_decimals(true);
+        assert_ne!(exact, parser);
+        let result = exact.parse(text).unwrap().unwrap();
+        assert_eq!(result.get("a"), Some(&Value::Decimal(Decimal::new(10, 2))));
+        assert_eq!(
+            result.get("b"),
+            Some(&Value::Decimal(Decimal::new(1250, 0)))
+        );
+        assert_eq!(result.get("c"), Some(&Value::Decimal(Decimal::new(125, 3))));
+        assert_eq!(result.get("d"), Some(&Value::Int(7)));
+
+        // Both engines and the reusable path convert the same way
+        let regex = exact.with_engine(Engine::Regex).unwrap();
+        assert_eq!(
+            regex.parse(text).unwrap().unwrap().values(),
+            result.values()
+        );
+        let mut locs = exact.capture_locations();
+        let mut values = Vec::new();
+        assert!(exact.parse_into(text, &mut locs, &mut values).unwrap());
+        assert_eq!(values[0], Value::Decimal(Decimal::new(10, 2)));
+
+        // Past 28 fractional digits is a conversion error
+        let tiny = format!("0.{}1 1e0 1% 2", "0".repeat(28));
+        assert!(exact.parse(&tiny).is_err());
+    }
+
     #[test]
     fn test_search() {
         let parser = Parser::new("{number:d}").unwrap();
@@ -333,11 +1659,611 @@ mod tests {
     #[test]
     fn test_findall() {
         let parser = Parser::new("{num:d}").unwrap();
-        let results: Vec<_> = parser.findall("Numbers: 1, 2, 3").unwrap().collect();
+        let results: Vec<_> = parser
+            .findall("Numbers: 1, 2, 3")
+            .collect::<Result<_>>()
+            .unwrap();
 
         assert_eq!(results.len(), 3);
         assert_eq!(results[0].get("num").unwrap().as_int(), Some(1));
         assert_eq!(results[1].get("num").unwrap().as_int(), Some(2));
         assert_eq!(results[2].get("num").unwrap().as_int(), Some(3));
     }
+
+    #[test]
+    fn test_findall_skips_empty_matches() {
+        let values = |pattern: &str, text: &str| -> Vec<String> {
+            Parser::new(pattern)
+                .unwrap()
+                .findall(text)
+                .map(|r| r.unwrap().get("x").unwrap().to_string())
+                .collect()
+        };
+        assert!(values("{x:/b?/}", "aaa").is_empty());
+        assert_eq!(values("{x:/a*/}", "bab"), ["a"]);
+        assert_eq!(values("{x:/a*/}", "aabaa"), ["aa", "aa"]);
+    }
+
+    #[test]
+    fn test_findall_retries_inside_rejected_match() {
+        // "1,2" disagrees on `x`, but the match "2,2" starts inside it
+        let parser = Parser::new("{x:d},{x:d}").unwrap();
+        let text = "1,2,2";
+        assert_eq!(
+            parser.search(text).unwrap().unwrap().get("x"),
+            Some(&Value::Int(2))
+        );
+        let found: Vec<_> = parser
+            .findall(text)
+            .map(|r| r.unwrap().match_span().start)
+            .collect();
+        assert_eq!(found, [2]);
+    }
+
+    #[test]
+    fn test_findall_is_lazy() {
+        // With and without a literal prefix to prefilter on
+        for pattern in ["#{num:d};", "{num:d};"] {
+            let parser = Parser::new(pattern).unwrap();
+            let text = "#1; #99999999999999999999999999999999999999999; #3;";
+
+            let mut matches = parser.findall(text);
+            assert_eq!(
+                matches.next().unwrap().unwrap().get("num"),
+                Some(&Value::Int(1))
+            );
+            // A field that doesn't convert fails on its own
+            assert_eq!(
+                matches.next().unwrap().unwrap_err().kind(),
+                crate::error::ErrorKind::ConversionError
+            );
+            assert_eq!(
+                matches.next().unwrap().unwrap().get("num"),
+                Some(&Value::Int(3))
+            );
+            assert!(matches.next().is_none());
+            assert!(matches.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_findall_cancellable() {
+        // With and without a literal prefix to prefilter on
+        for pattern in ["#{num:d}", "{num:d}"] {
+            let parser = Parser::new(pattern).unwrap();
+            let text = "#1 #2 #3 #4";
+
+            // Stop before the third search
+            let mut checks = 0;
+            let (results, stopped) = parser
+                .scan(text, || {
+                    checks += 1;
+                    checks > 2
+                })
+                .unwrap();
+            let nums: Vec<_> = results.iter().map(|r| r.get("num").cloned()).collect();
+            assert_eq!(nums, [Some(Value::Int(1)), Some(Value::Int(2))]);
+            assert!(stopped);
+
+            let token = CancelToken::new();
+            let scan = parser.findall_cancellable(text, &token).unwrap();
+            assert_eq!(scan.status, ScanStatus::Complete);
+            assert_eq!(scan.results.len(), 4);
+
+            token.cancel();
+            let scan = parser.findall_cancellable(text, &token).unwrap();
+            assert_eq!(scan.status, ScanStatus::Cancelled);
+            assert!(scan.results.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_introspection() {
+        let parser = Parser::new("[{level}] {0:d}/{1}").unwrap();
+        assert_eq!(parser.capture_count(), 3);
+        assert_eq!(parser.literal_prefix(), "[");
+        assert_eq!(parser.literal_suffix(), "");
+        assert!(!parser.is_anchored());
+
+        let parser = Parser::new("}}{x}{{").unwrap();
+        assert_eq!(
+            (parser.literal_prefix(), parser.literal_suffix()),
+            ("}", "{")
+        );
+        assert!(parser.is_anchored());
+
+        let parser = Parser::new("plain").unwrap();
+        assert_eq!(parser.capture_count(), 0);
+        assert_eq!(
+            (parser.literal_prefix(), parser.literal_suffix()),
+            ("plain", "plain")
+        );
+        assert!(parser.is_anchored());
+        assert!(!Parser::new("").unwrap().is_anchored());
+    }
+
+    #[test]
+    fn test_positional_access() {
+        let parser = Parser::new("{1:d}-{0:d} {name} {_01}").unwrap();
+        let result = parser.parse("2-1 x y").unwrap().unwrap();
+        assert_eq!(result.get_index(0), Some(&Value::Int(1)));
+        assert_eq!(result.get_index(1), Some(&Value::Int(2)));
+        assert_eq!(result.get_index(0), result.get("_0"));
+        assert_eq!(result.fixed(), [&Value::Int(1), &Value::Int(2)]);
+
+        let result = Parser::new("{a}").unwrap().parse("a").unwrap().unwrap();
+        assert!(result.get_index(0).is_none());
+        assert!(result.fixed().is_empty());
+    }
+
+    #[test]
+    fn test_anonymous_skipped() {
+        let parser = Parser::new("{} [{:d}] {level}: {msg}").unwrap();
+        let skipping = parser.with_anonymous_skipped(true).unwrap();
+        assert!(skipping.anonymous_skipped());
+        assert_ne!(skipping, parser);
+        assert_eq!(skipping.capture_count(), 2);
+
+        let text = "12:00 [99999999999999999999] WARN: disk full";
+        assert!(parser.parse(text).unwrap().unwrap().get("_1").is_some());
+        for engine in [Engine::Splitter, Engine::Regex] {
+            let result = skipping.with_engine(engine).unwrap().parse(text).unwrap();
+            let result = result.unwrap();
+            assert_eq!(result.values().len(), 2);
+            assert_eq!(result.get("level"), Some(&Value::from("WARN")));
+            assert_eq!(result.span("msg"), Some(35..44));
+            assert!(result.span("_0").is_none() && result.fixed().is_empty());
+            assert!(skipping.parse("12:00 [x] WARN: disk").unwrap().is_none());
+        }
+
+        let found = skipping.search(&format!("> {}", text)).unwrap().unwrap();
+        assert_eq!(found.get("level"), Some(&Value::from("WARN")));
+        assert_eq!(skipping.findall("a [1] b: c").count(), 1);
+
+        // Turning it back off restores the positional captures
+        let restored = skipping.with_anonymous_skipped(false).unwrap();
+        assert_eq!(restored, parser);
+        let skipping = skipping.with_exponent(2).unwrap();
+        assert!(skipping.anonymous_skipped());
+    }
+
+    #[test]
+    fn test_inline_regex() {
+        let parser = Parser::new(r"{token:/[A-Z]{3}-\d+/} {n:d} {rest:/(a|b)+/}!").unwrap();
+        assert_eq!(parser.engine(), Engine::Regex);
+        assert_eq!(parser.template().fields()[0].regex(), Some(r"[A-Z]{3}-\d+"));
+        assert_eq!(
+            parser.pattern(),
+            r"{token:/[A-Z]{3}-\d+/} {n:d} {rest:/(a|b)+/}!"
+        );
+
+        let result = parser.parse("ABC-123 7 abba!").unwrap().unwrap();
+        assert_eq!(result.get("token"), Some(&Value::from("ABC-123")));
+        assert_eq!(result.get("n"), Some(&Value::from(7)));
+        assert_eq!(result.get("rest"), Some(&Value::from("abba")));
+        assert!(parser.parse("AB-123 7 a!").unwrap().is_none());
+
+        let found = parser.search("id ZZZ-9 1 b! end").unwrap().unwrap();
+        assert_eq!(found.get("token"), Some(&Value::from("ZZZ-9")));
+
+        // Groups inside the regex don't shift the fields after it
+        let parser = Parser::new("{a:/(x)(y)?/}-{b:d}").unwrap();
+        let result = parser.parse("x-5").unwrap().unwrap();
+        assert_eq!(result.get("b"), Some(&Value::from(5)));
+        assert_eq!(parser.findall("x-1 xy-2").count(), 2);
+
+        let err = Parser::new("{a:/(/}").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::RegexError);
+        assert!(err.to_string().contains("field 'a'"));
+
+        // Inline regexes are only for parsing
+        assert!(crate::Formatter::new("{a:/x/}").is_err());
+    }
+
+    #[test]
+    fn test_choices() {
+        let parser = Parser::new("{id:d} {status:(OK|FAIL|SKIP)} in {t:f}s").unwrap();
+        assert_eq!(parser.engine(), Engine::Regex);
+        assert_eq!(
+            parser.template().fields()[1].choices(),
+            Some(&["OK".to_string(), "FAIL".to_string(), "SKIP".to_string()][..])
+        );
+        assert_eq!(parser.pattern(), "{id:d} {status:(OK|FAIL|SKIP)} in {t:f}s");
+
+        let result = parser.parse("7 FAIL in 0.5s").unwrap().unwrap();
+        assert_eq!(result.get("status"), Some(&Value::from("FAIL")));
+        assert_eq!(result.get("t"), Some(&Value::from(0.5)));
+        assert!(parser.parse("7 ERROR in 0.5s").unwrap().is_none());
+        assert_eq!(
+            parser
+                .findall("1 OK in 1s, 2 MEH in 2s, 3 SKIP in 3s")
+                .count(),
+            2
+        );
+
+        // Alternatives are literal text, and a longer one can follow a prefix
+        let parser = Parser::new("{op:(+|a.b|ab)}{rest:(c|bc)}").unwrap();
+        let result = parser.parse("abc").unwrap().unwrap();
+        assert_eq!(result.get("op"), Some(&Value::from("ab")));
+        assert!(parser.parse("axbc").unwrap().is_none());
+        assert_eq!(
+            parser.parse("+c").unwrap().unwrap().get("op"),
+            Some(&Value::from("+"))
+        );
+
+        let err = Parser::new("{a:(x||y)}").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        assert!(Parser::new("{a:()}").is_err());
+
+        // Repeats must offer the same choices
+        assert!(Parser::new("{a:(x|y)} {a:(x|y)}").is_ok());
+        assert!(Parser::new("{a:(x|y)} {a:(x|z)}").is_err());
+
+        // Choice sets are only for parsing
+        assert!(crate::Formatter::new("{a:(x|y)}").is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let parser = Parser::new("{key}={value:d}").unwrap();
+
+        let result = parser.parse("width=80").unwrap().unwrap();
+        assert_eq!(result.span("key"), Some(0..5));
+        assert_eq!(result.span("value"), Some(6..8));
+        assert_eq!(result.span("missing"), None);
+        assert_eq!(result.match_span(), 0..8);
+        assert_eq!(result.spans().len(), 2);
+
+        let result = parser.search("set é=1;").unwrap().unwrap();
+        assert_eq!(&result.text()[result.match_span()], "set é=1");
+        assert_eq!(result.span("value"), Some(7..8));
+
+        let parser = Parser::new("{n:d};").unwrap();
+        let spans: Vec<_> = parser
+            .findall("1; 22; 333;")
+            .map(|r| r.unwrap().match_span())
+            .collect();
+        assert_eq!(spans, [0..2, 3..6, 7..11]);
+    }
+
+    #[test]
+    fn test_empty_pattern() {
+        let parser = Parser::new("").unwrap();
+        assert!(parser.parse("").unwrap().unwrap().values().is_empty());
+        assert!(parser.parse(" ").unwrap().is_none());
+
+        let found = parser.search("abc").unwrap().unwrap();
+        assert!(found.values().is_empty());
+        assert_eq!(parser.findall("abc").count(), 0);
+        assert_eq!(parser.findall("").count(), 0);
+
+        let mut locs = parser.capture_locations();
+        let mut values = Vec::new();
+        assert!(parser.parse_into("", &mut locs, &mut values).unwrap());
+        assert!(!parser.parse_into("x", &mut locs, &mut values).unwrap());
+    }
+
+    #[test]
+    fn test_captures_use_template_specs() {
+        let parser = Parser::new("{a:>5} {:d} {b:.2f}|{c:x}").unwrap();
+        let specs: Vec<_> = parser
+            .template()
+            .fields()
+            .iter()
+            .map(|f| f.spec())
+            .collect();
+        let captured: Vec<_> = parser.captures.iter().map(|c| &*c.spec).collect();
+        assert_eq!(captured, specs);
+        let formatter = crate::Formatter::new(parser.pattern()).unwrap();
+        assert_eq!(parser.template(), formatter.template());
+    }
+
+    #[test]
+    fn test_padding_round_trip() {
+        use crate::Formatter;
+
+        for (pattern, value) in [
+            ("[{v:>10d}]", Value::from(42)),
+            ("[{v:>10d}]", Value::from(-42)),
+            ("[{v:6d}]", Value::from(7)),
+            ("[{v:*>6d}]", Value::from(7)),
+            ("[{v:=+8d}]", Value::from(5)),
+            ("[{v:=08d}]", Value::from(-5)),
+            ("[{v:0>5d}]", Value::from(0)),
+            ("[{v:^9.2f}]", Value::from(1.5)),
+            ("[{v:<8x}]", Value::from(255)),
+            ("[{v:<8}]", Value::from("ab")),
+            ("[{v:^9}]", Value::from("mid")),
+            ("[{v:->7}]", Value::from("r")),
+            ("[{v:8}]", Value::from("left")),
+            ("[{v:>}]", Value::from("x")),
+        ] {
+            let formatter = Formatter::new(pattern).unwrap();
+            let text = formatter.format(&[("v", value.clone())]).unwrap();
+            let result = Parser::new(pattern).unwrap().parse(&text).unwrap();
+            let result = result.unwrap_or_else(|| panic!("{} didn't match {:?}", pattern, text));
+            assert_eq!(result.get("v"), Some(&value), "{} on {:?}", pattern, text);
+            assert_eq!(result.span("v"), Some(1..text.len() - 1));
+        }
+
+        // Padding is optional, and a value that fills the width is fine too
+        let parser = Parser::new("{a:>4d}|{b:<4}|").unwrap();
+        let result = parser.parse("12345|abcde|").unwrap().unwrap();
+        assert_eq!(result.get("a"), Some(&Value::from(12345)));
+        assert_eq!(result.get("b"), Some(&Value::from("abcde")));
+        assert!(parser.with_engine(Engine::Splitter).is_err());
+    }
+
+    #[test]
+    fn test_repeated_names() {
+        let parser = Parser::new("{id}: {msg} (id {id})").unwrap();
+        assert_eq!(parser.engine(), Engine::Regex);
+        assert_eq!(parser.capture_count(), 2);
+        let result = parser.parse("a1: hello (id a1)").unwrap().unwrap();
+        assert_eq!(result.get("id").unwrap().as_str(), Some("a1"));
+        assert_eq!(result.span("id"), Some(0..2));
+        assert!(parser.parse("a1: hello (id b2)").unwrap().is_none());
+
+        // Search retries past a match whose repeats differ
+        let parser = Parser::new("<{tag}>{body}</{tag}>").unwrap();
+        let result = parser.search("<a>x</b> <b>y</b>").unwrap().unwrap();
+        assert_eq!(result.get("tag").unwrap().as_str(), Some("b"));
+        assert_eq!(result.match_span(), 9..17);
+
+        let parser = Parser::new("{n:d}={n:d}").unwrap();
+        let found: Vec<_> = parser
+            .findall("1=1 2=3 4=4")
+            .map(|r| r.unwrap().get("n").cloned())
+            .collect();
+        assert_eq!(found, [Some(Value::from(1)), Some(Value::from(4))]);
+        let parser = Parser::new("set {n:d}={n:d};").unwrap();
+        let found: Vec<_> = parser
+            .findall("set 1=2; set 3=3;")
+            .map(|r| r.unwrap().get("n").cloned())
+            .collect();
+        assert_eq!(found, [Some(Value::from(3))]);
+
+        // The text must match, not just the value
+        let parser = Parser::new("{n:d} {n:d}").unwrap();
+        assert!(parser.parse("7 07").unwrap().is_none());
+
+        let err = Parser::new("{a:d} {a:x}").unwrap_err();
+        assert_eq!(
+            err,
+            Error::spec_at("field 'a' is repeated with a different format spec", 7)
+        );
+        let err = Parser::new("{a}|{a}")
+            .unwrap()
+            .with_engine(Engine::Splitter)
+            .unwrap_err();
+        assert!(err.to_string().contains("field 'a' is repeated"));
+    }
+
+    #[test]
+    fn test_to_env() {
+        let parser = Parser::new("{n:d} {x:f} {s}|{0} {c:c}").unwrap();
+        let result = parser.parse("-3 1.5 $(rm -rf ~)|7 '").unwrap().unwrap();
+        assert_eq!(
+            result.to_env(),
+            r"n=-3; x=1.5; s='$(rm -rf ~)'; _0='7'; c=''\'''"
+        );
+
+        let parser = Parser::new("{größe:d}|{a}").unwrap();
+        let result = parser.parse("12|").unwrap();
+        assert!(result.is_none());
+        let result = parser.parse("12|b c").unwrap().unwrap();
+        assert_eq!(result.to_env(), "gr__e=12; a='b c'");
+        assert_eq!(
+            Parser::new("")
+                .unwrap()
+                .parse("")
+                .unwrap()
+                .unwrap()
+                .to_env(),
+            ""
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_to_json() {
+        let parser = Parser::new("{n:d} {x:f} {s}|{0} {c:c}").unwrap();
+        let result = parser.parse("-3 1.5 a\tb|7 \"").unwrap().unwrap();
+        assert_eq!(
+            result.to_json(),
+            r#"{"n":-3,"x":1.5,"s":"a\tb","_0":"7","c":"\""}"#
+        );
+
+        let sorted = parser.with_field_order(FieldOrder::Lexicographic);
+        let result = sorted.parse("1 2.5 x|y z").unwrap().unwrap();
+        assert_eq!(
+            result.to_json(),
+            r#"{"_0":"y","c":"z","n":1,"s":"x","x":2.5}"#
+        );
+        assert_eq!(
+            Parser::new("")
+                .unwrap()
+                .parse("")
+                .unwrap()
+                .unwrap()
+                .to_json(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn test_field_order() {
+        let parser = Parser::new("{z} {} {m:d} {a} {z}").unwrap();
+        assert_eq!(parser.field_order(), FieldOrder::Pattern);
+        let text = "q x 3 b q";
+        let names = |parser: &Parser| -> Vec<String> {
+            let result = parser.parse(text).unwrap().unwrap();
+            result.fields().map(|(name, _)| name.to_string()).collect()
+        };
+        assert_eq!(names(&parser), ["z", "_0", "m", "a"]);
+
+        let sorted = parser.with_field_order(FieldOrder::Lexicographic);
+        assert_eq!(names(&sorted), ["_0", "a", "m", "z"]);
+        let result = sorted.parse(text).unwrap().unwrap();
+        let ordered: Vec<_> = result.iter_ordered().map(|(name, _)| name).collect();
+        assert_eq!(ordered, ["z", "_0", "m", "a"]);
+        assert_ne!(sorted, parser);
+        assert_eq!(
+            sorted.parse(text).unwrap().unwrap().to_env(),
+            "_0='x'; a='b'; m=3; z='q'"
+        );
+
+        // The order survives recompiling, and skipped fields drop out
+        let skipped = sorted.with_anonymous_skipped(true).unwrap();
+        assert_eq!(skipped.field_order(), FieldOrder::Lexicographic);
+        assert_eq!(names(&skipped), ["a", "m", "z"]);
+        let found: Vec<_> = skipped
+            .findall(text)
+            .map(|r| r.unwrap().fields().count())
+            .collect();
+        assert_eq!(found, [3]);
+    }
+
+    #[test]
+    fn test_replace() {
+        use crate::error::ErrorKind;
+        use crate::Formatter;
+
+        let parser = Parser::new("size={n:d}").unwrap();
+        let formatter = Formatter::new("size={n:04d}").unwrap();
+        let text = "größe size=7, size=12 und size=x";
+        assert_eq!(
+            parser.replace_all(text, &formatter).unwrap(),
+            "größe size=0007, size=0012 und size=x"
+        );
+        assert_eq!(
+            parser.replace(text, &formatter).unwrap(),
+            "größe size=0007, size=12 und size=x"
+        );
+        assert_eq!(parser.replace_all("none", &formatter).unwrap(), "none");
+
+        // Positional captures are named `_0`, ...
+        let parser = Parser::new("{:d}+{:d}").unwrap();
+        let formatter = Formatter::new("{_1}+{_0}").unwrap();
+        assert_eq!(
+            parser.replace_all("1+2 and 30+4", &formatter).unwrap(),
+            "2+1 and 4+30"
+        );
+
+        let err = parser
+            .replace_all("1+2", &Formatter::new("{total}").unwrap())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+
+        let parser = Parser::builder()
+            .with_type("even", r"\d+", |s| {
+                let n: i64 = s.parse().ok()?;
+                (n % 2 == 0).then(|| Value::from(n))
+            })
+            .build("#{n:even}")
+            .unwrap();
+        let formatter = Formatter::new("#{n:02d}").unwrap();
+        assert_eq!(parser.replace_all("#2 #4", &formatter).unwrap(), "#02 #04");
+        let err = parser.replace_all("#2 #3", &formatter).unwrap_err();
+        assert!(err.is_conversion_error());
+    }
+
+    #[test]
+    fn test_equality_and_hash() {
+        use std::collections::HashSet;
+
+        let a = Parser::new("{x} = {y:d}").unwrap();
+        let b = Parser::new("{x:} = {y:d}").unwrap();
+        let c = Parser::new("{x} = {y:f}").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, a.with_engine(Engine::Regex).unwrap());
+
+        // The regex's internal cache is mutable, but isn't part of the hash
+        #[allow(clippy::mutable_key_type)]
+        let unique: HashSet<Parser> = [a, b, c].into_iter().collect();
+        assert_eq!(unique.len(), 2);
+    }
+
+    #[test]
+    fn test_engine_override() {
+        let parser = Parser::new("{a}-{b}-{c:d}").unwrap();
+        assert_eq!(parser.engine(), Engine::Splitter);
+
+        let regex = parser.with_engine(Engine::Regex).unwrap();
+        assert_eq!(regex.engine(), Engine::Regex);
+        let back = regex.with_engine(Engine::Splitter).unwrap();
+        assert_eq!(back.engine(), Engine::Splitter);
+
+        // Both engines backtrack the same way
+        for p in [&parser, &regex, &back] {
+            let result = p.parse("x-y-z-1").unwrap().unwrap();
+            assert_eq!(result.get("b").unwrap().as_str(), Some("y-z"));
+            assert!(p.parse("x-y-z").unwrap().is_none());
+        }
+
+        let err = Parser::new("{a}{b}")
+            .unwrap()
+            .with_engine(Engine::Splitter)
+            .unwrap_err();
+        assert!(err.is_pattern_error());
+        assert!(err.to_string().contains("field 'a' is not followed"));
+    }
+
+    #[test]
+    fn test_literal_prefilter() {
+        let parser = Parser::new("ERROR {code:d}: {msg},").unwrap();
+        assert!(parser.prefilter.is_some());
+
+        // The first candidate doesn't complete a match; the second does
+        let text = "ERROR x, INFO ok, ERROR 500: boom, ERROR 404: gone,";
+        let result = parser.search(text).unwrap().unwrap();
+        assert_eq!(result.get("code").unwrap().as_int(), Some(500));
+        assert!(parser.search("INFO 1: fine,").unwrap().is_none());
+
+        let codes: Vec<_> = parser
+            .findall(text)
+            .map(|r| r.unwrap().get("code").unwrap().as_int().unwrap())
+            .collect();
+        assert_eq!(codes, [500, 404]);
+
+        // Exact matches reject lines by their literal ends first
+        assert!(parser.parse("ERROR 500: boom,").unwrap().is_some());
+        assert!(parser.parse("ERROR 500: boom").unwrap().is_none());
+        assert!(parser.parse("WARN 500: boom,").unwrap().is_none());
+        let parser = Parser::new("ab{x}ba").unwrap();
+        assert!(parser.parse("aba").unwrap().is_none());
+        let result = parser.parse("abxba").unwrap().unwrap();
+        assert_eq!(result.get("x").unwrap().as_str(), Some("x"));
+
+        // Short prefixes are left to the regex
+        assert!(Parser::new("x={v}").unwrap().prefilter.is_none());
+    }
+
+    #[test]
+    fn test_clones_share_compiled_pattern() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Parser>();
+        assert_send_sync::<ParseResult>();
+
+        let parser = Parser::new("{level}: {code:d}").unwrap();
+        let clone = parser.clone();
+        assert!(Arc::ptr_eq(&parser.template, &clone.template));
+        assert!(Arc::ptr_eq(&parser.captures, &clone.captures));
+        let (Some(a), Some(b)) = (&parser.splitter, &clone.splitter) else {
+            panic!("expected the splitter engine");
+        };
+        assert!(Arc::ptr_eq(a, b));
+
+        std::thread::scope(|scope| {
+            for code in 0..4 {
+                let parser = parser.clone();
+                scope.spawn(move || {
+                    let text = format!("WARN: {code}");
+                    let result = parser.parse(&text).unwrap().unwrap();
+                    assert_eq!(result.get("code").unwrap().as_int(), Some(code));
+                });
+            }
+        });
+    }
 }
@@ -1,6 +1,17 @@
 //! Runtime string parsing using format specifications.
 
 mod builder;
+mod cancel;
+mod custom;
+#[cfg(feature = "chrono")]
+mod datetime;
 mod matcher;
+mod re;
+mod set;
+mod splitter;
 
-pub use matcher::{ParseResult, Parser};
+pub use cancel::{CancelToken, Scan, ScanStatus};
+pub use custom::ParserBuilder;
+pub(crate) use matcher::Locations;
+pub use matcher::{Engine, FieldOrder, FindAll, ParseResult, Parser};
+pub use set::ParserSet;
@@ -0,0 +1,13 @@
+//! The regex engine behind [`Parser`](super::Parser).
+//!
+//! `regex` is used when its feature is on; otherwise `regex-lite`, which is a
+//! fraction of the size but slower, and whose `\d` only matches ASCII digits.
+
+#[cfg(feature = "regex")]
+pub(crate) use regex::{CaptureLocations, Captures, Regex, RegexSet};
+
+#[cfg(all(feature = "regex-lite", not(feature = "regex")))]
+pub(crate) use regex_lite::{CaptureLocations, Captures, Regex};
+
+#[cfg(not(any(feature = "regex", feature = "regex-lite")))]
+compile_error!("the `parse` feature needs a regex engine: enable `regex` or `regex-lite`");
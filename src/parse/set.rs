@@ -0,0 +1,187 @@
+//! Matching a line against many patterns at once.
+
+use super::matcher::{ParseResult, Parser};
+#[cfg(feature = "regex")]
+use super::re::RegexSet;
+#[cfg(feature = "regex")]
+use crate::error::Error;
+use crate::error::Result;
+
+/// A set of parsers that finds which of its patterns a line matches.
+///
+/// Log files often mix dozens of line formats. Rather than trying each
+/// [`Parser`] in turn, a set compiles all of their patterns into one regex
+/// set that reports every pattern matching a line in a single pass, then
+/// parses the line with the first of them. Without the `regex` feature the
+/// parsers are tried one by one, with the same results.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::parse::ParserSet;
+///
+/// let set = ParserSet::new([
+///     "{ts} ERROR {code:d}: {message}",
+///     "{ts} INFO {message}",
+///     "{ts} {level} {message}",
+/// ])
+/// .unwrap();
+///
+/// let (index, result) = set.parse("12:00 ERROR 503: upstream down").unwrap().unwrap();
+/// assert_eq!(index, 0);
+/// assert_eq!(result.get("code").unwrap().as_int(), Some(503));
+///
+/// // The first matching pattern wins
+/// let (index, _) = set.parse("12:01 INFO started").unwrap().unwrap();
+/// assert_eq!(index, 1);
+/// let (index, _) = set.parse("12:02 DEBUG tick").unwrap().unwrap();
+/// assert_eq!(index, 2);
+///
+/// assert!(set.parse("garbage").unwrap().is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParserSet {
+    parsers: Vec<Parser>,
+    #[cfg(feature = "regex")]
+    set: RegexSet, // The parsers' anchored regexes, by index
+}
+
+impl ParserSet {
+    /// Create a set from patterns, compiling a [`Parser`] for each.
+    ///
+    /// Fails with the error of the first invalid pattern.
+    pub fn new<I, S>(patterns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let parsers = patterns
+            .into_iter()
+            .map(|pattern| Parser::new(pattern.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_parsers(parsers)
+    }
+
+    /// Create a set from parsers built elsewhere, such as with custom types.
+    ///
+    /// Each parser keeps its settings; the set only decides which one runs.
+    pub fn from_parsers(parsers: Vec<Parser>) -> Result<Self> {
+        #[cfg(feature = "regex")]
+        let set = RegexSet::new(parsers.iter().map(Parser::anchored_pattern)).map_err(|e| {
+            Error::RegexError {
+                message: format!("failed to compile regex set: {}", e),
+            }
+        })?;
+        Ok(ParserSet {
+            parsers,
+            #[cfg(feature = "regex")]
+            set,
+        })
+    }
+
+    /// Get the number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.parsers.len()
+    }
+
+    /// Check whether the set has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.parsers.is_empty()
+    }
+
+    /// Get the parser for the pattern at `index`.
+    pub fn get(&self, index: usize) -> Option<&Parser> {
+        self.parsers.get(index)
+    }
+
+    /// Get the parsers, in the order their patterns were given.
+    pub fn parsers(&self) -> &[Parser] {
+        &self.parsers
+    }
+
+    /// Parse a line with the first pattern that matches all of it.
+    ///
+    /// Returns the index of the pattern and its result, or `None` if no
+    /// pattern matches. Fails if the matching parser fails to convert a
+    /// field; later patterns aren't tried then.
+    pub fn parse(&self, text: &str) -> Result<Option<(usize, ParseResult)>> {
+        for index in self.candidates(text) {
+            if let Some(result) = self.parsers[index].parse(text)? {
+                return Ok(Some((index, result)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the indexes of the patterns that may match `text`, in order.
+    ///
+    /// The regex set can't check repeated field names, so each candidate
+    /// is confirmed by its parser.
+    fn candidates(&self, text: &str) -> impl Iterator<Item = usize> {
+        #[cfg(feature = "regex")]
+        return self.set.matches(text).into_iter();
+        #[cfg(not(feature = "regex"))]
+        {
+            let _ = text;
+            0..self.parsers.len()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    #[test]
+    fn test_parser_set() {
+        let set = ParserSet::new(["GET {path}", "{method} {path} {status:d}", "{a}={a}"]).unwrap();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.get(1).unwrap().pattern(), "{method} {path} {status:d}");
+
+        let (index, result) = set.parse("GET /index.html").unwrap().unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(result.get("path"), Some(&Value::from("/index.html")));
+
+        let (index, result) = set.parse("POST /login 302").unwrap().unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(result.get("status"), Some(&Value::from(302)));
+
+        // The set's regex matches `x=y`, but the repeated name doesn't agree
+        assert_eq!(set.parse("x=x").unwrap().unwrap().0, 2);
+        assert!(set.parse("x=y").unwrap().is_none());
+
+        let empty = ParserSet::new(Vec::<String>::new()).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.parse("anything").unwrap().is_none());
+
+        assert!(ParserSet::new(["{ok}", "{bad"]).is_err());
+    }
+
+    #[test]
+    fn test_from_parsers() {
+        let hex = Parser::builder()
+            .with_type("hex", "[0-9a-f]+", |s| {
+                i64::from_str_radix(s, 16).ok().map(Value::from)
+            })
+            .build("0x{n:hex}")
+            .unwrap();
+        let set = ParserSet::from_parsers(vec![hex, Parser::new("{n:d}").unwrap()]).unwrap();
+        assert_eq!(
+            set.parse("0xff").unwrap().unwrap().1.get("n"),
+            Some(&Value::from(255))
+        );
+        assert_eq!(set.parse("42").unwrap().unwrap().0, 1);
+
+        // A conversion failure stops the search
+        let strict = Parser::builder()
+            .with_type("even", r"\d+", |s| {
+                let n: i64 = s.parse().ok()?;
+                (n % 2 == 0).then(|| Value::from(n))
+            })
+            .build("{n:even}")
+            .unwrap();
+        let set = ParserSet::from_parsers(vec![strict, Parser::new("{n}").unwrap()]).unwrap();
+        assert!(set.parse("3").unwrap_err().is_conversion_error());
+    }
+}
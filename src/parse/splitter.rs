@@ -0,0 +1,417 @@
+//! Hand-written matcher for simple patterns.
+//!
+//! Many patterns are just literal text and fields, with every field followed
+//! by literal text that can't be confused with the field's contents, as in
+//! `{date} {level} [{thread:d}] {message}`. Such patterns can be matched by
+//! scanning for the literals directly, which is much cheaper than running the
+//! regex. The result is always the same as the anchored regex would produce:
+//! text fields are lazy and backtrack over later occurrences of their
+//! trailing literal, exactly like `.+?`.
+
+use super::builder::{padding, CaptureInfo, CaptureKind};
+use crate::error::{Error, Result};
+use crate::spec::TypeSpec;
+use memchr::{memchr, memmem};
+
+/// A field the splitter knows how to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    /// Untyped field without width or precision, matched like `.+?`
+    Text,
+    /// A single character, matched like `.`
+    Character,
+    Decimal,
+    Binary,
+    Octal,
+    Hex,
+    Float,
+    Percentage,
+}
+
+impl FieldKind {
+    fn for_capture(info: &CaptureInfo) -> Option<Self> {
+        let spec = &info.spec;
+        if padding(spec).is_some() {
+            return None;
+        }
+        let kind = match spec.type_spec.unwrap_or(TypeSpec::String) {
+            TypeSpec::String if spec.width.is_none() && spec.precision.is_none() => FieldKind::Text,
+            TypeSpec::String => return None,
+            TypeSpec::Character => FieldKind::Character,
+            TypeSpec::Decimal | TypeSpec::Number => FieldKind::Decimal,
+            TypeSpec::Binary => FieldKind::Binary,
+            TypeSpec::Octal => FieldKind::Octal,
+            TypeSpec::HexLower | TypeSpec::HexUpper => FieldKind::Hex,
+            TypeSpec::FixedLower
+            | TypeSpec::FixedUpper
+            | TypeSpec::ExponentLower
+            | TypeSpec::ExponentUpper
+            | TypeSpec::GeneralLower
+            | TypeSpec::GeneralUpper => FieldKind::Float,
+            TypeSpec::Percentage => FieldKind::Percentage,
+        };
+        Some(kind)
+    }
+
+    /// Check whether `b` can appear after the first byte of a match.
+    fn continues(self, b: u8) -> bool {
+        match self {
+            FieldKind::Text | FieldKind::Character => false,
+            FieldKind::Decimal => b.is_ascii_digit(),
+            FieldKind::Binary => matches!(b, b'0' | b'1' | b'b' | b'B'),
+            FieldKind::Octal => matches!(b, b'0'..=b'7' | b'o' | b'O'),
+            FieldKind::Hex => b.is_ascii_hexdigit() || matches!(b, b'x' | b'X'),
+            FieldKind::Float => b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'),
+            FieldKind::Percentage => b.is_ascii_digit() || matches!(b, b'.' | b'%'),
+        }
+    }
+
+    /// Check whether `s` is matched in full by the field's regex.
+    fn accepts(self, s: &[u8]) -> bool {
+        match self {
+            FieldKind::Text => !s.is_empty() && !s.contains(&b'\n'),
+            FieldKind::Character => s.len() == 1 && s[0] != b'\n',
+            FieldKind::Decimal => all_nonempty(strip_sign(s), |b| b.is_ascii_digit()),
+            FieldKind::Binary => radix(s, b"0b", b"0B", |b| matches!(b, b'0' | b'1')),
+            FieldKind::Octal => radix(s, b"0o", b"0O", |b| matches!(b, b'0'..=b'7')),
+            FieldKind::Hex => radix(s, b"0x", b"0X", |b| b.is_ascii_hexdigit()),
+            FieldKind::Float => match mantissa(strip_sign(s)) {
+                Some([]) => true,
+                Some([b'e' | b'E', exponent @ ..]) => {
+                    all_nonempty(strip_sign(exponent), |b| b.is_ascii_digit())
+                }
+                _ => false,
+            },
+            FieldKind::Percentage => mantissa(strip_sign(s)) == Some(&b"%"[..]),
+        }
+    }
+
+    /// Find the end of the only possible match starting at `pos`.
+    ///
+    /// Typed fields are only used when the literal after them can't continue
+    /// a match, so a match must end where the bytes stop continuing it.
+    fn scan(self, bytes: &[u8], pos: usize) -> Option<usize> {
+        let first = pos + 1;
+        if first > bytes.len() {
+            return None;
+        }
+        let end = match self {
+            FieldKind::Character => first,
+            _ => bytes[first..]
+                .iter()
+                .position(|&b| !self.continues(b))
+                .map_or(bytes.len(), |n| first + n),
+        };
+        self.accepts(&bytes[pos..end]).then_some(end)
+    }
+}
+
+fn strip_sign(s: &[u8]) -> &[u8] {
+    match s {
+        [b'+' | b'-', rest @ ..] => rest,
+        _ => s,
+    }
+}
+
+fn all_nonempty(s: &[u8], f: impl Fn(u8) -> bool) -> bool {
+    !s.is_empty() && s.iter().all(|&b| f(b))
+}
+
+/// Match `(?:0[pP])?[digits]+` in full.
+fn radix(s: &[u8], lower: &[u8], upper: &[u8], digit: impl Fn(u8) -> bool + Copy) -> bool {
+    let unprefixed = s.strip_prefix(lower).or_else(|| s.strip_prefix(upper));
+    unprefixed.is_some_and(|rest| all_nonempty(rest, digit)) || all_nonempty(s, digit)
+}
+
+/// Match `\d+\.?\d*|\.\d+` at the start of `s`, returning the rest.
+fn mantissa(s: &[u8]) -> Option<&[u8]> {
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let int = digits(s);
+    match &s[int..] {
+        [b'.', rest @ ..] if int > 0 => Some(&rest[digits(rest)..]),
+        rest if int > 0 => Some(rest),
+        [b'.', rest @ ..] if digits(rest) > 0 => Some(&rest[digits(rest)..]),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SplitField {
+    kind: FieldKind,
+    tail: memmem::Finder<'static>, // Literal text after the field
+}
+
+/// Matches a pattern by scanning for its literals instead of running a regex.
+#[derive(Debug, Clone)]
+pub(crate) struct Splitter {
+    head: String,
+    fields: Vec<SplitField>,
+}
+
+impl Splitter {
+    /// Create a splitter, or explain why the pattern doesn't qualify.
+    ///
+    /// `literals` are the texts around the fields, as returned by
+    /// [`literals`](super::builder::literals), and `captures` describe the fields.
+    pub(crate) fn new(literals: &[String], captures: &[CaptureInfo]) -> Result<Self> {
+        if let Some(info) = captures.iter().find(|info| !info.repeats.is_empty()) {
+            return Err(Error::spec(format!(
+                "field '{}' is repeated, which needs the regex engine",
+                info.name
+            )));
+        }
+        let (head, tails) = literals
+            .split_first()
+            .filter(|(_, tails)| tails.len() == captures.len())
+            .ok_or_else(|| Error::spec("pattern fields don't line up with its literals"))?;
+
+        let fields = captures
+            .iter()
+            .zip(tails)
+            .enumerate()
+            .map(|(i, (info, tail))| {
+                if info.kind != CaptureKind::Spec {
+                    return Err(Error::spec(format!(
+                        "field '{}' has its own regex, which needs the regex engine",
+                        info.name
+                    )));
+                }
+                let kind = FieldKind::for_capture(info).ok_or_else(|| {
+                    Error::spec(format!(
+                        "field '{}' has a width, alignment or precision, which needs the regex engine",
+                        info.name
+                    ))
+                })?;
+                let last = i + 1 == captures.len();
+                let separated = match tail.as_bytes().first() {
+                    Some(&b) => !kind.continues(b),
+                    None => last,
+                };
+                if !separated {
+                    return Err(Error::spec(format!(
+                        "field '{}' is not followed by literal text that ends it, \
+                         which needs the regex engine",
+                        info.name
+                    )));
+                }
+                Ok(SplitField {
+                    kind,
+                    tail: memmem::Finder::new(tail.as_bytes()).into_owned(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Splitter {
+            head: head.clone(),
+            fields,
+        })
+    }
+
+    /// Match `text` in full, storing the byte span of each field in `spans`.
+    ///
+    /// Returns `None` if the splitter can't decide, because the text isn't
+    /// ASCII (the regex treats Unicode digits as `\d`) or because matching
+    /// needed too much backtracking. The caller should use the regex then.
+    pub(crate) fn split(&self, text: &str, spans: &mut Vec<(usize, usize)>) -> Option<bool> {
+        if !text.is_ascii() {
+            return None;
+        }
+        if !text.starts_with(&self.head) {
+            return Some(false);
+        }
+
+        // Bound the work spent backtracking so the splitter stays linear
+        let mut budget = 2 * text.len() + 16;
+        spans.clear();
+        self.split_from(text.as_bytes(), 0, self.head.len(), spans, &mut budget)
+    }
+
+    fn split_from(
+        &self,
+        bytes: &[u8],
+        index: usize,
+        pos: usize,
+        spans: &mut Vec<(usize, usize)>,
+        budget: &mut usize,
+    ) -> Option<bool> {
+        let Some(field) = self.fields.get(index) else {
+            return Some(pos == bytes.len());
+        };
+        let tail = field.tail.needle();
+
+        if index + 1 == self.fields.len() {
+            // The last field must end exactly where its tail ends the text
+            let Some(end) = bytes.len().checked_sub(tail.len()) else {
+                return Some(false);
+            };
+            let matched = end > pos
+                && bytes[end..] == *tail
+                && match field.kind {
+                    FieldKind::Text => field.kind.accepts(&bytes[pos..end]),
+                    kind => kind.scan(bytes, pos) == Some(end),
+                };
+            spans.push((pos, end));
+            return Some(matched);
+        }
+
+        if field.kind != FieldKind::Text {
+            let Some(end) = field.kind.scan(bytes, pos) else {
+                return Some(false);
+            };
+            if !bytes[end..].starts_with(tail) {
+                return Some(false);
+            }
+            spans.push((pos, end));
+            return self.split_from(bytes, index + 1, end + tail.len(), spans, budget);
+        }
+
+        // Text fields are lazy: try each occurrence of the tail in turn,
+        // but never extend across a newline
+        let first = pos + 1;
+        if first > bytes.len() {
+            return Some(false);
+        }
+        let limit = memchr(b'\n', &bytes[pos..]).map_or(bytes.len(), |n| pos + n);
+        for offset in field.tail.find_iter(&bytes[first..]) {
+            let end = first + offset;
+            if end > limit {
+                break;
+            }
+            *budget = budget.checked_sub(1)?;
+
+            spans.truncate(index);
+            spans.push((pos, end));
+            if self.split_from(bytes, index + 1, end + tail.len(), spans, budget)? {
+                return Some(true);
+            }
+        }
+
+        Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::builder::build_regex_pattern;
+    use crate::parse::re::Regex;
+    use crate::template::Template;
+    use proptest::prelude::*;
+
+    fn splitter(pattern: &str) -> Option<Splitter> {
+        let template = Template::parse(pattern).unwrap();
+        let (_, captures) = build_regex_pattern(&template, None, &[], false).unwrap();
+        Splitter::new(&template.literals(), &captures).ok()
+    }
+
+    /// Compare the splitter against the anchored regex for the same pattern.
+    fn check(pattern: &str, text: &str) -> std::result::Result<(), TestCaseError> {
+        let template = Template::parse(pattern).unwrap();
+        let (regex_pattern, captures) = build_regex_pattern(&template, None, &[], false).unwrap();
+        let regex = Regex::new(&format!("^{}$", regex_pattern)).unwrap();
+        let splitter = Splitter::new(&template.literals(), &captures).unwrap();
+
+        let mut spans = Vec::new();
+        let Some(matched) = splitter.split(text, &mut spans) else {
+            return Ok(());
+        };
+        let expected = regex.captures(text).map(|cap| {
+            captures
+                .iter()
+                .map(|info| {
+                    let m = cap.get(info.group_index).unwrap();
+                    (m.start(), m.end())
+                })
+                .collect::<Vec<_>>()
+        });
+        prop_assert_eq!(
+            matched.then_some(spans),
+            expected,
+            "pattern {:?} on {:?}",
+            pattern,
+            text
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_qualifying_patterns() {
+        assert!(splitter("{date} {level} [{thread:d}] {latency:f}ms {message}").is_some());
+        assert!(splitter("{a:d}-{b:d}-{c:d}").is_some());
+        assert!(splitter("{value:.2%}").is_some());
+        assert!(splitter("plain text").is_some());
+
+        // Adjacent fields, ambiguous separators and width-constrained text
+        assert!(splitter("{a}{b}").is_none());
+        assert!(splitter("{a:f}-{b}").is_none());
+        assert!(splitter("{a:x}b{c}").is_none());
+        assert!(splitter("{a:>10} {b}").is_none());
+    }
+
+    #[test]
+    fn test_split_backtracks_like_regex() {
+        let splitter = splitter("{a}-{b}-{c:d}").unwrap();
+        let mut spans = Vec::new();
+        assert_eq!(splitter.split("x-y-z-1", &mut spans), Some(true));
+        assert_eq!(spans, [(0, 1), (2, 5), (6, 7)]);
+
+        assert_eq!(splitter.split("x-y-z", &mut spans), Some(false));
+        assert_eq!(splitter.split("x-y\n-1", &mut spans), Some(false));
+        assert_eq!(splitter.split("ü-y-1", &mut spans), None);
+    }
+
+    #[test]
+    fn test_split_gives_up_on_heavy_backtracking() {
+        let splitter = splitter("{a} {b} {c} {d:d}").unwrap();
+        let text = format!("{}x", "a ".repeat(200));
+        assert_eq!(splitter.split(&text, &mut Vec::new()), None);
+    }
+
+    #[test]
+    fn test_split_matches_regex_on_examples() {
+        let cases = [
+            (
+                "{date} {level} [{thread:d}] {latency:f}ms {message}",
+                "2024-01-15 WARN [12] 13.5ms slow request",
+            ),
+            (
+                "{date} {level} [{thread:d}] {latency:f}ms {message}",
+                "2024-01-15 WARN [x] 13.5ms slow",
+            ),
+            ("{a:d}-{b:d}", "10-20"),
+            ("{a:d}-{b:d}", "-10--20"),
+            ("{a:d}-{b:d}", "+-1"),
+            ("{x:x}/{y:b}/{z:o}", "0xff/0b101/0o17"),
+            ("{x:x}/{y:b}/{z:o}", "0x/0b/0o"),
+            ("{x:e};{y:g}", "1.5e-3;.5"),
+            ("{x:e};{y:g}", "1.;2e"),
+            ("{p:%} done", "12.5% done"),
+            ("{c:c}{{{rest}}}", "a{b}"),
+            ("[{a}]", "[]"),
+            ("{a} = {b}", "k = v = w"),
+        ];
+        for (pattern, text) in cases {
+            check(pattern, text).unwrap();
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn split_matches_regex(
+            pattern in prop::sample::select(vec![
+                "{a} {b}",
+                "{a}-{b}-{c:d}",
+                "[{a:d}] {b}",
+                "{a:d}.{b:d}.{c}",
+                "{a:f}ms {b}",
+                "{a:x},{b:o},{c:b}",
+                "{a:e}|{b:%}|{c:c}",
+                "x{a}y{b}x",
+                "{a} ",
+            ]),
+            text in "[-+0-9a-fxob.eE%|,;\\[\\] xy\n]{0,16}",
+        ) {
+            check(pattern, &text)?;
+        }
+    }
+}
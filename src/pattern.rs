@@ -0,0 +1,92 @@
+//! Structs bound to a pattern.
+//!
+//! [`GullwingFormat`] and [`GullwingParse`] tie a type to one pattern, so
+//! values of the type can be formatted and parsed without naming their
+//! fields at every call. With the `derive` feature they are derived from a
+//! `#[gullwing(pattern = "...")]` attribute, which checks at compile time
+//! that every field in the pattern is a field of the struct:
+//!
+//! ```
+//! # #[cfg(all(feature = "derive", feature = "parse"))] {
+//! use gullwing::{GullwingFormat, GullwingParse};
+//!
+//! #[derive(Debug, PartialEq, GullwingFormat, GullwingParse)]
+//! #[gullwing(pattern = "{method} {path} -> {status:d} in {millis:.1f}ms")]
+//! struct Request {
+//!     method: String,
+//!     path: String,
+//!     status: u16,
+//!     millis: f64,
+//! }
+//!
+//! let request = Request {
+//!     method: "GET".into(),
+//!     path: "/index.html".into(),
+//!     status: 200,
+//!     millis: 12.25,
+//! };
+//! let line = request.format().unwrap();
+//! assert_eq!(line, "GET /index.html -> 200 in 12.2ms");
+//!
+//! let parsed = Request::parse("POST /login -> 302 in 3.5ms").unwrap().unwrap();
+//! assert_eq!(parsed.status, 302);
+//! assert_eq!(parsed.millis, 3.5);
+//! # }
+//! ```
+//!
+//! The pattern's specs are checked when it is first used, and the compiled
+//! [`Formatter`] or [`Parser`](crate::Parser) is kept for later calls.
+
+use crate::error::Result;
+use crate::format::{Formatter, ValueProvider};
+#[cfg(feature = "parse")]
+use crate::parse::{ParseResult, Parser};
+
+/// A type that formats itself with a fixed pattern.
+///
+/// Field values come from the type's [`ValueProvider`] impl. Derive both with
+/// `#[derive(GullwingFormat)]`; every struct field named in the pattern must
+/// implement [`ToValue`](crate::ToValue).
+pub trait GullwingFormat: ValueProvider {
+    /// The pattern values of this type are formatted with.
+    const PATTERN: &'static str;
+
+    /// Get the formatter for [`PATTERN`](Self::PATTERN).
+    ///
+    /// Fails if the pattern is invalid.
+    fn formatter() -> Result<&'static Formatter>;
+
+    /// Format this value with the pattern.
+    fn format(&self) -> Result<String> {
+        Self::formatter()?.format_with(self)
+    }
+}
+
+/// A type that can be parsed from text matching a fixed pattern.
+///
+/// Derive it with `#[derive(GullwingParse)]`. Every struct field must appear
+/// in the pattern, unless it is marked `#[gullwing(skip)]` and filled with
+/// its `Default`, and must implement [`FromValue`](crate::FromValue).
+#[cfg(feature = "parse")]
+pub trait GullwingParse: Sized {
+    /// The pattern text is parsed with.
+    const PATTERN: &'static str;
+
+    /// Get the parser for [`PATTERN`](Self::PATTERN).
+    ///
+    /// Fails if the pattern is invalid.
+    fn parser() -> Result<&'static Parser>;
+
+    /// Build a value from the fields of a parse result.
+    fn from_result(result: &ParseResult) -> Result<Self>;
+
+    /// Parse text that matches the pattern exactly.
+    ///
+    /// Returns `Ok(None)` if the text doesn't match.
+    fn parse(text: &str) -> Result<Option<Self>> {
+        match Self::parser()?.parse(text)? {
+            Some(result) => Self::from_result(&result).map(Some),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Replaying pattern and text fixtures, for reproducing bugs.
+//!
+//! A [`Fixture`] is a pattern and a text, kept in a file whose first line is
+//! the pattern and whose remaining bytes, newlines and all, are the text.
+//! Replaying it compiles the pattern as both a [`Formatter`] and a
+//! [`Parser`], parses the text, and formats the parsed fields back, so one
+//! file captures a failure anywhere along that path.
+//!
+//! A bug report ships the fixture that shows the problem; a test replays a
+//! directory of them with [`replay_dir`] and asserts what should hold in a
+//! hook, keeping every reported case fixed.
+//!
+//! # Examples
+//!
+//! ```
+//! use gullwing::replay::Fixture;
+//!
+//! let fixture = Fixture::new("{name} is {age:d}", "Alice is 30");
+//! let replay = fixture.replay();
+//! assert!(replay.formatter.is_ok());
+//! assert_eq!(replay.formatted, Some(Ok("Alice is 30".to_string())));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::format::Formatter;
+use crate::parse::{ParseResult, Parser};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A pattern and a text to run through a formatter and a parser.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Fixture {
+    path: Option<PathBuf>, // The file it was read from, if any
+    pattern: String,
+    text: String,
+}
+
+impl Fixture {
+    /// Create a fixture from a pattern and a text.
+    pub fn new(pattern: impl Into<String>, text: impl Into<String>) -> Self {
+        Fixture {
+            path: None,
+            pattern: pattern.into(),
+            text: text.into(),
+        }
+    }
+
+    /// Read a fixture from a file.
+    ///
+    /// The first line is the pattern and the rest of the file the text. A
+    /// file without a newline is a pattern with an empty text. Fails if the
+    /// file can't be read or isn't UTF-8.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::write(format!("{}: {}", path.display(), e)))?;
+        let (pattern, text) = contents.split_once('\n').unwrap_or((&contents, ""));
+        Ok(Fixture {
+            path: Some(path.to_path_buf()),
+            pattern: pattern.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Write the fixture to a file that [`read`](Self::read) reads back.
+    ///
+    /// Fails if the pattern holds a newline, which the file can't represent,
+    /// or the file can't be written.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        if self.pattern.contains('\n') {
+            return Err(Error::write("a fixture's pattern can't contain a newline"));
+        }
+        fs::write(path, format!("{}\n{}", self.pattern, self.text)).map_err(Error::write)
+    }
+
+    /// Get the file the fixture was read from, if it was.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get the pattern.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Get the text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Run the fixture through a formatter and a parser.
+    pub fn replay(&self) -> Replay<'_> {
+        let formatter = Formatter::new(&self.pattern);
+        let parser = Parser::new(&self.pattern);
+        let parsed = parser.as_ref().ok().map(|parser| parser.parse(&self.text));
+        let formatted = match (&formatter, &parsed) {
+            (Ok(formatter), Some(Ok(Some(result)))) => Some(formatter.format_map(result.values())),
+            _ => None,
+        };
+        Replay {
+            fixture: self,
+            formatter,
+            parser,
+            parsed,
+            formatted,
+        }
+    }
+}
+
+/// What replaying a [`Fixture`] gave at each step.
+#[derive(Debug)]
+pub struct Replay<'f> {
+    /// The fixture replayed.
+    pub fixture: &'f Fixture,
+    /// The pattern compiled as a formatter.
+    pub formatter: Result<Formatter>,
+    /// The pattern compiled as a parser.
+    pub parser: Result<Parser>,
+    /// The text parsed, if the parser compiled.
+    pub parsed: Option<Result<Option<ParseResult>>>,
+    /// The parsed fields formatted, if the formatter compiled and the text
+    /// matched.
+    pub formatted: Option<Result<String>>,
+}
+
+/// Replay every fixture file in a directory, in file name order, calling
+/// `check` with each replay.
+///
+/// `check` holds the assertions; a panic in it fails the test replaying the
+/// directory, and [`Fixture::path`] tells which file did. Subdirectories are
+/// skipped. Returns the number of fixtures replayed, or an error if the
+/// directory or a file in it can't be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use gullwing::replay::replay_dir;
+///
+/// let count = replay_dir("tests/fixtures", |replay| {
+///     let path = replay.fixture.path().unwrap().display();
+///     if let Some(formatted) = &replay.formatted {
+///         assert!(formatted.is_ok(), "{path}: {formatted:?}");
+///     }
+/// })?;
+/// assert!(count > 0);
+/// # Ok::<(), gullwing::Error>(())
+/// ```
+pub fn replay_dir(dir: impl AsRef<Path>, mut check: impl FnMut(&Replay<'_>)) -> Result<usize> {
+    let dir = dir.as_ref();
+    let entries = fs::read_dir(dir)
+        .map_err(|e| Error::write(format!("{}: {}", dir.display(), e)))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(Error::write)?;
+    let mut paths: Vec<_> = entries.into_iter().filter(|path| path.is_file()).collect();
+    paths.sort();
+
+    for path in &paths {
+        let fixture = Fixture::read(path)?;
+        check(&fixture.replay());
+    }
+    Ok(paths.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::types::Value;
+
+    #[test]
+    fn test_replay_steps() {
+        let fixture = Fixture::new("{n:d}|{s:>4}", "12|  ab");
+        let replay = fixture.replay();
+        let parsed = replay.parsed.unwrap().unwrap().unwrap();
+        assert_eq!(parsed.get("n"), Some(&Value::from(12)));
+        assert_eq!(replay.formatted, Some(Ok("12|  ab".to_string())));
+
+        // Steps after a failure are skipped
+        let fixture = Fixture::new("{n:d}", "x");
+        let replay = fixture.replay();
+        assert!(matches!(replay.parsed, Some(Ok(None))));
+        assert_eq!(replay.formatted, None);
+
+        let fixture = Fixture::new("{n", "1");
+        let replay = fixture.replay();
+        assert!(replay.formatter.is_err() && replay.parser.is_err());
+        assert!(replay.parsed.is_none() && replay.formatted.is_none());
+    }
+
+    #[test]
+    fn test_replay_dir() {
+        let dir = std::env::temp_dir().join(format!("gullwing-replay-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        Fixture::new("{a}-{b}", "x-y\nz")
+            .write(dir.join("b"))
+            .unwrap();
+        Fixture::new("{v:.1f}", "2.5").write(dir.join("a")).unwrap();
+        fs::write(dir.join("c"), "{name}").unwrap();
+
+        let mut seen = Vec::new();
+        let count = replay_dir(&dir, |replay| {
+            let name = replay.fixture.path().unwrap().file_name().unwrap();
+            seen.push((
+                name.to_string_lossy().into_owned(),
+                replay.fixture.text().to_string(),
+                replay.formatted.clone(),
+            ));
+        })
+        .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            seen,
+            [
+                ("a".into(), "2.5".into(), Some(Ok("2.5".to_string()))),
+                // Fields stop at line breaks, and the text keeps them
+                ("b".into(), "x-y\nz".into(), None),
+                ("c".into(), "".into(), None),
+            ]
+        );
+
+        let err = Fixture::new("{a}\n", "").write(dir.join("d")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteError);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(replay_dir(&dir, |_| {}).is_err());
+    }
+}
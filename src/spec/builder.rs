@@ -0,0 +1,165 @@
+//! Programmatic construction of format specifications.
+
+use super::parser::FormatSpec;
+use super::types::{Alignment, Grouping, Sign, TypeSpec};
+use crate::error::{Error, Result};
+
+/// Builds a [`FormatSpec`] option by option.
+///
+/// Created with [`FormatSpec::builder`]. [`build`](Self::build) applies the
+/// same checks as parsing, so a built spec is one that could have been
+/// written as text.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::spec::{Alignment, FormatSpec, TypeSpec};
+///
+/// let spec = FormatSpec::builder()
+///     .width(10)
+///     .precision(2)
+///     .type_spec(TypeSpec::FixedLower)
+///     .align(Alignment::Right)
+///     .build()
+///     .unwrap();
+/// assert_eq!(spec, FormatSpec::parse(">10.2f").unwrap());
+///
+/// // Combinations Python rejects are errors here too
+/// let err = FormatSpec::builder().precision(2).type_spec(TypeSpec::Decimal).build();
+/// assert!(err.is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FormatSpecBuilder {
+    spec: FormatSpec,
+}
+
+impl FormatSpecBuilder {
+    /// Set the fill character. Requires an alignment.
+    pub fn fill(mut self, fill: char) -> Self {
+        self.spec.fill = Some(fill);
+        self
+    }
+
+    /// Set the alignment.
+    pub fn align(mut self, align: Alignment) -> Self {
+        self.spec.align = Some(align);
+        self
+    }
+
+    /// Set the sign option.
+    pub fn sign(mut self, sign: Sign) -> Self {
+        self.spec.sign = Some(sign);
+        self
+    }
+
+    /// Coerce negative zero to positive zero (`z`).
+    pub fn zero_flag(mut self, enabled: bool) -> Self {
+        self.spec.zero_flag = enabled;
+        self
+    }
+
+    /// Use the alternate form (`#`).
+    pub fn alternate(mut self, enabled: bool) -> Self {
+        self.spec.alternate = enabled;
+        self
+    }
+
+    /// Pad numbers with zeros after the sign (`0`).
+    pub fn zero_pad(mut self, enabled: bool) -> Self {
+        self.spec.zero_pad = enabled;
+        self
+    }
+
+    /// Set the minimum field width.
+    pub fn width(mut self, width: usize) -> Self {
+        self.spec.width = Some(width);
+        self
+    }
+
+    /// Set the thousands grouping.
+    pub fn grouping(mut self, grouping: Grouping) -> Self {
+        self.spec.grouping = Some(grouping);
+        self
+    }
+
+    /// Set the precision.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.spec.precision = Some(precision);
+        self
+    }
+
+    /// Set the presentation type.
+    pub fn type_spec(mut self, type_spec: TypeSpec) -> Self {
+        self.spec.type_spec = Some(type_spec);
+        self
+    }
+
+    /// Finish the spec, checking it with [`FormatSpec::validate`].
+    ///
+    /// A fill without an alignment is rejected as well, since the syntax
+    /// has no way to write it.
+    pub fn build(self) -> Result<FormatSpec> {
+        if self.spec.fill.is_some() && self.spec.align.is_none() {
+            return Err(Error::spec("fill character requires an alignment"));
+        }
+        self.spec.validate(None)?;
+        Ok(self.spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_matches_parse() {
+        let spec = FormatSpec::builder()
+            .fill('*')
+            .align(Alignment::Center)
+            .sign(Sign::Plus)
+            .zero_flag(true)
+            .width(12)
+            .grouping(Grouping::Comma)
+            .precision(3)
+            .type_spec(TypeSpec::FixedLower)
+            .build()
+            .unwrap();
+        assert_eq!(spec, FormatSpec::parse("*^+z12,.3f").unwrap());
+        assert_eq!(spec.to_string(), "*^+z12,.3f");
+
+        let spec = FormatSpec::builder()
+            .alternate(true)
+            .zero_pad(true)
+            .width(6)
+            .type_spec(TypeSpec::HexLower)
+            .build()
+            .unwrap();
+        assert_eq!(spec, FormatSpec::parse("#06x").unwrap());
+
+        assert_eq!(
+            FormatSpec::builder().build().unwrap(),
+            FormatSpec::default()
+        );
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        let err = FormatSpec::builder()
+            .fill('*')
+            .width(5)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::spec("fill character requires an alignment"));
+
+        assert!(FormatSpec::builder()
+            .grouping(Grouping::Comma)
+            .type_spec(TypeSpec::HexLower)
+            .build()
+            .is_err());
+        assert!(FormatSpec::builder()
+            .sign(Sign::Plus)
+            .type_spec(TypeSpec::String)
+            .build()
+            .is_err());
+    }
+}
@@ -10,8 +10,10 @@
 //!
 //! See: <https://docs.python.org/3/library/string.html#formatspec>
 
+pub mod builder;
 pub mod parser;
 pub mod types;
 
+pub use builder::FormatSpecBuilder;
 pub use parser::FormatSpec;
-pub use types::{Alignment, Grouping, Sign, TypeSpec};
+pub use types::{Alignment, Conversion, Grouping, Sign, TypeCategory, TypeInfo, TypeSpec};
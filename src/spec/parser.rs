@@ -1,14 +1,26 @@
 //! Parser for format specification strings.
 
+use super::builder::FormatSpecBuilder;
 use super::types::{Alignment, Grouping, Sign, TypeSpec};
 use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+/// The most distinct specs [`FormatSpec::shared`] keeps; past this, new specs
+/// are parsed without being cached.
+const SHARED_SPECS: usize = 4096;
+
+/// Parsed specs by their text.
+type SpecCache = HashMap<String, Arc<FormatSpec>, BuildHasherDefault<SpecHasher>>;
 
 /// A parsed format specification.
 ///
 /// Format: `[[fill]align][sign][z][#][0][width][grouping][.precision][type]`
 ///
 /// See: <https://docs.python.org/3/library/string.html#formatspec>
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct FormatSpec {
     /// Fill character (default: space)
     pub fill: Option<char>,
@@ -49,8 +61,119 @@ impl FormatSpec {
             return Ok(FormatSpec::default());
         }
 
-        let mut parser = SpecParser::new(input);
-        parser.parse()
+        SpecParser::new(input).parse()
+    }
+
+    /// Parse a spec, sharing one allocation among all fields in the process
+    /// that are written with the same spec text.
+    ///
+    /// Wide patterns repeat a handful of specs across many fields, so this
+    /// keeps their compile time and memory proportional to the distinct specs.
+    pub(crate) fn shared(input: &str) -> Result<Arc<Self>> {
+        static SPECS: OnceLock<Mutex<SpecCache>> = OnceLock::new();
+
+        if input.is_empty() {
+            return Ok(Self::empty());
+        }
+        let specs = SPECS.get_or_init(Default::default);
+        if let Some(spec) = specs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(input)
+        {
+            event!(TRACE, spec = input, "spec cache hit");
+            return Ok(Arc::clone(spec));
+        }
+        event!(TRACE, spec = input, "spec cache miss");
+
+        // Parse outside the lock; failures aren't cached
+        let spec = Arc::new(Self::parse(input)?);
+        let mut specs = specs.lock().unwrap_or_else(PoisonError::into_inner);
+        if specs.len() >= SHARED_SPECS {
+            return Ok(spec);
+        }
+        Ok(Arc::clone(specs.entry(input.to_string()).or_insert(spec)))
+    }
+
+    /// Get the shared default spec, as written `{}` or `{name}`.
+    pub(crate) fn empty() -> Arc<Self> {
+        static EMPTY: OnceLock<Arc<FormatSpec>> = OnceLock::new();
+        Arc::clone(EMPTY.get_or_init(Default::default))
+    }
+
+    /// Start building a spec option by option.
+    ///
+    /// See [`FormatSpecBuilder`] for an example.
+    pub fn builder() -> FormatSpecBuilder {
+        FormatSpecBuilder::default()
+    }
+
+    /// Check that the options are allowed together with the presentation type.
+    ///
+    /// Rejects the combinations Python rejects, such as a precision with an
+    /// integer type or a sign with a string. [`parse`](Self::parse) runs this
+    /// with no hint. A spec without a type can only be fully checked once the
+    /// value is known, so `type_hint` stands in for the missing type, e.g.
+    /// [`TypeSpec::String`] when the value is a string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::spec::{FormatSpec, TypeSpec};
+    ///
+    /// let spec = FormatSpec::parse("+10").unwrap();
+    /// assert!(spec.validate(Some(TypeSpec::Decimal)).is_ok());
+    /// assert!(spec.validate(Some(TypeSpec::String)).is_err());
+    /// ```
+    pub fn validate(&self, type_hint: Option<TypeSpec>) -> Result<()> {
+        let Some(type_spec) = self.type_spec.or(type_hint) else {
+            return Ok(());
+        };
+
+        // Python only groups some presentation types
+        if let Some(grouping) = self.grouping {
+            if !grouping.allowed_with(type_spec) {
+                return Err(grouping_error(grouping, type_spec));
+            }
+        }
+
+        let string = type_spec == TypeSpec::String;
+        let character = type_spec == TypeSpec::Character;
+        let target = if string {
+            "in string format specifier"
+        } else {
+            "in integer format specifier"
+        };
+        let rejected = |option: &str, target: &str| {
+            Err(Error::spec(format!("{} not allowed {}", option, target)))
+        };
+
+        // `n` also formats floats, so it is checked against the value instead
+        if self.precision.is_some() && type_spec.is_integer() && type_spec != TypeSpec::Number {
+            return rejected("precision", target);
+        }
+        if self.zero_flag && (string || type_spec.is_integer()) {
+            return rejected("negative zero coercion (z)", target);
+        }
+        match self.sign {
+            Some(Sign::Space) if string => return rejected("space", target),
+            Some(_) if string => return rejected("sign", target),
+            Some(_) if character => {
+                return rejected("sign", "with integer format specifier 'c'");
+            }
+            _ => {}
+        }
+        if self.alternate && string {
+            return rejected("alternate form (#)", target);
+        }
+        if self.alternate && character {
+            return rejected("alternate form (#)", "with integer format specifier 'c'");
+        }
+        if self.align == Some(Alignment::AfterSign) && string {
+            return rejected("'=' alignment", target);
+        }
+
+        Ok(())
     }
 
     /// Check if this spec is for a numeric type.
@@ -58,9 +181,73 @@ impl FormatSpec {
         self.type_spec.map(|t| t.is_numeric()).unwrap_or(false)
     }
 
-    /// Get the effective fill character (default: space).
+    /// Get the effective fill character.
+    ///
+    /// Without an explicit fill this is `0` when the `0` flag is set, as in
+    /// Python, and a space otherwise.
     pub fn fill_char(&self) -> char {
-        self.fill.unwrap_or(' ')
+        match self.fill {
+            Some(fill) => fill,
+            None if self.zero_pad => '0',
+            None => ' ',
+        }
+    }
+
+    /// Serialize this spec back to the format spec mini-language.
+    ///
+    /// Parsing the result gives back an equal spec, so patterns can be
+    /// rewritten by parsing, editing and printing their specs. The same text
+    /// is available through [`Display`](fmt::Display).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::spec::FormatSpec;
+    ///
+    /// let mut spec = FormatSpec::parse("*^+12,.3f").unwrap();
+    /// spec.width = Some(20);
+    /// assert_eq!(spec.to_spec_string(), "*^+20,.3f");
+    /// assert_eq!(FormatSpec::parse(&spec.to_string()).unwrap(), spec);
+    /// ```
+    pub fn to_spec_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for FormatSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A fill is only part of the syntax when followed by an alignment
+        if let Some(align) = self.align {
+            if let Some(fill) = self.fill {
+                f.write_char(fill)?;
+            }
+            f.write_char(align.to_char())?;
+        }
+        if let Some(sign) = self.sign {
+            f.write_char(sign.to_char())?;
+        }
+        if self.zero_flag {
+            f.write_char('z')?;
+        }
+        if self.alternate {
+            f.write_char('#')?;
+        }
+        if self.zero_pad {
+            f.write_char('0')?;
+        }
+        if let Some(width) = self.width {
+            write!(f, "{}", width)?;
+        }
+        if let Some(grouping) = self.grouping {
+            f.write_char(grouping.to_char())?;
+        }
+        if let Some(precision) = self.precision {
+            write!(f, ".{}", precision)?;
+        }
+        if let Some(type_spec) = self.type_spec {
+            f.write_char(type_spec.to_char())?;
+        }
+        Ok(())
     }
 }
 
@@ -80,7 +267,7 @@ impl<'a> SpecParser<'a> {
         }
     }
 
-    fn parse(&mut self) -> Result<FormatSpec> {
+    fn parse(mut self) -> Result<FormatSpec> {
         // Parse [[fill]align]
         self.parse_fill_and_align()?;
 
@@ -119,37 +306,31 @@ impl<'a> SpecParser<'a> {
 
         // Ensure we consumed all input
         if self.pos < self.input.len() {
-            return Err(Error::InvalidFormatSpec(format!(
-                "unexpected character at position {}: '{}'",
+            let c = self.input[self.pos..].chars().next().unwrap();
+            return Err(Error::spec_at(
+                format!("unexpected character '{}'", c),
                 self.pos,
-                self.input.chars().nth(self.pos).unwrap()
-            )));
+            ));
         }
 
-        Ok(self.spec.clone())
+        self.spec.validate(None)?;
+        Ok(self.spec)
     }
 
     fn parse_fill_and_align(&mut self) -> Result<()> {
-        // Look ahead for alignment characters
-        if let Some(first) = self.peek() {
-            if let Some(align) = Alignment::from_char(first) {
-                // Single character alignment: "<", ">", "^", "="
-                self.spec.align = Some(align);
-                self.advance();
-                return Ok(());
-            }
-        }
-
-        // Check for fill character followed by alignment
-        if self.remaining() >= 2 {
-            let chars: Vec<char> = self.input[self.pos..].chars().take(2).collect();
-            if chars.len() >= 2 {
-                if let Some(align) = Alignment::from_char(chars[1]) {
-                    self.spec.fill = Some(chars[0]);
-                    self.spec.align = Some(align);
-                    self.pos += 2;
-                }
-            }
+        let mut chars = self.input[self.pos..].chars();
+        let first = chars.next();
+        let second = chars.next();
+
+        // A fill character is only recognized before an alignment, so check
+        // for the two-character form first: "<<" is '<' fill, left aligned
+        if let (Some(fill), Some(align)) = (first, second.and_then(Alignment::from_char)) {
+            self.spec.fill = Some(fill);
+            self.spec.align = Some(align);
+            self.pos += fill.len_utf8() + 1;
+        } else if let Some(align) = first.and_then(Alignment::from_char) {
+            self.spec.align = Some(align);
+            self.advance();
         }
 
         Ok(())
@@ -176,11 +357,9 @@ impl<'a> SpecParser<'a> {
                     self.advance();
                 }
                 let width_str = &self.input[start..self.pos];
-                self.spec.width = Some(
-                    width_str
-                        .parse()
-                        .map_err(|_| Error::InvalidWidth(width_str.to_string()))?,
-                );
+                self.spec.width = Some(width_str.parse().map_err(|_| Error::InvalidWidth {
+                    value: width_str.to_string(),
+                })?);
             }
         }
         Ok(())
@@ -210,19 +389,20 @@ impl<'a> SpecParser<'a> {
                         self.advance();
                     }
                     let precision_str = &self.input[start..self.pos];
-                    self.spec.precision = Some(
-                        precision_str
-                            .parse()
-                            .map_err(|_| Error::InvalidWidth(precision_str.to_string()))?,
-                    );
+                    self.spec.precision =
+                        Some(precision_str.parse().map_err(|_| Error::InvalidWidth {
+                            value: precision_str.to_string(),
+                        })?);
                 } else {
-                    return Err(Error::InvalidFormatSpec(
-                        "precision must be followed by a number".to_string(),
+                    return Err(Error::spec_at(
+                        "precision must be followed by a number",
+                        self.pos,
                     ));
                 }
             } else {
-                return Err(Error::InvalidFormatSpec(
-                    "precision must be followed by a number".to_string(),
+                return Err(Error::spec_at(
+                    "precision must be followed by a number",
+                    self.pos,
                 ));
             }
         }
@@ -248,9 +428,35 @@ impl<'a> SpecParser<'a> {
             self.pos += c.len_utf8();
         }
     }
+}
+
+/// Build the error Python raises for a grouping its presentation type doesn't support.
+fn grouping_error(grouping: Grouping, type_spec: TypeSpec) -> Error {
+    Error::spec(format!(
+        "cannot specify '{}' with '{}'",
+        grouping.to_char(),
+        type_spec.to_char()
+    ))
+}
+
+/// FNV-1a, which hashes short spec texts much faster than the default hasher.
+struct SpecHasher(u64);
+
+impl Default for SpecHasher {
+    fn default() -> Self {
+        SpecHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
 
-    fn remaining(&self) -> usize {
-        self.input.len() - self.pos
+impl Hasher for SpecHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
     }
 }
 
@@ -288,6 +494,15 @@ mod tests {
         let spec = FormatSpec::parse("0>").unwrap();
         assert_eq!(spec.fill, Some('0'));
         assert_eq!(spec.align, Some(Alignment::Right));
+
+        // Multi-byte fills, and alignment characters used as the fill
+        let spec = FormatSpec::parse("\u{b7}^20").unwrap();
+        assert_eq!(spec.fill, Some('\u{b7}'));
+        assert_eq!(spec.width, Some(20));
+        let spec = FormatSpec::parse("<<5").unwrap();
+        assert_eq!((spec.fill, spec.align), (Some('<'), Some(Alignment::Left)));
+        let spec = FormatSpec::parse("^>").unwrap();
+        assert_eq!((spec.fill, spec.align), (Some('^'), Some(Alignment::Right)));
     }
 
     #[test]
@@ -305,14 +520,14 @@ mod tests {
     #[test]
     fn test_alternate_and_zero_pad() {
         let spec = FormatSpec::parse("#").unwrap();
-        assert_eq!(spec.alternate, true);
+        assert!(spec.alternate);
 
         let spec = FormatSpec::parse("0").unwrap();
-        assert_eq!(spec.zero_pad, true);
+        assert!(spec.zero_pad);
 
         let spec = FormatSpec::parse("#0").unwrap();
-        assert_eq!(spec.alternate, true);
-        assert_eq!(spec.zero_pad, true);
+        assert!(spec.alternate);
+        assert!(spec.zero_pad);
     }
 
     #[test]
@@ -375,15 +590,147 @@ mod tests {
     #[test]
     fn test_zero_pad_width() {
         let spec = FormatSpec::parse("05d").unwrap();
-        assert_eq!(spec.zero_pad, true);
+        assert!(spec.zero_pad);
         assert_eq!(spec.width, Some(5));
         assert_eq!(spec.type_spec, Some(TypeSpec::Decimal));
     }
 
+    #[test]
+    fn test_precision_rejected_for_integer_types() {
+        for spec in [".2d", ".2b", ".2o", ".2x", ".2X", ".2c", "08.3d"] {
+            let err = FormatSpec::parse(spec).unwrap_err();
+            assert_eq!(
+                err,
+                Error::spec("precision not allowed in integer format specifier".to_string()),
+                "spec {:?}",
+                spec
+            );
+        }
+
+        // Float and string types still accept precision, and so does `n`,
+        // which rejects it only when formatting an integer
+        assert!(FormatSpec::parse(".2f").is_ok());
+        assert!(FormatSpec::parse(".2n").is_ok());
+        assert!(FormatSpec::parse(".2s").is_ok());
+
+        // The locale supplies grouping for 'n'
+        assert_eq!(
+            FormatSpec::parse(",n").unwrap_err(),
+            Error::spec("cannot specify ',' with 'n'".to_string())
+        );
+        assert!(FormatSpec::parse("_n").is_err());
+        assert!(FormatSpec::parse(".2").is_ok());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for text in [
+            "",
+            "<",
+            "*^20",
+            "\u{b7}>5",
+            "<<5",
+            "+z#010_.3f",
+            " =8,d",
+            "-x",
+            "#06X",
+            ".2%",
+        ] {
+            let spec = FormatSpec::parse(text).unwrap();
+            assert_eq!(spec.to_string(), text);
+            assert_eq!(FormatSpec::parse(&spec.to_spec_string()).unwrap(), spec);
+        }
+
+        // Edited specs print in canonical order
+        let spec = FormatSpec {
+            type_spec: Some(TypeSpec::HexLower),
+            width: Some(8),
+            zero_pad: true,
+            alternate: true,
+            ..FormatSpec::default()
+        };
+        assert_eq!(spec.to_string(), "#08x");
+    }
+
+    #[test]
+    fn test_validate() {
+        let rejected = |spec: &str, hint| FormatSpec::parse(spec).unwrap().validate(hint);
+
+        // Without a type or hint there is nothing to check against
+        assert!(rejected("+=10,.2", None).is_ok());
+
+        let string = Some(TypeSpec::String);
+        for spec in ["+", "-", " ", "=5", "0=5", "z", "#", ","] {
+            assert!(rejected(spec, string).is_err(), "{:?}", spec);
+        }
+        assert!(rejected("*^10.3", string).is_ok());
+        assert_eq!(
+            rejected(" ", string).unwrap_err(),
+            Error::spec("space not allowed in string format specifier".to_string())
+        );
+
+        let decimal = Some(TypeSpec::Decimal);
+        assert!(rejected("+=10,", decimal).is_ok());
+        assert!(rejected(".2", decimal).is_err());
+        assert!(rejected("z", decimal).is_err());
+        assert!(rejected("z.2", Some(TypeSpec::FixedLower)).is_ok());
+
+        // An explicit type wins over the hint
+        assert!(rejected("+d", string).is_ok());
+
+        // Parsing rejects the same combinations for explicit types
+        assert_eq!(
+            FormatSpec::parse("+c").unwrap_err(),
+            Error::spec("sign not allowed with integer format specifier 'c'".to_string())
+        );
+        assert!(FormatSpec::parse("=5s").is_err());
+        assert!(FormatSpec::parse("=5c").is_ok());
+    }
+
+    #[test]
+    fn test_grouping_type_combinations() {
+        // ',' is for decimal and float types only; '_' also groups b/o/x/X
+        for ty in ["d", "e", "E", "f", "F", "g", "G", "%", ""] {
+            assert!(FormatSpec::parse(&format!(",{}", ty)).is_ok(), ",{}", ty);
+            assert!(FormatSpec::parse(&format!("_{}", ty)).is_ok(), "_{}", ty);
+        }
+        for ty in ["b", "o", "x", "X"] {
+            assert!(FormatSpec::parse(&format!("_{}", ty)).is_ok(), "_{}", ty);
+            assert_eq!(
+                FormatSpec::parse(&format!(",{}", ty)).unwrap_err(),
+                Error::spec(format!("cannot specify ',' with '{}'", ty))
+            );
+        }
+        for spec in [",s", "_s", ",c", "_c"] {
+            assert!(FormatSpec::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_zero_flag_rejected_for_integers() {
+        for spec in ["zd", "zx", "zc", "zn"] {
+            assert!(FormatSpec::parse(spec).is_err(), "spec {:?}", spec);
+        }
+        for spec in ["z", "zf", "ze", "zg", "z%", "z.1f"] {
+            assert!(FormatSpec::parse(spec).is_ok(), "spec {:?}", spec);
+        }
+    }
+
+    #[test]
+    fn test_alternate_form_rejected_for_strings() {
+        assert!(FormatSpec::parse("#s").is_err());
+        assert!(FormatSpec::parse("#c").is_err());
+        for spec in [
+            "#", "#d", "#n", "#b", "#o", "#x", "#X", "#e", "#f", "#g", "#%",
+        ] {
+            assert!(FormatSpec::parse(spec).is_ok(), "spec {:?}", spec);
+        }
+    }
+
     #[test]
     fn test_alternate_form() {
         let spec = FormatSpec::parse("#x").unwrap();
-        assert_eq!(spec.alternate, true);
+        assert!(spec.alternate);
         assert_eq!(spec.type_spec, Some(TypeSpec::HexLower));
     }
 }
@@ -1,9 +1,11 @@
 //! Types for format specification components.
 
+use std::fmt::Write;
+
 /// Alignment option for format specifications.
 ///
 /// See: <https://docs.python.org/3/library/string.html#formatspec>
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Alignment {
     /// Left-aligned: `<`
     Left,
@@ -41,7 +43,7 @@ impl Alignment {
 /// Sign option for numeric format specifications.
 ///
 /// See: <https://docs.python.org/3/library/string.html#formatspec>
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Sign {
     /// Only show sign for negative numbers: `-` (default)
     Minus,
@@ -75,7 +77,7 @@ impl Sign {
 /// Grouping option for numeric format specifications.
 ///
 /// See: <https://docs.python.org/3/library/string.html#formatspec>
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Grouping {
     /// Comma separator (every 3 digits): `,`
     Comma,
@@ -93,6 +95,21 @@ impl Grouping {
         }
     }
 
+    /// Check whether Python accepts this grouping with a presentation type.
+    ///
+    /// `,` only applies to decimal and float types, while `_` also groups
+    /// binary, octal and hex digits. Neither applies to strings, characters
+    /// or `n`, whose grouping comes from the locale.
+    pub fn allowed_with(self, type_spec: TypeSpec) -> bool {
+        match type_spec {
+            TypeSpec::String | TypeSpec::Character | TypeSpec::Number => false,
+            TypeSpec::Binary | TypeSpec::Octal | TypeSpec::HexLower | TypeSpec::HexUpper => {
+                self == Grouping::Underscore
+            }
+            _ => true,
+        }
+    }
+
     /// Convert to character representation.
     pub fn to_char(self) -> char {
         match self {
@@ -105,7 +122,7 @@ impl Grouping {
 /// Type specifier for format specifications.
 ///
 /// See: <https://docs.python.org/3/library/string.html#formatspec>
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TypeSpec {
     /// String (default): `s`
     String,
@@ -140,94 +157,233 @@ pub enum TypeSpec {
 }
 
 impl TypeSpec {
+    /// Every type specifier, with its metadata, in declaration order.
+    ///
+    /// This is the table [`from_char`](Self::from_char),
+    /// [`is_integer`](Self::is_integer) and [`is_float`](Self::is_float) read
+    /// from, so tools that list or validate specs stay in step with the parser.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::spec::{TypeCategory, TypeSpec};
+    ///
+    /// let float_codes: String = TypeSpec::all()
+    ///     .iter()
+    ///     .filter(|info| info.category == TypeCategory::Float)
+    ///     .map(|info| info.code)
+    ///     .collect();
+    /// assert_eq!(float_codes, "eEfFgG%");
+    ///
+    /// let hex = TypeSpec::HexLower.info();
+    /// assert_eq!((hex.code, hex.name), ('x', "hex"));
+    /// assert!(hex.integers && !hex.floats && !hex.strings);
+    /// ```
+    pub fn all() -> &'static [TypeInfo] {
+        TYPES
+    }
+
+    /// Get the metadata for this type specifier.
+    pub fn info(self) -> &'static TypeInfo {
+        &TYPES[self as usize]
+    }
+
     /// Parse a type specifier character.
     pub fn from_char(c: char) -> Option<Self> {
-        match c {
-            's' => Some(TypeSpec::String),
-            'b' => Some(TypeSpec::Binary),
-            'c' => Some(TypeSpec::Character),
-            'd' => Some(TypeSpec::Decimal),
-            'o' => Some(TypeSpec::Octal),
-            'x' => Some(TypeSpec::HexLower),
-            'X' => Some(TypeSpec::HexUpper),
-            'n' => Some(TypeSpec::Number),
-            'e' => Some(TypeSpec::ExponentLower),
-            'E' => Some(TypeSpec::ExponentUpper),
-            'f' => Some(TypeSpec::FixedLower),
-            'F' => Some(TypeSpec::FixedUpper),
-            'g' => Some(TypeSpec::GeneralLower),
-            'G' => Some(TypeSpec::GeneralUpper),
-            '%' => Some(TypeSpec::Percentage),
-            _ => None,
-        }
+        TYPES
+            .iter()
+            .find(|info| info.code == c)
+            .map(|info| info.type_spec)
     }
 
     /// Convert to character representation.
     pub fn to_char(self) -> char {
-        match self {
-            TypeSpec::String => 's',
-            TypeSpec::Binary => 'b',
-            TypeSpec::Character => 'c',
-            TypeSpec::Decimal => 'd',
-            TypeSpec::Octal => 'o',
-            TypeSpec::HexLower => 'x',
-            TypeSpec::HexUpper => 'X',
-            TypeSpec::Number => 'n',
-            TypeSpec::ExponentLower => 'e',
-            TypeSpec::ExponentUpper => 'E',
-            TypeSpec::FixedLower => 'f',
-            TypeSpec::FixedUpper => 'F',
-            TypeSpec::GeneralLower => 'g',
-            TypeSpec::GeneralUpper => 'G',
-            TypeSpec::Percentage => '%',
-        }
+        self.info().code
     }
 
     /// Check if this is a numeric type specifier.
     pub fn is_numeric(self) -> bool {
-        matches!(
-            self,
-            TypeSpec::Binary
-                | TypeSpec::Decimal
-                | TypeSpec::Octal
-                | TypeSpec::HexLower
-                | TypeSpec::HexUpper
-                | TypeSpec::Number
-                | TypeSpec::ExponentLower
-                | TypeSpec::ExponentUpper
-                | TypeSpec::FixedLower
-                | TypeSpec::FixedUpper
-                | TypeSpec::GeneralLower
-                | TypeSpec::GeneralUpper
-                | TypeSpec::Percentage
-        )
+        self.info().category != TypeCategory::String && self != TypeSpec::Character
     }
 
     /// Check if this is an integer type specifier.
     pub fn is_integer(self) -> bool {
-        matches!(
-            self,
-            TypeSpec::Binary
-                | TypeSpec::Character
-                | TypeSpec::Decimal
-                | TypeSpec::Octal
-                | TypeSpec::HexLower
-                | TypeSpec::HexUpper
-                | TypeSpec::Number
-        )
+        self.info().category == TypeCategory::Integer
     }
 
     /// Check if this is a float type specifier.
     pub fn is_float(self) -> bool {
-        matches!(
-            self,
-            TypeSpec::ExponentLower
-                | TypeSpec::ExponentUpper
-                | TypeSpec::FixedLower
-                | TypeSpec::FixedUpper
-                | TypeSpec::GeneralLower
-                | TypeSpec::GeneralUpper
-                | TypeSpec::Percentage
-        )
+        self.info().category == TypeCategory::Float
+    }
+}
+
+/// The family a [`TypeSpec`] belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeCategory {
+    /// Text presentation: `s`.
+    String,
+    /// Integer presentations: `b`, `c`, `d`, `o`, `x`, `X` and `n`.
+    Integer,
+    /// Float presentations: `e`, `E`, `f`, `F`, `g`, `G` and `%`.
+    Float,
+}
+
+/// Metadata about one type specifier, as listed by [`TypeSpec::all`].
+///
+/// The `integers`, `floats` and `strings` flags follow Python's `format()`,
+/// where `bool` counts as an integer. gullwing is more lenient in one place:
+/// `s` also formats numbers, using their `str()` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeInfo {
+    /// The type specifier.
+    pub type_spec: TypeSpec,
+    /// The character written in a spec, such as `'x'`.
+    pub code: char,
+    /// A short lowercase name, such as `"hex"`.
+    pub name: &'static str,
+    /// The family the specifier belongs to.
+    pub category: TypeCategory,
+    /// Whether integer values can be formatted with it.
+    pub integers: bool,
+    /// Whether float values can be formatted with it.
+    pub floats: bool,
+    /// Whether string values can be formatted with it.
+    pub strings: bool,
+}
+
+const fn type_info(
+    type_spec: TypeSpec,
+    code: char,
+    name: &'static str,
+    category: TypeCategory,
+) -> TypeInfo {
+    TypeInfo {
+        type_spec,
+        code,
+        name,
+        category,
+        integers: !matches!(category, TypeCategory::String),
+        floats: matches!(category, TypeCategory::Float) || matches!(type_spec, TypeSpec::Number),
+        strings: matches!(category, TypeCategory::String),
+    }
+}
+
+/// Indexed by `TypeSpec as usize`.
+const TYPES: &[TypeInfo] = &[
+    type_info(TypeSpec::String, 's', "string", TypeCategory::String),
+    type_info(TypeSpec::Binary, 'b', "binary", TypeCategory::Integer),
+    type_info(TypeSpec::Character, 'c', "character", TypeCategory::Integer),
+    type_info(TypeSpec::Decimal, 'd', "decimal", TypeCategory::Integer),
+    type_info(TypeSpec::Octal, 'o', "octal", TypeCategory::Integer),
+    type_info(TypeSpec::HexLower, 'x', "hex", TypeCategory::Integer),
+    type_info(TypeSpec::HexUpper, 'X', "upper hex", TypeCategory::Integer),
+    type_info(TypeSpec::Number, 'n', "number", TypeCategory::Integer),
+    type_info(
+        TypeSpec::ExponentLower,
+        'e',
+        "exponent",
+        TypeCategory::Float,
+    ),
+    type_info(
+        TypeSpec::ExponentUpper,
+        'E',
+        "upper exponent",
+        TypeCategory::Float,
+    ),
+    type_info(TypeSpec::FixedLower, 'f', "fixed", TypeCategory::Float),
+    type_info(
+        TypeSpec::FixedUpper,
+        'F',
+        "upper fixed",
+        TypeCategory::Float,
+    ),
+    type_info(TypeSpec::GeneralLower, 'g', "general", TypeCategory::Float),
+    type_info(
+        TypeSpec::GeneralUpper,
+        'G',
+        "upper general",
+        TypeCategory::Float,
+    ),
+    type_info(TypeSpec::Percentage, '%', "percentage", TypeCategory::Float),
+];
+
+/// Conversion flag applied to a value before its format spec.
+///
+/// Written between the field name and the spec: `{name!r:>20}`.
+///
+/// See: <https://docs.python.org/3/library/string.html#format-string-syntax>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Conversion {
+    /// Convert with `str()`: `!s`
+    Str,
+    /// Convert with `repr()`, quoting and escaping strings: `!r`
+    Repr,
+    /// Like `repr()`, but also escapes non-ASCII characters: `!a`
+    Ascii,
+}
+
+impl Conversion {
+    /// Parse a conversion character.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            's' => Some(Conversion::Str),
+            'r' => Some(Conversion::Repr),
+            'a' => Some(Conversion::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Convert to character representation.
+    pub fn to_char(self) -> char {
+        match self {
+            Conversion::Str => 's',
+            Conversion::Repr => 'r',
+            Conversion::Ascii => 'a',
+        }
+    }
+
+    /// Render a string the way this conversion would render a Python `str`.
+    ///
+    /// `!s` returns the text unchanged. `!r` and `!a` quote the text using
+    /// Python's quote selection and escape rules.
+    pub(crate) fn quote(self, text: &str) -> String {
+        if self == Conversion::Str {
+            return text.to_string();
+        }
+
+        // Python prefers single quotes unless the text contains only single quotes
+        let quote = if text.contains('\'') && !text.contains('"') {
+            '"'
+        } else {
+            '\''
+        };
+
+        let mut result = String::with_capacity(text.len() + 2);
+        result.push(quote);
+        for c in text.chars() {
+            match c {
+                '\\' => result.push_str("\\\\"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                c if c == quote => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                c if c.is_control() || (self == Conversion::Ascii && !c.is_ascii()) => {
+                    let code = c as u32;
+                    let _ = if code < 0x100 {
+                        write!(result, "\\x{:02x}", code)
+                    } else if code < 0x10000 {
+                        write!(result, "\\u{:04x}", code)
+                    } else {
+                        write!(result, "\\U{:08x}", code)
+                    };
+                }
+                c => result.push(c),
+            }
+        }
+        result.push(quote);
+        result
     }
 }
@@ -0,0 +1,702 @@
+//! The parsed form of a pattern, shared by formatting and parsing.
+//!
+//! [`Formatter`](crate::Formatter) and [`Parser`](crate::Parser) both start
+//! from a [`Template`], so a pattern's fields and specs are read the same way
+//! no matter which side uses it.
+
+use crate::error::{Error, Result};
+use crate::spec::{Conversion, FormatSpec};
+use std::fmt::{self, Write as _};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// A pattern split into literal text and replacement fields.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::spec::TypeSpec;
+/// use gullwing::{Formatter, Template};
+///
+/// let template = Template::parse("{name:>10} = {value:.2f}").unwrap();
+/// let names: Vec<_> = template.fields().iter().map(|f| f.name()).collect();
+/// assert_eq!(names, [Some("name"), Some("value")]);
+/// assert_eq!(template.fields()[1].spec().type_spec, Some(TypeSpec::FixedLower));
+///
+/// let formatter = Formatter::new("{name:>10} = {value:.2f}").unwrap();
+/// assert_eq!(formatter.template(), &template);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Template {
+    pattern: String,
+    fields: Vec<TemplateField>,
+    tail: String, // Text after the last field
+}
+
+/// A replacement field of a [`Template`], with the literal text before it.
+#[derive(Debug, Clone)]
+pub struct TemplateField {
+    prefix: String,
+    name: Option<String>,
+    index: Option<usize>,
+    path: Vec<Access>,
+    conversion: Option<Conversion>,
+    spec: Arc<FormatSpec>,    // Shared by all fields written with the same spec
+    strftime: Option<String>, // Datetime format used instead of `spec`
+    custom_type: Option<String>, // Registered or built-in parse-only type
+    regex: Option<String>,    // Inline regex of a `{name:/regex/}` parse field
+    choices: Option<Vec<String>>, // Alternatives of a `{name:(a|b)}` parse field
+    offset: usize,            // Byte offset of the field's contents in the pattern
+}
+
+/// One step of a field's path into a composite value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// `.name`, looked up with [`Value::get_attr`](crate::Value::get_attr)
+    Attr(String),
+    /// `[key]`, looked up with [`Value::get_item`](crate::Value::get_item)
+    Item(String),
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Attr(name) => write!(f, ".{}", name),
+            Access::Item(key) => write!(f, "[{}]", key),
+        }
+    }
+}
+
+impl Template {
+    /// Parse a pattern.
+    ///
+    /// Fields are checked for a valid name, path, conversion flag and spec;
+    /// whether a formatter or parser can use them is up to each side.
+    pub fn parse(pattern: &str) -> Result<Self> {
+        Self::parse_fields(pattern, None)
+    }
+
+    /// Parse a pattern for a parser.
+    ///
+    /// A spec for which `is_custom_type` returns `true` is read as the name
+    /// of a custom parse type, one written between slashes (`{name:/regex/}`)
+    /// as an inline regex, and one between parentheses (`{name:(a|b)}`) as a
+    /// choice set, instead of as a format spec.
+    #[cfg(feature = "parse")]
+    pub(crate) fn parse_with_types(
+        pattern: &str,
+        is_custom_type: &dyn Fn(&str) -> bool,
+    ) -> Result<Self> {
+        Self::parse_fields(pattern, Some(is_custom_type))
+    }
+
+    /// Parse a pattern, with parse-only specs read as in
+    /// [`parse_with_types`](Self::parse_with_types) if `parse_specs` is set.
+    fn parse_fields(pattern: &str, parse_specs: Option<&dyn Fn(&str) -> bool>) -> Result<Self> {
+        // Every field opens with a brace, so this is enough room for them
+        let bytes = pattern.as_bytes();
+        let mut fields = Vec::with_capacity(bytes.iter().filter(|&&b| b == b'{').count());
+        let mut prefix = String::new();
+        let mut auto_index = 0;
+        let mut pos = 0;
+
+        // Copy literal text a run at a time, up to the next brace
+        while let Some(offset) = pattern[pos..].find(['{', '}']) {
+            let brace = pos + offset;
+            prefix.push_str(&pattern[pos..brace]);
+            if bytes.get(brace + 1) == Some(&bytes[brace]) {
+                // Escaped brace
+                prefix.push(char::from(bytes[brace]));
+                pos = brace + 2;
+            } else if bytes[brace] == b'{' {
+                let end = closing_brace(pattern, brace)?;
+                let mut field = parse_field(&pattern[brace + 1..end], &mut auto_index, parse_specs)
+                    .map_err(|e| e.offset_by(brace + 1))?;
+                field.prefix = std::mem::take(&mut prefix);
+                field.offset = brace + 1;
+                fields.push(field);
+                pos = end + 1;
+            } else {
+                return Err(Error::spec_at("unmatched '}' in format string", brace));
+            }
+        }
+        prefix.push_str(&pattern[pos..]);
+
+        Ok(Template {
+            pattern: pattern.to_string(),
+            fields,
+            tail: prefix,
+        })
+    }
+
+    /// Get the pattern text.
+    ///
+    /// After an edit such as [`Formatter::with_spec`](crate::Formatter::with_spec),
+    /// this is an equivalent pattern written out from the fields.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Get the fields, in pattern order.
+    pub fn fields(&self) -> &[TemplateField] {
+        &self.fields
+    }
+
+    /// Get the literal text after the last field.
+    pub fn tail(&self) -> &str {
+        &self.tail
+    }
+
+    /// Get the literal text around the fields, with escaped braces unescaped.
+    ///
+    /// There is one more literal than there are fields, so field `i` sits
+    /// between literals `i` and `i + 1`.
+    #[cfg(feature = "parse")]
+    pub(crate) fn literals(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .map(|f| f.prefix.clone())
+            .chain(std::iter::once(self.tail.clone()))
+            .collect()
+    }
+
+    /// Return a copy with `spec` set on every field named `field`.
+    ///
+    /// `field` is written as in a pattern, e.g. `name`, `0` or `user.name`.
+    pub(crate) fn with_spec(&self, field: &str, spec: &FormatSpec) -> Result<Self> {
+        let (root, path) = parse_path(field).ok_or_else(|| Error::missing(field.to_string()))?;
+        let index = root.parse::<usize>().ok();
+        let mut template = self.clone();
+        let mut found = false;
+
+        for f in &mut template.fields {
+            if f.has_root(root, index) && f.path == path {
+                f.spec = Arc::new(spec.clone());
+                f.strftime = None;
+                f.custom_type = None;
+                f.regex = None;
+                f.choices = None;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(Error::missing(field.to_string()));
+        }
+
+        template.pattern = template.render();
+        Ok(template)
+    }
+
+    /// Return a copy with every field whose root is `field` replaced by the
+    /// literal text `render` returns for its position in [`fields`](Self::fields).
+    ///
+    /// `field` is a name or positional index; fields reaching into it with a
+    /// path are replaced too.
+    pub(crate) fn with_bound<F>(&self, field: &str, mut render: F) -> Result<Self>
+    where
+        F: FnMut(usize) -> Result<String>,
+    {
+        let index = field.parse::<usize>().ok();
+        let mut fields = Vec::with_capacity(self.fields.len());
+        let mut literal = String::new(); // Bound text waiting for the next field
+        let mut found = false;
+
+        for (i, f) in self.fields.iter().enumerate() {
+            if f.has_root(field, index) {
+                literal.push_str(&f.prefix);
+                literal.push_str(&render(i)?);
+                found = true;
+            } else {
+                let mut f = f.clone();
+                f.prefix.insert_str(0, &std::mem::take(&mut literal));
+                fields.push(f);
+            }
+        }
+
+        if !found {
+            return Err(Error::missing(field.to_string()));
+        }
+
+        literal.push_str(&self.tail);
+        let mut template = Template {
+            pattern: String::new(),
+            fields,
+            tail: literal,
+        };
+        template.pattern = template.render();
+        Ok(template)
+    }
+
+    /// Return a copy with `from` replaced by `to` in the literal text.
+    pub(crate) fn with_literal_replaced(&self, from: &str, to: &str) -> Self {
+        let mut template = self.clone();
+        if !from.is_empty() {
+            for f in &mut template.fields {
+                f.prefix = f.prefix.replace(from, to);
+            }
+            template.tail = template.tail.replace(from, to);
+            template.pattern = template.render();
+        }
+        template
+    }
+
+    /// Write the fields back out as a pattern, escaping braces in literal text.
+    fn render(&self) -> String {
+        let mut pattern = String::new();
+        for field in &self.fields {
+            push_literal(&mut pattern, &field.prefix);
+            pattern.push('{');
+            pattern.push_str(&field.display_name());
+            if let Some(conversion) = field.conversion {
+                pattern.push('!');
+                pattern.push(conversion.to_char());
+            }
+            let spec = match (&field.strftime, &field.custom_type, &field.regex) {
+                (Some(format), _, _) => format.clone(),
+                (None, Some(name), _) => name.clone(),
+                (None, None, Some(regex)) => format!("/{}/", regex),
+                (None, None, None) => match &field.choices {
+                    Some(choices) => format!("({})", choices.join("|")),
+                    None => field.spec.to_string(),
+                },
+            };
+            if !spec.is_empty() {
+                pattern.push(':');
+                pattern.push_str(&spec);
+            }
+            pattern.push('}');
+        }
+        push_literal(&mut pattern, &self.tail);
+        pattern
+    }
+}
+
+/// Templates are equal when their fields and literal text are, so `{}` and
+/// `{0}`, or `{x}` and `{x:}`, compare equal.
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields && self.tail == other.tail
+    }
+}
+
+impl Eq for Template {}
+
+impl Hash for Template {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fields.hash(state);
+        self.tail.hash(state);
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl TemplateField {
+    /// Get the literal text before this field, with escaped braces unescaped.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Get the field name, or `None` for a positional field.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Get the position of a positional field, numbering `{}` fields
+    /// automatically.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// Get the attribute and item path after the name, as in `{user.tags[0]}`.
+    pub fn path(&self) -> &[Access] {
+        &self.path
+    }
+
+    /// Get the conversion flag.
+    pub fn conversion(&self) -> Option<Conversion> {
+        self.conversion
+    }
+
+    /// Get the format spec.
+    pub fn spec(&self) -> &FormatSpec {
+        &self.spec
+    }
+
+    /// Get the format spec, shared with other fields that use the same one.
+    pub(crate) fn shared_spec(&self) -> &Arc<FormatSpec> {
+        &self.spec
+    }
+
+    /// Get the strftime-style format of a datetime field such as
+    /// `{ts:%Y-%m-%d}`, used in place of [`spec`](Self::spec).
+    ///
+    /// A spec is read this way when it contains `%` but isn't a valid
+    /// format spec, so `{x:%}` is still a percentage.
+    #[cfg(feature = "chrono")]
+    pub fn strftime(&self) -> Option<&str> {
+        self.strftime.as_deref()
+    }
+
+    /// Get the name of the parse-only type of a field such as `{addr:ip}`,
+    /// registered with [`ParserBuilder::with_type`](crate::parse::ParserBuilder::with_type)
+    /// or built in like the `ti` datetime type, and used in place of
+    /// [`spec`](Self::spec).
+    #[cfg(feature = "parse")]
+    pub fn custom_type(&self) -> Option<&str> {
+        self.custom_type.as_deref()
+    }
+
+    /// Get the inline regex of a parse field such as `{token:/[A-Z]{3}-\d+/}`,
+    /// used in place of [`spec`](Self::spec).
+    #[cfg(feature = "parse")]
+    pub fn regex(&self) -> Option<&str> {
+        self.regex.as_deref()
+    }
+
+    /// Get the alternatives of a parse field such as `{status:(OK|FAIL)}`,
+    /// which matches only one of them and is used in place of
+    /// [`spec`](Self::spec).
+    #[cfg(feature = "parse")]
+    pub fn choices(&self) -> Option<&[String]> {
+        self.choices.as_deref()
+    }
+
+    /// Get the byte offset of the field's contents in the original pattern.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Check whether this field is named `root`, or is positional field `index`.
+    fn has_root(&self, root: &str, index: Option<usize>) -> bool {
+        match (&self.name, self.index) {
+            (Some(name), _) => name == root,
+            (None, Some(i)) => Some(i) == index,
+            (None, None) => false,
+        }
+    }
+
+    /// Get the field name as written in a pattern, including its path.
+    pub(crate) fn display_name(&self) -> String {
+        let mut name = match (&self.name, self.index) {
+            (Some(name), _) => name.clone(),
+            (None, Some(index)) => index.to_string(),
+            (None, None) => String::new(),
+        };
+        for access in &self.path {
+            let _ = write!(name, "{}", access);
+        }
+        name
+    }
+
+    /// Get the parts compared for equality and hashing; the offset is left out.
+    fn key(&self) -> FieldKey<'_> {
+        (
+            &self.prefix,
+            self.name.as_deref(),
+            self.index,
+            &self.path,
+            self.conversion,
+            &self.spec,
+            self.strftime.as_deref(),
+            self.custom_type.as_deref(),
+            self.regex.as_deref(),
+            self.choices.as_deref(),
+        )
+    }
+}
+
+/// The parts of a field that affect output: prefix, name, index, attribute
+/// and item path, conversion, spec, datetime format, custom type, regex and
+/// choices.
+type FieldKey<'a> = (
+    &'a str,
+    Option<&'a str>,
+    Option<usize>,
+    &'a [Access],
+    Option<Conversion>,
+    &'a FormatSpec,
+    Option<&'a str>,
+    Option<&'a str>,
+    Option<&'a str>,
+    Option<&'a [String]>,
+);
+
+impl PartialEq for TemplateField {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for TemplateField {}
+
+impl Hash for TemplateField {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Append literal text to a pattern, doubling its braces.
+fn push_literal(pattern: &mut String, text: &str) {
+    for c in text.chars() {
+        if c == '{' || c == '}' {
+            pattern.push(c);
+        }
+        pattern.push(c);
+    }
+}
+
+/// Find the brace that closes the field opened at byte `open`.
+///
+/// Braces inside the field, as in an inline regex's `{3}`, must balance.
+fn closing_brace(pattern: &str, open: usize) -> Result<usize> {
+    let mut depth = 0;
+    for (i, b) in pattern.bytes().enumerate().skip(open + 1) {
+        match b {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Ok(i),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(Error::spec_at("unclosed '{' in format string", open))
+}
+
+/// Parse a field specification.
+///
+/// Returns a field with an empty prefix and zero offset.
+fn parse_field(
+    field: &str,
+    auto_index: &mut usize,
+    parse_specs: Option<&dyn Fn(&str) -> bool>,
+) -> Result<TemplateField> {
+    // Split on the first ':' outside of an item key, so `{a[:]}` works
+    let mut depth = false;
+    let split = field
+        .char_indices()
+        .find(|&(_, c)| {
+            match c {
+                '[' => depth = true,
+                ']' => depth = false,
+                _ => {}
+            }
+            c == ':' && !depth
+        })
+        .map_or(field.len(), |(i, _)| i);
+    let head = &field[..split];
+    let spec_part = field.get(split + 1..).unwrap_or("");
+
+    // Split off the conversion flag, which follows the path
+    let name_end = head.rfind(']').map_or(0, |i| i + 1);
+    let (name_part, conversion) = match head[name_end..].find('!') {
+        Some(bang) => {
+            let (name, flag) = head.split_at(name_end + bang);
+            let flag = &flag[1..];
+            let mut flag_chars = flag.chars();
+            let conversion = match (flag_chars.next(), flag_chars.next()) {
+                (Some(c), None) => Conversion::from_char(c),
+                _ => None,
+            };
+            match conversion {
+                Some(conversion) => (name, Some(conversion)),
+                None => {
+                    return Err(Error::spec(format!(
+                        "invalid conversion specifier '!{}'",
+                        flag
+                    )))
+                }
+            }
+        }
+        None => (head, None),
+    };
+
+    // Split off the attribute and item path
+    let (name_part, path) =
+        parse_path(name_part).ok_or_else(|| Error::invalid_name(field, head))?;
+
+    // Parse the name/index part
+    let (name, index) = if name_part.is_empty() {
+        // Auto-numbered positional field
+        let idx = *auto_index;
+        *auto_index += 1;
+        (None, Some(idx))
+    } else if let Ok(idx) = name_part.parse::<usize>() {
+        // Explicit positional field
+        (None, Some(idx))
+    } else if name_part.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        // Named field
+        (Some(name_part.to_string()), None)
+    } else {
+        return Err(Error::invalid_name(name_part, head));
+    };
+
+    let mut field = TemplateField {
+        prefix: String::new(),
+        name,
+        index,
+        path,
+        conversion,
+        spec: FormatSpec::empty(),
+        strftime: None,
+        custom_type: None,
+        regex: None,
+        choices: None,
+        offset: 0,
+    };
+
+    // Take the spec as a custom type name, inline regex or choice set when
+    // parsing, or parse it as a format spec or datetime format
+    if let Some(is_custom_type) = parse_specs {
+        if is_custom_type(spec_part) {
+            field.custom_type = Some(spec_part.to_string());
+            return Ok(field);
+        }
+        let regex = spec_part
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'));
+        if let Some(regex) = regex {
+            field.regex = Some(regex.to_string());
+            return Ok(field);
+        }
+        let choices = spec_part
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'));
+        if let Some(choices) = choices {
+            if choices.split('|').any(str::is_empty) {
+                return Err(Error::spec_at(
+                    format!("empty alternative in choice set '{}'", spec_part),
+                    head.len() + 1,
+                ));
+            }
+            field.choices = Some(choices.split('|').map(str::to_string).collect());
+            return Ok(field);
+        }
+    }
+    (field.spec, field.strftime) = match FormatSpec::shared(spec_part) {
+        Ok(spec) => (spec, None),
+        #[cfg(feature = "chrono")]
+        Err(_) if spec_part.contains('%') => {
+            check_strftime(spec_part).map_err(|e| e.offset_by(head.len() + 1))?;
+            (FormatSpec::empty(), Some(spec_part.to_string()))
+        }
+        Err(e) => return Err(e.offset_by(head.len() + 1)),
+    };
+
+    Ok(field)
+}
+
+/// Check that a datetime format only uses directives chrono knows.
+#[cfg(feature = "chrono")]
+fn check_strftime(format: &str) -> Result<()> {
+    use chrono::format::{Item, StrftimeItems};
+
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(Error::spec_at(
+            format!("invalid datetime format '{}'", format),
+            0,
+        ));
+    }
+    Ok(())
+}
+
+/// Split a field name into its root and its `.attr` / `[key]` path.
+///
+/// Returns `None` if an attribute or key is empty, an attribute is not a
+/// plain identifier, or anything but `.` or `[` follows a `]`.
+fn parse_path(name: &str) -> Option<(&str, Vec<Access>)> {
+    let root_end = name.find(['.', '[']).unwrap_or(name.len());
+    let (root, mut rest) = name.split_at(root_end);
+    let mut path = Vec::new();
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[1..];
+        let (access, len) = match c {
+            '.' => {
+                let len = rest.find(['.', '[']).unwrap_or(rest.len());
+                let attr = &rest[..len];
+                if attr.is_empty() || !attr.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return None;
+                }
+                (Access::Attr(attr.to_string()), len)
+            }
+            '[' => {
+                let len = rest.find(']').filter(|&len| len > 0)?;
+                (Access::Item(rest[..len].to_string()), len + 1)
+            }
+            _ => return None,
+        };
+        path.push(access);
+        rest = &rest[len..];
+    }
+
+    Some((root, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::TypeSpec;
+
+    #[test]
+    fn test_parse() {
+        let template = Template::parse("a {{b}} {x.y[0]!r:>4} {} {2:d}!").unwrap();
+        let fields = template.fields();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].prefix(), "a {b} ");
+        assert_eq!(fields[0].name(), Some("x"));
+        assert_eq!(
+            fields[0].path(),
+            [Access::Attr("y".to_string()), Access::Item("0".to_string())]
+        );
+        assert_eq!(fields[0].conversion(), Some(Conversion::Repr));
+        assert_eq!(fields[0].spec().width, Some(4));
+        assert_eq!(fields[0].offset, 9);
+        assert_eq!(fields[1].index(), Some(0));
+        assert_eq!(fields[2].index(), Some(2));
+        assert_eq!(fields[2].spec().type_spec, Some(TypeSpec::Decimal));
+        assert_eq!(template.tail(), "!");
+        #[cfg(feature = "parse")]
+        assert_eq!(template.literals(), ["a {b} ", " ", " ", "!"]);
+        assert_eq!(template.to_string(), "a {{b}} {x.y[0]!r:>4} {} {2:d}!");
+
+        assert!(Template::parse("{").is_err());
+        assert!(Template::parse("}").is_err());
+        assert!(Template::parse("{a[]}").is_err());
+        assert!(Template::parse("{a!x}").is_err());
+        assert!(Template::parse("{a:q}").is_err());
+    }
+
+    #[test]
+    fn test_shared_specs() {
+        let template = Template::parse("{a:>10.2f} {b:>10.2f} {c:>10} {d}").unwrap();
+        let other = Template::parse("{x:>10.2f}").unwrap();
+        let specs: Vec<_> = template.fields().iter().map(|f| f.shared_spec()).collect();
+        assert!(Arc::ptr_eq(specs[0], specs[1]));
+        assert!(Arc::ptr_eq(specs[0], other.fields()[0].shared_spec()));
+        assert!(!Arc::ptr_eq(specs[0], specs[2]));
+        assert_eq!(**specs[3], FormatSpec::default());
+
+        // Edits get a spec of their own
+        let edited = template
+            .with_spec("a", &FormatSpec::parse(">10.2f").unwrap())
+            .unwrap();
+        assert_eq!(edited.fields()[0].spec(), template.fields()[1].spec());
+    }
+
+    #[test]
+    fn test_edits() {
+        let template = Template::parse("{} | {name}").unwrap();
+        let edited = template
+            .with_spec("name", &FormatSpec::parse(">5").unwrap())
+            .unwrap()
+            .with_literal_replaced("|", "{}");
+        assert_eq!(edited.pattern(), "{0} {{}} {name:>5}");
+        assert_eq!(Template::parse(edited.pattern()).unwrap(), edited);
+        assert_eq!(Template::parse("{0} | {name}").unwrap(), template);
+        assert!(template.with_spec("other", &FormatSpec::default()).is_err());
+    }
+}
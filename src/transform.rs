@@ -0,0 +1,833 @@
+//! Parse-then-format pipelines.
+
+use crate::error::{Error, Result};
+use crate::format::Formatter;
+use crate::parse::{Locations, ParseResult, Parser};
+use crate::types::Value;
+use std::fs;
+use std::io::{BufRead, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Reformats text by parsing it with one pattern and formatting the fields with another.
+///
+/// This is the core of the `shuffle` use case. Fields in the output pattern
+/// are resolved to input captures once, when the transformer is built, so
+/// each line only pays for matching, conversion and formatting.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::Transformer;
+///
+/// let transformer = Transformer::new("{date} {level} {message}", "{level:<5}| {message}").unwrap();
+/// let output = transformer.transform("2024-01-15 INFO Hello").unwrap();
+/// assert_eq!(output.as_deref(), Some("INFO | Hello"));
+///
+/// assert_eq!(transformer.transform("garbage").unwrap(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Transformer {
+    parser: Parser,
+    formatter: Formatter,
+    slots: Vec<usize>, // Capture index for each output lookup slot
+}
+
+/// Reusable buffers for [`Transformer::transform_into`].
+///
+/// Keeping one `Scratch` per thread and passing it to every call lets the
+/// pipeline reach a steady state where no heap allocations happen per line:
+/// captured strings are written into buffers left over from previous lines.
+#[derive(Debug, Clone)]
+pub struct Scratch {
+    locs: Locations,
+    values: Vec<Value>,
+    buf: String,
+}
+
+/// How far [`Transformer::transform_reader`] has got through its input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// Bytes read so far, including line terminators.
+    pub bytes: u64,
+    /// Lines read so far.
+    pub lines: u64,
+    /// Lines that matched the input pattern and were written out.
+    pub matches: u64,
+}
+
+/// How far [`Transformer::transform_reader_checkpointed`] had got when it
+/// last saved its place.
+///
+/// The input is read up to `progress.bytes`, a line boundary, and the output
+/// written up to `written`, so a run resumed from here neither skips nor
+/// repeats a line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Checkpoint {
+    /// The totals so far; `progress.bytes` is where reading resumes.
+    pub progress: Progress,
+    /// Bytes written to the output so far.
+    pub written: u64,
+}
+
+/// Saves a checkpoint during a transform.
+type SaveCheckpoint<'a> = &'a mut dyn FnMut(Checkpoint) -> Result<()>;
+
+/// Somewhere to keep a [`Checkpoint`] between runs of a long transform.
+///
+/// [`FileCheckpoint`] keeps it in a file; `Option<Checkpoint>` keeps it in
+/// memory, which suits tests and transforms retried within one process.
+pub trait CheckpointStore {
+    /// Get the last checkpoint saved, or `None` to start from the beginning.
+    fn load(&mut self) -> Result<Option<Checkpoint>>;
+
+    /// Save a checkpoint, replacing the last one.
+    fn save(&mut self, checkpoint: Checkpoint) -> Result<()>;
+}
+
+impl CheckpointStore for Option<Checkpoint> {
+    fn load(&mut self) -> Result<Option<Checkpoint>> {
+        Ok(*self)
+    }
+
+    fn save(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        *self = Some(checkpoint);
+        Ok(())
+    }
+}
+
+/// A [`CheckpointStore`] keeping its checkpoint in a small text file.
+///
+/// Each save writes a temporary file next to it and renames it into place,
+/// so a crash mid-save leaves the previous checkpoint intact. A missing file
+/// means there is no checkpoint yet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileCheckpoint {
+    path: PathBuf,
+}
+
+impl FileCheckpoint {
+    /// Create a store keeping its checkpoint at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        FileCheckpoint {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get the path of the checkpoint file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CheckpointStore for FileCheckpoint {
+    fn load(&mut self) -> Result<Option<Checkpoint>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::write(e)),
+        };
+        let numbers = text
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<std::result::Result<Vec<u64>, _>>();
+        match numbers.as_deref() {
+            Ok(&[bytes, lines, matches, written]) => Ok(Some(Checkpoint {
+                progress: Progress {
+                    bytes,
+                    lines,
+                    matches,
+                },
+                written,
+            })),
+            _ => Err(Error::write(format!(
+                "invalid checkpoint file {}",
+                self.path.display()
+            ))),
+        }
+    }
+
+    fn save(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        let Checkpoint { progress, written } = checkpoint;
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(
+            &temporary,
+            format!(
+                "{} {} {} {}\n",
+                progress.bytes, progress.lines, progress.matches, written
+            ),
+        )
+        .and_then(|()| fs::rename(&temporary, &self.path))
+        .map_err(Error::write)
+    }
+}
+
+/// A line that [`Transformer::transform_reader_with_errors`] couldn't transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordError {
+    /// The 1-based number of the line in the input.
+    pub line: u64,
+    /// The line, without its terminator.
+    pub text: String,
+    /// Why a field failed to convert or format.
+    pub error: Error,
+}
+
+/// What [`Transformer::sample`] made of one line.
+#[derive(Debug, Clone)]
+pub struct SampleLine {
+    /// The line as given.
+    pub line: String,
+    /// The fields parsed from the line, or `None` if it didn't match the
+    /// input pattern or a field failed to convert.
+    pub fields: Option<ParseResult>,
+    /// The transformed line, `None` if the line didn't match, or the error
+    /// converting or formatting its fields.
+    pub output: Result<Option<String>>,
+}
+
+/// The outcome of a dry run of a [`Transformer`] over sample lines.
+#[derive(Debug, Clone, Default)]
+pub struct SampleReport {
+    /// Each sampled line with its fields and output, in order.
+    pub lines: Vec<SampleLine>,
+}
+
+impl SampleReport {
+    /// Count the lines that were transformed.
+    pub fn transformed(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|sample| matches!(sample.output, Ok(Some(_))))
+            .count()
+    }
+
+    /// Count the lines that didn't match the input pattern.
+    pub fn unmatched(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|sample| matches!(sample.output, Ok(None)))
+            .count()
+    }
+
+    /// Count the lines that matched but failed to convert or format.
+    pub fn failed(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|sample| sample.output.is_err())
+            .count()
+    }
+
+    /// Check whether every line was transformed.
+    pub fn is_clean(&self) -> bool {
+        self.transformed() == self.lines.len()
+    }
+}
+
+impl Transformer {
+    /// Create a transformer from an input (parse) pattern and an output (format) pattern.
+    ///
+    /// Every named field in the output pattern must be captured by the input
+    /// pattern; otherwise [`Error::MissingField`] is returned. Positional
+    /// fields are not supported in the output pattern.
+    pub fn new(input: &str, output: &str) -> Result<Self> {
+        let parser = Parser::new(input)?;
+        let formatter = Formatter::new(output)?;
+
+        let captures: Vec<Option<&str>> = parser.capture_names().collect();
+        let slots = formatter
+            .slot_names()
+            .into_iter()
+            .map(|name| {
+                captures
+                    .iter()
+                    .position(|capture| *capture == Some(name))
+                    .ok_or_else(|| Error::missing(name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Transformer {
+            parser,
+            formatter,
+            slots,
+        })
+    }
+
+    /// Get the parser for the input pattern.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    /// Get the formatter for the output pattern.
+    pub fn formatter(&self) -> &Formatter {
+        &self.formatter
+    }
+
+    /// Create reusable buffers for [`transform_into`](Self::transform_into).
+    pub fn scratch(&self) -> Scratch {
+        Scratch {
+            locs: self.parser.capture_locations(),
+            values: Vec::new(),
+            buf: String::new(),
+        }
+    }
+
+    /// Transform one line, returning `None` if it doesn't match the input pattern.
+    pub fn transform(&self, line: &str) -> Result<Option<String>> {
+        let mut out = String::new();
+        let matched = self.transform_into(line, &mut out, &mut self.scratch())?;
+        Ok(matched.then_some(out))
+    }
+
+    /// Transform one line, appending the output to `out`.
+    ///
+    /// Returns `false`, leaving `out` untouched, if the line doesn't match the
+    /// input pattern. Fields with a conversion flag (`!r`) still allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Transformer;
+    ///
+    /// let transformer = Transformer::new("{name},{score:d}", "{name:>6}: {score:03d}\n").unwrap();
+    /// let mut scratch = transformer.scratch();
+    /// let mut out = String::new();
+    ///
+    /// for line in ["alice,7", "bad line", "bob,42"] {
+    ///     transformer.transform_into(line, &mut out, &mut scratch).unwrap();
+    /// }
+    /// assert_eq!(out, " alice: 007\n   bob: 042\n");
+    /// ```
+    pub fn transform_into(
+        &self,
+        line: &str,
+        out: &mut String,
+        scratch: &mut Scratch,
+    ) -> Result<bool> {
+        let Scratch { locs, values, buf } = scratch;
+        if !self.parser.parse_into(line, locs, values)? {
+            return Ok(false);
+        }
+
+        let values = &*values;
+        let start = out.len();
+        self.formatter
+            .write_slots(out, buf, |slot| values.get(self.slots[slot]))?;
+        self.formatter.limit_len(out, start)?;
+        Ok(true)
+    }
+
+    /// Try the transformation on the first `n` of `lines`, reporting the
+    /// fields and output or error of each instead of stopping at the first
+    /// failure.
+    ///
+    /// This is a dry run for checking a pair of patterns against
+    /// representative data, as `gullwing --check` does, before running it
+    /// over a whole input. Lines are matched as given, without stripping
+    /// line terminators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Transformer;
+    ///
+    /// let transformer = Transformer::new("{k}={v:d}", "{k}: {v:>3}").unwrap();
+    /// let report = transformer.sample(["a=1", "skip me", "b=22", "c=3"], 3);
+    ///
+    /// assert_eq!(report.lines.len(), 3);
+    /// assert_eq!(report.lines[0].output.as_ref().unwrap().as_deref(), Some("a:   1"));
+    /// assert_eq!(report.lines[2].fields.as_ref().unwrap().get("v").unwrap().as_int(), Some(22));
+    /// assert_eq!((report.transformed(), report.unmatched(), report.failed()), (2, 1, 0));
+    /// assert!(!report.is_clean());
+    /// ```
+    pub fn sample<I, S>(&self, lines: I, n: usize) -> SampleReport
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let lines = lines
+            .into_iter()
+            .take(n)
+            .map(|line| {
+                let line = line.as_ref();
+                let fields = self.parser.parse(line);
+                let output = match &fields {
+                    Ok(Some(_)) => self.transform(line),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e.clone()),
+                };
+                SampleLine {
+                    line: line.to_string(),
+                    fields: fields.ok().flatten(),
+                    output,
+                }
+            })
+            .collect();
+        SampleReport { lines }
+    }
+
+    /// Transform every line of `reader`, writing each output followed by a newline to `writer`.
+    ///
+    /// Lines that don't match the input pattern are skipped. A trailing `\n`
+    /// or `\r\n` is stripped from each line before matching. After every
+    /// line, `progress` is called with the running totals, so callers can
+    /// drive a progress bar by comparing [`Progress::bytes`] with the input
+    /// size. The final totals are returned.
+    ///
+    /// Read and write failures are reported as [`Error::WriteError`]; the
+    /// first error stops the transform. To set lines that fail to transform
+    /// aside and carry on, use
+    /// [`transform_reader_with_errors`](Self::transform_reader_with_errors).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Transformer;
+    ///
+    /// let transformer = Transformer::new("{k}={v:d}", "{k}: {v:>3}").unwrap();
+    /// let input = "a=1\nskip me\nb=22\n";
+    /// let mut output = Vec::new();
+    ///
+    /// let mut updates = 0;
+    /// let totals = transformer
+    ///     .transform_reader(input.as_bytes(), &mut output, |_| updates += 1)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(output, b"a:   1\nb:  22\n");
+    /// assert_eq!((totals.bytes, totals.lines, totals.matches), (17, 3, 2));
+    /// assert_eq!(updates, 3);
+    /// ```
+    pub fn transform_reader<R: BufRead, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        progress: impl FnMut(Progress),
+    ) -> Result<Progress> {
+        self.transform_reader_with_errors(reader, writer, progress, |failed| Err(failed.error))
+    }
+
+    /// Transform every line of `reader` like
+    /// [`transform_reader`](Self::transform_reader), handing each line that
+    /// matches but fails to convert or format to `on_error` instead of
+    /// stopping.
+    ///
+    /// `on_error` gets the line and its error, to log it or write it to a
+    /// file for reprocessing; returning an error stops the transform with
+    /// it. Failed lines aren't counted in [`Progress::matches`]. Read and
+    /// write failures still stop the transform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Transformer;
+    ///
+    /// // `c` only formats code points
+    /// let transformer = Transformer::new("{k}={v:d}", "{k}: {v:c}").unwrap();
+    /// let mut output = Vec::new();
+    /// let mut failed = Vec::new();
+    ///
+    /// let totals = transformer
+    ///     .transform_reader_with_errors("a=65\nb=-1\nc=66\n".as_bytes(), &mut output, |_| {}, |e| {
+    ///         failed.push((e.line, e.text));
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(output, b"a: A\nc: B\n");
+    /// assert_eq!(failed, [(2, "b=-1".to_string())]);
+    /// assert_eq!(totals.matches, 2);
+    /// ```
+    pub fn transform_reader_with_errors<R: BufRead, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        progress: impl FnMut(Progress),
+        on_error: impl FnMut(RecordError) -> Result<()>,
+    ) -> Result<Progress> {
+        let start = Checkpoint::default();
+        self.transform_lines(reader, writer, start, progress, on_error, None)
+    }
+
+    /// Transform every line of `reader` like
+    /// [`transform_reader`](Self::transform_reader), saving a [`Checkpoint`]
+    /// to `store` every `every` lines and at the end, so an interrupted run
+    /// can resume where it left off.
+    ///
+    /// If `store` holds a checkpoint, `reader` is first moved to its input
+    /// offset and the totals carry on from it. The output isn't touched: when
+    /// resuming, cut it to [`Checkpoint::written`] bytes and pass a writer
+    /// appending to it, as below, since lines after the checkpoint may have
+    /// been written before the interruption. The writer is flushed before
+    /// each save. With `every` at 0, the only checkpoint is at the end.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use gullwing::transform::{CheckpointStore, FileCheckpoint};
+    /// use gullwing::Transformer;
+    /// use std::fs::{File, OpenOptions};
+    /// use std::io::BufReader;
+    ///
+    /// let transformer = Transformer::new("{ts} {level} {message}", "{level}: {message}")?;
+    /// let mut store = FileCheckpoint::new("access.log.checkpoint");
+    ///
+    /// let output = OpenOptions::new().create(true).append(true).open("access.out")?;
+    /// if let Some(checkpoint) = store.load()? {
+    ///     output.set_len(checkpoint.written)?;
+    /// }
+    ///
+    /// let input = BufReader::new(File::open("access.log")?);
+    /// transformer.transform_reader_checkpointed(input, output, &mut store, 100_000, |_| {})?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transform_reader_checkpointed<R, W, S>(
+        &self,
+        mut reader: R,
+        writer: W,
+        store: &mut S,
+        every: u64,
+        progress: impl FnMut(Progress),
+    ) -> Result<Progress>
+    where
+        R: BufRead + Seek,
+        W: Write,
+        S: CheckpointStore + ?Sized,
+    {
+        let start = store.load()?.unwrap_or_default();
+        reader
+            .seek(SeekFrom::Start(start.progress.bytes))
+            .map_err(Error::write)?;
+        let mut save = |checkpoint| store.save(checkpoint);
+        self.transform_lines(
+            reader,
+            writer,
+            start,
+            progress,
+            |failed| Err(failed.error),
+            Some((every, &mut save)),
+        )
+    }
+
+    /// Transform the lines of `reader` from the `start` totals on, saving a
+    /// checkpoint every so many lines and at the end if asked to.
+    fn transform_lines<R: BufRead, W: Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+        start: Checkpoint,
+        mut progress: impl FnMut(Progress),
+        mut on_error: impl FnMut(RecordError) -> Result<()>,
+        mut checkpoint: Option<(u64, SaveCheckpoint<'_>)>,
+    ) -> Result<Progress> {
+        let mut scratch = self.scratch();
+        let mut line = String::new();
+        let mut out = String::new();
+        let mut totals = start.progress;
+        let mut written = start.written;
+
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line).map_err(Error::write)?;
+            if read == 0 {
+                break;
+            }
+            totals.bytes += read as u64;
+            totals.lines += 1;
+
+            let text = line.strip_suffix('\n').unwrap_or(&line);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            out.clear();
+            match self.transform_into(text, &mut out, &mut scratch) {
+                Ok(true) => {
+                    out.push('\n');
+                    writer.write_all(out.as_bytes()).map_err(Error::write)?;
+                    written += out.len() as u64;
+                    totals.matches += 1;
+                }
+                Ok(false) => {}
+                Err(error) => on_error(RecordError {
+                    line: totals.lines,
+                    text: text.to_string(),
+                    error,
+                })?,
+            }
+            progress(totals);
+
+            if let Some((every, save)) = &mut checkpoint {
+                if totals.lines.is_multiple_of(*every) {
+                    writer.flush().map_err(Error::write)?;
+                    save(Checkpoint {
+                        progress: totals,
+                        written,
+                    })?;
+                }
+            }
+        }
+
+        writer.flush().map_err(Error::write)?;
+        if let Some((_, save)) = &mut checkpoint {
+            save(Checkpoint {
+                progress: totals,
+                written,
+            })?;
+        }
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_transform_reorders_and_formats() {
+        let transformer =
+            Transformer::new("{a:d} {b} {c:f}", "{c:.1f}|{b!r}|{a:+d}|{b:>3}").unwrap();
+        assert_eq!(
+            transformer.transform("7 x 2.25").unwrap().as_deref(),
+            Some("2.2|'x'|+7|  x")
+        );
+        assert_eq!(transformer.transform("7 x").unwrap(), None);
+    }
+
+    #[test]
+    fn test_transform_into_reuses_scratch() {
+        let transformer = Transformer::new("{k}={v}", "{v}<-{k};").unwrap();
+        let mut scratch = transformer.scratch();
+        let mut out = String::new();
+
+        assert!(transformer
+            .transform_into("alpha=1", &mut out, &mut scratch)
+            .unwrap());
+        assert!(!transformer
+            .transform_into("nope", &mut out, &mut scratch)
+            .unwrap());
+        assert!(transformer
+            .transform_into("b=22", &mut out, &mut scratch)
+            .unwrap());
+        assert_eq!(out, "1<-alpha;22<-b;");
+    }
+
+    #[test]
+    fn test_transform_reader_reports_progress() {
+        let transformer = Transformer::new("{n:d}", "<{n:02d}>").unwrap();
+        let mut output = Vec::new();
+        let mut updates = Vec::new();
+
+        let totals = transformer
+            .transform_reader("1\r\nx\n23".as_bytes(), &mut output, |p| {
+                updates.push((p.bytes, p.lines, p.matches))
+            })
+            .unwrap();
+
+        assert_eq!(output, b"<01>\n<23>\n");
+        assert_eq!(updates, [(3, 1, 1), (5, 2, 1), (7, 3, 2)]);
+        assert_eq!(
+            totals,
+            Progress {
+                bytes: 7,
+                lines: 3,
+                matches: 2
+            }
+        );
+
+        let err = transformer
+            .transform_reader(&b"\xff\n"[..], &mut output, |_| {})
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteError);
+    }
+
+    #[test]
+    fn test_transform_reader_with_errors() {
+        let transformer = Transformer::new("{n:d} {s}", "{s}{n:c}").unwrap();
+        let input = "65 a\n-1 b\n99999999999999999999999999999999999999999 c\nx\n66 d\r\n";
+        let mut output = Vec::new();
+        let mut failed = Vec::new();
+
+        let totals = transformer
+            .transform_reader_with_errors(
+                input.as_bytes(),
+                &mut output,
+                |_| {},
+                |e| {
+                    failed.push(e);
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(output, b"aA\ndB\n");
+        assert_eq!((totals.lines, totals.matches), (5, 2));
+
+        // Format and conversion failures alike, with the line as read
+        let lines: Vec<_> = failed.iter().map(|e| (e.line, e.text.as_str())).collect();
+        assert_eq!(
+            lines,
+            [
+                (2, "-1 b"),
+                (3, "99999999999999999999999999999999999999999 c")
+            ]
+        );
+        assert!(failed[1].error.is_conversion_error());
+
+        // The sink's error stops the transform
+        let err = transformer
+            .transform_reader_with_errors(
+                input.as_bytes(),
+                Vec::new(),
+                |_| {},
+                |e| Err(Error::missing(e.text)),
+            )
+            .unwrap_err();
+        assert_eq!(err, Error::missing("-1 b"));
+
+        // Without a sink the first failure stops the transform
+        let err = transformer
+            .transform_reader(input.as_bytes(), Vec::new(), |_| {})
+            .unwrap_err();
+        assert_eq!(err, failed[0].error);
+    }
+
+    /// A writer that fails once it has taken `limit` bytes.
+    struct Interrupted {
+        out: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for Interrupted {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let room = self.limit - self.out.len();
+            if room == 0 {
+                return Err(std::io::Error::other("interrupted"));
+            }
+            let n = buf.len().min(room);
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_transform_reader_checkpointed_resumes() {
+        let transformer = Transformer::new("{k} {v:d}", "{v:02d}:{k}").unwrap();
+        let input: String = (1..=10).map(|v| format!("k{v} {v}\n")).collect();
+        let mut expected = Vec::new();
+        transformer
+            .transform_reader(input.as_bytes(), &mut expected, |_| {})
+            .unwrap();
+
+        // Cut off partway through the sixth line, after the checkpoint at the third
+        let mut store = None;
+        let mut writer = Interrupted {
+            out: Vec::new(),
+            limit: 32,
+        };
+        let err = transformer
+            .transform_reader_checkpointed(Cursor::new(&input), &mut writer, &mut store, 3, |_| {})
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WriteError);
+        let checkpoint = store.unwrap();
+        assert_eq!((checkpoint.progress.lines, checkpoint.written), (3, 18));
+        assert_eq!(checkpoint.progress.bytes, 15);
+
+        // Resuming from the checkpoint finishes the output exactly
+        let mut output = writer.out;
+        output.truncate(checkpoint.written as usize);
+        let mut seen = Vec::new();
+        let totals = transformer
+            .transform_reader_checkpointed(Cursor::new(&input), &mut output, &mut store, 3, |p| {
+                seen.push(p.lines)
+            })
+            .unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(seen, (4..=10).collect::<Vec<_>>());
+        assert_eq!((totals.lines, totals.matches), (10, 10));
+        assert_eq!(totals.bytes, input.len() as u64);
+        assert_eq!(store.unwrap().progress, totals);
+
+        // A finished run resumes to nothing more
+        let mut more = Vec::new();
+        transformer
+            .transform_reader_checkpointed(Cursor::new(&input), &mut more, &mut store, 0, |_| {})
+            .unwrap();
+        assert!(more.is_empty());
+    }
+
+    #[test]
+    fn test_file_checkpoint() {
+        let path = std::env::temp_dir().join(format!("gullwing-checkpoint-{}", std::process::id()));
+        let mut store = FileCheckpoint::new(&path);
+        assert_eq!(store.path(), path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let checkpoint = Checkpoint {
+            progress: Progress {
+                bytes: 120,
+                lines: 7,
+                matches: 5,
+            },
+            written: 96,
+        };
+        store.save(checkpoint).unwrap();
+        assert_eq!(FileCheckpoint::new(&path).load().unwrap(), Some(checkpoint));
+
+        fs::write(&path, "120 7").unwrap();
+        assert_eq!(store.load().unwrap_err().kind(), ErrorKind::WriteError);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sample_reports_each_line() {
+        let transformer = Transformer::new("{n:d} {s}", "{s}{n:c}").unwrap();
+        let report = transformer.sample(["65 ab", "x y", "1 {", "-1 z", "66 b"], 4);
+        assert_eq!(report.lines.len(), 4);
+
+        let [ok, unmatched, _, bad_char] = &report.lines[..] else {
+            unreachable!()
+        };
+        assert_eq!(ok.output, Ok(Some("abA".to_string())));
+        assert_eq!(ok.fields.as_ref().unwrap().get("n"), Some(&Value::from(65)));
+        assert_eq!(unmatched.output, Ok(None));
+        assert!(unmatched.fields.is_none());
+
+        // The fields of a line that matched but failed to format are kept
+        assert_eq!(bad_char.line, "-1 z");
+        assert_eq!(
+            bad_char.fields.as_ref().unwrap().get("s"),
+            Some(&Value::from("z"))
+        );
+        assert!(bad_char.output.is_err());
+
+        assert_eq!(
+            (report.transformed(), report.unmatched(), report.failed()),
+            (2, 1, 1)
+        );
+        assert!(!report.is_clean());
+        assert!(transformer.sample(["66 b"], 1).is_clean());
+        assert!(transformer.sample(["x"], 0).is_clean());
+
+        // A field that fails to convert leaves no fields
+        let transformer = Transformer::new("{n:d}", "{n}").unwrap();
+        let report = transformer.sample(["99999999999999999999999999999999999999999"], 1);
+        assert!(report.lines[0]
+            .output
+            .as_ref()
+            .unwrap_err()
+            .is_conversion_error());
+        assert!(report.lines[0].fields.is_none());
+    }
+
+    #[test]
+    fn test_output_field_must_be_captured() {
+        let err = Transformer::new("{a}", "{a} {b}").unwrap_err();
+        assert_eq!(err, Error::missing("b"));
+
+        assert!(Transformer::new("{a}", "{}").is_ok());
+        let transformer = Transformer::new("{a}", "{0}").unwrap();
+        assert!(transformer.transform("x").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Deserializing Rust types from [`Value`]s with `serde`.
+
+use super::Value;
+use crate::error::Error;
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::DeserializeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Value {
+    /// Deserialize a Rust value from this value.
+    ///
+    /// Maps deserialize into structs, lists into sequences and strings into
+    /// unit enum variants. Numbers and booleans also deserialize from strings
+    /// that parse as the requested type, so fields captured without a type
+    /// (`{port}`) fill `u16` struct fields like typed ones (`{port:d}`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Value;
+    ///
+    /// let value = Value::List(vec![Value::from(1), Value::from("2")]);
+    /// let pair: (u8, f64) = value.deserialize_into().unwrap();
+    /// assert_eq!(pair, (1, 2.0));
+    /// ```
+    pub fn deserialize_into<T: DeserializeOwned>(self) -> Result<T, Error> {
+        T::deserialize(self)
+    }
+
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            Value::Null => de::Unexpected::Unit,
+            Value::Str(s) => de::Unexpected::Str(s),
+            Value::Int(i) => de::Unexpected::Signed(*i),
+            Value::UInt(u) => de::Unexpected::Unsigned(*u),
+            Value::Float(f) => de::Unexpected::Float(*f),
+            Value::Bool(b) => de::Unexpected::Bool(*b),
+            Value::Char(c) => de::Unexpected::Char(*c),
+            Value::List(_) => de::Unexpected::Seq,
+            Value::Map(_) => de::Unexpected::Map,
+            _ => de::Unexpected::Other("value"),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserialize a number or boolean, parsing it first if it was captured as a string.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $ty:ty),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    Value::Str(s) => match <$ty>::from_str(s.trim()) {
+                        Ok(parsed) => parsed.into_deserializer().$method(visitor),
+                        Err(_) => Err(de::Error::invalid_value(
+                            de::Unexpected::Str(&s),
+                            &visitor,
+                        )),
+                    },
+                    other => other.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => visitor.visit_unit(),
+            Value::Str(Cow::Borrowed(s)) => visitor.visit_borrowed_str(s),
+            Value::Str(Cow::Owned(s)) => visitor.visit_string(s),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::UInt(u) => visitor.visit_u64(u),
+            Value::Int128(i) => visitor.visit_i128(i),
+            Value::UInt128(u) => visitor.visit_u128(u),
+            Value::Float(f) => visitor.visit_f64(f),
+            #[cfg(feature = "rust_decimal")]
+            Value::Decimal(d) => visitor.visit_string(d.to_string()),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::List(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Map(map) => {
+                let mut entries = MapDeserializer::new(map.into_iter());
+                let value = visitor.visit_map(&mut entries)?;
+                entries.end()?;
+                Ok(value)
+            }
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => visitor.visit_string(dt.to_rfc3339()),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => bool,
+        deserialize_i8 => i8,
+        deserialize_i16 => i16,
+        deserialize_i32 => i32,
+        deserialize_i64 => i64,
+        deserialize_i128 => i128,
+        deserialize_u8 => u8,
+        deserialize_u16 => u16,
+        deserialize_u32 => u32,
+        deserialize_u64 => u64,
+        deserialize_u128 => u128,
+        deserialize_f32 => f32,
+        deserialize_f64 => f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::Str(s) => visitor.visit_enum(StringDeserializer::<Error>::new(s.into_owned())),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Info,
+        Warn,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        level: Level,
+        port: u16,
+        ratio: f32,
+        tags: Vec<String>,
+        user: Option<String>,
+        missing: Option<i32>,
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let map = HashMap::from([
+            ("level".to_string(), Value::from("warn")),
+            ("port".to_string(), Value::from(" 8080")),
+            ("ratio".to_string(), Value::from(1)),
+            ("tags".to_string(), Value::List(vec![Value::from("a")])),
+            ("user".to_string(), Value::Null),
+        ]);
+        let record: Record = Value::Map(map).deserialize_into().unwrap();
+        assert_eq!(
+            record,
+            Record {
+                level: Level::Warn,
+                port: 8080,
+                ratio: 1.0,
+                tags: vec!["a".to_string()],
+                user: None,
+                missing: None,
+            }
+        );
+        assert_ne!(record.level, Level::Info);
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        let err = Value::from("x").deserialize_into::<u8>().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DeserializeError);
+        assert_eq!(
+            err.to_string(),
+            "deserialize error: invalid value: string \"x\", expected u8"
+        );
+
+        let err = Value::from(300).deserialize_into::<u8>().unwrap_err();
+        assert!(err.to_string().contains("300"));
+
+        let map = Value::Map(HashMap::new());
+        let err = map.deserialize_into::<Record>().unwrap_err();
+        assert!(err.to_string().contains("missing field"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Conversions between [`Value`] and [`serde_json::Value`].
+
+use super::{ToValue, Value};
+use serde_json::{Map, Number};
+
+/// Convert JSON into a value.
+///
+/// Integers become [`Value::Int`], or [`Value::UInt`] above `i64::MAX`, and
+/// other numbers become [`Value::Float`]. Arrays and objects become lists and
+/// maps, so `{user.name}` and `{tags[0]}` fields can reach into them.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::{Formatter, Value};
+/// use std::collections::HashMap;
+///
+/// let event: serde_json::Value =
+///     serde_json::from_str(r#"{"user": {"name": "Alice"}, "took": 0.25}"#).unwrap();
+/// let values = match Value::from(event) {
+///     Value::Map(values) => values,
+///     _ => unreachable!(),
+/// };
+///
+/// let formatter = Formatter::new("{user.name}: {took:.0%}").unwrap();
+/// assert_eq!(formatter.format_map(&values).unwrap(), "Alice: 25%");
+/// ```
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    Value::Int(i)
+                } else if let Some(u) = n.as_u64() {
+                    Value::UInt(u)
+                } else {
+                    Value::Float(n.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            serde_json::Value::String(s) => Value::from(s),
+            serde_json::Value::Array(items) => {
+                Value::List(items.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(map) => Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Convert a value into JSON.
+///
+/// JSON numbers can't hold every value exactly, so 128-bit integers outside
+/// the 64-bit range and decimals become strings of their digits rather than
+/// lossy floats. NaN and infinities become `null`, like
+/// `serde_json::Value::from(f64)`. Characters become one-character strings
+/// and datetimes become RFC 3339 strings.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "parse")] {
+/// use gullwing::{Parser, Value};
+///
+/// let parser = Parser::new("{method} {path} {status:d}").unwrap();
+/// let result = parser.parse("GET /index.html 200").unwrap().unwrap();
+///
+/// let json = serde_json::Value::from(Value::Map(result.values().clone()));
+/// assert_eq!(json["status"], 200);
+/// assert_eq!(json["path"], "/index.html");
+/// # }
+/// ```
+impl ToValue for serde_json::Value {
+    fn to_value(&self) -> Value {
+        Value::from(self.clone())
+    }
+}
+
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Str(s) => serde_json::Value::String(s.into_owned()),
+            Value::Int(i) => serde_json::Value::Number(i.into()),
+            Value::UInt(u) => serde_json::Value::Number(u.into()),
+            Value::Int128(i) => i64::try_from(i)
+                .map(Number::from)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(|_| serde_json::Value::String(i.to_string())),
+            Value::UInt128(u) => u64::try_from(u)
+                .map(Number::from)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(|_| serde_json::Value::String(u.to_string())),
+            Value::Float(f) => serde_json::Value::from(f),
+            #[cfg(feature = "rust_decimal")]
+            Value::Decimal(d) => serde_json::Value::String(d.to_string()),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => serde_json::Value::String(dt.to_rfc3339()),
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Char(c) => serde_json::Value::String(c.to_string()),
+            Value::List(items) => {
+                serde_json::Value::Array(items.into_iter().map(serde_json::Value::from).collect())
+            }
+            Value::Map(map) => serde_json::Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, serde_json::Value::from(value)))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_json() {
+        let value = Value::from(json!({
+            "name": "Alice",
+            "age": 30,
+            "big": u64::MAX,
+            "ratio": 0.5,
+            "tags": ["a", true, null],
+        }));
+        assert_eq!(value.get_attr("name"), Some(&Value::from("Alice")));
+        assert_eq!(value.get_attr("age"), Some(&Value::Int(30)));
+        assert_eq!(value.get_attr("big"), Some(&Value::UInt(u64::MAX)));
+        assert_eq!(value.get_attr("ratio"), Some(&Value::Float(0.5)));
+        assert_eq!(
+            value.get_attr("tags"),
+            Some(&Value::List(vec![
+                Value::from("a"),
+                Value::Bool(true),
+                Value::Null
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_into_json() {
+        let json = json!({"n": -3, "s": "x", "list": [1.5, null, false], "nested": {"k": 1}});
+        assert_eq!(serde_json::Value::from(Value::from(json.clone())), json);
+
+        assert_eq!(serde_json::Value::from(Value::Char('c')), json!("c"));
+        assert_eq!(serde_json::Value::from(Value::Float(f64::NAN)), json!(null));
+        assert_eq!(serde_json::Value::from(Value::Int128(-5)), json!(-5));
+        assert_eq!(
+            serde_json::Value::from(Value::UInt128(u128::MAX)),
+            json!(u128::MAX.to_string())
+        );
+    }
+}
@@ -1,26 +1,78 @@
 //! Value types for formatting and parsing.
 
 use crate::error::{Error, Result};
+use crate::spec::Conversion;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "serde")]
+mod ser;
+
 /// A value that can be formatted or parsed.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    /// String value
-    Str(String),
+    /// No value, like Python's `None`
+    Null,
+    /// String value, borrowed when it is a `'static` string such as a
+    /// literal, so constants don't need an allocation
+    Str(Cow<'static, str>),
     /// Signed integer value
     Int(i64),
     /// Unsigned integer value
     UInt(u64),
+    /// 128-bit signed integer value, for integers beyond the 64-bit range
+    Int128(i128),
+    /// 128-bit unsigned integer value, for integers beyond the 64-bit range
+    UInt128(u128),
     /// Floating point value
     Float(f64),
+    /// Exact decimal value, formatted and parsed without binary rounding
+    #[cfg(feature = "rust_decimal")]
+    Decimal(rust_decimal::Decimal),
     /// Boolean value
     Bool(bool),
     /// Character value
     Char(char),
+    /// List of values, addressable with `{name[0]}` fields
+    List(Vec<Value>),
+    /// Map of named values, addressable with `{name.attr}` or `{name[key]}` fields
+    Map(HashMap<String, Value>),
+    /// Date and time with a UTC offset, formatted with strftime-style specs
+    /// such as `{ts:%Y-%m-%d}`
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::DateTime<chrono::FixedOffset>),
 }
 
 impl Value {
+    /// Create a string value that borrows `s` instead of copying it.
+    ///
+    /// Unlike `Value::from(&str)`, this never allocates, and it works in
+    /// constants.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::{Formatter, Value};
+    ///
+    /// const UNKNOWN: Value = Value::from_static("unknown");
+    ///
+    /// let formatter = Formatter::new("user={user}").unwrap();
+    /// assert_eq!(formatter.format(&[("user", UNKNOWN)]).unwrap(), "user=unknown");
+    /// ```
+    pub const fn from_static(s: &'static str) -> Self {
+        Value::Str(Cow::Borrowed(s))
+    }
+
+    /// Check whether this value is [`Value::Null`].
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
     /// Get this value as a string slice, if possible.
     pub fn as_str(&self) -> Option<&str> {
         match self {
@@ -31,36 +83,77 @@ impl Value {
 
     /// Get this value as an integer, if possible.
     pub fn as_int(&self) -> Option<i64> {
-        match self {
-            Value::Int(i) => Some(*i),
-            Value::UInt(u) if *u <= i64::MAX as u64 => Some(*u as i64),
-            Value::Bool(true) => Some(1),
-            Value::Bool(false) => Some(0),
-            _ => None,
-        }
+        self.as_i128().and_then(|i| i64::try_from(i).ok())
     }
 
     /// Get this value as an unsigned integer, if possible.
     pub fn as_uint(&self) -> Option<u64> {
+        self.as_u128().and_then(|u| u64::try_from(u).ok())
+    }
+
+    /// Get this value as a 128-bit integer, if possible.
+    pub fn as_i128(&self) -> Option<i128> {
         match self {
-            Value::UInt(u) => Some(*u),
-            Value::Int(i) if *i >= 0 => Some(*i as u64),
-            Value::Bool(true) => Some(1),
-            Value::Bool(false) => Some(0),
+            Value::Int(i) => Some(i128::from(*i)),
+            Value::UInt(u) => Some(i128::from(*u)),
+            Value::Int128(i) => Some(*i),
+            Value::UInt128(u) => i128::try_from(*u).ok(),
+            Value::Bool(b) => Some(i128::from(*b)),
             _ => None,
         }
     }
 
+    /// Get this value as a 128-bit unsigned integer, if possible.
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Value::UInt128(u) => Some(*u),
+            _ => self.as_i128().and_then(|i| u128::try_from(i).ok()),
+        }
+    }
+
+    /// Split an integer value into its sign and magnitude.
+    ///
+    /// Covers every integer variant, so formatting never has to pick a
+    /// common type that some values don't fit in.
+    pub(crate) fn int_parts(&self) -> Result<(bool, u128)> {
+        match self {
+            Value::Int(i) => Ok((*i < 0, u128::from(i.unsigned_abs()))),
+            Value::Int128(i) => Ok((*i < 0, i.unsigned_abs())),
+            _ => self
+                .as_u128()
+                .map(|u| (false, u))
+                .ok_or_else(|| Error::conversion("int", self)),
+        }
+    }
+
     /// Get this value as a float, if possible.
     pub fn as_float(&self) -> Option<f64> {
         match self {
             Value::Float(f) => Some(*f),
             Value::Int(i) => Some(*i as f64),
             Value::UInt(u) => Some(*u as f64),
+            Value::Int128(i) => Some(*i as f64),
+            Value::UInt128(u) => Some(*u as f64),
+            #[cfg(feature = "rust_decimal")]
+            Value::Decimal(d) => rust_decimal::prelude::ToPrimitive::to_f64(d),
             _ => None,
         }
     }
 
+    /// Get this value as an exact decimal, if possible.
+    ///
+    /// Integers convert when they fit in a [`Decimal`](rust_decimal::Decimal);
+    /// floats don't, since most of them have no exact decimal value.
+    #[cfg(feature = "rust_decimal")]
+    pub fn as_decimal(&self) -> Option<rust_decimal::Decimal> {
+        match self {
+            Value::Decimal(d) => Some(*d),
+            _ => self
+                .as_i128()
+                .and_then(|i| rust_decimal::Decimal::try_from_i128_with_scale(i, 0).ok()),
+        }
+    }
+
     /// Get this value as a boolean, if possible.
     pub fn as_bool(&self) -> Option<bool> {
         match self {
@@ -73,53 +166,224 @@ impl Value {
     pub fn as_char(&self) -> Option<char> {
         match self {
             Value::Char(c) => Some(*c),
-            Value::Str(s) if s.len() == 1 => s.chars().next(),
+            Value::Str(s) if s.chars().count() == 1 => s.chars().next(),
+            _ => None,
+        }
+    }
+
+    /// Get this value as a list, if possible.
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Get this value as a map, if possible.
+    pub fn as_map(&self) -> Option<&HashMap<String, Value>> {
+        match self {
+            Value::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Get this value as a datetime, if possible.
+    #[cfg(feature = "chrono")]
+    pub fn as_datetime(&self) -> Option<&chrono::DateTime<chrono::FixedOffset>> {
+        match self {
+            Value::DateTime(dt) => Some(dt),
+            _ => None,
+        }
+    }
+
+    /// Look up an attribute of a map value.
+    ///
+    /// This is what a `{name.attr}` field resolves to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Value;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut user = HashMap::new();
+    /// user.insert("name".to_string(), Value::from("Alice"));
+    /// let user = Value::from(user);
+    ///
+    /// assert_eq!(user.get_attr("name"), Some(&Value::from("Alice")));
+    /// assert_eq!(user.get_attr("email"), None);
+    /// ```
+    pub fn get_attr(&self, name: &str) -> Option<&Value> {
+        self.as_map().and_then(|m| m.get(name))
+    }
+
+    /// Look up an item of a list or map value.
+    ///
+    /// This is what a `{name[key]}` field resolves to: lists are indexed by
+    /// position, maps by key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Value;
+    ///
+    /// let tags = Value::from(vec![Value::from("a"), Value::from("b")]);
+    /// assert_eq!(tags.get_item("1"), Some(&Value::from("b")));
+    /// assert_eq!(tags.get_item("2"), None);
+    /// assert_eq!(tags.get_item("x"), None);
+    /// ```
+    pub fn get_item(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::List(l) => key.parse::<usize>().ok().and_then(|i| l.get(i)),
+            Value::Map(m) => m.get(key),
             _ => None,
         }
     }
 
+    /// Write this value the way Python's `repr()` would show it.
+    ///
+    /// Used for the items of lists and maps, so `["a", 1]` displays as
+    /// `['a', 1]` rather than `[a, 1]`.
+    fn write_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => f.write_str(&Conversion::Repr.quote(s)),
+            Value::Char(c) => f.write_str(&Conversion::Repr.quote(c.encode_utf8(&mut [0; 4]))),
+            Value::Bool(true) => f.write_str("True"),
+            Value::Bool(false) => f.write_str("False"),
+            Value::Float(fl) if fl.is_nan() => f.write_str("nan"),
+            Value::Float(fl) if fl.is_infinite() => {
+                f.write_str(if *fl > 0.0 { "inf" } else { "-inf" })
+            }
+            Value::Float(fl) => write!(f, "{:?}", fl),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => write_datetime_repr(f, dt),
+            #[cfg(feature = "rust_decimal")]
+            Value::Decimal(d) => write!(f, "Decimal('{}')", d),
+            _ => write!(f, "{}", self),
+        }
+    }
+
     /// Try to convert this value to an integer for formatting.
     pub fn to_int(&self) -> Result<i64> {
-        self.as_int()
-            .ok_or_else(|| Error::ConversionError(format!("cannot convert {:?} to int", self)))
+        self.as_int().ok_or_else(|| Error::conversion("int", self))
     }
 
     /// Try to convert this value to an unsigned integer for formatting.
     pub fn to_uint(&self) -> Result<u64> {
         self.as_uint()
-            .ok_or_else(|| Error::ConversionError(format!("cannot convert {:?} to uint", self)))
+            .ok_or_else(|| Error::conversion("uint", self))
     }
 
     /// Try to convert this value to a float for formatting.
     pub fn to_float(&self) -> Result<f64> {
         self.as_float()
-            .ok_or_else(|| Error::ConversionError(format!("cannot convert {:?} to float", self)))
+            .ok_or_else(|| Error::conversion("float", self))
     }
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Value::Null => write!(f, "None"),
             Value::Str(s) => write!(f, "{}", s),
             Value::Int(i) => write!(f, "{}", i),
             Value::UInt(u) => write!(f, "{}", u),
+            Value::Int128(i) => write!(f, "{}", i),
+            Value::UInt128(u) => write!(f, "{}", u),
             Value::Float(fl) => write!(f, "{}", fl),
+            #[cfg(feature = "rust_decimal")]
+            Value::Decimal(d) => write!(f, "{}", d),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Char(c) => write!(f, "{}", c),
+            Value::List(l) => {
+                write!(f, "[")?;
+                for (i, item) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.write_repr(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(m) => {
+                // Sort keys so the output is deterministic
+                let mut keys: Vec<&String> = m.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", Conversion::Repr.quote(key))?;
+                    m[key].write_repr(f)?;
+                }
+                write!(f, "}}")
+            }
+            // Like Python's `str()` of a datetime
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) if dt.timestamp_subsec_nanos() == 0 => {
+                write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S%:z"))
+            }
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S%.6f%:z")),
         }
     }
 }
 
+/// Write a datetime the way Python's `repr()` shows an aware datetime.
+#[cfg(feature = "chrono")]
+fn write_datetime_repr(
+    f: &mut fmt::Formatter<'_>,
+    dt: &chrono::DateTime<chrono::FixedOffset>,
+) -> fmt::Result {
+    use chrono::{Datelike, Timelike};
+
+    write!(
+        f,
+        "datetime.datetime({}, {}, {}, {}, {}",
+        dt.year(),
+        dt.month(),
+        dt.day(),
+        dt.hour(),
+        dt.minute()
+    )?;
+    let micros = dt.timestamp_subsec_micros();
+    if dt.second() != 0 || micros != 0 {
+        write!(f, ", {}", dt.second())?;
+    }
+    if micros != 0 {
+        write!(f, ", {}", micros)?;
+    }
+
+    let offset = dt.offset().local_minus_utc();
+    if offset == 0 {
+        return f.write_str(", tzinfo=datetime.timezone.utc)");
+    }
+    // timedelta normalizes to whole days plus non-negative seconds
+    let (days, seconds) = (offset.div_euclid(86_400), offset.rem_euclid(86_400));
+    f.write_str(", tzinfo=datetime.timezone(datetime.timedelta(")?;
+    if days != 0 {
+        write!(f, "days={}, ", days)?;
+    }
+    write!(f, "seconds={})))", seconds)
+}
+
 // Implement From for common types
 impl From<String> for Value {
     fn from(s: String) -> Self {
-        Value::Str(s)
+        Value::Str(Cow::Owned(s))
     }
 }
 
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
-        Value::Str(s.to_string())
+        Value::Str(Cow::Owned(s.to_string()))
+    }
+}
+
+impl From<Cow<'static, str>> for Value {
+    fn from(s: Cow<'static, str>) -> Self {
+        Value::Str(s)
     }
 }
 
@@ -147,6 +411,48 @@ impl From<u32> for Value {
     }
 }
 
+impl From<i128> for Value {
+    fn from(i: i128) -> Self {
+        Value::Int128(i)
+    }
+}
+
+impl From<u128> for Value {
+    fn from(u: u128) -> Self {
+        Value::UInt128(u)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(i: i16) -> Self {
+        Value::Int(i as i64)
+    }
+}
+
+impl From<i8> for Value {
+    fn from(i: i8) -> Self {
+        Value::Int(i as i64)
+    }
+}
+
+impl From<u16> for Value {
+    fn from(u: u16) -> Self {
+        Value::UInt(u as u64)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(u: u8) -> Self {
+        Value::UInt(u as u64)
+    }
+}
+
+impl From<isize> for Value {
+    fn from(i: isize) -> Self {
+        Value::Int(i as i64)
+    }
+}
+
 impl From<usize> for Value {
     fn from(u: usize) -> Self {
         Value::UInt(u as u64)
@@ -165,6 +471,13 @@ impl From<f32> for Value {
     }
 }
 
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for Value {
+    fn from(d: rust_decimal::Decimal) -> Self {
+        Value::Decimal(d)
+    }
+}
+
 impl From<bool> for Value {
     fn from(b: bool) -> Self {
         Value::Bool(b)
@@ -177,15 +490,324 @@ impl From<char> for Value {
     }
 }
 
+impl<T: Into<Value>> From<Option<T>> for Value {
+    /// Convert `None` to [`Value::Null`].
+    fn from(o: Option<T>) -> Self {
+        o.map_or(Value::Null, Into::into)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Value {
+    fn from(dt: chrono::DateTime<Tz>) -> Self {
+        Value::DateTime(dt.fixed_offset())
+    }
+}
+
+/// A datetime without an offset is taken to be in UTC.
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDateTime> for Value {
+    fn from(dt: chrono::NaiveDateTime) -> Self {
+        Value::DateTime(dt.and_utc().fixed_offset())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(l: Vec<Value>) -> Self {
+        Value::List(l)
+    }
+}
+
+impl From<HashMap<String, Value>> for Value {
+    fn from(m: HashMap<String, Value>) -> Self {
+        Value::Map(m)
+    }
+}
+
+/// Conversion into a [`Value`] for formatting.
+///
+/// Implemented for `Value` and for the types gullwing converts into one with
+/// `From`, such as integers and strings, so maps of them can be passed to
+/// [`Formatter::format_map`](crate::Formatter::format_map) directly. Your own
+/// types can implement it by hand.
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::{Formatter, ToValue, Value};
+/// use std::collections::HashMap;
+///
+/// struct Celsius(f64);
+///
+/// impl ToValue for Celsius {
+///     fn to_value(&self) -> Value {
+///         Value::Float(self.0)
+///     }
+/// }
+///
+/// let formatter = Formatter::new("{kitchen:.1f} {garage:.1f}").unwrap();
+/// let temperatures = HashMap::from([
+///     ("kitchen".to_string(), Celsius(21.5)),
+///     ("garage".to_string(), Celsius(8.25)),
+/// ]);
+/// assert_eq!(formatter.format_map(&temperatures).unwrap(), "21.5 8.2");
+///
+/// let counts = HashMap::from([("n".to_string(), 3u32)]);
+/// assert_eq!(Formatter::new("{n:03d}").unwrap().format_map(&counts).unwrap(), "003");
+/// ```
+pub trait ToValue {
+    /// Convert to a [`Value`].
+    fn to_value(&self) -> Value;
+
+    /// Convert to a [`Value`], borrowing `self` if it already is one.
+    ///
+    /// Lets [`Formatter::format_into`](crate::Formatter::format_into) write
+    /// maps of `Value`s without copying them. The default wraps
+    /// [`to_value`](Self::to_value).
+    fn to_value_ref(&self) -> Cow<'_, Value> {
+        Cow::Owned(self.to_value())
+    }
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+
+    fn to_value_ref(&self) -> Cow<'_, Value> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<T: ToValue + ?Sized> ToValue for &T {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+
+    fn to_value_ref(&self) -> Cow<'_, Value> {
+        (**self).to_value_ref()
+    }
+}
+
+impl ToValue for str {
+    fn to_value(&self) -> Value {
+        Value::from(self)
+    }
+}
+
+/// `None` converts to [`Value::Null`].
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToValue::to_value)
+    }
+
+    fn to_value_ref(&self) -> Cow<'_, Value> {
+        match self {
+            Some(value) => value.to_value_ref(),
+            None => Cow::Owned(Value::Null),
+        }
+    }
+}
+
+/// Implement [`ToValue`] for types with a `From<T> for Value` impl.
+macro_rules! to_value_from {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self) -> Value {
+                    Value::from(self.clone())
+                }
+            }
+        )*
+    };
+}
+
+to_value_from!(
+    String,
+    Cow<'static, str>,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    bool,
+    char,
+    Vec<Value>,
+    HashMap<String, Value>,
+);
+
+#[cfg(feature = "rust_decimal")]
+to_value_from!(rust_decimal::Decimal);
+
+#[cfg(feature = "chrono")]
+to_value_from!(chrono::NaiveDateTime);
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> ToValue for chrono::DateTime<Tz> {
+    fn to_value(&self) -> Value {
+        Value::from(self.clone())
+    }
+}
+
+/// Conversion from a parsed [`Value`] into a Rust type.
+///
+/// Used by `#[derive(GullwingParse)]` to fill struct fields from a
+/// [`ParseResult`](crate::ParseResult). Integers convert to any integer type
+/// they fit in, and `Option<T>` accepts missing fields and [`Value::Null`].
+///
+/// # Examples
+///
+/// ```
+/// use gullwing::{FromValue, Value};
+///
+/// assert_eq!(u8::from_value(&Value::Int(200)), Ok(200));
+/// assert!(u8::from_value(&Value::Int(300)).is_err());
+/// assert_eq!(Option::<u8>::from_field(None, "port"), Ok(None));
+/// assert!(u8::from_field(None, "port").unwrap_err().is_missing_field());
+/// ```
+pub trait FromValue: Sized {
+    /// Convert a value, failing with [`Error::ConversionError`] if it doesn't fit.
+    fn from_value(value: &Value) -> Result<Self>;
+
+    /// Convert the value of a named field, which may be missing.
+    ///
+    /// By default a missing field is an [`Error::MissingField`].
+    fn from_field(value: Option<&Value>, name: &str) -> Result<Self> {
+        match value {
+            Some(value) => Self::from_value(value),
+            None => Err(Error::missing(name.to_string())),
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Str(s) => Ok(s.to_string()),
+            Value::Char(c) => Ok(c.to_string()),
+            _ => Err(Error::conversion("str", value)),
+        }
+    }
+}
+
+macro_rules! impl_from_value_int {
+    ($($t:ty),*) => {
+        $(
+            impl FromValue for $t {
+                fn from_value(value: &Value) -> Result<Self> {
+                    value
+                        .as_i128()
+                        .and_then(|i| <$t>::try_from(i).ok())
+                        .ok_or_else(|| Error::conversion(stringify!($t), value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_value_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, usize);
+
+impl FromValue for u128 {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_u128()
+            .ok_or_else(|| Error::conversion("u128", value))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.to_float()
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.to_float().map(|f| f as f32)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_bool()
+            .ok_or_else(|| Error::conversion("bool", value))
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_char()
+            .ok_or_else(|| Error::conversion("char", value))
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl FromValue for rust_decimal::Decimal {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_decimal()
+            .ok_or_else(|| Error::conversion("decimal", value))
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+
+    fn from_field(value: Option<&Value>, _name: &str) -> Result<Self> {
+        value.map_or(Ok(None), Self::from_value)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
+    fn test_to_value() {
+        assert_eq!(7u32.to_value(), Value::UInt(7));
+        assert_eq!("x".to_value(), Value::from("x"));
+        assert_eq!(Some(1.5).to_value(), Value::Float(1.5));
+        assert_eq!(None::<i32>.to_value(), Value::Null);
+        assert_eq!(
+            Value::from(vec![Value::from(1)]).to_value(),
+            Value::List(vec![Value::Int(1)])
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_value_conversions() {
         // String conversions
         let v = Value::from("hello");
         assert_eq!(v.as_str(), Some("hello"));
+        assert!(matches!(v, Value::Str(Cow::Owned(_))));
+        let v = Value::from_static("hello");
+        assert!(matches!(v, Value::Str(Cow::Borrowed("hello"))));
+        assert_eq!(v, Value::from("hello".to_string()));
+        assert_eq!(Value::from(Cow::Borrowed("hi")), Value::from_static("hi"));
+        assert_eq!(String::from_value(&v), Ok("hello".to_string()));
 
         // Integer conversions
         let v = Value::from(42i64);
@@ -204,14 +826,82 @@ mod tests {
         // Char conversions
         let v = Value::from('a');
         assert_eq!(v.as_char(), Some('a'));
+
+        // 128-bit conversions
+        let v = Value::from(u64::MAX);
+        assert_eq!(v.as_int(), None);
+        assert_eq!(v.as_i128(), Some(u64::MAX as i128));
+        let v = Value::from(i128::MIN);
+        assert_eq!(v.as_int(), None);
+        assert_eq!(v.as_u128(), None);
+        assert_eq!(v.int_parts(), Ok((true, 1 << 127)));
+        assert_eq!(v.to_string(), i128::MIN.to_string());
+        let v = Value::from(u128::MAX);
+        assert_eq!(v.as_i128(), None);
+        assert_eq!(v.int_parts(), Ok((false, u128::MAX)));
+        assert_eq!(Value::from(-5i128).as_int(), Some(-5));
+        assert_eq!(Value::from(true).int_parts(), Ok((false, 1)));
+        assert!(Value::from(2.5).int_parts().is_err());
+
+        // Null conversions
+        assert_eq!(Value::from(Some(3)), Value::from(3));
+        let v = Value::from(None::<i64>);
+        assert!(v.is_null());
+        assert_eq!(v.as_int(), None);
+        assert_eq!(v.as_str(), None);
+        assert!(v.to_float().is_err());
+    }
+
+    #[test]
+    fn test_map_attributes() {
+        let mut inner = HashMap::new();
+        inner.insert("b".to_string(), Value::from(2));
+        inner.insert("a".to_string(), Value::from(1));
+        let v = Value::from(inner);
+
+        assert_eq!(v.get_attr("a"), Some(&Value::from(1)));
+        assert_eq!(v.get_attr("missing"), None);
+        assert_eq!(Value::from(1).get_attr("a"), None);
+        assert_eq!(v.as_int(), None);
+        assert_eq!(v.to_string(), "{'a': 1, 'b': 2}");
+    }
+
+    #[test]
+    fn test_list_items() {
+        let mut inner = HashMap::new();
+        inner.insert("k".to_string(), Value::from(true));
+        let v = Value::from(vec![
+            Value::from("it's"),
+            Value::from(1.0),
+            Value::from(-3),
+            Value::from(inner),
+            Value::from(vec![Value::from('c'), Value::from(f64::NEG_INFINITY)]),
+        ]);
+
+        assert_eq!(v.get_item("2"), Some(&Value::from(-3)));
+        assert_eq!(v.get_item("5"), None);
+        assert_eq!(v.get_item("-1"), None);
+        assert_eq!(
+            v.get_item("3").and_then(|m| m.get_item("k")),
+            Some(&Value::from(true))
+        );
+        assert_eq!(v.get_attr("k"), None);
+        assert_eq!(v.as_list().map(<[Value]>::len), Some(5));
+        assert_eq!(
+            v.to_string(),
+            "[\"it's\", 1.0, -3, {'k': True}, ['c', -inf]]"
+        );
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_display() {
         assert_eq!(Value::from("hello").to_string(), "hello");
         assert_eq!(Value::from(42).to_string(), "42");
         assert_eq!(Value::from(3.14).to_string(), "3.14");
         assert_eq!(Value::from(true).to_string(), "true");
         assert_eq!(Value::from('a').to_string(), "a");
+        assert_eq!(Value::Null.to_string(), "None");
+        assert_eq!(Value::from(vec![Value::Null]).to_string(), "[None]");
     }
 }
@@ -0,0 +1,456 @@
+//! Serializing Rust values into [`Value`]s with `serde`.
+
+use super::Value;
+use crate::error::Error;
+use serde::ser::{self, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::SerializeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Value {
+    /// Serialize a Rust value into a value.
+    ///
+    /// Structs and maps become maps, so their fields can be formatted with
+    /// `{name}` and nested ones with `{outer.inner}`; sequences and tuples
+    /// become lists, `None` and `()` become [`Value::Null`], and unit enum
+    /// variants become their names. Other enum variants become a map from
+    /// the variant name to its contents, as in JSON. Fails if a map key isn't
+    /// a string, number, character or boolean.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gullwing::Value;
+    ///
+    /// let value = Value::from_serialize(&(1, "two", [3.5])).unwrap();
+    /// assert_eq!(
+    ///     value,
+    ///     Value::List(vec![
+    ///         Value::from(1),
+    ///         Value::from("two"),
+    ///         Value::List(vec![Value::from(3.5)]),
+    ///     ])
+    /// );
+    /// ```
+    pub fn from_serialize<T: Serialize + ?Sized>(data: &T) -> Result<Value, Error> {
+        data.serialize(ValueSerializer)
+    }
+}
+
+/// Wrap the contents of an enum variant in a map keyed by its name.
+fn variant(name: &'static str, value: Value) -> Value {
+    Value::Map(HashMap::from([(name.to_string(), value)]))
+}
+
+/// Turn a serialized map key into a field name.
+fn key_name(key: Value) -> Result<String, Error> {
+    match key {
+        Value::Str(s) => Ok(s.into_owned()),
+        Value::Int(i) => Ok(i.to_string()),
+        Value::UInt(u) => Ok(u.to_string()),
+        Value::Int128(i) => Ok(i.to_string()),
+        Value::UInt128(u) => Ok(u.to_string()),
+        Value::Char(c) => Ok(c.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(ser::Error::custom(format!(
+            "map keys must be strings, numbers, characters or booleans, not {:?}",
+            other
+        ))),
+    }
+}
+
+/// Serializer producing a [`Value`].
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> {
+        Ok(Value::Int128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok(Value::UInt(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> {
+        Ok(Value::UInt128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::List(v.iter().map(|&b| Value::from(b)).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::from_static(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            map: HashMap::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            map: HashMap::with_capacity(len),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Collects sequences, tuples and tuple variants into a [`Value::List`].
+struct SeqSerializer {
+    items: Vec<Value>,
+    variant: Option<&'static str>, // Set for tuple variants
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        let list = Value::List(self.items);
+        match self.variant {
+            Some(name) => variant(name, list),
+            None => list,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+/// Collects maps, structs and struct variants into a [`Value::Map`].
+struct MapSerializer {
+    map: HashMap<String, Value>,
+    key: Option<String>,           // Key waiting for its value
+    variant: Option<&'static str>, // Set for struct variants
+}
+
+impl MapSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.map
+            .insert(key.to_string(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        let map = Value::Map(self.map);
+        match self.variant {
+            Some(name) => variant(name, map),
+            None => map,
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key_name(key.serialize(ValueSerializer)?)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("map value serialized before its key"))?;
+        self.map.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Segment(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[test]
+    fn test_serialize_values() {
+        assert_eq!(Value::from_serialize(&None::<i32>), Ok(Value::Null));
+        assert_eq!(Value::from_serialize(&Some(7u16)), Ok(Value::UInt(7)));
+        assert_eq!(Value::from_serialize(&-1i128), Ok(Value::Int128(-1)));
+        assert_eq!(Value::from_serialize("s"), Ok(Value::from("s")));
+        assert_eq!(Value::from_serialize(&'c'), Ok(Value::Char('c')));
+
+        assert_eq!(
+            Value::from_serialize(&Shape::Point),
+            Ok(Value::from_static("Point"))
+        );
+        assert_eq!(
+            Value::from_serialize(&Shape::Circle(1.5)),
+            Ok(variant("Circle", Value::Float(1.5)))
+        );
+        assert_eq!(
+            Value::from_serialize(&Shape::Segment(1, 2)),
+            Ok(variant(
+                "Segment",
+                Value::List(vec![Value::from(1), Value::from(2)])
+            ))
+        );
+        let rect = Value::from_serialize(&Shape::Rect { w: 3, h: 4 }).unwrap();
+        let inner = rect.get_attr("Rect").unwrap();
+        assert_eq!(inner.get_attr("h"), Some(&Value::UInt(4)));
+    }
+
+    #[test]
+    fn test_serialize_map_keys() {
+        let numbered = BTreeMap::from([(1, "one"), (2, "two")]);
+        let value = Value::from_serialize(&numbered).unwrap();
+        assert_eq!(value.get_item("2"), Some(&Value::from("two")));
+
+        let nested = BTreeMap::from([((1, 2), "pair")]);
+        let err = Value::from_serialize(&nested).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SerializeError);
+        assert!(err.to_string().contains("map keys must be"));
+    }
+}
@@ -0,0 +1,345 @@
+//! JavaScript bindings, for the browser or Node.
+//!
+//! Built for a `wasm32` target with `wasm-bindgen`, the feature exports
+//! three classes to JavaScript: `Formatter`, `Parser` and `ParseResult`,
+//! wrapping the Rust types of the same names, so web tooling writes patterns
+//! in the same language as the backend that reads them.
+//!
+//! Values cross the boundary as plain JavaScript values. A formatter takes an
+//! object of fields, whose integral numbers become integers so `{n:d}` works,
+//! and whose arrays and nested objects can be reached with `{tags[0]}` and
+//! `{user.name}`. Parsed integers come back as numbers, or as `BigInt`s when
+//! they are too large for a number to hold exactly.
+//!
+//! ```js
+//! import { Formatter, Parser } from "gullwing";
+//!
+//! const parser = new Parser("{method} {path} {status:d}");
+//! const result = parser.parse("GET /index.html 200");
+//! result.get("status");  // 200
+//! result.span("path");   // Uint32Array [4, 15]
+//!
+//! const formatter = new Formatter("{status} {method:<6} {path}");
+//! formatter.format(result.toObject());  // "200 GET    /index.html"
+//! ```
+
+use crate::error::Result;
+use crate::format::Formatter;
+use crate::parse::{ParseResult, Parser};
+use crate::types::Value;
+use js_sys::{Array, BigInt, Object, Reflect};
+use std::collections::HashMap;
+use std::ops::Range;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// The largest integer a JavaScript number holds exactly, `2^53 - 1`.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// A [`Formatter`] exported to JavaScript as `Formatter`.
+#[wasm_bindgen(js_name = Formatter)]
+#[derive(Debug, Clone)]
+pub struct WasmFormatter {
+    inner: Formatter,
+}
+
+#[wasm_bindgen(js_class = Formatter)]
+impl WasmFormatter {
+    /// Compile a pattern, throwing if it is invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str) -> std::result::Result<WasmFormatter, JsError> {
+        Ok(Formatter::new(pattern)?.into())
+    }
+
+    /// The pattern this formatter was compiled from.
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> String {
+        self.inner.pattern().to_string()
+    }
+
+    /// Format the fields of a JavaScript object, throwing if one is missing
+    /// or can't be rendered with its spec.
+    pub fn format(&self, values: &JsValue) -> std::result::Result<String, JsError> {
+        let values = match value_from_js(values)? {
+            Value::Map(values) => values,
+            _ => return Err(JsError::new("expected an object of field values")),
+        };
+        Ok(self.inner.format_map(&values)?)
+    }
+}
+
+impl From<Formatter> for WasmFormatter {
+    fn from(inner: Formatter) -> Self {
+        WasmFormatter { inner }
+    }
+}
+
+/// A [`Parser`] exported to JavaScript as `Parser`.
+#[wasm_bindgen(js_name = Parser)]
+#[derive(Debug, Clone)]
+pub struct WasmParser {
+    inner: Parser,
+}
+
+#[wasm_bindgen(js_class = Parser)]
+impl WasmParser {
+    /// Compile a pattern, throwing if it is invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: &str) -> std::result::Result<WasmParser, JsError> {
+        Ok(Parser::new(pattern)?.into())
+    }
+
+    /// The pattern this parser was compiled from.
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> String {
+        self.inner.pattern().to_string()
+    }
+
+    /// Match the whole of `text`, giving `undefined` if it doesn't match.
+    pub fn parse(&self, text: &str) -> std::result::Result<Option<WasmParseResult>, JsError> {
+        Ok(self.inner.parse(text)?.map(WasmParseResult::from))
+    }
+
+    /// Find the first match anywhere in `text`, giving `undefined` if there
+    /// is none.
+    pub fn search(&self, text: &str) -> std::result::Result<Option<WasmParseResult>, JsError> {
+        Ok(self.inner.search(text)?.map(WasmParseResult::from))
+    }
+
+    /// Find every non-overlapping match in `text`, as an array of results.
+    #[wasm_bindgen(js_name = findAll)]
+    pub fn find_all(&self, text: &str) -> std::result::Result<Array, JsError> {
+        let results = self.inner.findall(text).collect::<Result<Vec<_>>>()?;
+        Ok(results
+            .into_iter()
+            .map(|result| JsValue::from(WasmParseResult::from(result)))
+            .collect())
+    }
+}
+
+impl From<Parser> for WasmParser {
+    fn from(inner: Parser) -> Self {
+        WasmParser { inner }
+    }
+}
+
+/// A [`ParseResult`] exported to JavaScript as `ParseResult`.
+///
+/// Spans are given in UTF-16 code units, so they index JavaScript strings.
+#[wasm_bindgen(js_name = ParseResult)]
+#[derive(Debug, Clone)]
+pub struct WasmParseResult {
+    inner: ParseResult,
+}
+
+#[wasm_bindgen(js_class = ParseResult)]
+impl WasmParseResult {
+    /// The text that was matched.
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.inner.text().to_string()
+    }
+
+    /// Get a field's value, or `undefined` if there is no such field.
+    pub fn get(&self, name: &str) -> JsValue {
+        self.inner.get(name).map_or(JsValue::UNDEFINED, value_to_js)
+    }
+
+    /// Get the `[start, end]` of a field in the text, or `undefined` if there
+    /// is no such field.
+    pub fn span(&self, name: &str) -> Option<Vec<u32>> {
+        let span = self.inner.span(name)?;
+        let (start, end) = utf16_span(self.inner.text(), span);
+        Some(vec![start, end])
+    }
+
+    /// The names of the fields, in pattern order.
+    #[wasm_bindgen(js_name = fieldNames)]
+    pub fn field_names(&self) -> Vec<String> {
+        self.inner
+            .fields()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// The fields as a plain object, in pattern order.
+    ///
+    /// `JSON.stringify` uses this too, through `toJSON`.
+    #[wasm_bindgen(js_name = toObject)]
+    pub fn to_object(&self) -> Object {
+        let object = Object::new();
+        for (name, value) in self.inner.fields() {
+            // Setting a property on a new plain object can't fail
+            let _ = Reflect::set(&object, &JsValue::from_str(name), &value_to_js(value));
+        }
+        object
+    }
+
+    /// The fields as a plain object, for `JSON.stringify`.
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> Object {
+        self.to_object()
+    }
+}
+
+impl From<ParseResult> for WasmParseResult {
+    fn from(inner: ParseResult) -> Self {
+        WasmParseResult { inner }
+    }
+}
+
+impl From<WasmParseResult> for ParseResult {
+    fn from(result: WasmParseResult) -> Self {
+        result.inner
+    }
+}
+
+/// Convert a JavaScript value into a value.
+///
+/// Integral numbers in the safe range become [`Value::Int`] and other numbers
+/// [`Value::Float`]; `BigInt`s become the smallest integer variant holding
+/// them. `null` and `undefined` become [`Value::Null`].
+fn value_from_js(value: &JsValue) -> std::result::Result<Value, JsError> {
+    if value.is_null() || value.is_undefined() {
+        Ok(Value::Null)
+    } else if let Some(b) = value.as_bool() {
+        Ok(Value::Bool(b))
+    } else if let Some(n) = value.as_f64() {
+        Ok(number_value(n))
+    } else if let Some(s) = value.as_string() {
+        Ok(Value::from(s))
+    } else if let Some(n) = value.dyn_ref::<BigInt>() {
+        let digits = String::from(
+            n.to_string(10)
+                .map_err(|_| JsError::new("invalid BigInt"))?,
+        );
+        bigint_value(&digits).ok_or_else(|| JsError::new("BigInt out of the 128-bit range"))
+    } else if let Some(items) = value.dyn_ref::<Array>() {
+        items
+            .iter()
+            .map(|item| value_from_js(&item))
+            .collect::<std::result::Result<_, _>>()
+            .map(Value::List)
+    } else if value.is_object() {
+        let mut map = HashMap::new();
+        for entry in Object::entries(value.unchecked_ref()).iter() {
+            let entry: Array = entry.unchecked_into();
+            let key = entry.get(0).as_string().unwrap_or_default();
+            map.insert(key, value_from_js(&entry.get(1))?);
+        }
+        Ok(Value::Map(map))
+    } else {
+        Err(JsError::new("unsupported JavaScript value"))
+    }
+}
+
+/// Convert a value into a JavaScript value.
+///
+/// Integers too large for a number to hold exactly become `BigInt`s, and
+/// values JavaScript has no type for become strings, as in JSON.
+fn value_to_js(value: &Value) -> JsValue {
+    match value {
+        Value::Null => JsValue::NULL,
+        Value::Str(s) => JsValue::from_str(s),
+        Value::Int(i) => integer_to_js(*i as i128),
+        Value::UInt(u) => integer_to_js(*u as i128),
+        Value::Int128(i) => integer_to_js(*i),
+        Value::UInt128(u) => match i128::try_from(*u) {
+            Ok(i) => integer_to_js(i),
+            Err(_) => JsValue::from(*u),
+        },
+        Value::Float(f) => JsValue::from_f64(*f),
+        #[cfg(feature = "rust_decimal")]
+        Value::Decimal(d) => JsValue::from_str(&d.to_string()),
+        Value::Bool(b) => JsValue::from_bool(*b),
+        Value::Char(c) => JsValue::from_str(&c.to_string()),
+        Value::List(items) => items.iter().map(value_to_js).collect::<Array>().into(),
+        Value::Map(map) => {
+            let object = Object::new();
+            for (key, value) in map {
+                let _ = Reflect::set(&object, &JsValue::from_str(key), &value_to_js(value));
+            }
+            object.into()
+        }
+        #[cfg(feature = "chrono")]
+        Value::DateTime(dt) => JsValue::from_str(&dt.to_rfc3339()),
+    }
+}
+
+/// An integer as a number when it is exact, else as a `BigInt`.
+fn integer_to_js(i: i128) -> JsValue {
+    if (i as f64).abs() <= MAX_SAFE_INTEGER {
+        JsValue::from_f64(i as f64)
+    } else {
+        JsValue::from(i)
+    }
+}
+
+/// The value of a JavaScript number: an integer if it is integral and exact.
+fn number_value(n: f64) -> Value {
+    let negative_zero = n == 0.0 && n.is_sign_negative();
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER && !negative_zero {
+        Value::Int(n as i64)
+    } else {
+        Value::Float(n)
+    }
+}
+
+/// The value of a `BigInt`'s decimal digits, in the smallest integer variant.
+fn bigint_value(digits: &str) -> Option<Value> {
+    digits
+        .parse()
+        .map(Value::Int)
+        .or_else(|_| digits.parse().map(Value::UInt))
+        .or_else(|_| digits.parse().map(Value::Int128))
+        .or_else(|_| digits.parse().map(Value::UInt128))
+        .ok()
+}
+
+/// A byte span of `text` in UTF-16 code units.
+fn utf16_span(text: &str, span: Range<usize>) -> (u32, u32) {
+    let start = text[..span.start].encode_utf16().count();
+    let len = text[span].encode_utf16().count();
+    (start as u32, (start + len) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_value() {
+        assert_eq!(number_value(42.0), Value::Int(42));
+        assert_eq!(number_value(-7.0), Value::Int(-7));
+        assert_eq!(number_value(2.5), Value::Float(2.5));
+        assert_eq!(number_value(1e300), Value::Float(1e300));
+        assert!(matches!(number_value(-0.0), Value::Float(z) if z.is_sign_negative()));
+        assert!(matches!(number_value(f64::NAN), Value::Float(n) if n.is_nan()));
+    }
+
+    #[test]
+    fn test_bigint_value() {
+        assert_eq!(bigint_value("-12"), Some(Value::Int(-12)));
+        assert_eq!(
+            bigint_value("18446744073709551615"),
+            Some(Value::UInt(u64::MAX))
+        );
+        assert_eq!(
+            bigint_value("-18446744073709551616"),
+            Some(Value::Int128(-(1 << 64)))
+        );
+        assert_eq!(
+            bigint_value(&u128::MAX.to_string()),
+            Some(Value::UInt128(u128::MAX))
+        );
+        assert_eq!(bigint_value(&format!("{}0", u128::MAX)), None);
+    }
+
+    #[test]
+    fn test_utf16_span() {
+        let text = "naïve 🦀 crab";
+        let start = text.find("crab").unwrap();
+        assert_eq!(utf16_span(text, start..text.len()), (9, 13));
+        assert_eq!(utf16_span(text, 0..6), (0, 5));
+    }
+}
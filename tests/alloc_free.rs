@@ -0,0 +1,109 @@
+//! Verifies that the transform pipeline and `Formatter::format_into` are
+//! allocation-free in steady state.
+//!
+//! This lives in its own test binary because it installs a counting global
+//! allocator, and contains a single test so no other test allocates concurrently.
+
+use gullwing::{Formatter, Transformer, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn hot_paths_do_not_allocate_per_record() {
+    let transformer = Transformer::new(
+        "{date} {level} [{thread:d}] {latency:f}ms {message}",
+        "{level:<5} {date} {thread:>4d} {latency:8.2f} {message:.20}",
+    )
+    .unwrap();
+
+    let lines = [
+        "2024-01-15 INFO [1] 0.25ms started",
+        "2024-01-15 WARN [12] 13.5ms slow request to /api/users",
+        "not a log line",
+        "2024-01-16 ERROR [3] 1024.125ms upstream timed out after retrying",
+    ];
+
+    let mut scratch = transformer.scratch();
+    let mut out = String::with_capacity(4096);
+
+    // Warm up so the scratch buffers reach their steady-state capacity
+    for line in lines {
+        transformer
+            .transform_into(line, &mut out, &mut scratch)
+            .unwrap();
+    }
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..100 {
+        for line in lines {
+            out.clear();
+            transformer
+                .transform_into(line, &mut out, &mut scratch)
+                .unwrap();
+        }
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    assert_eq!(allocations, 0, "steady-state pipeline allocated");
+    assert_eq!(out, "ERROR 2024-01-16    3  1024.12 upstream timed out a");
+
+    // Formatting into a reused buffer shares the per-thread render scratch
+    let formatter = Formatter::new("{level:<5} {thread:>4d} {latency:8.2f} {message:>6}").unwrap();
+    let values = HashMap::from([
+        ("level".to_string(), Value::from("WARN")),
+        ("thread".to_string(), Value::from(12)),
+        ("latency".to_string(), Value::from(13.5)),
+        ("message".to_string(), Value::from("slow")),
+    ]);
+    let mut buf = String::new();
+    formatter.format_into(&mut buf, &values).unwrap();
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..100 {
+        buf.clear();
+        formatter.format_into(&mut buf, &values).unwrap();
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    assert_eq!(allocations, 0, "format_into allocated");
+    assert_eq!(buf, "WARN    12    13.50   slow");
+
+    // Other providers borrow their values too
+    let sorted: BTreeMap<&str, Value> = values
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.clone()))
+        .collect();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..100 {
+        buf.clear();
+        formatter.format_into(&mut buf, &sorted).unwrap();
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    assert_eq!(allocations, 0, "format_into allocated with a BTreeMap");
+    assert_eq!(buf, "WARN    12    13.50   slow");
+}
@@ -0,0 +1,369 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+
+    fn run_shuffle(
+        input_pattern: &str,
+        output_pattern: &str,
+        input_data: &str,
+    ) -> Result<String, String> {
+        run_shuffle_args(&[input_pattern, output_pattern], input_data)
+    }
+
+    fn run_shuffle_args(args: &[&str], input_data: &str) -> Result<String, String> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_gullwing"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn shuffle: {}", e))?;
+
+        // Write input data
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input_data.as_bytes())
+                .map_err(|e| format!("Failed to write to stdin: {}", e))?;
+        }
+
+        // Get output
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for shuffle: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Shuffle failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    #[test]
+    fn test_shuffle_simple_reorder() {
+        let input = "{name} {age:d}";
+        let output = "{age:d} {name}";
+        let data = "Alice 30\nBob 25\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "30 Alice\n25 Bob\n");
+    }
+
+    #[test]
+    fn test_shuffle_log_transformation() {
+        let input = "{timestamp} {level} {message}";
+        let output = "[{level}] {message}";
+        let data = "2024-01-15T10:30:00 INFO Server started\n2024-01-15T10:30:01 ERROR Connection failed\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "[INFO] Server started\n[ERROR] Connection failed\n");
+    }
+
+    #[test]
+    fn test_shuffle_csv_reformat() {
+        let input = "{id:d},{name},{score:f}";
+        let output = "ID: {id:03d} | Name: {name} | Score: {score:.1f}";
+        let data = "5,Alice,95.7\n10,Bob,87.3\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(
+            result,
+            "ID: 005 | Name: Alice | Score: 95.7\nID: 010 | Name: Bob | Score: 87.3\n"
+        );
+    }
+
+    #[test]
+    fn test_shuffle_extract_fields() {
+        let input = "{a} {b} {c}";
+        let output = "{b}";
+        let data = "first second third\nuno dos tres\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "second\ndos\n");
+    }
+
+    #[test]
+    fn test_shuffle_integer_formatting() {
+        let input = "{value:d}";
+        let output = "{value:05d}";
+        let data = "42\n123\n7\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "00042\n00123\n00007\n");
+    }
+
+    #[test]
+    fn test_shuffle_hex_conversion() {
+        let input = "{value:d}";
+        let output = "0x{value:x}";
+        let data = "255\n16\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "0xff\n0x10\n");
+    }
+
+    #[test]
+    fn test_shuffle_no_match_lines_skipped() {
+        let input = "{value:d}";
+        let output = "{value}";
+        let data = "123\nnot a number\n456\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        // Lines that don't match should be skipped
+        assert_eq!(result, "123\n456\n");
+    }
+
+    #[test]
+    fn test_shuffle_empty_input() {
+        let input = "{value}";
+        let output = "{value}";
+        let data = "";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn test_shuffle_multiple_fields_same_value() {
+        let input = "{x}";
+        let output = "{x} {x} {x}";
+        let data = "test\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "test test test\n");
+    }
+
+    #[test]
+    fn test_shuffle_float_precision() {
+        let input = "{value:f}";
+        let output = "{value:.2f}";
+        let data = "3.14159\n2.71828\n";
+
+        let result = run_shuffle(input, output, data).unwrap();
+        assert_eq!(result, "3.14\n2.72\n");
+    }
+
+    #[test]
+    fn test_shuffle_shell_output() {
+        let data = "Alice 30\nnot a match\nO'Brien 41\n";
+        let result = run_shuffle_args(&["--output", "shell", "{name} {age:d}"], data).unwrap();
+        assert_eq!(result, "name='Alice'; age=30\nname='O'\\''Brien'; age=41\n");
+
+        assert!(run_shuffle_args(&["--output", "xml", "{name}"], data).is_err());
+    }
+
+    #[test]
+    fn test_shuffle_json_output() {
+        let data = "Alice 30
+not a match
+\"Al\" 41
+";
+        let result = run_shuffle_args(&["--output", "json", "{name} {age:d}"], data).unwrap();
+        assert_eq!(
+            result,
+            "{\"name\":\"Alice\",\"age\":30}\n{\"name\":\"\\\"Al\\\"\",\"age\":41}\n"
+        );
+
+        let result = run_shuffle_args(&["--output", "json", "--passthrough", "{n:d}"], "1\nx\n");
+        assert_eq!(result.unwrap(), "{\"n\":1}\nx\n");
+    }
+
+    #[test]
+    fn test_shuffle_repl() {
+        let samples = std::env::temp_dir().join(format!("shuffle-repl-{}.txt", std::process::id()));
+        std::fs::write(&samples, "Alice 30\nBob x\n").unwrap();
+        let commands =
+            "out {age:03d} {name}\nin {name} {age:q}\nin {name} {age:d}!\nexport\nquit\n";
+
+        let result = run_shuffle_args(
+            &["repl", samples.to_str().unwrap(), "{name} {age:d}"],
+            commands,
+        );
+        std::fs::remove_file(&samples).unwrap();
+        let result = result.unwrap();
+
+        assert!(result.starts_with("2 sample lines"));
+        assert!(result.contains("    age  = Int(30)\n    -> 030 Alice\n"));
+        assert!(result.contains("1 of 2 lines match"));
+        assert!(result.contains("input pattern error: "));
+        assert!(result.contains("    {name} {age:q}\n                ^\n"));
+        assert!(result.contains("    no match: doesn't end with \"!\""));
+        assert!(result.ends_with("gullwing '{name} {age:d}!' '{age:03d} {name}'\n"));
+    }
+
+    #[test]
+    fn test_shuffle_unmatched_policies() {
+        let data = "1\nnot a number\n2\n";
+        let args = ["{n:d}", "<{n:02d}>"];
+
+        let skipped = run_shuffle_args(&["--skip", args[0], args[1]], data).unwrap();
+        assert_eq!(skipped, "<01>\n<02>\n");
+
+        let copied = run_shuffle_args(&["--passthrough", args[0], args[1]], data).unwrap();
+        assert_eq!(copied, "<01>\nnot a number\n<02>\n");
+
+        let err = run_shuffle_args(&["--error", args[0], args[1]], data).unwrap_err();
+        assert!(err.contains("record 2 doesn't match"), "{}", err);
+        assert!(run_shuffle_args(&["--error", args[0], args[1]], "1\n2\n").is_ok());
+    }
+
+    #[test]
+    fn test_shuffle_files_and_null_records() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("gullwing-in-{}.txt", std::process::id()));
+        let output = dir.join(format!("gullwing-out-{}.txt", std::process::id()));
+        std::fs::write(&input, "a=1\0b=2\r\0junk\n=3\0").unwrap();
+
+        let result = run_shuffle_args(
+            &[
+                "--null",
+                "--passthrough",
+                "--input-file",
+                input.to_str().unwrap(),
+                "--output-file",
+                output.to_str().unwrap(),
+                "{k}={v:d}",
+                "{v}:{k!r}",
+            ],
+            "ignored=3\n",
+        );
+        let written = std::fs::read(&output);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(result.unwrap(), "");
+        // Only newline records lose a trailing `\r`
+        assert_eq!(written.unwrap(), b"1:'a'\x00b=2\r\x00junk\n=3\x00");
+    }
+
+    #[test]
+    fn test_shuffle_errors_to() {
+        let errors =
+            std::env::temp_dir().join(format!("gullwing-errors-{}.txt", std::process::id()));
+        let data = "a=65\nb=-1\nskip\nc=66\nd=-2\n";
+        let args = ["{k}={v:d}", "{k}:{v:c}"];
+
+        // Only --stop-on-error stops the run at the first failed record
+        let err = run_shuffle_args(&["--stop-on-error", args[0], args[1]], data).unwrap_err();
+        assert!(err.contains("record 2: "), "{}", err);
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_gullwing"))
+            .args(["--errors-to", errors.to_str().unwrap()])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        let failed = std::fs::read_to_string(&errors);
+        std::fs::remove_file(&errors).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"a:A\nc:B\n");
+        assert_eq!(failed.unwrap(), "b=-1\nd=-2\n");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("gullwing: record 2: "), "{}", stderr);
+        assert!(stderr.contains("gullwing: record 5: "), "{}", stderr);
+    }
+
+    #[test]
+    fn test_shuffle_conversion_errors_continue() {
+        // A record that fails to convert is reported and the run goes on
+        let mut child = Command::new(env!("CARGO_BIN_EXE_gullwing"))
+            .args(["{k}={v:d}", "{k}:{v:c}"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"a=65\nb=-1\nc=66\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"a:A\nc:B\n");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("gullwing: record 2: "), "{}", stderr);
+    }
+
+    #[test]
+    fn test_shuffle_check() {
+        let data = "a=1\nskip\nb=x\n";
+        let report = run_shuffle_args(&["--check", "{k}={v}", "{k}: {v:>3}"], data).unwrap();
+        assert_eq!(
+            report,
+            "1: ok: a:   1\n2: no match: skip\n3: ok: b:   x\n3 records: 2 transformed, 1 didn't match, 0 failed\n"
+        );
+
+        // Failures are listed and fail the run, as are mismatches with --error
+        for (args, count) in [
+            (&["--check", "{k}={v}", "{v:d}"][..], "2 of 3"),
+            (&["--check", "--error", "{k}={v}", "{k}"], "1 of 3"),
+        ] {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_gullwing"))
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(data.as_bytes())
+                .unwrap();
+            let output = child.wait_with_output().unwrap();
+            assert_eq!(output.status.code(), Some(1), "{:?}", args);
+            assert!(String::from_utf8_lossy(&output.stdout).contains("3 records: "));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains(&format!("{} records failed the check", count)));
+        }
+
+        // Only the first records are tried
+        let many = "1\n".repeat(25);
+        let report = run_shuffle_args(&["--check", "{n:d}", "{n}"], &many).unwrap();
+        assert!(report.ends_with("10 records: 10 transformed, 0 didn't match, 0 failed\n"));
+    }
+
+    #[test]
+    fn test_shuffle_usage_errors() {
+        for args in [
+            &["{a}"][..],
+            &["{a}", "{a}", "{a}"],
+            &["--bogus", "{a}", "{a}"],
+            &["--input-file"],
+            &["--output", "shell", "{a}", "{a}"],
+            &["--check", "--output", "json", "{a}"],
+            &["--stop-on-error", "--errors-to", "/dev/null", "{a}", "{a}"],
+        ] {
+            let output = Command::new(env!("CARGO_BIN_EXE_gullwing"))
+                .args(args)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        }
+
+        let help = run_shuffle_args(&["--help"], "").unwrap();
+        assert!(help.starts_with("Usage: gullwing"));
+
+        let err = run_shuffle_args(&["--input-file", "/nonexistent/in", "{a}", "{a}"], "");
+        assert!(err.unwrap_err().contains("/nonexistent/in"));
+    }
+}
@@ -0,0 +1,95 @@
+use gullwing::{GullwingFormat, GullwingParse, Value, ValueProvider};
+
+#[derive(Debug, Clone, PartialEq, GullwingFormat, GullwingParse)]
+#[gullwing(pattern = "[{level}] {target}: {message} ({elapsed:.3f}s, retry={retry:d})")]
+struct LogLine {
+    level: String,
+    target: String,
+    message: String,
+    elapsed: f64,
+    retry: Option<u8>,
+}
+
+#[test]
+fn derived_format_and_parse_round_trip() {
+    let line = LogLine {
+        level: "WARN".to_string(),
+        target: "db".to_string(),
+        message: "slow query".to_string(),
+        elapsed: 1.5,
+        retry: Some(2),
+    };
+    let text = line.format().unwrap();
+    assert_eq!(text, "[WARN] db: slow query (1.500s, retry=2)");
+
+    let parsed = LogLine::parse(&text).unwrap().unwrap();
+    assert_eq!(parsed, line);
+    assert!(LogLine::parse("not a log line").unwrap().is_none());
+
+    // Optional fields format as None when unset
+    let unset = LogLine {
+        retry: None,
+        ..line
+    };
+    assert!(unset.format().unwrap().ends_with("retry=None)"));
+}
+
+#[derive(GullwingFormat)]
+#[gullwing(pattern = "{type}/{id:04d} {meta[region]} {{{id}}}")]
+struct Resource {
+    r#type: &'static str,
+    id: u32,
+    meta: std::collections::HashMap<String, Value>,
+}
+
+#[test]
+fn derived_format_handles_raw_idents_paths_and_repeats() {
+    let resource = Resource {
+        r#type: "vm",
+        id: 7,
+        meta: [("region".to_string(), Value::from("eu"))].into(),
+    };
+    assert_eq!(resource.format().unwrap(), "vm/0007 eu {7}");
+    assert_eq!(resource.get("id"), Some(Value::UInt(7)));
+    assert_eq!(resource.get("other"), None);
+    assert_eq!(Resource::PATTERN, "{type}/{id:04d} {meta[region]} {{{id}}}");
+}
+
+#[derive(Debug, GullwingParse)]
+#[gullwing(pattern = "{uid:d} {name}")]
+struct Renamed {
+    #[gullwing(rename = "uid")]
+    id: i64,
+    name: String,
+    #[gullwing(skip)]
+    seen: bool,
+}
+
+#[test]
+fn derived_parse_renames_and_skips() {
+    let parsed = Renamed::parse("42 alice").unwrap().unwrap();
+    assert_eq!(
+        (parsed.id, parsed.name.as_str(), parsed.seen),
+        (42, "alice", false)
+    );
+}
+
+#[derive(Debug, GullwingParse)]
+#[gullwing(pattern = "{port:d}")]
+struct Port {
+    port: u8,
+}
+
+#[test]
+fn derived_parse_reports_conversion_and_pattern_errors() {
+    assert_eq!(Port::parse("80").unwrap().unwrap().port, 80);
+    assert!(Port::parse("8080").unwrap_err().is_conversion_error());
+
+    #[derive(GullwingFormat)]
+    #[gullwing(pattern = "{x:q}")]
+    struct BadSpec {
+        x: i32,
+    }
+    assert!(BadSpec { x: 1 }.format().unwrap_err().is_pattern_error());
+    assert!(BadSpec::formatter().is_err());
+}
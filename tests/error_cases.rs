@@ -1,4 +1,4 @@
-use gullwing::{Error, Formatter, Parser, Value};
+use gullwing::{Error, ErrorKind, Formatter, Parser, Value};
 use std::collections::HashMap;
 
 #[cfg(test)]
@@ -15,20 +15,18 @@ mod error_tests {
 
     #[test]
     fn invalid_format_spec_unknown_type() {
-        // 'z' is not a valid type specifier, but the parser may accept it
-        // and treat it as a default/string type. This test documents current behavior.
-        let formatter = Formatter::new("{value:z}");
-
-        // If the formatter was created successfully, unknown types might be treated as string type
-        if let Ok(formatter) = formatter {
-            let mut values = HashMap::new();
-            values.insert("value".to_string(), Value::from("test"));
-            // Should format successfully as string
-            let result = formatter.format_map(&values);
-            assert!(result.is_ok());
-        }
-        // Otherwise, it should error during parsing
-        // Both behaviors are acceptable
+        // 'z' is not a type specifier but the negative zero coercion flag, so
+        // the spec parses. Like Python, it is rejected once the value turns
+        // out to be a string.
+        let formatter = Formatter::new("{value:z}").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("value".to_string(), Value::from("test"));
+        let err = formatter.format_map(&values).unwrap_err();
+        assert!(err.is_pattern_error());
+
+        values.insert("value".to_string(), Value::from(-0.0));
+        assert!(formatter.format_map(&values).is_ok());
     }
 
     #[test]
@@ -44,13 +42,13 @@ mod error_tests {
     #[test]
     fn missing_field_error() {
         let formatter = Formatter::new("{missing_field}").unwrap();
-        let values = HashMap::new();
+        let values: HashMap<String, Value> = HashMap::new();
         let result = formatter.format_map(&values);
 
         assert!(result.is_err());
         match result {
-            Err(Error::MissingField(field)) => {
-                assert_eq!(field, "missing_field");
+            Err(Error::MissingField { name }) => {
+                assert_eq!(name, "missing_field");
             }
             _ => panic!("Expected MissingField error"),
         }
@@ -65,7 +63,7 @@ mod error_tests {
         let result = formatter.format_map(&values);
         assert!(result.is_err());
         match result {
-            Err(Error::ConversionError(_)) => {}
+            Err(Error::ConversionError { .. }) => {}
             _ => panic!("Expected ConversionError"),
         }
     }
@@ -79,11 +77,35 @@ mod error_tests {
         let result = formatter.format_map(&values);
         assert!(result.is_err());
         match result {
-            Err(Error::ConversionError(_)) => {}
+            Err(Error::ConversionError { .. }) => {}
             _ => panic!("Expected ConversionError"),
         }
     }
 
+    #[test]
+    fn precision_with_integer_type() {
+        let result = Formatter::new("{value:.2d}");
+        assert!(matches!(result, Err(Error::InvalidFormatSpec { .. })));
+    }
+
+    #[test]
+    fn precision_with_integer_value_and_no_type() {
+        // Python: format(5, '.2') raises "Precision not allowed in integer format specifier"
+        let formatter = Formatter::new("{value:.2}").unwrap();
+        let mut values = HashMap::new();
+        values.insert("value".to_string(), Value::from(5));
+
+        let result = formatter.format_map(&values);
+        match result {
+            Err(Error::InvalidFormatSpec { message, .. }) => assert!(message.contains("precision")),
+            _ => panic!("Expected InvalidFormatSpec error"),
+        }
+
+        // The same spec still truncates strings
+        values.insert("value".to_string(), Value::from("hello"));
+        assert_eq!(formatter.format_map(&values).unwrap(), "he");
+    }
+
     #[test]
     fn negative_with_unsigned_format() {
         let formatter = Formatter::new("{value:x}").unwrap();
@@ -91,11 +113,59 @@ mod error_tests {
         values.insert("value".to_string(), Value::from(-42));
 
         // Negative numbers can't be formatted as hex (unsigned operation)
-        let result = formatter.format_map(&values);
+        let _result = formatter.format_map(&values);
         // This might succeed or fail depending on implementation
         // If it succeeds, it should handle the conversion gracefully
     }
 
+    #[test]
+    fn error_positions_in_pattern() {
+        let err = Formatter::new("ab}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidFormatSpec);
+        assert_eq!(err.position(), Some(2));
+
+        let err = Formatter::new("xy {value").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+
+        // Spec errors point into the pattern, not just the spec
+        let err = Formatter::new("id={value:>5q}").unwrap_err();
+        assert_eq!(err.position(), Some(12));
+        assert!(err.is_pattern_error());
+
+        let err = Parser::new("{a} {b:.x}").unwrap_err();
+        assert_eq!(err.position(), Some(8));
+
+        let err = Formatter::new("{a-b}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidFieldName);
+        assert_eq!(err.field_name(), Some("a-b"));
+        assert_eq!(err.position(), Some(1));
+    }
+
+    #[test]
+    fn whitespace_in_field_names_is_rejected() {
+        // Names are never trimmed; both tokenizers point at the whitespace
+        for (pattern, name, position) in [
+            ("x { name }", " name ", 3),
+            ("{name }", "name ", 5),
+            ("{ }", " ", 1),
+            ("{name :>4}", "name ", 5),
+            ("{a. b}", "a. b", 3),
+        ] {
+            for err in [
+                Formatter::new(pattern).unwrap_err(),
+                Parser::new(pattern).unwrap_err(),
+            ] {
+                assert_eq!(err.kind(), ErrorKind::InvalidFieldName, "{:?}", pattern);
+                assert_eq!(err.field_name(), Some(name), "{:?}", pattern);
+                assert_eq!(err.position(), Some(position), "{:?}", pattern);
+            }
+        }
+
+        // Whitespace in the spec is a fill, not part of the name
+        assert!(Formatter::new("{name: >4}").is_ok());
+        assert!(Parser::new("{name: >4}").is_ok());
+    }
+
     // ===== Parsing Errors =====
 
     #[test]
@@ -145,7 +215,7 @@ mod error_tests {
 
         assert!(result.is_err());
         match result {
-            Err(Error::ConversionError(_)) => {}
+            Err(Error::ConversionError { .. }) => {}
             _ => panic!("Expected ConversionError"),
         }
     }
@@ -157,7 +227,7 @@ mod error_tests {
 
         assert!(result.is_err());
         match result {
-            Err(Error::ConversionError(_)) => {}
+            Err(Error::ConversionError { .. }) => {}
             _ => panic!("Expected ConversionError"),
         }
     }
@@ -176,7 +246,7 @@ mod error_tests {
     #[test]
     fn empty_format_string() {
         let formatter = Formatter::new("").unwrap();
-        let values = HashMap::new();
+        let values: HashMap<String, Value> = HashMap::new();
         let result = formatter.format_map(&values).unwrap();
         assert_eq!(result, "");
     }
@@ -184,7 +254,7 @@ mod error_tests {
     #[test]
     fn format_string_without_fields() {
         let formatter = Formatter::new("Hello, World!").unwrap();
-        let values = HashMap::new();
+        let values: HashMap<String, Value> = HashMap::new();
         let result = formatter.format_map(&values).unwrap();
         assert_eq!(result, "Hello, World!");
     }
@@ -192,7 +262,7 @@ mod error_tests {
     #[test]
     fn escaped_braces() {
         let formatter = Formatter::new("{{literal}}").unwrap();
-        let values = HashMap::new();
+        let values: HashMap<String, Value> = HashMap::new();
         let result = formatter.format_map(&values).unwrap();
         assert_eq!(result, "{literal}");
     }
@@ -211,13 +281,13 @@ mod error_tests {
     #[test]
     fn multiple_missing_fields() {
         let formatter = Formatter::new("{a} {b} {c}").unwrap();
-        let values = HashMap::new();
+        let values: HashMap<String, Value> = HashMap::new();
         let result = formatter.format_map(&values);
 
         assert!(result.is_err());
         // Should error on first missing field
         match result {
-            Err(Error::MissingField(_)) => {}
+            Err(Error::MissingField { .. }) => {}
             _ => panic!("Expected MissingField error"),
         }
     }
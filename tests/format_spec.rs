@@ -1,6 +1,6 @@
 //! Comprehensive tests for format specification parsing.
 
-use gullwing::spec::{Alignment, FormatSpec, Grouping, Sign, TypeSpec};
+use gullwing::spec::{Alignment, FormatSpec, Grouping, Sign, TypeCategory, TypeSpec};
 
 #[test]
 fn test_empty_format_spec() {
@@ -324,3 +324,36 @@ fn test_large_width_and_precision() {
     assert_eq!(spec.precision, Some(999));
     assert_eq!(spec.type_spec, Some(TypeSpec::FixedLower));
 }
+
+#[test]
+fn test_type_table() {
+    let codes: String = TypeSpec::all().iter().map(|info| info.code).collect();
+    assert_eq!(codes, "sbcdoxXneEfFgG%");
+
+    for info in TypeSpec::all() {
+        assert_eq!(info.type_spec.info(), info);
+        assert_eq!(TypeSpec::from_char(info.code), Some(info.type_spec));
+        assert_eq!(info.type_spec.to_char(), info.code);
+        assert_eq!(
+            FormatSpec::parse(&info.code.to_string()).unwrap().type_spec,
+            Some(info.type_spec)
+        );
+    }
+    assert_eq!(TypeSpec::from_char('z'), None);
+
+    // Python's accept matrix: ints take every numeric code, floats skip the
+    // integer-only ones, strings only take `s`
+    let accepts = |pick: fn(&gullwing::spec::TypeInfo) -> bool| -> String {
+        TypeSpec::all()
+            .iter()
+            .filter(|info| pick(info))
+            .map(|info| info.code)
+            .collect()
+    };
+    assert_eq!(accepts(|info| info.integers), "bcdoxXneEfFgG%");
+    assert_eq!(accepts(|info| info.floats), "neEfFgG%");
+    assert_eq!(accepts(|info| info.strings), "s");
+
+    assert_eq!(TypeSpec::Number.info().category, TypeCategory::Integer);
+    assert!(TypeSpec::Number.is_numeric() && !TypeSpec::Character.is_numeric());
+}
@@ -0,0 +1,152 @@
+//! Checks the spans and events recorded by the `instrument` feature.
+
+use gullwing::{Formatter, Parser, Transformer, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Records each span as `name kind/method` and each event as its message.
+#[derive(Default)]
+struct Recorder {
+    lines: Arc<Mutex<Vec<String>>>,
+    next_id: Mutex<u64>,
+}
+
+/// Collects the fields of a span or event into one line.
+#[derive(Default)]
+struct Fields(Vec<String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "kind" | "method" => self.0.push(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.0.push(format!("{:?}", value)),
+            "engine" => self.0.push(format!("{:?}", value)),
+            _ => {}
+        }
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let line = format!("{} {}", span.metadata().name(), fields.0.join(" "));
+        self.lines.lock().unwrap().push(line);
+        let mut id = self.next_id.lock().unwrap();
+        *id += 1;
+        Id::from_u64(*id)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.lines.lock().unwrap().push(fields.0.join(" "));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// Run `f` and return what it recorded.
+fn record(f: impl FnOnce()) -> Vec<String> {
+    let recorder = Recorder::default();
+    let lines = Arc::clone(&recorder.lines);
+    tracing::subscriber::with_default(recorder, f);
+    let lines = lines.lock().unwrap().clone();
+    lines
+}
+
+#[test]
+fn compile_spans_and_engine() {
+    let lines = record(|| {
+        Parser::new("{a:d},{b}").unwrap();
+        Parser::new("{a:>5}|{b}").unwrap();
+        Formatter::new("{a}").unwrap();
+    });
+    // Whether specs are cached already depends on the tests run before
+    let lines: Vec<_> = lines
+        .into_iter()
+        .filter(|line| !line.starts_with("spec cache"))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "compile parser",
+            "engine selected Splitter",
+            "compile parser",
+            "engine selected Regex",
+            "compile formatter",
+        ]
+    );
+}
+
+#[test]
+fn per_record_spans() {
+    let parser = Parser::new("{n:d}").unwrap();
+    let formatter = Formatter::new("{n:x}").unwrap();
+    let transformer = Transformer::new("{n:d}", "{n:o}").unwrap();
+
+    let lines = record(|| {
+        let result = parser.parse("12").unwrap().unwrap();
+        formatter.format_map(result.values()).unwrap();
+        parser.search("x 1").unwrap();
+        assert_eq!(parser.findall("1 2").count(), 2);
+        formatter.format_positional(&[]).unwrap_err();
+        transformer.transform("8").unwrap();
+    });
+    assert_eq!(
+        lines,
+        [
+            "parse parse",
+            "format format_map",
+            "parse search",
+            "parse findall",
+            "parse findall",
+            "parse findall",
+            "format format_positional",
+            "parse transform",
+            "format transform",
+        ]
+    );
+}
+
+#[test]
+fn cache_and_fallback_events() {
+    // A spec no other test uses, so the first lookup misses
+    let lines = record(|| {
+        Formatter::new("{a:_^17.3} {b:_^17.3}").unwrap();
+    });
+    assert_eq!(
+        lines,
+        ["compile formatter", "spec cache miss", "spec cache hit"]
+    );
+
+    // The splitter leaves text that isn't ASCII to the regex
+    let parser = Parser::new("{a} {b}").unwrap();
+    let lines = record(|| {
+        let values = parser.parse("é y").unwrap().unwrap();
+        assert_eq!(values.get("a"), Some(&Value::from("é")));
+    });
+    assert!(
+        lines.contains(&"splitter undecided; matching with the regex".to_string()),
+        "{lines:?}"
+    );
+}
@@ -0,0 +1,362 @@
+//! Compatibility suite against the Python `parse` package.
+//!
+//! Each case records what `parse.parse(pattern, text)` returns in Python.
+//! Positional fields, which Python exposes as `result.fixed`, are compared
+//! against the `_0`, `_1`, ... names gullwing assigns to them.
+//!
+//! Cases where gullwing intentionally (or for now) behaves differently carry a
+//! [`Divergence`] describing what gullwing does instead and why. Those cases
+//! assert gullwing's actual behavior, so closing a gap makes the test fail
+//! until the divergence marker is removed.
+
+use gullwing::{Formatter, Parser, Template, Value};
+use proptest::prelude::*;
+
+/// The outcome of parsing a string.
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    /// The text matched and produced these fields.
+    Match(&'static [(&'static str, V)]),
+    /// The text did not match the pattern.
+    NoMatch,
+    /// The pattern was rejected.
+    Error,
+}
+
+/// Expected field values, kept `const`-friendly.
+#[derive(Debug, Clone, Copy)]
+enum V {
+    S(&'static str),
+    I(i64),
+    F(f64),
+}
+
+impl V {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            V::S(s) => value.as_str() == Some(s),
+            V::I(i) => value.as_int() == Some(i),
+            V::F(f) => value.as_float().is_some_and(|v| (v - f).abs() < 1e-9),
+        }
+    }
+}
+
+/// A known difference from the Python `parse` package.
+#[derive(Debug, Clone, Copy)]
+struct Divergence {
+    /// What gullwing does instead.
+    gullwing: Outcome,
+    /// Why the behavior differs.
+    reason: &'static str,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Case {
+    pattern: &'static str,
+    text: &'static str,
+    python: Outcome,
+    divergence: Option<Divergence>,
+}
+
+const fn case(pattern: &'static str, text: &'static str, python: Outcome) -> Case {
+    Case {
+        pattern,
+        text,
+        python,
+        divergence: None,
+    }
+}
+
+const fn diverges(
+    pattern: &'static str,
+    text: &'static str,
+    python: Outcome,
+    gullwing: Outcome,
+    reason: &'static str,
+) -> Case {
+    Case {
+        pattern,
+        text,
+        python,
+        divergence: Some(Divergence { gullwing, reason }),
+    }
+}
+
+use Outcome::{Error, Match, NoMatch};
+use V::{F, I, S};
+
+const CASES: &[Case] = &[
+    // Plain text and escaping
+    case("hello", "hello", Match(&[])),
+    case("hello", "goodbye", NoMatch),
+    case("{{hello}}", "{hello}", Match(&[])),
+    case("a.b*c {}", "a.b*c d", Match(&[("_0", S("d"))])),
+    case("(x) [{}]", "(x) [y]", Match(&[("_0", S("y"))])),
+    // Positional and named fields
+    case("{}", "hello", Match(&[("_0", S("hello"))])),
+    case("hello {}", "hello world", Match(&[("_0", S("world"))])),
+    case("{} {}", "a b", Match(&[("_0", S("a")), ("_1", S("b"))])),
+    case(
+        "{}, {}",
+        "a, b, c",
+        Match(&[("_0", S("a")), ("_1", S("b, c"))]),
+    ),
+    case("{name}", "Alice", Match(&[("name", S("Alice"))])),
+    case(
+        "{first} {last}",
+        "Grace Hopper",
+        Match(&[("first", S("Grace")), ("last", S("Hopper"))]),
+    ),
+    case(
+        "{name} is {age:d} years old",
+        "Alice is 30 years old",
+        Match(&[("name", S("Alice")), ("age", I(30))]),
+    ),
+    case("{}", "", NoMatch),
+    // Integers
+    case("{:d}", "12", Match(&[("_0", I(12))])),
+    case("{:d}", "-12", Match(&[("_0", I(-12))])),
+    case("{:d}", "+12", Match(&[("_0", I(12))])),
+    case("{:d}", "12a", NoMatch),
+    case("{:d}", "abc", NoMatch),
+    case("{:b}", "1010", Match(&[("_0", I(10))])),
+    case("{:b}", "0b1010", Match(&[("_0", I(10))])),
+    case("{:o}", "17", Match(&[("_0", I(15))])),
+    case("{:o}", "0o17", Match(&[("_0", I(15))])),
+    case("{:x}", "ff", Match(&[("_0", I(255))])),
+    case("{:x}", "0xFF", Match(&[("_0", I(255))])),
+    case("{:x}", "fg", NoMatch),
+    case("{a:d}-{b:d}", "10-20", Match(&[("a", I(10)), ("b", I(20))])),
+    // Floats
+    case("{:f}", "3.5", Match(&[("_0", F(3.5))])),
+    case("{:f}", "-0.25", Match(&[("_0", F(-0.25))])),
+    case("{:f}", ".5", Match(&[("_0", F(0.5))])),
+    case("{:e}", "1.5e3", Match(&[("_0", F(1500.0))])),
+    case("{:e}", "1.5E-03", Match(&[("_0", F(0.0015))])),
+    case("{:g}", "1.5", Match(&[("_0", F(1.5))])),
+    case("{:g}", "2e10", Match(&[("_0", F(2e10))])),
+    case("{:%}", "50%", Match(&[("_0", F(0.5))])),
+    case("{:%}", "12.5%", Match(&[("_0", F(0.125))])),
+    case("{:%}", "50", NoMatch),
+    // Mixed
+    case(
+        "{x:d},{y:f} {label}",
+        "3,4.5 point",
+        Match(&[("x", I(3)), ("y", F(4.5)), ("label", S("point"))]),
+    ),
+    // Alignment and width padding
+    case("{:>}", "   hi", Match(&[("_0", S("hi"))])),
+    case("{:4d}", "  12", Match(&[("_0", I(12))])),
+    case("[{:^7}]", "[  hi   ]", Match(&[("_0", S("hi"))])),
+    // Repeated names
+    case("{a} {a}", "x x", Match(&[("a", S("x"))])),
+    case("{a} {a}", "x y", NoMatch),
+    case(
+        "{a:d}-{b}-{a:d}",
+        "1-x-1",
+        Match(&[("a", I(1)), ("b", S("x"))]),
+    ),
+    case("{a:d}-{a:d}", "1-2", NoMatch),
+    // Malformed patterns
+    case("{", "x", Error),
+    case("}", "x", Error),
+    case("{:.}", "x", Error),
+    // ----- Divergences -----
+    diverges(
+        "Hello {}",
+        "hello world",
+        Match(&[("_0", S("world"))]),
+        NoMatch,
+        "parse matches case-insensitively by default; gullwing is case-sensitive",
+    ),
+    diverges(
+        "{}",
+        "a\nb",
+        Match(&[("_0", S("a\nb"))]),
+        NoMatch,
+        "parse compiles with re.DOTALL; gullwing fields do not span newlines",
+    ),
+    diverges(
+        "{:f}",
+        "3",
+        NoMatch,
+        Match(&[("_0", F(3.0))]),
+        "parse requires a decimal point for 'f'; gullwing accepts integers",
+    ),
+    diverges(
+        "{:d}",
+        "0x1f",
+        Match(&[("_0", I(31))]),
+        NoMatch,
+        "parse accepts 0b/0o/0x prefixes for 'd'; gullwing only takes decimal digits",
+    ),
+    diverges(
+        "{:d}",
+        " 12",
+        Match(&[("_0", I(12))]),
+        NoMatch,
+        "parse allows a leading space as the sign; gullwing does not",
+    ),
+    diverges(
+        "{:n}",
+        "1,000",
+        Match(&[("_0", I(1000))]),
+        NoMatch,
+        "parse accepts thousands separators for 'n'; gullwing matches plain digits",
+    ),
+    diverges(
+        "{a.b}",
+        "x",
+        Match(&[("a.b", S("x"))]),
+        Error,
+        "parse keeps dotted names as flat keys; gullwing reserves '.' for attribute access",
+    ),
+];
+
+fn check(case: &Case, expected: Outcome) -> std::result::Result<(), String> {
+    let context = format!("pattern {:?} on {:?}", case.pattern, case.text);
+    let parsed = match Parser::new(case.pattern) {
+        Ok(parser) => parser.parse(case.text).unwrap(),
+        Err(_) if matches!(expected, Error) => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "{}: expected {:?}, got error {}",
+                context, expected, e
+            ))
+        }
+    };
+    let (fields, parsed) = match (expected, parsed) {
+        (NoMatch, None) => return Ok(()),
+        (Match(fields), Some(parsed)) => (fields, parsed),
+        (want, got) => return Err(format!("{}: expected {:?}, got {:?}", context, want, got)),
+    };
+    if parsed.values().len() != fields.len() {
+        return Err(format!(
+            "{}: expected {} fields, got {:?}",
+            context,
+            fields.len(),
+            parsed.values()
+        ));
+    }
+    for (name, want) in fields {
+        match parsed.get(name) {
+            Some(got) if want.matches(got) => {}
+            got => {
+                return Err(format!(
+                    "{}: field {:?} expected {:?}, got {:?}",
+                    context, name, want, got
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn python_parse_corpus() {
+    let failures: Vec<String> = CASES
+        .iter()
+        .filter(|case| case.divergence.is_none())
+        .filter_map(|case| check(case, case.python).err())
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn documented_divergences() {
+    let failures: Vec<String> = CASES
+        .iter()
+        .filter_map(|case| case.divergence.map(|d| (case, d)))
+        .filter_map(|(case, d)| {
+            check(case, d.gullwing)
+                .err()
+                .map(|e| format!("{} (divergence: {})", e, d.reason))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "behavior changed for documented divergences; update the corpus:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn formatter_and_parser_share_field_specs() {
+    let specs =
+        |t: &Template| -> Vec<String> { t.fields().iter().map(|f| f.spec().to_string()).collect() };
+    for case in CASES {
+        let Ok(parser) = Parser::new(case.pattern) else {
+            continue;
+        };
+        let formatter = Formatter::new(case.pattern).unwrap();
+        let template = Template::parse(case.pattern).unwrap();
+
+        assert_eq!(parser.template(), &template, "pattern {:?}", case.pattern);
+        assert_eq!(
+            formatter.template(),
+            &template,
+            "pattern {:?}",
+            case.pattern
+        );
+        assert_eq!(
+            specs(parser.template()),
+            specs(formatter.template()),
+            "pattern {:?}",
+            case.pattern
+        );
+    }
+}
+
+proptest! {
+    /// `{:d}` recovers any integer written with `str(n)`, like Python
+    #[test]
+    fn decimal_matches_python_str(n in any::<i64>()) {
+        let parser = Parser::new("{:d}").unwrap();
+        let parsed = parser.parse(&n.to_string()).unwrap().unwrap();
+        prop_assert_eq!(parsed.get("_0").unwrap().as_int(), Some(n));
+    }
+
+    /// `{:x}` recovers hex written with or without the `0x` prefix
+    #[test]
+    fn hex_matches_python_hex(n in 0i64..i64::MAX, prefix in any::<bool>()) {
+        let text = if prefix { format!("{:#x}", n) } else { format!("{:x}", n) };
+        let parser = Parser::new("{:x}").unwrap();
+        let parsed = parser.parse(&text).unwrap().unwrap();
+        prop_assert_eq!(parsed.get("_0").unwrap().as_int(), Some(n));
+    }
+
+    /// Literal text around a field is matched verbatim, whatever regex
+    /// metacharacters it contains
+    #[test]
+    fn literals_are_escaped(
+        before in "[a-z.*+?()|^$\\[\\] ]{0,8}",
+        after in "[a-z.*+?()|^$\\[\\] ]{0,8}",
+        n in any::<i32>(),
+    ) {
+        let pattern = format!("{}{{:d}}{}", before, after);
+        let parser = Parser::new(&pattern).unwrap();
+        let parsed = parser.parse(&format!("{}{}{}", before, n, after)).unwrap().unwrap();
+        prop_assert_eq!(parsed.get("_0").unwrap().as_int(), Some(n as i64));
+    }
+
+    /// `findall` agrees with a plain `re.finditer`-style scan when the
+    /// pattern's literal prefix is used to skip ahead
+    #[test]
+    fn findall_matches_regex_scan(text in "(id=[0-9]{0,3};|id|=|[0-9]|;| ){0,40}") {
+        let parser = Parser::new("id={n:d};").unwrap();
+        let expected: Vec<i64> = regex_lite::Regex::new(r"id=([-+]?\d+);")
+            .unwrap()
+            .captures_iter(&text)
+            .map(|cap| cap[1].parse().unwrap())
+            .collect();
+        let found: Vec<i64> = parser
+            .findall(&text)
+            .map(|r| r.unwrap().get("n").unwrap().as_int().unwrap())
+            .collect();
+        prop_assert_eq!(
+            parser.search(&text).unwrap().map(|r| r.get("n").unwrap().as_int().unwrap()),
+            expected.first().copied()
+        );
+        prop_assert_eq!(found, expected);
+    }
+}
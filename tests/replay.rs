@@ -0,0 +1,28 @@
+//! Replays the reproducers in `tests/fixtures`.
+//!
+//! Each fixture is a pattern on its first line and a text after it, which
+//! must parse with the pattern and format back to exactly the same text.
+//! Add a file here to keep a reported round-trip bug fixed.
+
+use gullwing::replay::replay_dir;
+
+#[test]
+fn fixtures_round_trip() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    let count = replay_dir(dir, |replay| {
+        let path = replay.fixture.path().unwrap().display();
+        assert!(replay.formatter.is_ok(), "{path}: {:?}", replay.formatter);
+        assert!(
+            matches!(replay.parsed, Some(Ok(Some(_)))),
+            "{path}: text doesn't parse: {:?}",
+            replay.parsed
+        );
+        assert_eq!(
+            replay.formatted.as_ref().unwrap().as_deref(),
+            Ok(replay.fixture.text()),
+            "{path}"
+        );
+    })
+    .unwrap();
+    assert!(count > 0);
+}
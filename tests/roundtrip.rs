@@ -1,4 +1,5 @@
-use gullwing::{Formatter, Parser, Value};
+use gullwing::spec::FormatSpec;
+use gullwing::{FloatRepr, Formatter, Parser, Value};
 use proptest::prelude::*;
 use std::collections::HashMap;
 
@@ -7,6 +8,16 @@ mod roundtrip_tests {
     use super::*;
 
     proptest! {
+        /// Test that printing a parsed spec gives text that parses to the same spec
+        #[test]
+        fn roundtrip_spec_display(
+            text in "([^{}]?[<>=^])?[-+ ]?z?#?0?[0-9]{0,3}[,_]?(\\.[0-9]{1,2})?[bcdeEfFgGnosxX%]?"
+        ) {
+            if let Ok(spec) = FormatSpec::parse(&text) {
+                prop_assert_eq!(FormatSpec::parse(&spec.to_string()).unwrap(), spec);
+            }
+        }
+
         /// Test that integers can be formatted and parsed back to the same value
         #[test]
         fn roundtrip_decimal_int(n in -1000000i64..1000000i64) {
@@ -44,6 +55,20 @@ mod roundtrip_tests {
             prop_assert!((parsed_value - n).abs() < 0.01, "Expected ~{}, got {}", n, parsed_value);
         }
 
+        /// Test that floats written like Python's `str()` parse back exactly
+        #[test]
+        fn roundtrip_python_float_repr(n in any::<f64>().prop_filter("finite", |n| n.is_finite())) {
+            let formatter = Formatter::new("{value}").unwrap().with_float_repr(FloatRepr::Python);
+            let parser = Parser::new("{value:g}").unwrap();
+
+            let mut values = HashMap::new();
+            values.insert("value".to_string(), Value::from(n));
+
+            let formatted = formatter.format_map(&values).unwrap();
+            let parsed = parser.parse(&formatted).unwrap().unwrap();
+            prop_assert_eq!(parsed.get("value").unwrap().as_float(), Some(n), "{}", formatted);
+        }
+
         /// Test that hex integers can be formatted and parsed back
         #[test]
         fn roundtrip_hex(n in 0u64..1000000u64) {